@@ -0,0 +1,195 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! Allocation counting, to measure how much each frame allocates.
+//!
+//! This is a debugging instrument. Counting adds atomic operations to every
+//! allocation of the program, so it shouldn't be enabled in release builds.
+//!
+//! To use it, enable the `diagnostics` feature and install [`CountingAllocator`]
+//! as the global allocator of your application:
+//!
+//! ```
+//! use masonry::alloc_counter::CountingAllocator;
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator = CountingAllocator::system();
+//! # fn main() {}
+//! ```
+//!
+//! Masonry then records the allocations made during each frame of each window,
+//! from [`AppRoot::prepare_paint`] to the end of [`AppRoot::paint`]. Submit
+//! [`DUMP_ALLOCATION_COUNTS`] to a window to log its counts and reset them.
+//!
+//! Without the counting allocator, all counts stay at zero.
+//!
+//! [`AppRoot::prepare_paint`]: crate::AppRoot::prepare_paint
+//! [`AppRoot::paint`]: crate::AppRoot::paint
+//! [`DUMP_ALLOCATION_COUNTS`]: crate::command::DUMP_ALLOCATION_COUNTS
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static BYTES_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+/// A global allocator that counts allocations, wrapping another allocator.
+///
+/// See the [module documentation](self).
+#[derive(Debug, Default)]
+pub struct CountingAllocator<A = System> {
+    inner: A,
+}
+
+/// Allocation counts, either since the start of the program or over a period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocationCounts {
+    /// The number of allocations, including reallocations.
+    pub allocations: u64,
+    /// The number of deallocations.
+    pub deallocations: u64,
+    /// The number of bytes requested by allocations.
+    pub bytes_allocated: u64,
+}
+
+/// The allocations made by the frames of a window.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FrameAllocations {
+    frame_start: Option<AllocationCounts>,
+    /// The allocations of the last complete frame.
+    pub(crate) last_frame: AllocationCounts,
+    /// The allocations of all frames since the last reset.
+    pub(crate) total: AllocationCounts,
+    /// The number of frames since the last reset.
+    pub(crate) frames: u64,
+}
+
+// ---
+
+impl CountingAllocator<System> {
+    /// Create a counting allocator that wraps the system allocator.
+    pub const fn system() -> Self {
+        CountingAllocator { inner: System }
+    }
+}
+
+impl<A> CountingAllocator<A> {
+    /// Create a counting allocator that wraps `inner`.
+    pub const fn new(inner: A) -> Self {
+        CountingAllocator { inner }
+    }
+}
+
+fn count_allocation(size: usize) {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES_ALLOCATED.fetch_add(size as u64, Ordering::Relaxed);
+}
+
+// SAFETY: all methods forward to the inner allocator.
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation(layout.size());
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation(layout.size());
+        self.inner.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation(new_size);
+        self.inner.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        self.inner.dealloc(ptr, layout)
+    }
+}
+
+impl AllocationCounts {
+    /// The counts since the start of the program.
+    ///
+    /// These are all zero unless [`CountingAllocator`] is the global allocator.
+    pub fn current() -> Self {
+        AllocationCounts {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+            bytes_allocated: BYTES_ALLOCATED.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The counts between `earlier` and `self`.
+    pub fn since(self, earlier: AllocationCounts) -> Self {
+        AllocationCounts {
+            allocations: self.allocations.wrapping_sub(earlier.allocations),
+            deallocations: self.deallocations.wrapping_sub(earlier.deallocations),
+            bytes_allocated: self.bytes_allocated.wrapping_sub(earlier.bytes_allocated),
+        }
+    }
+
+    fn add(&mut self, other: AllocationCounts) {
+        self.allocations += other.allocations;
+        self.deallocations += other.deallocations;
+        self.bytes_allocated += other.bytes_allocated;
+    }
+}
+
+impl FrameAllocations {
+    /// Start a frame, unless one was already started.
+    pub(crate) fn start_frame(&mut self, now: AllocationCounts) {
+        self.frame_start.get_or_insert(now);
+    }
+
+    /// End the current frame, which started at `now` if it wasn't started.
+    pub(crate) fn end_frame(&mut self, now: AllocationCounts) {
+        let start = self.frame_start.take().unwrap_or(now);
+        self.last_frame = now.since(start);
+        self.total.add(self.last_frame);
+        self.frames += 1;
+    }
+
+    /// Clear the totals.
+    pub(crate) fn reset(&mut self) {
+        self.total = AllocationCounts::default();
+        self.frames = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(allocations: u64) -> AllocationCounts {
+        AllocationCounts {
+            allocations,
+            deallocations: allocations / 2,
+            bytes_allocated: allocations * 8,
+        }
+    }
+
+    #[test]
+    fn frame_allocations() {
+        let mut frames = FrameAllocations::default();
+
+        frames.start_frame(counts(10));
+        // Starting again in the same frame doesn't move the start.
+        frames.start_frame(counts(12));
+        frames.end_frame(counts(14));
+        assert_eq!(frames.last_frame, counts(4));
+
+        frames.start_frame(counts(20));
+        frames.end_frame(counts(26));
+        assert_eq!(frames.last_frame, counts(6));
+        assert_eq!(frames.total, counts(10));
+        assert_eq!(frames.frames, 2);
+
+        frames.reset();
+        assert_eq!(frames.total, AllocationCounts::default());
+        assert_eq!(frames.frames, 0);
+        assert_eq!(frames.last_frame, counts(6));
+    }
+}
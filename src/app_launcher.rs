@@ -2,19 +2,27 @@
 // "as-is" basis without warranties of any kind. See the LICENSE file for
 // details.
 
+use std::time::Duration;
+
 use druid_shell::{Application as AppHandle, Error as PlatformError};
 
 use crate::app_delegate::AppDelegate;
-use crate::app_root::AppRoot;
+use crate::app_root::{AppRoot, EventInterceptor, EventSpy, Interception, DEFAULT_IDLE_BUDGET};
 use crate::ext_event::{ExtEventQueue, ExtEventSink};
-use crate::platform::{MasonryAppHandler, WindowDescription};
-use crate::Env;
+use crate::platform::{query_accessibility_settings, MasonryAppHandler, WindowDescription};
+use crate::util::set_debug_panic_handler;
+use crate::{Env, Event, WindowId};
 
 /// Handles initial setup of an application, and starts the runloop.
 pub struct AppLauncher {
     windows: Vec<WindowDescription>,
     app_delegate: Option<Box<dyn AppDelegate>>,
     ext_event_queue: ExtEventQueue,
+    debug_panic_handler: Option<Box<dyn Fn(&str)>>,
+    idle_budget: Duration,
+    layout_budget: Option<Duration>,
+    event_spies: Vec<EventSpy>,
+    event_interceptor: Option<EventInterceptor>,
 }
 
 impl AppLauncher {
@@ -24,6 +32,11 @@ impl AppLauncher {
             windows: vec![window],
             app_delegate: None,
             ext_event_queue: ExtEventQueue::new(),
+            debug_panic_handler: None,
+            idle_budget: DEFAULT_IDLE_BUDGET,
+            layout_budget: None,
+            event_spies: Vec::new(),
+            event_interceptor: None,
         }
     }
 
@@ -35,6 +48,83 @@ impl AppLauncher {
         self
     }
 
+    /// Route internal consistency errors to `handler` instead of panicking.
+    ///
+    /// The handler is installed when the app is launched. See
+    /// [`set_debug_panic_handler`] for details.
+    ///
+    /// [`set_debug_panic_handler`]: crate::set_debug_panic_handler
+    pub fn with_debug_panic_handler(mut self, handler: impl Fn(&str) + 'static) -> Self {
+        self.debug_panic_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Set how long queued commands and external events may be processed in
+    /// a single idle callback.
+    ///
+    /// When a burst of work takes longer than this, the rest is processed in
+    /// later idle callbacks, in the same order, so the window can paint in
+    /// between. The default is 50ms.
+    pub fn with_idle_budget(mut self, budget: Duration) -> Self {
+        self.idle_budget = budget;
+        self
+    }
+
+    /// Spread the layout of large widget trees over several frames.
+    ///
+    /// Once a layout pass has taken longer than `budget`, widgets that weren't
+    /// visible in the previous frame aren't laid out anymore. The ones that
+    /// don't need a new layout, and get the same constraints as last time,
+    /// keep their layout. The others are deferred: they keep their previous
+    /// size, aren't painted, and are laid out in the next frame, which is
+    /// requested right away. Widgets that were visible are always laid out,
+    /// so the visible content is shown sooner after a large change, and the
+    /// off-screen content catches up in the following frames.
+    ///
+    /// Visibility is that of the previous frame, so widgets that were never
+    /// laid out, eg in the first layout of the window, are laid out in tree
+    /// order until the budget is spent, visible or not. Frames that are
+    /// painted right away, by [`CAPTURE_WINDOW`] or for
+    /// [`WindowConfig::layout_on_connect`], lay out everything.
+    ///
+    /// [`CAPTURE_WINDOW`]: crate::command::CAPTURE_WINDOW
+    /// [`WindowConfig::layout_on_connect`]: crate::WindowConfig::layout_on_connect
+    ///
+    /// Widgets must tolerate being painted before their deferred descendants
+    /// are laid out. A deferred descendant has the size it had before, or a
+    /// zero size if it was never laid out, and is at the position it was last
+    /// placed at. Containers whose size depends on such descendants, eg a
+    /// list taller than the window, are laid out again once they are.
+    ///
+    /// By default, layout passes run to completion.
+    pub fn with_layout_budget(mut self, budget: Duration) -> Self {
+        self.layout_budget = Some(budget);
+        self
+    }
+
+    /// Add a callback that observes every event sent to a window, without
+    /// being able to handle it.
+    ///
+    /// See [`AppRoot::add_event_spy`].
+    pub fn with_event_spy(
+        mut self,
+        spy: impl FnMut(WindowId, &Event, instant::Instant) + 'static,
+    ) -> Self {
+        self.event_spies.push(Box::new(spy));
+        self
+    }
+
+    /// Set a callback that may consume or replace the user input sent to a window.
+    ///
+    /// See [`AppRoot::set_event_interceptor`].
+    pub fn with_event_interceptor(
+        mut self,
+        interceptor: impl FnMut(WindowId, Event) -> Interception + 'static,
+    ) -> Self {
+        self.event_interceptor = Some(Box::new(interceptor));
+        self
+    }
+
     /// Initialize a minimal tracing subscriber with DEBUG max level for printing logs out to
     /// stderr.
     ///
@@ -82,14 +172,26 @@ impl AppLauncher {
     /// Returns an error if a window cannot be instantiated. This is usually
     /// a fatal error.
     pub fn launch(self) -> Result<(), PlatformError> {
+        if let Some(handler) = self.debug_panic_handler {
+            set_debug_panic_handler(handler);
+        }
+
         let app = AppHandle::new()?;
-        let state = AppRoot::create(
+        let mut env = Env::with_theme().adding(Env::LOCALE, AppHandle::get_locale());
+        env.set_accessibility_settings(query_accessibility_settings());
+        let mut state = AppRoot::create(
             app.clone(),
             self.windows,
             self.app_delegate,
             self.ext_event_queue,
-            Env::with_theme(),
+            env,
+            self.idle_budget,
+            self.layout_budget,
+            self.event_spies,
         )?;
+        if let Some(interceptor) = self.event_interceptor {
+            state.set_event_interceptor(interceptor);
+        }
         let handler = MasonryAppHandler::new(state);
 
         app.run(Some(Box::new(handler)));
@@ -9,42 +9,54 @@
 #![allow(dead_code)]
 
 use std::cell::{RefCell, RefMut};
-use std::collections::{HashMap, VecDeque};
-use std::ops::DerefMut;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
+use std::time::Duration;
 
 use druid_shell::text::InputHandler;
 // TODO - rename Application to AppHandle in glazier
 // See https://github.com/linebender/glazier/issues/44
-use druid_shell::{Application as AppHandle, WindowHandle};
+use druid_shell::{Application as AppHandle, WindowHandle, WindowState};
 use druid_shell::{
-    Cursor, FileDialogToken, FileInfo, Region, TextFieldToken, TimerToken, WindowBuilder,
+    Code, Cursor, FileDialogToken, FileInfo, IdleToken, KbKey, KeyEvent, Modifiers, MouseButton,
+    Region, Screen, TextFieldToken, TimerToken, WindowBuilder,
 };
 // Automatically defaults to std::time::Instant on non Wasm platforms
 use instant::Instant;
-use tracing::{error, info, info_span};
+use tracing::{error, info, info_span, warn};
 
 use crate::action::ActionQueue;
 use crate::app_delegate::{AppDelegate, DelegateCtx, NullDelegate};
 use crate::command::CommandQueue;
-use crate::contexts::GlobalPassCtx;
+use crate::contexts::{
+    sort_z_ops, AfterLayoutCallback, BrushCache, GlobalPassCtx, LayoutRectRequest,
+};
 use crate::debug_logger::DebugLogger;
-use crate::ext_event::{ExtEventQueue, ExtEventSink, ExtMessage};
-use crate::kurbo::{Point, Size};
-use crate::piet::{Color, Piet, RenderContext};
+use crate::drag::{drop_candidates, DragEvent, DragPhase, DragRequest, DragSession};
+use crate::ext_event::{process_ext_events, ExtEventHost, ExtEventQueue, ExtEventSink, ExtMessage};
+use crate::kurbo::{Affine, Point, Rect, Shape, Size, Vec2};
+use crate::piet::{
+    Color, Device, Error as PietError, ImageBuf, ImageFormat, InterpolationMode, Piet, PietImage,
+    PietText, RenderContext,
+};
 use crate::platform::{
-    DialogInfo, WindowConfig, WindowSizePolicy, EXT_EVENT_IDLE_TOKEN, RUN_COMMANDS_TOKEN,
+    aspect_corrected_size, content_constraints, DialogInfo, WindowConfig, WindowSizePolicy,
+    EXT_EVENT_IDLE_TOKEN, RUN_COMMANDS_TOKEN,
 };
 use crate::testing::MockTimerQueue;
 use crate::text::TextFieldRegistration;
 use crate::widget::{FocusChange, StoreInWidgetMut, WidgetMut, WidgetRef, WidgetState};
 use crate::{
-    command as sys_cmd, ArcStr, BoxConstraints, Command, Env, Event, EventCtx, Handled,
-    InternalEvent, InternalLifeCycle, LayoutCtx, LifeCycle, LifeCycleCtx, MasonryWinHandler,
-    PaintCtx, PlatformError, Target, Widget, WidgetCtx, WidgetId, WidgetPod, WindowDescription,
-    WindowId,
+    command as sys_cmd, AccessibilitySettings, ArcStr, BoxConstraints, Command, ContextMenuRequest,
+    Env, Event, EventCtx, Handled, InternalEvent, InternalLifeCycle, LayoutCtx, LifeCycle,
+    LifeCycleCtx, MasonryWinHandler, MouseEvent, PaintCtx, PlatformError, Target, Widget,
+    WidgetCtx, WidgetId, WidgetPod, WindowDescription, WindowId,
 };
 
+/// The default for [`AppLauncher::with_idle_budget`](crate::AppLauncher::with_idle_budget).
+pub(crate) const DEFAULT_IDLE_BUDGET: Duration = Duration::from_millis(50);
+
 /// The type of a function that will be called once an IME field is updated.
 pub type ImeUpdateFn = dyn FnOnce(druid_shell::text::Event);
 
@@ -81,6 +93,65 @@ struct AppRootInner {
     #[allow(unused)]
     menu_window: Option<WindowId>,
     env: Env,
+    /// How long an idle callback may spend on queued work before yielding.
+    idle_budget: Duration,
+    /// How long a window's layout may take before off-screen work is deferred.
+    layout_budget: Option<Duration>,
+    /// Set while a batch of external events runs, so that the paint
+    /// invalidations of all its commands are sent to the platform once, at
+    /// the end of the batch.
+    batching_invalidations: bool,
+    /// Callbacks observing the events sent to windows.
+    event_spies: Vec<EventSpy>,
+    /// A callback that may consume or replace the events sent to windows.
+    event_interceptor: Option<EventInterceptor>,
+    /// The window that last received a pointer event, where commands sent to
+    /// [`Target::Pointer`] are routed.
+    pointer_window: Option<WindowId>,
+}
+
+/// A callback registered with [`AppRoot::add_event_spy`].
+pub(crate) type EventSpy = Box<dyn FnMut(WindowId, &Event, Instant)>;
+
+/// A callback set with [`AppRoot::set_event_interceptor`].
+pub(crate) type EventInterceptor = Box<dyn FnMut(WindowId, Event) -> Interception>;
+
+/// What an event interceptor does with an event.
+///
+/// See [`AppRoot::set_event_interceptor`].
+#[derive(Debug, Clone)]
+pub enum Interception {
+    /// Stop the event: it isn't dispatched, and is reported as handled.
+    Consume,
+    /// Dispatch this event, which is either the intercepted event or a
+    /// replacement.
+    Dispatch(Event),
+}
+
+/// Run the event spies and the event interceptor on an event sent to a window.
+///
+/// Returns the event to dispatch, or `None` if the interceptor consumed it.
+/// Only user input goes through the interceptor: window and timer events, as
+/// well as internal events, carry the bookkeeping of the window and are
+/// always dispatched.
+pub(crate) fn filter_event(
+    spies: &mut [EventSpy],
+    interceptor: Option<&mut EventInterceptor>,
+    window_id: WindowId,
+    event: Event,
+    now: Instant,
+) -> Option<Event> {
+    for spy in spies.iter_mut() {
+        spy(window_id, &event, now);
+    }
+
+    match interceptor {
+        Some(interceptor) if event.is_user_input() => match interceptor(window_id, event) {
+            Interception::Consume => None,
+            Interception::Dispatch(event) => Some(event),
+        },
+        _ => Some(event),
+    }
 }
 
 /// The parts of a window, pending construction, that are dependent on top level app state
@@ -90,6 +161,50 @@ struct PendingWindow {
     title: ArcStr,
     transparent: bool,
     size_policy: WindowSizePolicy,
+    keep_aspect_ratio: Option<f64>,
+    min_size: Option<Size>,
+    background_color: Option<Color>,
+    clear: bool,
+    replay_last_frame: bool,
+    initial_size: Option<Size>,
+    layout_on_connect: bool,
+}
+
+/// The last frame painted in a window. See [`WindowConfig::replay_last_frame`].
+struct LastFrame {
+    image: PietImage,
+    // The window size and scale when the frame was captured.
+    size: Size,
+    scale: f64,
+}
+
+/// An entry of a window's focus chain.
+///
+/// See [`WindowRoot::focus_chain_entries`].
+#[derive(Clone, Debug)]
+pub struct FocusChainEntry {
+    /// The id of the focusable widget.
+    pub id: WidgetId,
+    /// The widget's layout rect, in window coordinates.
+    pub window_layout_rect: Rect,
+    /// The widget's abridged type name.
+    pub type_name: &'static str,
+    /// The widget's debug text, if any. See [`Widget::get_debug_text`].
+    pub debug_text: Option<String>,
+}
+
+/// A timer requested by a widget, that hasn't fired yet.
+///
+/// See [`WindowRoot::pending_timers`].
+#[cfg(any(test, feature = "diagnostics"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingTimer {
+    /// The token the widget receives with [`Event::Timer`].
+    pub token: TimerToken,
+    /// The widget that requested the timer.
+    pub widget_id: WidgetId,
+    /// When the timer is due.
+    pub deadline: Instant,
 }
 
 // TODO - refactor out again
@@ -100,21 +215,107 @@ pub struct WindowRoot {
     pub(crate) id: WindowId,
     pub(crate) root: WidgetPod<Box<dyn Widget>>,
     pub(crate) title: ArcStr,
-    size_policy: WindowSizePolicy,
-    size: Size,
+    pub(crate) size_policy: WindowSizePolicy,
+    pub(crate) size: Size,
     invalid: Region,
     // Is `Some` if the most recently displayed frame was an animation frame.
     pub(crate) last_anim: Option<Instant>,
     pub(crate) last_mouse_pos: Option<Point>,
+    last_mouse_event: Option<MouseEvent>,
+    // The cursor last set on the window.
+    cursor: Cursor,
+    // Number of MouseMove events synthesized since the pointer last moved or
+    // the window was last painted.
+    synthetic_mouse_moves: u32,
+    // Whether hot state changes are suppressed, see `EventCtx::suppress_hot_changes`.
+    hot_suppressed: bool,
     pub(crate) focus: Option<WidgetId>,
+    // Whether the window has focus from the platform. New windows are usually
+    // shown in the foreground, and not all platforms report it, so this starts
+    // out true.
+    pub(crate) window_focused: bool,
+    // Whether the keyboard was used since the last mouse press.
+    pub(crate) focus_visible: bool,
+    // The widget under the cursor of assistive technologies.
+    pub(crate) accessibility_focus: Option<WidgetId>,
     pub(crate) ext_event_sink: ExtEventSink,
     pub(crate) handle: WindowHandle,
-    pub(crate) timers: HashMap<TimerToken, WidgetId>,
+    pub(crate) timers: HashMap<TimerToken, (WidgetId, Instant)>,
     // Used in unit tests - see `src/testing/mock_timer_queue.rs`
     pub(crate) mock_timer_queue: Option<MockTimerQueue>,
     pub(crate) transparent: bool,
+    pub(crate) window_state: WindowState,
+    // The width / height ratio to keep when the window is resized, if any.
+    pub(crate) keep_aspect_ratio: Option<f64>,
+    // The minimum drawing area size, for windows sized to their content.
+    pub(crate) min_size: Option<Size>,
+    // The work area of the screen showing the window, for windows sized to
+    // their content.
+    pub(crate) screen_size: Option<Size>,
+    // The color the window is cleared to before painting, if not the default.
+    pub(crate) background_color: Option<Color>,
+    // Whether the window is cleared before painting.
+    pub(crate) clear: bool,
+    // Whether an image of the last frame is kept, to repaint from it when
+    // nothing changed.
+    pub(crate) replay_last_frame: bool,
+    last_frame: Option<LastFrame>,
+    // The frame painted when the window was connected, with the window size it
+    // was painted at. See `WindowConfig::layout_on_connect`.
+    pub(crate) connect_frame: Option<(ImageBuf, Size)>,
+    // Whether anything was invalidated since the last frame was painted.
+    frame_changed: bool,
     pub(crate) ime_handlers: Vec<(TextFieldToken, TextFieldRegistration)>,
     pub(crate) ime_focus_change: Option<Option<TextFieldToken>>,
+    brush_cache: BrushCache,
+    // The text factory of the render context passed to `render_widget_to`, if any.
+    render_text: Option<PietText>,
+    // The scale passed to `render_widget_to`, during its passes.
+    render_scale: Option<f64>,
+    // Whether the window is being painted into an image, see `capture`.
+    paint_offscreen: bool,
+    // See `AppLauncher::with_layout_budget`.
+    pub(crate) layout_budget: Option<Duration>,
+    // When the layout passes of the current frame run out of budget.
+    layout_deadline: Option<Instant>,
+    // The widget found by hit testing at the last pointer position, with its
+    // layout rect in window coordinates. See `Env::DEBUG_HIT_TEST`.
+    pub(crate) hit_test_target: Option<(WidgetId, Rect)>,
+    drag_session: Option<DragSession>,
+    // The physical keys currently held down, with the number of times each was
+    // repeated. Logical keys can't be used: they change with the modifiers, eg
+    // if Shift is released before the key.
+    held_keys: HashMap<Code, u32>,
+    // See `Env::MULTI_CLICK_INTERVAL`.
+    last_click: Option<LastClick>,
+    // Callbacks registered with `EventCtx::run_after_layout`, waiting for the next layout.
+    after_layout: Vec<(WidgetId, AfterLayoutCallback)>,
+    // Callbacks whose layout is done, waiting for the end of the update cycle.
+    pub(crate) after_layout_ready: Vec<(WidgetId, AfterLayoutCallback)>,
+    // The callback being delivered by a `RouteAfterLayout` event.
+    after_layout_callback: Option<AfterLayoutCallback>,
+    // Widgets observing the layout rect of other widgets.
+    layout_rect_observers: Vec<LayoutRectObserver>,
+    #[cfg(feature = "diagnostics")]
+    frame_allocations: crate::alloc_counter::FrameAllocations,
+}
+
+/// A widget observing the layout rect of another widget, see
+/// [`EventCtx::observe_layout_rect`].
+struct LayoutRectObserver {
+    observer: WidgetId,
+    target: WidgetId,
+    // The rect last sent to the observer, in window coordinates.
+    last_rect: Option<Rect>,
+}
+
+/// The last mouse press, used to count multi-clicks.
+struct LastClick {
+    button: MouseButton,
+    time: Instant,
+    // In window coordinates.
+    pos: Point,
+    count: u8,
 }
 
 // ---
@@ -131,6 +332,9 @@ impl AppRoot {
         app_delegate: Option<Box<dyn AppDelegate>>,
         ext_event_queue: ExtEventQueue,
         env: Env,
+        idle_budget: Duration,
+        layout_budget: Option<Duration>,
+        event_spies: Vec<EventSpy>,
     ) -> Result<Self, PlatformError> {
         let inner = Rc::new(RefCell::new(AppRootInner {
             app_handle: app,
@@ -144,6 +348,12 @@ impl AppRoot {
             main_window_id: windows.first().unwrap().id,
             menu_window: None,
             env,
+            idle_budget,
+            layout_budget,
+            batching_invalidations: false,
+            event_spies,
+            event_interceptor: None,
+            pointer_window: None,
             window_requests: VecDeque::new(),
             pending_windows: Default::default(),
             active_windows: Default::default(),
@@ -163,12 +373,17 @@ impl AppRoot {
     /// This should be called by the platform after processing from
     /// [`druid_shell::WindowBuilder`] finishes.
     pub fn window_connected(&mut self, window_id: WindowId, handle: WindowHandle) {
+        // See WindowConfig::layout_on_connect.
+        let mut connect_layout = None;
         {
             let mut inner = self.inner.borrow_mut();
             let inner = inner.deref_mut();
 
             if let Some(pending) = inner.pending_windows.remove(&window_id) {
-                let win = WindowRoot::new(
+                if pending.layout_on_connect {
+                    connect_layout = Some(pending.initial_size);
+                }
+                let mut win = WindowRoot::new(
                     window_id,
                     handle,
                     inner.ext_event_queue.make_sink(),
@@ -178,6 +393,17 @@ impl AppRoot {
                     pending.size_policy,
                     None,
                 );
+                win.keep_aspect_ratio = pending.keep_aspect_ratio;
+                win.min_size = pending.min_size;
+                if pending.size_policy == WindowSizePolicy::Content {
+                    win.screen_size = screen_work_area(&win.handle).map(|area| area.size());
+                }
+                win.background_color = pending.background_color;
+                win.clear = pending.clear;
+                win.replay_last_frame = pending.replay_last_frame;
+                win.layout_budget = inner.layout_budget;
+                // The title may have been changed since the window was built.
+                win.handle.set_title(&win.title);
                 let existing = inner.active_windows.insert(window_id, win);
                 debug_assert!(existing.is_none(), "duplicate window");
             } else {
@@ -196,8 +422,24 @@ impl AppRoot {
         let event = Event::WindowConnected;
         self.do_window_event(window_id, event);
 
+        if let Some(initial_size) = connect_layout {
+            let mut inner = self.inner.borrow_mut();
+            let inner = inner.deref_mut();
+            if let Some(win) = inner.active_windows.get_mut(&window_id) {
+                win.layout_on_connect(
+                    initial_size,
+                    &mut inner.debug_logger,
+                    &mut inner.command_queue,
+                    &mut inner.action_queue,
+                    &inner.env,
+                );
+                // Nothing is invalid, but the window still needs a first frame.
+                win.handle.invalidate();
+            }
+        }
+
         self.process_commands_and_actions();
-        self.inner().invalidate_paint_regions();
+        self.inner().invalidate_and_finalize();
         self.process_ime_changes();
         self.process_window_requests();
     }
@@ -231,8 +473,151 @@ impl AppRoot {
     }
 
     /// Notify the app that a window has acquired focus (eg the user clicked on it).
-    pub fn window_got_focus(&mut self, _window_id: WindowId) {
+    pub fn window_got_focus(&mut self, window_id: WindowId) {
         // TODO - menu stuff
+        self.set_window_focused(window_id, true);
+        self.update_window_state(window_id);
+    }
+
+    /// Notify the app that a window has lost focus (eg the user switched to another application).
+    pub fn window_lost_focus(&mut self, window_id: WindowId) {
+        self.set_window_focused(window_id, false);
+        self.update_window_state(window_id);
+    }
+
+    fn set_window_focused(&mut self, window_id: WindowId, focused: bool) {
+        {
+            let mut inner = self.inner.borrow_mut();
+            let inner = inner.deref_mut();
+            if let Some(win) = inner.active_windows.get_mut(&window_id) {
+                win.set_window_focused(
+                    focused,
+                    &mut inner.debug_logger,
+                    &mut inner.command_queue,
+                    &mut inner.action_queue,
+                    &inner.env,
+                );
+            }
+        }
+
+        self.process_commands_and_actions();
+        self.inner().invalidate_and_finalize();
+    }
+
+    /// Check whether the window was minimized, maximized or restored since the
+    /// last check, and send [`Event::WindowStateChanged`] to its widgets if so.
+    ///
+    /// druid-shell doesn't report window state changes directly, so this is
+    /// called after events that usually accompany them (resizes, focus).
+    pub fn update_window_state(&mut self, window_id: WindowId) {
+        let change = {
+            let mut inner = self.inner();
+            inner.active_windows.get_mut(&window_id).and_then(|win| {
+                let new = win.handle.get_window_state();
+                let old = std::mem::replace(&mut win.window_state, new);
+                (old != new).then(|| (old, new))
+            })
+        };
+
+        if let Some((old, new)) = change {
+            info!(
+                "window {:?} state changed from {:?} to {:?}",
+                window_id, old, new
+            );
+            self.handle_event(Event::WindowStateChanged { old, new }, window_id);
+        }
+    }
+
+    /// Return the title of a given window.
+    ///
+    /// This includes windows that aren't connected yet.
+    pub fn window_title(&self, window_id: WindowId) -> Option<ArcStr> {
+        let inner = self.inner.borrow();
+        match inner.active_windows.get(&window_id) {
+            Some(win) => Some(win.title().clone()),
+            None => inner
+                .pending_windows
+                .get(&window_id)
+                .map(|pending| pending.title.clone()),
+        }
+    }
+
+    /// Return the focus chain of a given window, in Tab order.
+    ///
+    /// See [`WindowRoot::focus_chain_entries`].
+    pub fn focus_chain_entries(&self, window_id: WindowId) -> Vec<FocusChainEntry> {
+        self.inner
+            .borrow()
+            .active_windows
+            .get(&window_id)
+            .map(|win| win.focus_chain_entries())
+            .unwrap_or_default()
+    }
+
+    /// Return the timers of a given window that haven't fired yet.
+    ///
+    /// See [`WindowRoot::pending_timers`].
+    #[cfg(any(test, feature = "diagnostics"))]
+    pub fn pending_timers(&self, window_id: WindowId) -> Vec<PendingTimer> {
+        self.inner
+            .borrow()
+            .active_windows
+            .get(&window_id)
+            .map(|win| win.pending_timers())
+            .unwrap_or_default()
+    }
+
+    /// Register a callback that observes every event sent to a window.
+    ///
+    /// The callback receives the window's id, the event and the time it was
+    /// received, before the event is dispatched to the [`AppDelegate`] and the
+    /// widgets. It can't handle the event or change how it propagates, so it
+    /// suits analytics, tutorials and debugging overlays. Commands aren't
+    /// events of a window, and aren't observed.
+    ///
+    /// Several spies may be registered; they're called in registration order.
+    /// A spy must not call back into the `AppRoot`.
+    pub fn add_event_spy(&mut self, spy: impl FnMut(WindowId, &Event, Instant) + 'static) {
+        self.inner().event_spies.push(Box::new(spy));
+    }
+
+    /// Set a callback that may consume or replace the user input sent to a window.
+    ///
+    /// The interceptor receives the window's id and the event, and returns an
+    /// [`Interception`]: either consume the event, which then counts as
+    /// handled, or dispatch an event, the same one or a replacement. This
+    /// suits app-wide behaviors that would otherwise wrap the whole UI, such as
+    /// blocking input while a modal task runs, or replaying recorded input.
+    ///
+    /// Only user input is intercepted, as listed by [`Event::is_user_input`].
+    /// Other events, such as [`Event::WindowSize`] or [`Event::Timer`], keep
+    /// the window's state in sync with the platform, and are always dispatched.
+    ///
+    /// Events go through, in order:
+    /// - the event spies (see [`add_event_spy`](Self::add_event_spy)), which
+    /// observe the event as it was received, even if it's later consumed;
+    /// - the interceptor;
+    /// - the [`AppDelegate`], which receives the replacement event if there is one;
+    /// - the widgets of the window.
+    ///
+    /// There's no app-level table of keyboard shortcuts: shortcuts are
+    /// handled by the delegate and by widgets, such as the copy and paste keys
+    /// of [`TextBox`](crate::widget::TextBox), so the interceptor sees key
+    /// events before any shortcut is matched.
+    ///
+    /// Commands aren't events of a window, and aren't intercepted. There's a
+    /// single interceptor; setting one replaces the previous one. The
+    /// interceptor must not call back into the `AppRoot`.
+    pub fn set_event_interceptor(
+        &mut self,
+        interceptor: impl FnMut(WindowId, Event) -> Interception + 'static,
+    ) {
+        self.inner().event_interceptor = Some(Box::new(interceptor));
+    }
+
+    /// Remove the callback set with [`set_event_interceptor`](Self::set_event_interceptor).
+    pub fn clear_event_interceptor(&mut self) {
+        self.inner().event_interceptor = None;
     }
 
     /// Send an event to the widget hierarchy.
@@ -255,7 +640,7 @@ impl AppRoot {
         }
 
         self.process_commands_and_actions();
-        self.inner().invalidate_paint_regions();
+        self.inner().invalidate_and_finalize();
         self.process_ime_changes();
         self.process_window_requests();
 
@@ -289,7 +674,7 @@ impl AppRoot {
             self.do_cmd(cmd);
             self.process_commands_and_actions();
             self.process_ime_changes();
-            self.inner().invalidate_paint_regions();
+            self.inner().invalidate_and_finalize();
         } else {
             tracing::error!("unknown dialog token");
         }
@@ -312,8 +697,9 @@ impl AppRoot {
                     &inner.env,
                 );
             }
-            inner.invalidate_paint_regions();
         }
+        self.run_high_priority_commands(window_id);
+        self.inner().invalidate_and_finalize();
         self.process_window_requests();
     }
 
@@ -325,6 +711,9 @@ impl AppRoot {
         let mut inner = self.inner.borrow_mut();
         let inner = inner.deref_mut();
         if let Some(win) = inner.active_windows.get_mut(&window_id) {
+            if win.window_state == WindowState::Minimized {
+                return;
+            }
             win.do_paint(
                 piet,
                 invalid,
@@ -336,19 +725,112 @@ impl AppRoot {
         }
     }
 
+    /// Lay out a given window's root widget at `size` and paint it into `piet`.
+    ///
+    /// This is independent of the window's surface and size, and can be used to
+    /// export a widget's rendering to an image. Text is measured with `piet`'s
+    /// text factory.
+    ///
+    /// The window is laid out again at its own size before its next paint.
+    ///
+    /// `piet`'s target is assumed to have one device pixel per logical pixel,
+    /// see [`render_widget_to_with_scale`](Self::render_widget_to_with_scale)
+    /// for other targets.
+    pub fn render_widget_to(&mut self, window_id: WindowId, piet: &mut Piet, size: Size) {
+        self.render_widget_to_with_scale(window_id, piet, size, 1.0);
+    }
+
+    /// Lay out a given window's root widget at `size` and paint it into `piet`,
+    /// whose target has `scale` device pixels per logical pixel.
+    ///
+    /// `scale` is eg `2.0` for a bitmap target created with a `pix_scale` of 2.
+    /// During the export, [`PaintCtx::scale`](crate::PaintCtx::scale) returns
+    /// it, and text layouts are rebuilt for it.
+    ///
+    /// The window is laid out again at its own size and scale before its next
+    /// paint. See [`render_widget_to`](Self::render_widget_to) for details.
+    pub fn render_widget_to_with_scale(
+        &mut self,
+        window_id: WindowId,
+        piet: &mut Piet,
+        size: Size,
+        scale: f64,
+    ) {
+        let mut inner = self.inner.borrow_mut();
+        let inner = inner.deref_mut();
+        if let Some(win) = inner.active_windows.get_mut(&window_id) {
+            win.render_widget_to(
+                piet,
+                size,
+                scale,
+                &mut inner.debug_logger,
+                &mut inner.command_queue,
+                &mut inner.action_queue,
+                &inner.env,
+            );
+        }
+    }
+
+    /// Lay out a given window's root widget at `size` and paint it into a new
+    /// image, `scale` times the size in pixels.
+    ///
+    /// This is [`render_widget_to_with_scale`](Self::render_widget_to_with_scale)
+    /// with a bitmap target, eg to save a widget as a 2x PNG.
+    pub fn export_image(
+        &mut self,
+        window_id: WindowId,
+        size: Size,
+        scale: f64,
+    ) -> Result<ImageBuf, PietError> {
+        let mut inner = self.inner.borrow_mut();
+        let inner = inner.deref_mut();
+        match inner.active_windows.get_mut(&window_id) {
+            Some(win) => win.export_image(
+                size,
+                scale,
+                &mut inner.debug_logger,
+                &mut inner.command_queue,
+                &mut inner.action_queue,
+                &inner.env,
+            ),
+            None => Err(PietError::InvalidInput),
+        }
+    }
+
     /// Run any leftover commands from previous events.
+    ///
+    /// If this takes longer than the idle budget, the remaining commands are
+    /// left in the queue and run in a later idle callback.
     pub fn run_commands(&mut self) {
-        self.process_commands_and_actions();
-        self.inner().invalidate_paint_regions();
+        let deadline = self.idle_deadline();
+        if !self.process_queued_work(Some(deadline)) {
+            self.inner().schedule_idle(RUN_COMMANDS_TOKEN);
+        }
+        self.inner().invalidate_and_finalize();
         self.process_ime_changes();
         self.process_window_requests();
     }
 
     /// Run any events in the background event queue, usually sent by a background thread.
+    ///
+    /// If this takes longer than the idle budget, the remaining events are
+    /// left in the queue and run in a later idle callback.
+    ///
+    /// Background threads often update many widgets at once, so the paint
+    /// invalidations of the whole batch are merged into their union, which is
+    /// sent to the platform once, at the end.
     pub fn run_ext_events(&mut self) {
-        self.process_ext_events();
-        self.process_commands_and_actions();
-        self.inner().invalidate_paint_regions();
+        let deadline = self.idle_deadline();
+        self.inner().batching_invalidations = true;
+        if !process_ext_events(self, Some(deadline)) {
+            // The commands they submitted run with the rest of the events,
+            // in the same order as if we hadn't yielded.
+            self.inner().schedule_idle(EXT_EVENT_IDLE_TOKEN);
+        } else if !self.process_queued_work(Some(deadline)) {
+            self.inner().schedule_idle(RUN_COMMANDS_TOKEN);
+        }
+        self.inner().invalidate_and_finalize();
+        self.inner().batching_invalidations = false;
         self.process_ime_changes();
         self.process_window_requests();
     }
@@ -400,9 +882,61 @@ impl AppRoot {
         self.inner.borrow_mut()
     }
 
+    fn idle_deadline(&self) -> Instant {
+        Instant::now() + self.inner().idle_budget
+    }
+
+    /// Lay out the window, and run the high-priority commands submitted
+    /// meanwhile, so that their effect is visible in the next paint.
+    ///
+    /// This repeats for commands submitted by the layout passes that follow,
+    /// up to [`MAX_HIGH_PRIORITY_ROUNDS`] times.
+    fn run_high_priority_commands(&mut self, window_id: WindowId) {
+        for _ in 0..MAX_HIGH_PRIORITY_ROUNDS {
+            let commands = {
+                let mut inner = self.inner.borrow_mut();
+                let inner = inner.deref_mut();
+                if let Some(win) = inner.active_windows.get_mut(&window_id) {
+                    if win.root.state().needs_layout {
+                        win.layout(
+                            &mut inner.debug_logger,
+                            &mut inner.command_queue,
+                            &mut inner.action_queue,
+                            &inner.env,
+                        );
+                    }
+                }
+                sys_cmd::take_high_priority_commands(&mut inner.command_queue)
+            };
+            if commands.is_empty() {
+                return;
+            }
+            for cmd in commands {
+                self.do_cmd(cmd);
+            }
+        }
+    }
+
     // TODO - rename?
     fn process_commands_and_actions(&mut self) {
+        self.process_queued_work(None);
+    }
+
+    /// Run queued commands and actions until the queues are empty, or `deadline`
+    /// has passed.
+    ///
+    /// Returns `false` if work is left in the queues.
+    fn process_queued_work(&mut self, deadline: Option<Instant>) -> bool {
+        // We always make some progress, even with a zero budget.
+        let mut first = true;
         loop {
+            if !std::mem::take(&mut first)
+                && deadline_passed(deadline, Instant::now())
+                && self.inner().has_queued_work()
+            {
+                return false;
+            }
+
             let next_cmd = self.inner().command_queue.pop_front();
             if let Some(cmd) = next_cmd {
                 self.do_cmd(cmd);
@@ -418,29 +952,7 @@ impl AppRoot {
             }
 
             // else - no more commands or actions
-            break;
-        }
-    }
-
-    fn process_ext_events(&mut self) {
-        loop {
-            let ext_cmd = self.inner().ext_event_queue.recv();
-            match ext_cmd {
-                Some(ExtMessage::Command(selector, payload, target)) => {
-                    self.do_cmd(Command::from_ext(selector, payload, target))
-                }
-                Some(ExtMessage::Promise(promise_result, widget_id, window_id)) => {
-                    // TODO
-                    self.do_window_event(
-                        window_id,
-                        Event::Internal(InternalEvent::RoutePromiseResult(
-                            promise_result,
-                            widget_id,
-                        )),
-                    );
-                }
-                None => break,
-            }
+            return true;
         }
     }
 
@@ -466,6 +978,11 @@ impl AppRoot {
     /// Handle a command. Top level commands (e.g. for creating and destroying
     /// windows) have their logic here; other commands are passed to the window.
     fn do_cmd(&mut self, cmd: Command) {
+        if let Some(batch) = cmd.try_get(sys_cmd::COMMAND_BATCH) {
+            self.do_cmd_batch(batch.clone());
+            return;
+        }
+
         if self.with_delegate(|delegate, ctx, env| delegate.on_command(ctx, &cmd, env))
             == Handled::Yes
         {
@@ -484,6 +1001,12 @@ impl AppRoot {
                 self.inner().request_new_window(cmd);
             }
             _ if cmd.is(sys_cmd::CLOSE_ALL_WINDOWS) => self.inner().request_close_all_windows(),
+            _ if cmd.is(sys_cmd::SET_LOCALE) => {
+                self.set_locale(cmd.get(sys_cmd::SET_LOCALE).clone());
+            }
+            _ if cmd.is(sys_cmd::SET_ACCESSIBILITY_SETTINGS) => {
+                self.set_accessibility_settings(*cmd.get(sys_cmd::SET_ACCESSIBILITY_SETTINGS));
+            }
             //T::Window(id) if cmd.is(sys_cmd::INVALIDATE_IME) => self.inner().invalidate_ime(cmd, id),
             // these should come from a window
             // FIXME: we need to be able to open a file without a window handle
@@ -495,6 +1018,20 @@ impl AppRoot {
                 self.inner().request_close_window(id);
             }
             T::Window(id) if cmd.is(sys_cmd::SHOW_WINDOW) => self.inner().request_show_window(id),
+            T::Window(id) if cmd.is(sys_cmd::SET_WINDOW_ROOT) => self.set_window_root(cmd, id),
+            T::Window(id) if cmd.is(sys_cmd::SET_WINDOW_TITLE) => {
+                self.set_window_title(cmd.get(sys_cmd::SET_WINDOW_TITLE).clone(), id);
+            }
+            T::Window(id) if cmd.is(sys_cmd::SET_ACCESSIBILITY_FOCUS) => {
+                self.set_accessibility_focus(*cmd.get(sys_cmd::SET_ACCESSIBILITY_FOCUS), id);
+            }
+            #[cfg(feature = "diagnostics")]
+            T::Window(id) if cmd.is(sys_cmd::DUMP_ALLOCATION_COUNTS) => {
+                self.dump_allocation_counts(id);
+            }
+            T::Window(id) if cmd.is(sys_cmd::CAPTURE_WINDOW) => {
+                self.capture_window(*cmd.get(sys_cmd::CAPTURE_WINDOW), id);
+            }
             //T::Window(id) if cmd.is(sys_cmd::PASTE) => self.inner().do_paste(id),
             _ if cmd.is(sys_cmd::CLOSE_WINDOW) => {
                 tracing::warn!("CLOSE_WINDOW command must target a window.")
@@ -502,6 +1039,18 @@ impl AppRoot {
             _ if cmd.is(sys_cmd::SHOW_WINDOW) => {
                 tracing::warn!("SHOW_WINDOW command must target a window.")
             }
+            _ if cmd.is(sys_cmd::SET_WINDOW_ROOT) => {
+                tracing::warn!("SET_WINDOW_ROOT command must target a window.")
+            }
+            _ if cmd.is(sys_cmd::SET_WINDOW_TITLE) => {
+                tracing::warn!("SET_WINDOW_TITLE command must target a window.")
+            }
+            _ if cmd.is(sys_cmd::SET_ACCESSIBILITY_FOCUS) => {
+                tracing::warn!("SET_ACCESSIBILITY_FOCUS command must target a window.")
+            }
+            _ if cmd.is(sys_cmd::CAPTURE_WINDOW) => {
+                tracing::warn!("CAPTURE_WINDOW command must target a window.")
+            }
             // TODO - uncomment
             /*
             _ if cmd.is(sys_cmd::SHOW_OPEN_PANEL) => {
@@ -514,73 +1063,253 @@ impl AppRoot {
         }
     }
 
-    fn do_window_event(&mut self, source_id: WindowId, event: Event) -> Handled {
-        if matches!(
-            event,
-            Event::Command(..) | Event::Internal(InternalEvent::TargetedCommand(..))
-        ) {
-            unreachable!("commands should be dispatched via dispatch_cmd");
+    fn capture_window(&mut self, request: sys_cmd::CaptureRequest, window_id: WindowId) {
+        let mut inner = self.inner.borrow_mut();
+        let inner = inner.deref_mut();
+        let win = match inner.active_windows.get_mut(&window_id) {
+            Some(win) => win,
+            None => {
+                warn!("CAPTURE_WINDOW targets unknown window {:?}", window_id);
+                return;
+            }
+        };
+        let image = win
+            .capture(
+                &mut inner.debug_logger,
+                &mut inner.command_queue,
+                &mut inner.action_queue,
+                &inner.env,
+            )
+            .unwrap_or_else(|err| {
+                warn!("failed to capture window {:?}: {}", window_id, err);
+                ImageBuf::empty()
+            });
+        let result = request.token.make_result(image);
+        let _ = win
+            .ext_event_sink
+            .resolve_promise(result, request.widget, window_id);
+    }
+
+    /// Store the new locale in the root env, and notify every window.
+    fn set_locale(&mut self, locale: ArcStr) {
+        self.update_env(|env| env.set(Env::LOCALE, locale));
+    }
+
+    /// Store the new accessibility settings in the root env, and notify every window.
+    fn set_accessibility_settings(&mut self, settings: AccessibilitySettings) {
+        self.update_env(|env| env.set_accessibility_settings(settings));
+    }
+
+    /// Change the root env, and notify every window.
+    fn update_env(&mut self, update: impl FnOnce(&mut Env)) {
+        let mut inner = self.inner.borrow_mut();
+        let inner = inner.deref_mut();
+        update(&mut inner.env);
+        for win in inner.active_windows.values_mut() {
+            win.env_changed(
+                &mut inner.debug_logger,
+                &mut inner.command_queue,
+                &mut inner.action_queue,
+                &inner.env,
+            );
         }
+    }
 
-        if self.with_delegate(|delegate, ctx, env| delegate.on_event(ctx, source_id, &event, env))
-            == Handled::Yes
-        {
-            return Handled::Yes;
+    /// Set the title of a window, connected or not.
+    fn set_window_title(&mut self, title: ArcStr, window_id: WindowId) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(win) = inner.active_windows.get_mut(&window_id) {
+            win.set_title(title);
+        } else if let Some(pending) = inner.pending_windows.get_mut(&window_id) {
+            pending.title = title;
+        } else {
+            warn!("SET_WINDOW_TITLE targets unknown window {:?}", window_id);
         }
+    }
 
+    /// Replace the root widget of a window.
+    fn set_window_root(&mut self, cmd: Command, window_id: WindowId) {
+        let root = match cmd.get(sys_cmd::SET_WINDOW_ROOT).take() {
+            Some(root) => root,
+            None => {
+                warn!("SET_WINDOW_ROOT payload was already taken");
+                return;
+            }
+        };
         let mut inner = self.inner.borrow_mut();
         let inner = inner.deref_mut();
-
-        if let Some(win) = inner.active_windows.get_mut(&source_id) {
-            win.event(
-                event,
+        if let Some(win) = inner.active_windows.get_mut(&window_id) {
+            win.set_root(
+                root,
                 &mut inner.debug_logger,
                 &mut inner.command_queue,
                 &mut inner.action_queue,
                 &inner.env,
-            )
+            );
         } else {
-            // TODO - error message?
-            Handled::No
+            warn!("SET_WINDOW_ROOT targets unknown window {:?}", window_id);
         }
     }
 
-    /// A helper fn for setting up the `DelegateCtx`. Takes a closure with
-    /// an arbitrary return type `R`, and returns `Some(R)` if an `AppDelegate`
-    /// is configured.
-    fn with_delegate<R>(
-        &mut self,
-        f: impl FnOnce(&mut dyn AppDelegate, &mut DelegateCtx, &Env) -> R,
-    ) -> R {
+    /// Move the accessibility focus of a window.
+    fn set_accessibility_focus(&mut self, widget_id: Option<WidgetId>, window_id: WindowId) {
         let mut inner = self.inner.borrow_mut();
         let inner = inner.deref_mut();
-
-        let mut window = inner.active_windows.get_mut(&inner.main_window_id).unwrap();
-        let mut fake_widget_state;
-        let res = {
-            let mut global_state = GlobalPassCtx::new(
-                window.ext_event_sink.clone(),
+        if let Some(win) = inner.active_windows.get_mut(&window_id) {
+            win.set_accessibility_focus(
+                widget_id,
                 &mut inner.debug_logger,
                 &mut inner.command_queue,
                 &mut inner.action_queue,
-                &mut window.timers,
-                window.mock_timer_queue.as_mut(),
-                &window.handle,
-                inner.main_window_id,
-                window.focus,
+                &inner.env,
             );
-            fake_widget_state = window.root.state.clone();
-
-            let main_root_ctx = WidgetCtx {
-                global_state: &mut global_state,
-                widget_state: &mut window.root.state,
-            };
-            let main_root_widget = WidgetMut {
-                parent_widget_state: &mut fake_widget_state,
-                inner: Box::from_widget_and_ctx(&mut window.root.inner, main_root_ctx),
-            };
+        }
+    }
 
-            let mut ctx = DelegateCtx {
+    /// Log the allocation counts of a window, and reset them.
+    #[cfg(feature = "diagnostics")]
+    fn dump_allocation_counts(&mut self, window_id: WindowId) {
+        let mut inner = self.inner.borrow_mut();
+        let win = match inner.active_windows.get_mut(&window_id) {
+            Some(win) => win,
+            None => {
+                warn!(
+                    "DUMP_ALLOCATION_COUNTS targets unknown window {:?}",
+                    window_id
+                );
+                return;
+            }
+        };
+        let counts = &mut win.frame_allocations;
+        let per_frame = |count: u64| count as f64 / counts.frames.max(1) as f64;
+        info!(
+            "Allocations in window {:?}: last frame made {} allocations ({} bytes) and {} deallocations; \
+            {} frames since last dump, averaging {:.1} allocations ({:.0} bytes) per frame",
+            window_id,
+            counts.last_frame.allocations,
+            counts.last_frame.bytes_allocated,
+            counts.last_frame.deallocations,
+            counts.frames,
+            per_frame(counts.total.allocations),
+            per_frame(counts.total.bytes_allocated),
+        );
+        counts.reset();
+    }
+
+    /// Run a batch of commands, delivering all widget-targeted commands
+    /// in a single pass per window.
+    fn do_cmd_batch(&mut self, cmds: Vec<Command>) {
+        let mut widget_cmds = Vec::new();
+        for cmd in cmds {
+            if let Target::Widget(_) = cmd.target() {
+                if self.with_delegate(|delegate, ctx, env| delegate.on_command(ctx, &cmd, env))
+                    == Handled::No
+                {
+                    widget_cmds.push(cmd);
+                }
+            } else {
+                self.do_cmd(cmd);
+            }
+        }
+
+        if !widget_cmds.is_empty() {
+            self.inner().dispatch_cmd_batch(widget_cmds);
+        }
+    }
+
+    fn do_window_event(&mut self, source_id: WindowId, event: Event) -> Handled {
+        if matches!(
+            event,
+            Event::Command(..)
+                | Event::Internal(InternalEvent::TargetedCommand(..))
+                | Event::Internal(InternalEvent::TargetedCommands(..))
+        ) {
+            unreachable!("commands should be dispatched via dispatch_cmd");
+        }
+
+        let event = {
+            let mut inner = self.inner();
+            let inner = inner.deref_mut();
+            match filter_event(
+                &mut inner.event_spies,
+                inner.event_interceptor.as_mut(),
+                source_id,
+                event,
+                Instant::now(),
+            ) {
+                Some(event) => event,
+                None => return Handled::Yes,
+            }
+        };
+
+        if self.with_delegate(|delegate, ctx, env| delegate.on_event(ctx, source_id, &event, env))
+            == Handled::Yes
+        {
+            return Handled::Yes;
+        }
+
+        let mut inner = self.inner.borrow_mut();
+        let inner = inner.deref_mut();
+
+        if let Event::MouseDown(_) | Event::MouseUp(_) | Event::MouseMove(_) | Event::Wheel(_) =
+            event
+        {
+            inner.pointer_window = Some(source_id);
+        }
+
+        if let Some(win) = inner.active_windows.get_mut(&source_id) {
+            win.event(
+                event,
+                &mut inner.debug_logger,
+                &mut inner.command_queue,
+                &mut inner.action_queue,
+                &inner.env,
+            )
+        } else {
+            // TODO - error message?
+            Handled::No
+        }
+    }
+
+    /// A helper fn for setting up the `DelegateCtx`. Takes a closure with
+    /// an arbitrary return type `R`, and returns `Some(R)` if an `AppDelegate`
+    /// is configured.
+    fn with_delegate<R>(
+        &mut self,
+        f: impl FnOnce(&mut dyn AppDelegate, &mut DelegateCtx, &Env) -> R,
+    ) -> R {
+        let mut inner = self.inner.borrow_mut();
+        let inner = inner.deref_mut();
+
+        let mut window = inner.active_windows.get_mut(&inner.main_window_id).unwrap();
+        let mut fake_widget_state;
+        let res = {
+            let mut global_state = GlobalPassCtx::new(
+                window.ext_event_sink.clone(),
+                &mut inner.debug_logger,
+                &mut inner.command_queue,
+                &mut inner.action_queue,
+                &mut window.timers,
+                window.mock_timer_queue.as_mut(),
+                &window.handle,
+                inner.main_window_id,
+                window.focus,
+                window.window_focused,
+                window.focus_visible,
+                window.accessibility_focus,
+            );
+            fake_widget_state = window.root.state.clone();
+
+            let main_root_ctx = WidgetCtx {
+                global_state: &mut global_state,
+                widget_state: &mut window.root.state,
+            };
+            let main_root_widget = WidgetMut {
+                parent_widget_state: &mut fake_widget_state,
+                inner: Box::from_widget_and_ctx(&mut window.root.inner, main_root_ctx),
+            };
+
+            let mut ctx = DelegateCtx {
                 //command_queue: &mut inner.command_queue,
                 ext_event_queue: &mut inner.ext_event_queue,
                 main_root_widget,
@@ -637,24 +1366,123 @@ impl AppRoot {
             title,
             transparent: config.transparent.unwrap_or(false),
             size_policy: config.size_policy,
+            keep_aspect_ratio: config.keep_aspect_ratio,
+            min_size: config.min_size,
+            background_color: config.background_color.clone(),
+            clear: config.clear.unwrap_or(true),
+            replay_last_frame: config.replay_last_frame.unwrap_or(false),
+            initial_size: config.size,
+            layout_on_connect: config.layout_on_connect.unwrap_or(false),
         };
 
         let existing = self.inner.borrow_mut().pending_windows.insert(id, pending);
         assert!(existing.is_none(), "duplicate pending window {id:?}");
 
-        builder.build()
+        let handle = builder.build()?;
+        // Most platforms connect the window while building it. Windows sized to
+        // their content are then laid out now, so that they're shown at their
+        // final size; otherwise they're laid out before their first paint.
+        if config.size_policy == WindowSizePolicy::Content {
+            self.layout_window(id);
+        }
+        Ok(handle)
+    }
+
+    /// Lay out a window, if it's connected.
+    fn layout_window(&mut self, window_id: WindowId) {
+        {
+            let mut inner = self.inner.borrow_mut();
+            let inner = inner.deref_mut();
+            if let Some(win) = inner.active_windows.get_mut(&window_id) {
+                win.layout(
+                    &mut inner.debug_logger,
+                    &mut inner.command_queue,
+                    &mut inner.action_queue,
+                    &inner.env,
+                );
+            }
+            inner.invalidate_and_finalize();
+        }
+        self.process_window_requests();
+    }
+}
+
+impl ExtEventHost for AppRoot {
+    fn recv_ext_message(&mut self) -> Option<ExtMessage> {
+        self.inner().ext_event_queue.recv()
+    }
+
+    fn has_pending_ext_messages(&self) -> bool {
+        self.inner().ext_event_queue.has_pending_items()
+    }
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn deliver_ext_message(&mut self, message: ExtMessage) {
+        match message {
+            ExtMessage::Command(selector, payload, target) => {
+                self.do_cmd(Command::from_ext(selector, payload, target))
+            }
+            ExtMessage::Promise(promise_result, widget_id, window_id) => {
+                // TODO
+                self.do_window_event(
+                    window_id,
+                    Event::Internal(InternalEvent::RoutePromiseResult(promise_result, widget_id)),
+                );
+            }
+        }
     }
 }
 
 impl AppRootInner {
-    /// invalidate any window handles that need it.
+    /// Run the work that waits for the end of an update cycle, and invalidate
+    /// any window handles that need it.
     ///
     /// This should always be called at the end of an event update cycle,
     /// including for lifecycle events.
-    fn invalidate_paint_regions(&mut self) {
-        for win in self.active_windows.values_mut() {
+    fn invalidate_and_finalize(&mut self) {
+        let AppRootInner {
+            active_windows,
+            debug_logger,
+            command_queue,
+            action_queue,
+            env,
+            batching_invalidations,
+            ..
+        } = self;
+        let queued_work = command_queue.len() + action_queue.len();
+        for win in active_windows.values_mut() {
+            win.finalize_update(debug_logger, command_queue, action_queue, env);
+            if *batching_invalidations {
+                win.coalesce_invalid_to_bounds();
+            }
             win.invalidate_paint_region();
         }
+        // Commands and actions submitted by after-layout callbacks would
+        // otherwise wait for the next platform event.
+        if command_queue.len() + action_queue.len() > queued_work {
+            self.schedule_idle(RUN_COMMANDS_TOKEN);
+        }
+    }
+
+    fn has_queued_work(&self) -> bool {
+        !self.command_queue.is_empty() || !self.action_queue.is_empty()
+    }
+
+    /// Ask the platform to call us back on idle with `token`, to resume work
+    /// that went over the idle budget.
+    fn schedule_idle(&mut self, token: IdleToken) {
+        let idle_handle = self
+            .active_windows
+            .values()
+            .find_map(|win| win.handle.get_idle_handle());
+        if let Some(mut idle_handle) = idle_handle {
+            idle_handle.schedule_idle(token);
+        } else {
+            error!("failed to get idle handle");
+        }
     }
 
     /// Set the idle handle that will be used to wake us when external events arrive.
@@ -729,11 +1557,37 @@ impl AppRootInner {
     fn request_configure_window(&mut self, config: &WindowConfig, id: WindowId) {
         if let Some(win) = self.active_windows.get_mut(&id) {
             config.apply_to_handle(&mut win.handle);
+            if config.keep_aspect_ratio.is_some() {
+                win.keep_aspect_ratio = config.keep_aspect_ratio;
+            }
+            if config.min_size.is_some() {
+                win.min_size = config.min_size;
+                if win.size_policy == WindowSizePolicy::Content {
+                    win.root.state.needs_layout = true;
+                }
+            }
+            if config.background_color.is_some() || config.clear.is_some() {
+                if config.background_color.is_some() {
+                    win.background_color = config.background_color.clone();
+                }
+                if let Some(clear) = config.clear {
+                    win.clear = clear;
+                }
+                win.invalid.add_rect(win.size.to_rect());
+            }
+            if let Some(replay) = config.replay_last_frame {
+                win.replay_last_frame = replay;
+                if !replay {
+                    win.last_frame = None;
+                }
+            }
         }
     }
 
     fn dispatch_cmd(&mut self, cmd: Command) -> Handled {
-        self.invalidate_paint_regions();
+        if !self.batching_invalidations {
+            self.invalidate_and_finalize();
+        }
         match cmd.target() {
             Target::Global => {
                 for w in self.active_windows.values_mut() {
@@ -784,6 +1638,17 @@ impl AppRootInner {
                     }
                 }
             }
+            Target::Pointer => {
+                let target = self
+                    .pointer_window
+                    .and_then(|id| self.active_windows.get(&id))
+                    .and_then(|w| w.widget_under_pointer())
+                    .map(|widget| widget.id());
+                match target {
+                    Some(id) => return self.dispatch_cmd(cmd.to(id)),
+                    None => warn!("{:?} dropped: no widget under the pointer", cmd),
+                }
+            }
             Target::Auto => {
                 tracing::error!("{:?} reached window handler with `Target::Auto`", cmd);
             }
@@ -791,6 +1656,34 @@ impl AppRootInner {
         Handled::No
     }
 
+    /// Send widget-targeted commands to every window that might contain their
+    /// targets, with one traversal per window.
+    fn dispatch_cmd_batch(&mut self, cmds: Vec<Command>) {
+        if !self.batching_invalidations {
+            self.invalidate_and_finalize();
+        }
+        for w in self.active_windows.values_mut() {
+            let window_cmds: Vec<_> = cmds
+                .iter()
+                .filter(|cmd| match cmd.target() {
+                    Target::Widget(id) => w.may_contain_widget(id),
+                    _ => false,
+                })
+                .cloned()
+                .collect();
+            if window_cmds.is_empty() {
+                continue;
+            }
+            w.event(
+                Event::Internal(InternalEvent::TargetedCommands(window_cmds)),
+                &mut self.debug_logger,
+                &mut self.command_queue,
+                &mut self.action_queue,
+                &self.env,
+            );
+        }
+    }
+
     #[cfg(FALSE)]
     fn show_open_panel(&mut self, cmd: Command, window_id: WindowId) {
         let options = cmd.get(sys_cmd::SHOW_OPEN_PANEL).to_owned();
@@ -855,6 +1748,120 @@ impl AppRootInner {
 
 // ---
 
+/// How many `MouseMove` events may be synthesized after layout between two frames.
+const MAX_SYNTHETIC_MOUSE_MOVES: u32 = 4;
+
+/// How many times high-priority commands may be run before a frame, see
+/// [`Command::with_priority`].
+pub(crate) const MAX_HIGH_PRIORITY_ROUNDS: usize = 4;
+
+/// The rects of `region`, without those covered by another one.
+///
+/// Widgets often request paint for the same area several times in a batch of
+/// events, and the platform is asked to invalidate each rect separately.
+pub(crate) fn coalesced_rects(region: &Region) -> Vec<Rect> {
+    let rects = region.rects();
+    let covers = |outer: Rect, inner: Rect| outer.union(inner) == outer;
+    let mut coalesced = Vec::with_capacity(rects.len());
+    for (i, &rect) in rects.iter().enumerate() {
+        let is_covered = rects.iter().enumerate().any(|(j, &other)| {
+            // Of two equal rects, the first one is kept.
+            j != i && covers(other, rect) && (j < i || !covers(rect, other))
+        });
+        if !is_covered {
+            coalesced.push(rect);
+        }
+    }
+    coalesced
+}
+
+/// Whether an idle callback went over its budget.
+pub(crate) fn deadline_passed(deadline: Option<Instant>, now: Instant) -> bool {
+    deadline.map_or(false, |deadline| now >= deadline)
+}
+
+/// Add and remove the layout rect observers requested during a pass.
+fn apply_layout_rect_requests(
+    observers: &mut Vec<LayoutRectObserver>,
+    requests: &mut Vec<LayoutRectRequest>,
+) {
+    for request in requests.drain(..) {
+        let existing = observers
+            .iter()
+            .position(|entry| entry.observer == request.observer && entry.target == request.target);
+        match (existing, request.observe) {
+            (None, true) => observers.push(LayoutRectObserver {
+                observer: request.observer,
+                target: request.target,
+                last_rect: None,
+            }),
+            (Some(idx), false) => {
+                observers.remove(idx);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Record the window layout rects of the widgets of `ids` in `widget`'s subtree.
+fn collect_window_layout_rects(
+    widget: WidgetRef<'_, dyn Widget>,
+    ids: &HashSet<WidgetId>,
+    rects: &mut HashMap<WidgetId, Rect>,
+) {
+    let state = widget.state();
+    if ids.contains(&state.id) {
+        rects.insert(state.id, state.window_layout_rect());
+    }
+    for child in widget.children() {
+        if rects.len() == ids.len() {
+            return;
+        }
+        collect_window_layout_rects(child, ids, rects);
+    }
+}
+
+/// The work area of the monitor showing the window, or of the primary monitor
+/// if the window isn't positioned yet.
+fn screen_work_area(handle: &WindowHandle) -> Option<Rect> {
+    let position = handle.get_position();
+    let monitors = Screen::get_monitors();
+    monitors
+        .iter()
+        .find(|monitor| monitor.virtual_rect().contains(position))
+        .or_else(|| monitors.iter().find(|monitor| monitor.is_primary()))
+        .map(|monitor| monitor.virtual_work_rect())
+}
+
+/// Whether the hot state of some widget doesn't match the pointer position.
+///
+/// `pos` is `None` where an ancestor clips the pointer out.
+fn hot_state_is_stale(widget: WidgetRef<'_, dyn Widget>, pos: Option<Point>) -> bool {
+    let state = widget.state();
+    if state.is_stashed {
+        return false;
+    }
+    let should_be_hot = pos.map_or(false, |pos| state.window_layout_rect().winding(pos) != 0);
+    // A change waiting for the hover delay isn't stale.
+    let will_be_hot = state.pending_hot.map_or(state.is_hot, |(is_hot, _)| is_hot);
+    let origin = state.window_origin().to_vec2();
+    let child_pos = state
+        .clip_pointer_pos(pos.map(|pos| pos - origin))
+        .map(|pos| pos + origin);
+    // Children covered by the topmost one don't see the pointer.
+    let hit_child = child_pos
+        .and_then(|pos| widget.get_child_at_pos(pos - origin))
+        .map(|child| child.state().id);
+    will_be_hot != should_be_hot
+        || widget.children().into_iter().any(|child| {
+            let child_pos = match hit_child {
+                Some(id) if id != child.state().id => None,
+                _ => child_pos,
+            };
+            hot_state_is_stale(child, child_pos)
+        })
+}
+
 impl WindowRoot {
     pub(crate) fn new(
         id: WindowId,
@@ -874,20 +1881,61 @@ impl WindowRoot {
             invalid: Region::EMPTY,
             title,
             transparent,
+            window_state: WindowState::Restored,
+            keep_aspect_ratio: None,
+            background_color: None,
+            clear: true,
+            replay_last_frame: false,
+            min_size: None,
+            screen_size: None,
+            last_frame: None,
+            connect_frame: None,
+            frame_changed: true,
             last_anim: None,
             last_mouse_pos: None,
+            last_mouse_event: None,
+            cursor: Cursor::Arrow,
+            synthetic_mouse_moves: 0,
+            hot_suppressed: false,
             focus: None,
+            window_focused: true,
+            focus_visible: false,
+            accessibility_focus: None,
             ext_event_sink,
             handle,
             timers: HashMap::new(),
             mock_timer_queue,
             ime_handlers: Vec::new(),
             ime_focus_change: None,
+            brush_cache: BrushCache::default(),
+            render_text: None,
+            render_scale: None,
+            paint_offscreen: false,
+            layout_budget: None,
+            layout_deadline: None,
+            hit_test_target: None,
+            drag_session: None,
+            held_keys: HashMap::new(),
+            last_click: None,
+            after_layout: Vec::new(),
+            after_layout_ready: Vec::new(),
+            after_layout_callback: None,
+            layout_rect_observers: Vec::new(),
+            #[cfg(feature = "diagnostics")]
+            frame_allocations: Default::default(),
         }
     }
 
     // TODO - Add 'get_global_ctx() -> GlobalPassCtx' method
 
+    /// The current time, or the mocked time in unit tests.
+    pub(crate) fn now(&self) -> Instant {
+        match &self.mock_timer_queue {
+            Some(timer_queue) => timer_queue.now(),
+            None => Instant::now(),
+        }
+    }
+
     /// `true` iff any child requested an animation frame since the last `AnimFrame` event.
     pub(crate) fn wants_animation_frame(&self) -> bool {
         self.root.state().request_anim
@@ -919,19 +1967,7 @@ impl WindowRoot {
         if widget_state.children_changed {
             // Anytime widgets are removed we check and see if any of those
             // widgets had IME sessions and unregister them if so.
-            let WindowRoot {
-                ime_handlers,
-                handle,
-                ..
-            } = self;
-            ime_handlers.retain(|(token, v)| {
-                let will_retain = v.is_alive();
-                if !will_retain {
-                    tracing::debug!("{:?} removed", token);
-                    handle.remove_text_field(*token);
-                }
-                will_retain
-            });
+            self.remove_dead_ime_handlers();
 
             self.lifecycle(
                 &LifeCycle::Internal(InternalLifeCycle::RouteWidgetAdded),
@@ -1022,23 +2058,86 @@ impl WindowRoot {
         env: &Env,
     ) -> Handled {
         match &event {
-            Event::WindowSize(size) => self.size = *size,
+            // The window was already laid out and painted at this size as it
+            // was connected, see `layout_on_connect`.
+            Event::WindowSize(size) if self.connect_frame.is_some() && *size == self.size => {
+                return Handled::No;
+            }
+            Event::WindowSize(size) => {
+                self.correct_aspect_ratio(*size);
+                self.size = *size;
+                self.brush_cache.clear();
+            }
+            Event::KeyDown(key_event) => {
+                self.set_focus_visible(true);
+                if key_event.key == KbKey::Escape && self.drag_session.is_some() {
+                    self.cancel_drag(debug_logger, command_queue, action_queue, env);
+                    return Handled::Yes;
+                }
+                if key_event.key == KbKey::F12 && env.get(Env::DEBUG_LAYOUT_DUMP) {
+                    info!(
+                        "Layout of window {:?}:\n{}",
+                        self.id,
+                        self.root.as_dyn().serialize_layout()
+                    );
+                }
+            }
             Event::MouseDown(e) | Event::MouseUp(e) | Event::MouseMove(e) | Event::Wheel(e) => {
-                self.last_mouse_pos = Some(e.pos)
+                if let Event::MouseDown(_) = &event {
+                    self.set_focus_visible(false);
+                    self.hot_suppressed = false;
+                }
+                // Synthetic moves repeat the last position, so they don't reset the count.
+                if self.last_mouse_pos != Some(e.pos) {
+                    self.synthetic_mouse_moves = 0;
+                    // Hot state follows a moving pointer again.
+                    self.hot_suppressed = false;
+                }
+                self.last_mouse_pos = Some(e.pos);
+                self.last_mouse_event = Some(e.clone());
+                if let Event::MouseMove(_) = &event {
+                    if env.get(Env::DEBUG_HIT_TEST) {
+                        self.update_hit_test_target(Some(e.pos));
+                    }
+                }
+            }
+            // A captured pointer is still tracked outside the window.
+            Event::Internal(InternalEvent::MouseLeave) if !self.root.has_active() => {
+                self.last_mouse_pos = None;
+                self.hot_suppressed = false;
+                self.update_hit_test_target(None);
+            }
+            Event::WindowStateChanged {
+                old: WindowState::Minimized,
+                ..
+            } => {
+                // Paint was skipped while minimized, so everything is stale.
+                self.invalid.add_rect(self.size.to_rect());
             }
-            Event::Internal(InternalEvent::MouseLeave) => self.last_mouse_pos = None,
             _ => (),
         }
 
         let event = match event {
             Event::Timer(token) => {
-                if let Some(widget_id) = self.timers.get(&token) {
+                if let Some((widget_id, _)) = self.timers.get(&token) {
                     Event::Internal(InternalEvent::RouteTimer(token, *widget_id))
                 } else {
                     error!("No widget found for timer {:?}", token);
                     return Handled::No;
                 }
             }
+            Event::KeyDown(key_event) => Event::KeyDown(self.track_key_down(key_event)),
+            Event::MouseDown(mouse_event) => {
+                Event::MouseDown(self.track_mouse_down(mouse_event, env))
+            }
+            Event::MouseMove(mouse_event) => {
+                self.forget_click_if_moved(mouse_event.window_pos, env);
+                Event::MouseMove(mouse_event)
+            }
+            Event::KeyUp(key_event) => {
+                self.held_keys.remove(&key_event.code);
+                Event::KeyUp(key_event)
+            }
             other => other,
         };
 
@@ -1054,7 +2153,7 @@ impl WindowRoot {
         }
 
         let mut widget_state = WidgetState::new(self.root.id(), Some(self.size), "<root>");
-        let is_handled = {
+        let (is_handled, drag_request, hot_suppressed) = {
             let mut global_state = GlobalPassCtx::new(
                 self.ext_event_sink.clone(),
                 debug_logger,
@@ -1065,7 +2164,19 @@ impl WindowRoot {
                 &self.handle,
                 self.id,
                 self.focus,
+                self.window_focused,
+                self.focus_visible,
+                self.accessibility_focus,
             );
+            global_state.hot_suppressed = self.hot_suppressed;
+            if let Event::KeyDown(key_event) | Event::KeyUp(key_event) = &event {
+                global_state.key_event = Some(key_event.clone());
+                global_state.key_repeat_count =
+                    self.held_keys.get(&key_event.code).copied().unwrap_or(0);
+            }
+            if let Event::Internal(InternalEvent::RouteAfterLayout(_)) = &event {
+                global_state.after_layout_callback = self.after_layout_callback.take();
+            }
             let mut notifications = VecDeque::new();
 
             let mut ctx = EventCtx {
@@ -1078,9 +2189,11 @@ impl WindowRoot {
             };
 
             {
-                ctx.global_state
-                    .debug_logger
-                    .push_important_span(&format!("EVENT {}", event.short_name()));
+                if ctx.global_state.debug_logger.is_active() {
+                    ctx.global_state
+                        .debug_logger
+                        .push_important_span(&format!("EVENT {}", event.short_name()));
+                }
                 let _span = info_span!("event").entered();
                 self.root.on_event(&mut ctx, &event, env);
                 ctx.global_state.debug_logger.pop_span();
@@ -1093,8 +2206,21 @@ impl WindowRoot {
                 }
             }
 
-            Handled::from(ctx.is_handled)
+            let is_handled = Handled::from(ctx.is_handled);
+            self.after_layout
+                .append(&mut global_state.after_layout_requests);
+            apply_layout_rect_requests(
+                &mut self.layout_rect_observers,
+                &mut global_state.layout_rect_requests,
+            );
+            (
+                is_handled,
+                global_state.drag_request.take(),
+                global_state.hot_suppressed,
+            )
         };
+        let hot_resumed = self.hot_suppressed && !hot_suppressed;
+        self.hot_suppressed = hot_suppressed;
 
         // Clean up the timer token and do it immediately after the event handling
         // because the token may be reused and re-added in a lifecycle pass below.
@@ -1103,16 +2229,16 @@ impl WindowRoot {
         }
 
         if let Some(cursor) = &widget_state.cursor {
-            self.handle.set_cursor(cursor);
+            self.set_cursor(cursor.clone());
         } else if matches!(
             event,
             Event::MouseMove(..) | Event::Internal(InternalEvent::MouseLeave)
         ) {
-            self.handle.set_cursor(&Cursor::Arrow);
+            self.set_cursor(Cursor::Arrow);
         }
 
         if matches!(
-            (event, self.size_policy),
+            (&event, self.size_policy),
             (Event::WindowSize(_), WindowSizePolicy::Content)
         ) {
             // Because our initial size can be zero, the window system won't ask us to paint.
@@ -1131,19 +2257,86 @@ impl WindowRoot {
 
         self.root.as_dyn().debug_validate(false);
 
+        if hot_resumed {
+            self.synthesize_mouse_move(debug_logger, command_queue, action_queue, env);
+        }
+        if let Event::KeyDown(key_event) | Event::KeyUp(key_event) = &event {
+            self.send_modifiers_to_pointer(
+                key_event.mods,
+                debug_logger,
+                command_queue,
+                action_queue,
+                env,
+            );
+        }
+        if !is_handled.is_handled() {
+            if let Some(request) = self.context_menu_request(&event) {
+                self.event(
+                    Event::ContextMenuRequested(request),
+                    debug_logger,
+                    command_queue,
+                    action_queue,
+                    env,
+                );
+            }
+        }
+
+        let source_removed = self.drag_session.as_ref().map_or(false, |session| {
+            self.root
+                .as_dyn()
+                .find_widget_by_id(session.source)
+                .is_none()
+        });
+        if source_removed {
+            self.cancel_drag(debug_logger, command_queue, action_queue, env);
+        }
+        if let Some(drag_request) = drag_request {
+            self.start_drag(drag_request, debug_logger, command_queue, action_queue, env);
+        }
+        match &event {
+            Event::MouseMove(e) => self.drag_pointer_event(
+                e.pos,
+                false,
+                debug_logger,
+                command_queue,
+                action_queue,
+                env,
+            ),
+            Event::MouseUp(e) => {
+                self.drag_pointer_event(e.pos, true, debug_logger, command_queue, action_queue, env)
+            }
+            _ => (),
+        }
+
         is_handled
     }
 
-    pub(crate) fn lifecycle(
+    /// Start a drag requested with [`EventCtx::begin_drag`].
+    ///
+    /// The preview isn't part of the widget tree, so we run the passes it needs
+    /// here: it gets `WidgetAdded` and a single layout.
+    fn start_drag(
         &mut self,
-        event: &LifeCycle,
+        request: DragRequest,
         debug_logger: &mut DebugLogger,
         command_queue: &mut CommandQueue,
         action_queue: &mut ActionQueue,
         env: &Env,
-        process_commands: bool,
     ) {
-        let mut widget_state = WidgetState::new(self.root.id(), Some(self.size), "<root>");
+        if self.drag_session.is_some() {
+            warn!("begin_drag called while a drag is already active, ignoring");
+            return;
+        }
+        let pos = match self.last_mouse_pos {
+            Some(pos) => pos,
+            None => {
+                warn!("begin_drag called while the pointer is outside the window, ignoring");
+                return;
+            }
+        };
+
+        let mut session = DragSession::new(request);
+        let mut widget_state = WidgetState::new(session.preview.id(), None, "<drag preview>");
         let mut global_state = GlobalPassCtx::new(
             self.ext_event_sink.clone(),
             debug_logger,
@@ -1154,6 +2347,236 @@ impl WindowRoot {
             &self.handle,
             self.id,
             self.focus,
+            self.window_focused,
+            self.focus_visible,
+            self.accessibility_focus,
+        );
+        {
+            let mut ctx = LifeCycleCtx {
+                global_state: &mut global_state,
+                widget_state: &mut widget_state,
+            };
+            let event = LifeCycle::Internal(InternalLifeCycle::RouteWidgetAdded);
+            session.preview.lifecycle(&mut ctx, &event, env);
+        }
+        let mut layout_ctx = LayoutCtx {
+            global_state: &mut global_state,
+            widget_state: &mut widget_state,
+            mouse_pos: None,
+            resize_pending: false,
+        };
+        let bc = BoxConstraints::new(Size::ZERO, self.size);
+        session.preview.layout(&mut layout_ctx, &bc, env);
+        layout_ctx.place_child(&mut session.preview, pos, env);
+
+        self.invalid.add_rect(session.preview.paint_rect());
+        self.drag_session = Some(session);
+    }
+
+    /// Move the drag preview, update the drop target and send `Drag` events.
+    ///
+    /// If `released` is true, the payload is dropped on the target, if any,
+    /// and the drag ends.
+    fn drag_pointer_event(
+        &mut self,
+        pos: Point,
+        released: bool,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        // Taken out of `self` while we send events to the widget tree.
+        let mut session = match self.drag_session.take() {
+            Some(session) => session,
+            None => return,
+        };
+
+        self.invalid.add_rect(session.preview.paint_rect());
+        session.preview.state.origin = pos;
+        if !released {
+            self.invalid.add_rect(session.preview.paint_rect());
+        }
+
+        let candidates = drop_candidates(self.root.as_dyn(), pos);
+        let old_target = session.target.filter(|target| candidates.contains(target));
+        let mut new_target = old_target;
+        // Widgets that were just hovered, and are deeper than the current
+        // target, get a chance to become the target.
+        for &id in &candidates {
+            if Some(id) == old_target {
+                break;
+            }
+            if session.hovered.contains(&id) {
+                continue;
+            }
+            let event = session.make_event(DragPhase::Enter, pos);
+            let handled =
+                self.route_drag_event(id, event, debug_logger, command_queue, action_queue, env);
+            if handled.is_handled() {
+                new_target = Some(id);
+                break;
+            }
+        }
+
+        if new_target != session.target {
+            if let Some(target) = session.target {
+                let event = session.make_event(DragPhase::Leave, pos);
+                self.route_drag_event(
+                    target,
+                    event,
+                    debug_logger,
+                    command_queue,
+                    action_queue,
+                    env,
+                );
+            }
+        } else if let (Some(target), false) = (new_target, released) {
+            let event = session.make_event(DragPhase::Over, pos);
+            self.route_drag_event(
+                target,
+                event,
+                debug_logger,
+                command_queue,
+                action_queue,
+                env,
+            );
+        }
+        session.target = new_target;
+        session.hovered = candidates;
+
+        if released {
+            if let Some(target) = session.target {
+                let event = session.make_event(DragPhase::Drop, pos);
+                self.route_drag_event(
+                    target,
+                    event,
+                    debug_logger,
+                    command_queue,
+                    action_queue,
+                    env,
+                );
+            }
+            self.remove_drag_preview(session, debug_logger, command_queue, action_queue, env);
+        } else {
+            self.drag_session = Some(session);
+        }
+    }
+
+    /// Cancel the active drag, if any.
+    ///
+    /// The preview is removed, the current drop target and the source
+    /// receive a `Cancel` drag event, and the active widgets stop being active.
+    fn cancel_drag(
+        &mut self,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        let session = match self.drag_session.take() {
+            Some(session) => session,
+            None => return,
+        };
+        self.invalid.add_rect(session.preview.paint_rect());
+
+        let pos = session.preview.state.origin;
+        let mut recipients = Vec::new();
+        recipients.extend(session.target);
+        if session.target != Some(session.source) {
+            recipients.push(session.source);
+        }
+        for id in recipients {
+            let event = session.make_event(DragPhase::Cancel, pos);
+            self.route_drag_event(id, event, debug_logger, command_queue, action_queue, env);
+        }
+        self.remove_drag_preview(session, debug_logger, command_queue, action_queue, env);
+
+        // The source captured the pointer to start the drag; the button is
+        // still down, but the gesture is over.
+        self.event(
+            Event::Internal(InternalEvent::PointerClaimed),
+            debug_logger,
+            command_queue,
+            action_queue,
+            env,
+        );
+    }
+
+    /// Send `WidgetRemoved` to the preview of a drag that ended.
+    fn remove_drag_preview(
+        &mut self,
+        mut session: DragSession,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        let mut widget_state = WidgetState::new(session.preview.id(), None, "<drag preview>");
+        let mut global_state = GlobalPassCtx::new(
+            self.ext_event_sink.clone(),
+            debug_logger,
+            command_queue,
+            action_queue,
+            &mut self.timers,
+            self.mock_timer_queue.as_mut(),
+            &self.handle,
+            self.id,
+            self.focus,
+            self.window_focused,
+            self.focus_visible,
+            self.accessibility_focus,
+        );
+        let mut ctx = LifeCycleCtx {
+            global_state: &mut global_state,
+            widget_state: &mut widget_state,
+        };
+        session
+            .preview
+            .lifecycle(&mut ctx, &LifeCycle::WidgetRemoved, env);
+    }
+
+    fn route_drag_event(
+        &mut self,
+        target: WidgetId,
+        event: DragEvent,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) -> Handled {
+        self.event(
+            Event::Internal(InternalEvent::RouteDrag(target, event)),
+            debug_logger,
+            command_queue,
+            action_queue,
+            env,
+        )
+    }
+
+    pub(crate) fn lifecycle(
+        &mut self,
+        event: &LifeCycle,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+        process_commands: bool,
+    ) {
+        let mut widget_state = WidgetState::new(self.root.id(), Some(self.size), "<root>");
+        let mut global_state = GlobalPassCtx::new(
+            self.ext_event_sink.clone(),
+            debug_logger,
+            command_queue,
+            action_queue,
+            &mut self.timers,
+            self.mock_timer_queue.as_mut(),
+            &self.handle,
+            self.id,
+            self.focus,
+            self.window_focused,
+            self.focus_visible,
+            self.accessibility_focus,
         );
         let mut ctx = LifeCycleCtx {
             global_state: &mut global_state,
@@ -1161,114 +2584,844 @@ impl WindowRoot {
         };
 
         {
-            ctx.global_state
-                .debug_logger
-                .push_important_span(&format!("LIFECYCLE {}", event.short_name()));
+            if ctx.global_state.debug_logger.is_active() {
+                ctx.global_state
+                    .debug_logger
+                    .push_important_span(&format!("LIFECYCLE {}", event.short_name()));
+            }
             let _span = info_span!("lifecycle").entered();
             self.root.lifecycle(&mut ctx, event, env);
             ctx.global_state.debug_logger.pop_span();
         }
+        apply_layout_rect_requests(
+            &mut self.layout_rect_observers,
+            &mut global_state.layout_rect_requests,
+        );
+
+        self.post_event_processing(
+            &mut widget_state,
+            debug_logger,
+            command_queue,
+            action_queue,
+            env,
+            process_commands,
+        );
+    }
+
+    /// Update whether the window has focus from the platform, and send
+    /// [`StatusChange::WindowFocusChanged`](crate::StatusChange::WindowFocusChanged)
+    /// to every widget if it changed.
+    pub(crate) fn set_window_focused(
+        &mut self,
+        focused: bool,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        if self.window_focused == focused {
+            return;
+        }
+        self.window_focused = focused;
+        if !focused {
+            // We won't see the releases of keys held while another window has focus.
+            self.held_keys.clear();
+        }
+        self.lifecycle(
+            &LifeCycle::Internal(InternalLifeCycle::RouteWindowFocusChanged(focused)),
+            debug_logger,
+            command_queue,
+            action_queue,
+            env,
+            false,
+        );
+    }
+
+    /// Move the accessibility focus to `new`, scroll it into view, and move
+    /// keyboard focus along if the env asks for it.
+    ///
+    /// See [`SET_ACCESSIBILITY_FOCUS`](crate::command::SET_ACCESSIBILITY_FOCUS).
+    pub(crate) fn set_accessibility_focus(
+        &mut self,
+        new: Option<WidgetId>,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        let old = self.accessibility_focus;
+        if old == new {
+            return;
+        }
+        self.accessibility_focus = new;
+        self.lifecycle(
+            &LifeCycle::Internal(InternalLifeCycle::RouteAccessibilityFocusChanged { old, new }),
+            debug_logger,
+            command_queue,
+            action_queue,
+            env,
+            false,
+        );
+
+        let new = match new {
+            Some(new) => new,
+            None => return,
+        };
+        self.event(
+            Event::Internal(InternalEvent::RoutePanToWidget(new)),
+            debug_logger,
+            command_queue,
+            action_queue,
+            env,
+        );
+        if env.focus_follows_accessibility_focus() && self.focus_chain().contains(&new) {
+            let mut widget_state = WidgetState::new(self.root.id(), Some(self.size), "<root>");
+            widget_state.request_focus = Some(FocusChange::Focus(new));
+            self.update_focus(
+                &mut widget_state,
+                debug_logger,
+                command_queue,
+                action_queue,
+                env,
+            );
+        }
+    }
+
+    /// Record a key press, and mark it as a repeat if the key is already held.
+    ///
+    /// Keys without a physical code aren't tracked, and only repeat if the
+    /// platform says so.
+    fn track_key_down(&mut self, mut key_event: KeyEvent) -> KeyEvent {
+        if key_event.code == Code::Unidentified {
+            return key_event;
+        }
+        let repeats = self
+            .held_keys
+            .entry(key_event.code)
+            .and_modify(|repeats| *repeats += 1)
+            .or_insert(u32::from(key_event.repeat));
+        // Not every platform reports repeats, so a second press without a
+        // release counts as one too.
+        key_event.repeat |= *repeats > 0;
+        key_event
+    }
+
+    /// Record a mouse press, and set its click count.
+    ///
+    /// Platforms don't count clicks consistently, so the count is computed
+    /// here: a press continues the previous click if it's made with the same
+    /// button, within [`Env::MULTI_CLICK_INTERVAL`] and
+    /// [`Env::MULTI_CLICK_DISTANCE`] of it.
+    fn track_mouse_down(&mut self, mut mouse_event: MouseEvent, env: &Env) -> MouseEvent {
+        let now = self.now();
+        let interval = Duration::from_millis(env.get(Env::MULTI_CLICK_INTERVAL));
+        let max_distance = env.get(Env::MULTI_CLICK_DISTANCE);
+
+        let count = match &self.last_click {
+            Some(last)
+                if last.button == mouse_event.button
+                    && now.saturating_duration_since(last.time) <= interval
+                    && last.pos.distance(mouse_event.window_pos) <= max_distance =>
+            {
+                last.count.saturating_add(1)
+            }
+            _ => 1,
+        };
+        self.last_click = Some(LastClick {
+            button: mouse_event.button,
+            time: now,
+            pos: mouse_event.window_pos,
+            count,
+        });
+        mouse_event.count = count;
+        mouse_event
+    }
+
+    /// Stop counting clicks once the pointer moved too far from the last press.
+    fn forget_click_if_moved(&mut self, window_pos: Point, env: &Env) {
+        let max_distance = env.get(Env::MULTI_CLICK_DISTANCE);
+        if let Some(last) = &self.last_click {
+            if last.pos.distance(window_pos) > max_distance {
+                self.last_click = None;
+            }
+        }
+    }
+
+    /// Send [`LifeCycle::EnvChanged`] to every widget, after the root env changed.
+    pub(crate) fn env_changed(
+        &mut self,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        self.lifecycle(
+            &LifeCycle::EnvChanged,
+            debug_logger,
+            command_queue,
+            action_queue,
+            env,
+            false,
+        );
+        self.invalid.add_rect(self.size.to_rect());
+        let hit_test_pos = self.last_mouse_pos.filter(|_| env.get(Env::DEBUG_HIT_TEST));
+        self.update_hit_test_target(hit_test_pos);
+    }
+
+    /// Replace the root widget, keeping the window's size and scale.
+    ///
+    /// The old tree receives [`LifeCycle::WidgetRemoved`], and the new one is
+    /// added and laid out right away. Focus, timers and any active drag are reset,
+    /// since they refer to widgets of the old tree.
+    pub(crate) fn set_root(
+        &mut self,
+        root: Box<dyn Widget>,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        self.lifecycle(
+            &LifeCycle::WidgetRemoved,
+            debug_logger,
+            command_queue,
+            action_queue,
+            env,
+            false,
+        );
+
+        let old_focus_was_ime = self.focus.map_or(false, |focus| {
+            self.ime_handlers
+                .iter()
+                .any(|(_, reg)| reg.widget_id == focus)
+        });
+        if old_focus_was_ime {
+            self.ime_focus_change = Some(None);
+        }
+        self.focus = None;
+        self.accessibility_focus = None;
+        self.timers.clear();
+        if let Some(session) = self.drag_session.take() {
+            self.remove_drag_preview(session, debug_logger, command_queue, action_queue, env);
+        }
+        self.after_layout.clear();
+        self.after_layout_ready.clear();
+        self.layout_rect_observers.clear();
+
+        self.root = WidgetPod::new(root);
+        self.remove_dead_ime_handlers();
+
+        // WindowConnected adds the new tree, and WindowSize marks it for layout.
+        for event in [Event::WindowConnected, Event::WindowSize(self.size)] {
+            self.event(event, debug_logger, command_queue, action_queue, env);
+        }
+        self.layout(debug_logger, command_queue, action_queue, env);
+        self.invalid.add_rect(self.size.to_rect());
+    }
+
+    /// Unregister the IME sessions of widgets that were dropped.
+    fn remove_dead_ime_handlers(&mut self) {
+        let WindowRoot {
+            ime_handlers,
+            handle,
+            ..
+        } = self;
+        ime_handlers.retain(|(token, v)| {
+            let will_retain = v.is_alive();
+            if !will_retain {
+                tracing::debug!("{:?} removed", token);
+                handle.remove_text_field(*token);
+            }
+            will_retain
+        });
+    }
+
+    /// Update whether focus should be shown, and repaint the focused widget
+    /// if that changed.
+    fn set_focus_visible(&mut self, focus_visible: bool) {
+        if self.focus_visible == focus_visible {
+            return;
+        }
+        self.focus_visible = focus_visible;
+        let focused_widget = self
+            .focus
+            .and_then(|id| self.root.as_dyn().find_widget_by_id(id));
+        if let Some(widget) = focused_widget {
+            let state = widget.state();
+            let rect = state.paint_rect() + state.parent_window_origin.to_vec2();
+            self.invalid.add_rect(rect);
+        }
+    }
+
+    pub(crate) fn invalidate_paint_region(&mut self) {
+        let invalid = self.take_invalid();
+        if self.root.state().needs_layout {
+            // TODO - this might be too coarse
+            self.handle.invalidate();
+        } else {
+            for rect in coalesced_rects(&invalid) {
+                self.handle.invalidate_rect(rect);
+            }
+        }
+    }
+
+    /// Replace the invalid region by its bounding box, so that the platform
+    /// is asked for a single repaint.
+    ///
+    /// Used at the end of a batch of external events, which may invalidate
+    /// many small areas.
+    pub(crate) fn coalesce_invalid_to_bounds(&mut self) {
+        if !self.invalid.is_empty() {
+            self.invalid = Region::from(self.invalid.bounding_box());
+        }
+    }
+
+    /// Take the region invalidated since the last call, to be repainted.
+    pub(crate) fn take_invalid(&mut self) -> Region {
+        if !self.invalid.is_empty() {
+            self.frame_changed = true;
+        }
+        std::mem::replace(&mut self.invalid, Region::EMPTY)
+    }
+
+    /// Ask the platform for a corrected size if `new_size` doesn't match the
+    /// window's aspect ratio. See [`WindowConfig::keep_aspect_ratio`].
+    fn correct_aspect_ratio(&mut self, new_size: Size) {
+        let ratio = match self.keep_aspect_ratio {
+            Some(ratio) if self.size_policy == WindowSizePolicy::User => ratio,
+            _ => return,
+        };
+        // Don't fight the window manager.
+        if self.window_state == WindowState::Maximized {
+            return;
+        }
+        if let Some(corrected) = aspect_corrected_size(ratio, self.size, new_size) {
+            let insets = self.handle.content_insets();
+            self.handle.set_size((corrected.to_rect() + insets).size());
+        }
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn invalid(&self) -> &Region {
+        &self.invalid
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn invalid_mut(&mut self) -> &mut Region {
+        &mut self.invalid
+    }
+
+    /// Get ready for painting, by doing layout and sending an `AnimFrame` event.
+    pub(crate) fn prepare_paint(
+        &mut self,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        #[cfg(feature = "diagnostics")]
+        self.frame_allocations
+            .start_frame(crate::alloc_counter::AllocationCounts::current());
+
+        let now = Instant::now();
+        // TODO: this calculation uses wall-clock time of the paint call, which
+        // potentially has jitter.
+        //
+        // See https://github.com/linebender/druid/issues/85 for discussion.
+        let last = self.last_anim.take();
+        let elapsed_ns = last.map(|t| now.duration_since(t).as_nanos()).unwrap_or(0) as u64;
+
+        if self.wants_animation_frame() {
+            self.event(
+                Event::AnimFrame(elapsed_ns),
+                debug_logger,
+                command_queue,
+                action_queue,
+                env,
+            );
+            self.last_anim = Some(now);
+        }
+    }
+
+    pub(crate) fn do_paint(
+        &mut self,
+        piet: &mut Piet,
+        invalid: &Region,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        if self.root.state().needs_layout || self.root.state().has_deferred_layout {
+            self.frame_changed = true;
+            self.layout(debug_logger, command_queue, action_queue, env);
+        }
+
+        let connect_frame = self.connect_frame.take();
+        if !self.frame_changed && self.draw_connect_frame(piet, connect_frame) {
+            // The first frame was painted when the window was connected, and
+            // nothing changed since.
+        } else if !self.frame_changed && self.draw_last_frame(piet, env) {
+            // The platform asked for a repaint, but nothing changed since the
+            // last frame, which was drawn again.
+        } else {
+            // Only the invalid region is cleared, so that the rest of the
+            // surface keeps the previous frame.
+            if let Some(color) = self.clear_color(env) {
+                for &r in invalid.rects() {
+                    piet.clear(Some(r), color.clone());
+                }
+            }
+            self.paint(
+                piet,
+                invalid,
+                debug_logger,
+                command_queue,
+                action_queue,
+                env,
+            );
+            self.capture_last_frame(piet, invalid);
+        }
+        self.synthetic_mouse_moves = 0;
+
+        #[cfg(feature = "diagnostics")]
+        self.frame_allocations
+            .end_frame(crate::alloc_counter::AllocationCounts::current());
+    }
+
+    /// Draw the frame painted when the window was connected, if it's at the
+    /// window's current size.
+    fn draw_connect_frame(&self, piet: &mut Piet, frame: Option<(ImageBuf, Size)>) -> bool {
+        match frame {
+            Some((image, size)) if size == self.size => {
+                let image = image.to_image(piet);
+                let rect = self.size.to_rect();
+                piet.draw_image(&image, rect, InterpolationMode::Bilinear);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Draw the image of the last frame, if there is one at the window's
+    /// current size and scale.
+    ///
+    /// The surface is cleared first, so that the transparent parts of the
+    /// image don't show what was drawn before.
+    fn draw_last_frame(&self, piet: &mut Piet, env: &Env) -> bool {
+        match &self.last_frame {
+            Some(frame) if frame.size == self.size && frame.scale == self.scale() => {
+                let rect = self.size.to_rect();
+                let color = self.clear_color(env).unwrap_or(Color::TRANSPARENT);
+                piet.clear(Some(rect), color);
+                piet.draw_image(&frame.image, rect, InterpolationMode::NearestNeighbor);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Keep an image of the frame that was just painted, if the window
+    /// replays its last frame.
+    fn capture_last_frame(&mut self, piet: &mut Piet, invalid: &Region) {
+        self.frame_changed = false;
+        self.last_frame = None;
+        if !self.replay_last_frame {
+            return;
+        }
+
+        // Outside of the invalid region, the surface may not hold the previous
+        // frame, so only frames that repainted the whole window are captured.
+        let window_rect = self.size.to_rect();
+        let fully_painted = invalid
+            .rects()
+            .iter()
+            .any(|rect| rect.union(window_rect) == *rect);
+        if fully_painted {
+            match piet.capture_image_area(window_rect) {
+                Ok(image) => {
+                    self.last_frame = Some(LastFrame {
+                        image,
+                        size: self.size,
+                        scale: self.scale(),
+                    })
+                }
+                Err(e) => warn!("Failed to capture the last frame of {:?}: '{}'", self.id, e),
+            }
+        }
+    }
+
+    /// The number of device pixels per logical pixel of the window.
+    fn scale(&self) -> f64 {
+        self.handle.get_scale().map_or(1.0, |scale| scale.x())
+    }
+
+    /// The color to clear the surface to before painting, or `None` if the
+    /// window isn't cleared.
+    fn clear_color(&self, env: &Env) -> Option<Color> {
+        if !self.clear {
+            None
+        } else if let Some(color) = &self.background_color {
+            Some(color.clone())
+        } else if self.transparent {
+            Some(Color::TRANSPARENT)
+        } else {
+            Some(env.get(crate::theme::WINDOW_BACKGROUND_COLOR))
+        }
+    }
 
-        self.post_event_processing(
-            &mut widget_state,
+    pub(crate) fn render_widget_to(
+        &mut self,
+        piet: &mut Piet,
+        size: Size,
+        scale: f64,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        let window_size = std::mem::replace(&mut self.size, size);
+        let size_policy = std::mem::replace(&mut self.size_policy, WindowSizePolicy::User);
+        self.render_text = Some(piet.text().clone());
+        self.render_scale = Some(scale);
+        self.paint_offscreen = true;
+        // Text layouts are rebuilt for the export scale, and rebuilt again
+        // by the next layout, which doesn't have this key.
+        let env = &env.clone().adding(Env::RENDER_SCALE, scale);
+
+        self.layout(debug_logger, command_queue, action_queue, env);
+        if let Some(color) = self.clear_color(env) {
+            piet.clear(None, color);
+        }
+        self.paint(
+            piet,
+            &Region::from(size.to_rect()),
             debug_logger,
             command_queue,
             action_queue,
             env,
-            process_commands,
         );
-    }
 
-    pub(crate) fn invalidate_paint_region(&mut self) {
-        if self.root.state().needs_layout {
-            // TODO - this might be too coarse
-            self.handle.invalidate();
-        } else {
-            for rect in self.invalid.rects() {
-                self.handle.invalidate_rect(*rect);
-            }
-        }
-        self.invalid.clear();
-    }
+        self.render_text = None;
+        self.render_scale = None;
+        self.paint_offscreen = false;
+        self.size = window_size;
+        self.size_policy = size_policy;
 
-    #[allow(dead_code)]
-    pub(crate) fn invalid(&self) -> &Region {
-        &self.invalid
+        // The widget tree was laid out for the export size; lay it out again
+        // for the window.
+        self.root.state.needs_layout = true;
+        self.invalid.add_rect(self.size.to_rect());
     }
 
-    #[allow(dead_code)]
-    pub(crate) fn invalid_mut(&mut self) -> &mut Region {
-        &mut self.invalid
+    /// Lay out the root widget at `size` and paint it into a new image, with
+    /// `scale` device pixels per logical pixel.
+    pub(crate) fn export_image(
+        &mut self,
+        size: Size,
+        scale: f64,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) -> Result<ImageBuf, PietError> {
+        let width = (size.width * scale).ceil() as usize;
+        let height = (size.height * scale).ceil() as usize;
+
+        let mut device = Device::new()?;
+        let mut target = device.bitmap_target(width, height, scale)?;
+        {
+            let mut piet = target.render_context();
+            self.render_widget_to(
+                &mut piet,
+                size,
+                scale,
+                debug_logger,
+                command_queue,
+                action_queue,
+                env,
+            );
+            piet.finish()?;
+        }
+        target.to_image_buf(ImageFormat::RgbaPremul)
     }
 
-    /// Get ready for painting, by doing layout and sending an `AnimFrame` event.
-    pub(crate) fn prepare_paint(
+    /// Paint the current frame into an image, at the window's physical resolution.
+    pub(crate) fn capture(
         &mut self,
         debug_logger: &mut DebugLogger,
         command_queue: &mut CommandQueue,
         action_queue: &mut ActionQueue,
         env: &Env,
-    ) {
-        let now = Instant::now();
-        // TODO: this calculation uses wall-clock time of the paint call, which
-        // potentially has jitter.
-        //
-        // See https://github.com/linebender/druid/issues/85 for discussion.
-        let last = self.last_anim.take();
-        let elapsed_ns = last.map(|t| now.duration_since(t).as_nanos()).unwrap_or(0) as u64;
+    ) -> Result<ImageBuf, PietError> {
+        if self.root.state().needs_layout || self.root.state().has_deferred_layout {
+            self.frame_changed = true;
+            self.layout_unbudgeted(debug_logger, command_queue, action_queue, env);
+        }
 
-        if self.wants_animation_frame() {
-            self.event(
-                Event::AnimFrame(elapsed_ns),
+        let (scale_x, scale_y) = match self.handle.get_scale() {
+            Ok(scale) => (scale.x(), scale.y()),
+            Err(_) => (1.0, 1.0),
+        };
+        let width = (self.size.width * scale_x).ceil() as usize;
+        let height = (self.size.height * scale_y).ceil() as usize;
+
+        let mut device = Device::new()?;
+        let mut target = device.bitmap_target(width, height, 1.0)?;
+        {
+            let mut piet = target.render_context();
+            if let Some(color) = self.clear_color(env) {
+                piet.clear(None, color);
+            }
+            piet.transform(Affine::scale_non_uniform(scale_x, scale_y));
+            // The layer caches hold images of the window's surface, which
+            // can't be drawn on this one.
+            self.paint_offscreen = true;
+            self.paint(
+                &mut piet,
+                &Region::from(self.size.to_rect()),
                 debug_logger,
                 command_queue,
                 action_queue,
                 env,
             );
-            self.last_anim = Some(now);
+            self.paint_offscreen = false;
+            piet.finish()?;
         }
+        // Painting consumed the invalid region, but none of it reached the
+        // screen, so the next frame repaints everything.
+        self.invalid.add_rect(self.size.to_rect());
+        self.frame_changed = true;
+        target.to_image_buf(ImageFormat::RgbaPremul)
     }
 
-    pub(crate) fn do_paint(
+    /// Lay out and paint the window as it's connected, before it's shown.
+    ///
+    /// Windows sized by the user haven't received a [`Event::WindowSize`]
+    /// yet, so they're sent one with the size of the handle or, if the
+    /// platform doesn't know it yet, `initial_size`. When the platform reports
+    /// a different size, the root is laid out again; the same size is ignored.
+    ///
+    /// The platform only provides a surface to paint on when it asks for a
+    /// frame, so the window is painted into an image, which the first frame
+    /// draws if nothing changed in the meantime.
+    pub(crate) fn layout_on_connect(
         &mut self,
-        piet: &mut Piet,
-        invalid: &Region,
+        initial_size: Option<Size>,
         debug_logger: &mut DebugLogger,
         command_queue: &mut CommandQueue,
         action_queue: &mut ActionQueue,
         env: &Env,
     ) {
-        if self.root.state().needs_layout {
-            self.layout(debug_logger, command_queue, action_queue, env);
+        if self.size_policy == WindowSizePolicy::User && self.size == Size::ZERO {
+            let handle_size = self.handle.get_size();
+            let size = if handle_size != Size::ZERO {
+                Some(handle_size)
+            } else {
+                initial_size
+            };
+            match size {
+                Some(size) => {
+                    let event = Event::WindowSize(size);
+                    self.event(event, debug_logger, command_queue, action_queue, env);
+                }
+                None => return,
+            }
+        }
+        self.layout_unbudgeted(debug_logger, command_queue, action_queue, env);
+        match self.capture(debug_logger, command_queue, action_queue, env) {
+            Ok(image) => {
+                self.connect_frame = Some((image, self.size));
+                // The image is the whole frame: the first paint only needs to
+                // draw it, unless something else is invalidated.
+                self.invalid = Region::EMPTY;
+                self.frame_changed = false;
+            }
+            Err(e) => {
+                warn!("Failed to paint {:?} as it was connected: '{}'", self.id, e);
+                self.invalid.add_rect(self.size.to_rect());
+            }
         }
+    }
 
-        for &r in invalid.rects() {
-            piet.clear(
-                Some(r),
-                if self.transparent {
-                    Color::TRANSPARENT
-                } else {
-                    env.get(crate::theme::WINDOW_BACKGROUND_COLOR)
-                },
-            );
+    pub(crate) fn layout(
+        &mut self,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        let had_deferred_layout = self.root.state().has_deferred_layout;
+        // The passes of a frame share a deadline. Export passes lay out
+        // everything.
+        self.layout_deadline = match self.layout_budget {
+            Some(budget) if self.render_text.is_none() => Some(self.now() + budget),
+            _ => None,
+        };
+        self.layout_pass(debug_logger, command_queue, action_queue, env);
+
+        // Export passes use a different size than the window, so callbacks
+        // and observers wait for the window's own layout.
+        if self.render_text.is_some() {
+            return;
         }
-        self.paint(
-            piet,
-            invalid,
+        if !self.after_layout.is_empty() {
+            // The callbacks run at the end of the update cycle, see
+            // `finalize_update`. Layout can happen while painting, outside of
+            // an update cycle, so make sure one follows.
+            self.after_layout_ready.append(&mut self.after_layout);
+            if let Some(mut handle) = self.handle.get_idle_handle() {
+                handle.schedule_idle(RUN_COMMANDS_TOKEN);
+            }
+        }
+        self.notify_layout_rect_observers(debug_logger, command_queue, action_queue, env);
+        // The widgets may have moved under the pointer.
+        if env.get(Env::DEBUG_HIT_TEST) {
+            self.update_hit_test_target(self.last_mouse_pos);
+        }
+
+        self.layout_deadline = None;
+        // Deferred widgets are laid out in the next frame, and painted once
+        // they are.
+        if had_deferred_layout {
+            self.invalid.add_rect(self.size.to_rect());
+        }
+        if self.root.state().has_deferred_layout {
+            self.handle.request_anim_frame();
+        }
+    }
+
+    /// Lay out the window without deferring any widget, for frames that are
+    /// painted right away and not again when deferred widgets catch up.
+    fn layout_unbudgeted(
+        &mut self,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        let budget = self.layout_budget.take();
+        self.layout(debug_logger, command_queue, action_queue, env);
+        self.layout_budget = budget;
+    }
+
+    /// Send [`LAYOUT_RECT_CHANGED`](sys_cmd::LAYOUT_RECT_CHANGED) to the
+    /// observers of widgets whose layout rect changed since the last time.
+    ///
+    /// Observers that were removed from the tree are forgotten. Observed
+    /// widgets that aren't in the tree are skipped.
+    fn notify_layout_rect_observers(
+        &mut self,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        if self.layout_rect_observers.is_empty() {
+            return;
+        }
+        let ids: HashSet<_> = self
+            .layout_rect_observers
+            .iter()
+            .flat_map(|entry| [entry.observer, entry.target])
+            .collect();
+        let mut rects = HashMap::new();
+        collect_window_layout_rects(self.root.as_dyn(), &ids, &mut rects);
+        self.layout_rect_observers
+            .retain(|entry| rects.contains_key(&entry.observer));
+
+        let mut commands = Vec::new();
+        for entry in &mut self.layout_rect_observers {
+            let rect = match rects.get(&entry.target) {
+                Some(rect) => *rect,
+                None => continue,
+            };
+            if entry.last_rect != Some(rect) {
+                entry.last_rect = Some(rect);
+                let change = sys_cmd::LayoutRectChange {
+                    widget: entry.target,
+                    rect,
+                };
+                commands.push(sys_cmd::LAYOUT_RECT_CHANGED.with(change).to(entry.observer));
+            }
+        }
+        if commands.is_empty() {
+            return;
+        }
+
+        self.event(
+            Event::Internal(InternalEvent::TargetedCommands(commands)),
             debug_logger,
             command_queue,
             action_queue,
             env,
         );
+        // Observers may have changed the layout in response.
+        if self.root.state().needs_layout {
+            self.layout_pass(debug_logger, command_queue, action_queue, env);
+        }
     }
 
-    pub(crate) fn layout(
+    /// Finish an update cycle: run the callbacks registered with
+    /// `EventCtx::run_after_layout` whose layout is done.
+    ///
+    /// Each callback is delivered to its widget with a `RouteAfterLayout` event.
+    /// Callbacks registered meanwhile are kept for the next layout. If the
+    /// callbacks changed the layout, eg by scrolling, the window is laid out
+    /// again.
+    pub(crate) fn finalize_update(
+        &mut self,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        if self.after_layout_ready.is_empty() {
+            return;
+        }
+        for (widget_id, callback) in std::mem::take(&mut self.after_layout_ready) {
+            self.after_layout_callback = Some(callback);
+            self.event(
+                Event::Internal(InternalEvent::RouteAfterLayout(widget_id)),
+                debug_logger,
+                command_queue,
+                action_queue,
+                env,
+            );
+        }
+        if self.root.state().needs_layout {
+            self.layout(debug_logger, command_queue, action_queue, env);
+            self.invalid.add_rect(self.size.to_rect());
+        }
+    }
+
+    fn layout_pass(
         &mut self,
         debug_logger: &mut DebugLogger,
         command_queue: &mut CommandQueue,
         action_queue: &mut ActionQueue,
         env: &Env,
     ) {
+        // The platform may already know about a size we haven't received a
+        // `WindowSize` event for yet. Mock and export passes have no real window.
+        let platform_size = self.handle.get_size();
+        let resize_pending = self.size_policy == WindowSizePolicy::User
+            && self.mock_timer_queue.is_none()
+            && self.render_text.is_none()
+            && platform_size != Size::ZERO
+            && platform_size != self.size;
+
+        let bc = match self.size_policy {
+            WindowSizePolicy::User => BoxConstraints::tight(self.size),
+            WindowSizePolicy::Content => {
+                let max_size = match self.screen_size {
+                    Some(size) => (size.to_rect() - self.handle.content_insets()).size(),
+                    None => Size::new(f64::INFINITY, f64::INFINITY),
+                };
+                content_constraints(self.min_size, max_size)
+            }
+        };
+
         let mut widget_state = WidgetState::new(self.root.id(), Some(self.size), "<root>");
         let mut global_state = GlobalPassCtx::new(
             self.ext_event_sink.clone(),
@@ -1280,15 +3433,24 @@ impl WindowRoot {
             &self.handle,
             self.id,
             self.focus,
+            self.window_focused,
+            self.focus_visible,
+            self.accessibility_focus,
         );
+        if let Some(text) = &self.render_text {
+            global_state.text = text.clone();
+        }
+        if let Some(scale) = self.render_scale {
+            global_state.scale = scale;
+        }
+        global_state.window_size = self.size;
+        global_state.hot_suppressed = self.hot_suppressed;
+        global_state.layout_deadline = self.layout_deadline;
         let mut layout_ctx = LayoutCtx {
             global_state: &mut global_state,
             widget_state: &mut widget_state,
             mouse_pos: self.last_mouse_pos,
-        };
-        let bc = match self.size_policy {
-            WindowSizePolicy::User => BoxConstraints::tight(self.size),
-            WindowSizePolicy::Content => BoxConstraints::UNBOUNDED,
+            resize_pending,
         };
 
         let content_size = {
@@ -1326,6 +3488,132 @@ impl WindowRoot {
             env,
             true,
         );
+
+        self.synthesize_mouse_move(debug_logger, command_queue, action_queue, env);
+    }
+
+    /// If layout moved widgets under a stationary pointer, send a `MouseMove`
+    /// at the last known pointer position so that hot state is updated.
+    ///
+    /// `place_child` updates the hot state of the widgets being placed, but not
+    /// of their descendants, whose position in the window may have changed too.
+    fn synthesize_mouse_move(
+        &mut self,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        let (pos, last_event) = match (self.last_mouse_pos, &self.last_mouse_event) {
+            (Some(pos), Some(last_event)) => (pos, last_event.clone()),
+            _ => return,
+        };
+        // Suppressed hot changes are resolved by a single move once they resume.
+        if self.render_text.is_some()
+            || self.hot_suppressed
+            || !hot_state_is_stale(self.root.as_dyn(), Some(pos))
+        {
+            return;
+        }
+        // The synthetic move may itself change layout, eg a widget that shrinks
+        // when hovered. We cap the number of moves until the next frame.
+        if self.synthetic_mouse_moves >= MAX_SYNTHETIC_MOUSE_MOVES {
+            return;
+        }
+        self.synthetic_mouse_moves += 1;
+
+        let event = MouseEvent {
+            pos,
+            window_pos: pos,
+            count: 0,
+            focus: false,
+            button: MouseButton::None,
+            wheel_delta: Vec2::ZERO,
+            ..last_event
+        };
+        self.event(
+            Event::MouseMove(event),
+            debug_logger,
+            command_queue,
+            action_queue,
+            env,
+        );
+    }
+
+    /// The context menu request triggered by an unhandled event, if any.
+    ///
+    /// Menus are requested with a secondary click (or Ctrl+click on macOS),
+    /// the context menu key, or Shift+F10.
+    fn context_menu_request(&self, event: &Event) -> Option<ContextMenuRequest> {
+        match event {
+            Event::MouseDown(mouse_event) => {
+                let is_secondary = mouse_event.button == MouseButton::Right
+                    || (cfg!(target_os = "macos")
+                        && mouse_event.button == MouseButton::Left
+                        && mouse_event.mods.ctrl());
+                is_secondary.then_some(ContextMenuRequest {
+                    window_pos: mouse_event.pos,
+                    from_keyboard: false,
+                })
+            }
+            Event::KeyDown(key_event) => {
+                let is_menu_key = key_event.key == KbKey::ContextMenu
+                    || (key_event.key == KbKey::F10 && key_event.mods.shift());
+                if !is_menu_key {
+                    return None;
+                }
+                let rect = self.focused_widget()?.state().window_layout_rect();
+                Some(ContextMenuRequest {
+                    window_pos: Point::new(rect.x0, rect.y1),
+                    from_keyboard: true,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// If a key event changed the held modifiers, send a `MouseMove` with the
+    /// new modifiers at the last known pointer position.
+    ///
+    /// This lets the widgets under a stationary pointer update their cursor,
+    /// eg to show a crosshair while Alt is held.
+    fn send_modifiers_to_pointer(
+        &mut self,
+        mods: Modifiers,
+        debug_logger: &mut DebugLogger,
+        command_queue: &mut CommandQueue,
+        action_queue: &mut ActionQueue,
+        env: &Env,
+    ) {
+        let (pos, last_event) = match (self.last_mouse_pos, &self.last_mouse_event) {
+            (Some(pos), Some(last_event)) => (pos, last_event.clone()),
+            _ => return,
+        };
+        if self.render_text.is_some() || last_event.mods == mods {
+            return;
+        }
+        // Only the widgets under the pointer, or holding it, would react.
+        if !self.root.state().has_hot && !self.root.has_active() {
+            return;
+        }
+
+        let event = MouseEvent {
+            pos,
+            window_pos: pos,
+            mods,
+            count: 0,
+            focus: false,
+            button: MouseButton::None,
+            wheel_delta: Vec2::ZERO,
+            ..last_event
+        };
+        self.event(
+            Event::MouseMove(event),
+            debug_logger,
+            command_queue,
+            action_queue,
+            env,
+        );
     }
 
     fn paint(
@@ -1348,7 +3636,17 @@ impl WindowRoot {
             &self.handle,
             self.id,
             self.focus,
+            self.window_focused,
+            self.focus_visible,
+            self.accessibility_focus,
         );
+        if let Some(text) = &self.render_text {
+            global_state.text = text.clone();
+        }
+        if let Some(scale) = self.render_scale {
+            global_state.scale = scale;
+        }
+        global_state.paint_offscreen = self.paint_offscreen;
         let mut ctx = PaintCtx {
             render_ctx: piet,
             global_state: &mut global_state,
@@ -1356,6 +3654,7 @@ impl WindowRoot {
             z_ops: Vec::new(),
             region: invalid.clone(),
             depth: 0,
+            brush_cache: &mut self.brush_cache,
         };
 
         let root = &mut self.root;
@@ -1363,16 +3662,32 @@ impl WindowRoot {
             ctx.with_child_ctx(invalid.clone(), |ctx| root.paint_raw(ctx, env));
         });
 
+        // The ops of every subtree were appended to the root context, so
+        // they're sorted together.
         let mut z_ops = std::mem::take(&mut ctx.z_ops);
-        z_ops.sort_by_key(|k| k.z_index);
-
-        for z_op in z_ops.into_iter() {
-            ctx.with_child_ctx(invalid.clone(), |ctx| {
-                ctx.with_save(|ctx| {
-                    ctx.render_ctx.transform(z_op.transform);
-                    (z_op.paint_func)(ctx);
+        while !z_ops.is_empty() {
+            sort_z_ops(&mut z_ops);
+            for z_op in z_ops {
+                ctx.with_child_ctx(invalid.clone(), |ctx| {
+                    ctx.with_save(|ctx| {
+                        ctx.render_ctx.transform(z_op.transform);
+                        (z_op.paint_func)(ctx);
+                    });
                 });
-            });
+            }
+            // Ops submitted by the ops we just ran go on top of them.
+            z_ops = std::mem::take(&mut ctx.z_ops);
+        }
+
+        if let Some(session) = &mut self.drag_session {
+            ctx.with_child_ctx(invalid.clone(), |ctx| session.preview.paint(ctx, env));
+        }
+
+        if env.get(Env::DEBUG_HIT_TEST) {
+            if let Some((_, rect)) = self.hit_test_target {
+                ctx.fill(rect, &Color::rgba8(255, 0, 255, 48));
+                ctx.stroke(rect, &Color::rgb8(255, 0, 255), 2.0);
+            }
         }
 
         if self.wants_animation_frame() {
@@ -1380,6 +3695,30 @@ impl WindowRoot {
         }
     }
 
+    /// Find the widget at `pos` for the hit test overlay, and log it if it
+    /// changed. See [`Env::DEBUG_HIT_TEST`].
+    fn update_hit_test_target(&mut self, pos: Option<Point>) {
+        let target = pos
+            .and_then(|pos| self.find_widget_at_pos(pos))
+            .map(|widget| (widget.state().id, widget.state().window_layout_rect()));
+        if target == self.hit_test_target {
+            return;
+        }
+        if let Some(widget) = target.and_then(|(id, _)| self.find_widget_by_id(id)) {
+            info!(
+                "Hit test at {:?}: '{}' #{}",
+                pos.unwrap_or_default(),
+                widget.deref().short_type_name(),
+                widget.state().id.to_raw(),
+            );
+        }
+        // The outline is drawn over the edges of the rect.
+        for (_, rect) in [self.hit_test_target, target].into_iter().flatten() {
+            self.invalid.add_rect(rect.inset(2.0));
+        }
+        self.hit_test_target = target;
+    }
+
     pub(crate) fn get_ime_handler(
         &mut self,
         req_token: TextFieldToken,
@@ -1546,8 +3885,81 @@ impl WindowRoot {
         self.root.as_dyn().find_widget_at_pos(pos)
     }
 
+    /// Return the innermost widget under the pointer, if the pointer is in
+    /// the window.
+    ///
+    /// This is the widget commands sent to [`Target::Pointer`] are routed to.
+    pub fn widget_under_pointer(&self) -> Option<WidgetRef<'_, dyn Widget>> {
+        self.find_widget_at_pos(self.last_mouse_pos?)
+    }
+
+    /// Return the cursor last set on the window.
+    pub fn cursor(&self) -> &Cursor {
+        &self.cursor
+    }
+
+    /// Set the window's cursor.
+    fn set_cursor(&mut self, cursor: Cursor) {
+        self.handle.set_cursor(&cursor);
+        self.cursor = cursor;
+    }
+
+    /// Return whether the window is minimized, maximized or neither.
+    pub fn window_state(&self) -> WindowState {
+        self.window_state
+    }
+
+    /// Return the window's title.
+    pub fn title(&self) -> &ArcStr {
+        &self.title
+    }
+
+    /// Set the window's title.
+    pub(crate) fn set_title(&mut self, title: ArcStr) {
+        self.handle.set_title(&title);
+        self.title = title;
+    }
+
     /// Return the widget that receives keyboard events.
     pub fn focused_widget(&self) -> Option<WidgetRef<'_, dyn Widget>> {
         self.find_widget_by_id(self.focus?)
     }
+
+    /// Return the window's focus chain, in Tab order.
+    ///
+    /// This is the chain built during the last [`LifeCycle::BuildFocusChain`] pass,
+    /// so it excludes disabled and hidden widgets. It can be used for debugging
+    /// Tab order, or to draw a custom overlay over focusable widgets.
+    pub fn focus_chain_entries(&self) -> Vec<FocusChainEntry> {
+        self.focus_chain()
+            .iter()
+            .filter_map(|id| self.find_widget_by_id(*id))
+            .map(|widget| FocusChainEntry {
+                id: widget.id(),
+                window_layout_rect: widget.state().window_layout_rect(),
+                type_name: widget.deref().short_type_name(),
+                debug_text: widget.deref().get_debug_text(),
+            })
+            .collect()
+    }
+
+    /// Return the timers requested by the window's widgets that haven't fired
+    /// yet, from the earliest deadline to the latest.
+    ///
+    /// This can be used to check that a widget scheduled the timers it should
+    /// have, for instance that a debounce left a single timer.
+    #[cfg(any(test, feature = "diagnostics"))]
+    pub fn pending_timers(&self) -> Vec<PendingTimer> {
+        let mut timers: Vec<_> = self
+            .timers
+            .iter()
+            .map(|(&token, &(widget_id, deadline))| PendingTimer {
+                token,
+                widget_id,
+                deadline,
+            })
+            .collect();
+        timers.sort_by_key(|timer| timer.deadline);
+        timers
+    }
 }
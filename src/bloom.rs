@@ -37,11 +37,22 @@ impl<T: ?Sized + Hash> Bloom<T> {
     /// Does not count unique entries; this is just the number of times
     /// `add()` was called since the filter was created or last `clear()`ed.
     // it feels wrong to call this 'len'?
-    #[cfg(test)]
+    #[cfg(any(test, feature = "diagnostics"))]
     pub fn entry_count(&self) -> usize {
         self.entry_count
     }
 
+    /// Estimate the probability that `may_contain` returns `true` for an item
+    /// that wasn't added.
+    ///
+    /// This is the probability that both bits of a random item are set,
+    /// given the bits currently set in the filter.
+    #[cfg(any(test, feature = "diagnostics"))]
+    pub fn false_positive_rate(&self) -> f64 {
+        let fill_ratio = self.bits.count_ones() as f64 / NUM_BITS as f64;
+        fill_ratio * fill_ratio
+    }
+
     /// Return the raw bits of this filter.
     #[allow(dead_code)]
     pub fn to_raw(&self) -> u64 {
@@ -134,6 +145,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn false_positive_rate() {
+        let mut bloom = Bloom::default();
+        assert_eq!(bloom.false_positive_rate(), 0.0);
+        bloom.add(&0);
+        assert!(bloom.false_positive_rate() > 0.0);
+        for i in 0..1000 {
+            bloom.add(&i);
+        }
+        assert_eq!(bloom.false_positive_rate(), 1.0);
+    }
+
     #[test]
     fn union() {
         let mut bloom1 = Bloom::default();
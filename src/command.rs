@@ -65,6 +65,7 @@ pub struct Command {
     symbol: SelectorSymbol,
     payload: Arc<dyn Any>,
     target: Target,
+    priority: CommandPriority,
 }
 
 /// A message passed up the tree from a [`Widget`] to its ancestors.
@@ -121,6 +122,22 @@ pub struct SingleUse<T>(Mutex<Option<T>>);
 /// Our queue type
 pub(crate) type CommandQueue = VecDeque<Command>;
 
+/// How soon a [`Command`] is run, see [`Command::with_priority`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommandPriority {
+    /// The command is run after the current event or pass.
+    ///
+    /// Commands submitted while a frame is being prepared, eg during layout,
+    /// wait until the frame is painted.
+    #[default]
+    Normal,
+    /// The command is run before the next paint.
+    ///
+    /// This is for commands whose effect must be visible in the frame being
+    /// prepared, such as cancelling an operation that's drawn.
+    High,
+}
+
 /// The target of a [`Command`].
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Target {
@@ -140,6 +157,12 @@ pub enum Target {
     Window(WindowId),
     /// The target is a specific widget.
     Widget(WidgetId),
+    /// The target is the innermost widget under the pointer, in the window
+    /// the pointer is over.
+    ///
+    /// The widget is found when the command is dispatched. If there is none,
+    /// the command is dropped.
+    Pointer,
     // FIXME - remove this variant
     /// The target will be determined automatically.
     ///
@@ -159,9 +182,13 @@ mod sys {
 
     use druid_shell::FileInfo;
 
-    use super::{Selector, SingleUse};
+    use super::{Command, Selector, SingleUse};
+    use crate::piet::ImageBuf;
     use crate::platform::WindowConfig;
-    use crate::WidgetId;
+    use crate::promise::PromiseToken;
+    use crate::text::Selection;
+    use crate::undo::{EditDelta, UndoableEdit};
+    use crate::{AccessibilitySettings, ArcStr, Rect, Widget, WidgetId};
 
     /// Quit the running application. This command is handled by the Masonry library.
     pub const QUIT_APP: Selector = Selector::new("masonry-builtin.quit-app");
@@ -191,6 +218,30 @@ mod sys {
     /// will automatically target the window containing the widget.
     pub const CLOSE_WINDOW: Selector = Selector::new("masonry-builtin.close-window");
 
+    /// Replace the root widget of a window.
+    ///
+    /// The old widget tree receives [`LifeCycle::WidgetRemoved`] and is dropped,
+    /// then the new tree is added and laid out at the current window size.
+    /// Focus is reset.
+    ///
+    /// The command must target a specific window.
+    /// When calling `submit_command` on a `Widget`s context, passing `None` as target
+    /// will automatically target the window containing the widget.
+    ///
+    /// [`LifeCycle::WidgetRemoved`]: crate::LifeCycle::WidgetRemoved
+    pub const SET_WINDOW_ROOT: Selector<SingleUse<Box<dyn Widget>>> =
+        Selector::new("masonry-builtin.set-window-root");
+
+    /// Set the title of a window.
+    ///
+    /// If the window isn't connected yet, the title is applied once it is.
+    ///
+    /// The command must target a specific window.
+    /// When calling `submit_command` on a `Widget`s context, passing `None` as target
+    /// will automatically target the window containing the widget.
+    pub const SET_WINDOW_TITLE: Selector<ArcStr> =
+        Selector::new("masonry-builtin.set-window-title");
+
     /// Close all windows.
     pub const CLOSE_ALL_WINDOWS: Selector = Selector::new("masonry-builtin.close-all-windows");
 
@@ -271,9 +322,150 @@ mod sys {
     /// Redo.
     pub const REDO: Selector = Selector::new("masonry-builtin.menu-redo");
 
+    /// Submitted as a notification by a widget that made an edit that can be
+    /// undone, to be recorded by an ancestor's [`UndoStack`].
+    ///
+    /// A widget applying an [`APPLY_EDIT`] command shouldn't submit this.
+    ///
+    /// [`UndoStack`]: crate::UndoStack
+    pub const UNDOABLE_EDIT: Selector<UndoableEdit> =
+        Selector::new("masonry-builtin.undoable-edit");
+
+    /// Sent by an [`UndoStack`] to the widget that made an edit, with the delta
+    /// to apply to undo or redo it.
+    ///
+    /// [`UndoStack`]: crate::UndoStack
+    pub const APPLY_EDIT: Selector<EditDelta> = Selector::new("masonry-builtin.apply-edit");
+
     /// Select all.
     pub const SELECT_ALL: Selector = Selector::new("masonry-builtin.menu-select-all");
 
+    /// Move the caret of a text widget to a byte offset in its text.
+    ///
+    /// Sent to a window, this is handled by the [`TextBox`] with focus. It can
+    /// also target a `TextBox` directly. The selection collapses to the caret,
+    /// which is scrolled into view. An offset past the end of the text, or
+    /// inside a character, moves back to the previous character boundary.
+    ///
+    /// [`TextBox`]: crate::widget::TextBox
+    pub const SET_CARET: Selector<usize> = Selector::new("masonry-builtin.set-caret");
+
+    /// Set the selection of a text widget, in byte offsets in its text.
+    ///
+    /// This is routed and handled like [`SET_CARET`]; the active end of the
+    /// selection is scrolled into view.
+    pub const SET_SELECTION: Selector<Selection> = Selector::new("masonry-builtin.set-selection");
+
+    /// Sent to a widget to perform its primary action, as if it had been clicked.
+    ///
+    /// Widgets with a primary action, like [`Button`] and [`Checkbox`], handle
+    /// this command. It's meant for accessibility tools and scripting, and
+    /// for tests that shouldn't depend on the widget's position.
+    ///
+    /// [`Button`]: crate::widget::Button
+    /// [`Checkbox`]: crate::widget::Checkbox
+    pub const ACTIVATE: Selector = Selector::new("masonry-builtin.activate");
+
+    /// Submitted as a notification by a member of a [`RadioGroup`] to ask to become
+    /// the selected member.
+    ///
+    /// [`RadioGroup`]: crate::widget::RadioGroup
+    pub const SELECT_RADIO_MEMBER: Selector = Selector::new("masonry-builtin.select-radio-member");
+
+    /// Submitted as a notification by a [`Checkbox`] when the user toggles it.
+    /// The payload is the new checked state.
+    ///
+    /// [`Checkbox`]: crate::widget::Checkbox
+    pub const CHECKBOX_TOGGLED: Selector<bool> = Selector::new("masonry-builtin.checkbox-toggled");
+
+    /// Sent by a [`RadioGroup`] to each of its members whenever the selection
+    /// changes. The payload is `true` for the selected member.
+    ///
+    /// [`RadioGroup`]: crate::widget::RadioGroup
+    pub const SET_RADIO_SELECTED: Selector<bool> =
+        Selector::new("masonry-builtin.set-radio-selected");
+
+    /// Change the locale used to format numbers and dates, in every window.
+    ///
+    /// The payload is a language tag such as `"fr-FR"`. It's stored in the
+    /// root [`Env`] under [`Env::LOCALE`], and widgets are notified with
+    /// [`LifeCycle::EnvChanged`].
+    ///
+    /// [`Env`]: crate::Env
+    /// [`Env::LOCALE`]: crate::Env::LOCALE
+    /// [`LifeCycle::EnvChanged`]: crate::LifeCycle::EnvChanged
+    pub const SET_LOCALE: Selector<ArcStr> = Selector::new("masonry-builtin.set-locale");
+
+    /// Change the accessibility settings, in every window.
+    ///
+    /// Applications start with the settings of the OS, where Masonry can read
+    /// them. Submit this command when they change, or to override them. They're
+    /// stored in the root [`Env`], and widgets are notified with
+    /// [`LifeCycle::EnvChanged`].
+    ///
+    /// [`Env`]: crate::Env
+    /// [`LifeCycle::EnvChanged`]: crate::LifeCycle::EnvChanged
+    pub const SET_ACCESSIBILITY_SETTINGS: Selector<AccessibilitySettings> =
+        Selector::new("masonry-builtin.set-accessibility-settings");
+
+    /// Move the accessibility focus of a window to a widget, or clear it.
+    ///
+    /// This is the position of the cursor of assistive technologies such as
+    /// screen readers, which the accessibility layer reports with this command.
+    /// The widget is scrolled into view, and receives
+    /// [`StatusChange::AccessibilityFocusChanged`]. If
+    /// [`Env::focus_follows_accessibility_focus`] is set and the widget can be
+    /// focused, it gets keyboard focus too.
+    ///
+    /// The command must target a specific window. Activating the widget from
+    /// the assistive technology is done with [`ACTIVATE`].
+    ///
+    /// [`StatusChange::AccessibilityFocusChanged`]: crate::StatusChange::AccessibilityFocusChanged
+    /// [`Env::focus_follows_accessibility_focus`]: crate::Env::focus_follows_accessibility_focus
+    pub const SET_ACCESSIBILITY_FOCUS: Selector<Option<WidgetId>> =
+        Selector::new("masonry-builtin.set-accessibility-focus");
+
+    /// Log the allocation counts of a window's frames, and reset them.
+    ///
+    /// This logs the allocations of the last frame, and the average per frame
+    /// since the last reset. See the [`alloc_counter`](crate::alloc_counter)
+    /// module to enable counting.
+    ///
+    /// The command must target a specific window.
+    #[cfg(feature = "diagnostics")]
+    pub const DUMP_ALLOCATION_COUNTS: Selector =
+        Selector::new("masonry-builtin.dump-allocation-counts");
+
+    /// Render the current frame of a window into an image.
+    ///
+    /// The whole window is painted off-screen, including z-ordered content and
+    /// overlays, at its physical resolution. The image is delivered to the
+    /// requesting widget as an [`Event::PromiseResult`]. An empty image is
+    /// delivered if rendering fails.
+    ///
+    /// The command must target a specific window. Widgets usually submit it
+    /// with [`EventCtx::capture_window`].
+    ///
+    /// [`Event::PromiseResult`]: crate::Event::PromiseResult
+    /// [`EventCtx::capture_window`]: crate::EventCtx::capture_window
+    pub const CAPTURE_WINDOW: Selector<CaptureRequest> =
+        Selector::new("masonry-builtin.capture-window");
+
+    /// Sent to a widget observing the layout rect of another widget, when that
+    /// rect changes.
+    ///
+    /// Widgets start observing with [`EventCtx::observe_layout_rect`]. The
+    /// command is delivered right after the layout pass that moved or resized
+    /// the observed widget.
+    ///
+    /// [`EventCtx::observe_layout_rect`]: crate::EventCtx::observe_layout_rect
+    pub const LAYOUT_RECT_CHANGED: Selector<LayoutRectChange> =
+        Selector::new("masonry-builtin.layout-rect-changed");
+
+    /// A batch of commands submitted with `submit_commands`, to be routed together.
+    pub(crate) const COMMAND_BATCH: Selector<Vec<Command>> =
+        Selector::new("masonry-builtin.command-batch");
+
     /// Text input state has changed, and we need to notify the platform.
     pub(crate) const INVALIDATE_IME: Selector<ImeInvalidation> =
         Selector::new("masonry-builtin.invalidate-ime");
@@ -284,6 +476,24 @@ mod sys {
         pub widget: WidgetId,
         pub event: druid_shell::text::Event,
     }
+
+    /// The payload of [`CAPTURE_WINDOW`].
+    #[derive(Debug, Clone, Copy)]
+    pub struct CaptureRequest {
+        /// The promise resolved with the image.
+        pub token: PromiseToken<ImageBuf>,
+        /// The widget receiving the image.
+        pub widget: WidgetId,
+    }
+
+    /// The payload of [`LAYOUT_RECT_CHANGED`].
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct LayoutRectChange {
+        /// The observed widget.
+        pub widget: WidgetId,
+        /// The new layout rect of the widget, in window coordinates.
+        pub rect: Rect,
+    }
 }
 
 impl Selector<()> {
@@ -334,6 +544,7 @@ impl Command {
             symbol: selector.symbol(),
             payload: Arc::new(payload),
             target: target.into(),
+            priority: CommandPriority::Normal,
         }
     }
 
@@ -343,6 +554,7 @@ impl Command {
             symbol,
             payload: payload.into(),
             target,
+            priority: CommandPriority::Normal,
         }
         .default_to(Target::Global)
     }
@@ -377,6 +589,30 @@ impl Command {
         self
     }
 
+    /// Set how soon the `Command` is run.
+    ///
+    /// Commands are normally run after the event or pass that submitted them.
+    /// But commands submitted while a frame is being prepared, during an
+    /// animation frame or layout, wait until the frame is painted. With
+    /// [`CommandPriority::High`], they're run before the frame is painted, and
+    /// the window is laid out again if they changed its layout.
+    ///
+    /// Outside of frame preparation, the priority makes no difference: all
+    /// commands run in the order they were submitted. Before a paint,
+    /// high-priority commands run in the order they were submitted, ahead of
+    /// the normal-priority commands waiting for the paint. High-priority
+    /// commands submitted by the layout pass that follows them also run before
+    /// the paint, up to a few rounds; past that, they wait like normal commands.
+    pub fn with_priority(mut self, priority: CommandPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Returns the `Command`'s [`CommandPriority`].
+    pub fn priority(&self) -> CommandPriority {
+        self.priority
+    }
+
     /// Returns the `Command`'s [`Target`].
     ///
     /// [`Command::to`] can be used to change the [`Target`].
@@ -488,12 +724,28 @@ impl<T: Any> SingleUse<T> {
     }
 }
 
+/// Remove the high-priority commands from `queue`, in the order they were submitted.
+pub(crate) fn take_high_priority_commands(queue: &mut CommandQueue) -> Vec<Command> {
+    if queue
+        .iter()
+        .all(|cmd| cmd.priority == CommandPriority::Normal)
+    {
+        return Vec::new();
+    }
+    let (high, normal) = std::mem::take(queue)
+        .into_iter()
+        .partition(|cmd| cmd.priority == CommandPriority::High);
+    *queue = normal;
+    high
+}
+
 impl From<Selector> for Command {
     fn from(selector: Selector) -> Command {
         Command {
             symbol: selector.symbol(),
             payload: Arc::new(()),
             target: Target::Auto,
+            priority: CommandPriority::Normal,
         }
     }
 }
@@ -11,21 +11,29 @@ use std::rc::Rc;
 use std::time::Duration;
 
 use druid_shell::text::Event as ImeInvalidation;
-use druid_shell::{Cursor, Region, TimerToken, WindowHandle};
+use druid_shell::{Code, Cursor, KbKey, KeyEvent, Region, TimerToken, WindowHandle};
+use instant::Instant;
 use tracing::{error, trace, warn};
 
 use crate::action::{Action, ActionQueue};
-use crate::command::{Command, CommandQueue, Notification, SingleUse};
+use crate::command::{Command, CommandPriority, CommandQueue, Notification, SingleUse};
 use crate::debug_logger::DebugLogger;
+use crate::drag::DragRequest;
 use crate::ext_event::ExtEventSink;
-use crate::piet::{Piet, PietText, RenderContext};
+use crate::kurbo::Shape;
+use crate::piet::{
+    Brush, FixedGradient, FixedLinearGradient, FixedRadialGradient, GradientStop, GradientStops,
+    ImageBuf, IntoBrush, LineCap, Piet, PietText, RenderContext, StrokeStyle, Text,
+    TextLayout as _, TextLayoutBuilder,
+};
 use crate::platform::WindowDescription;
 use crate::promise::PromiseToken;
 use crate::testing::MockTimerQueue;
-use crate::text::{ImeHandlerRef, TextFieldRegistration};
+use crate::text::{FontDescriptor, ImeHandlerRef, TextFieldRegistration, TextLayout, TextStorage};
 use crate::widget::{CursorChange, FocusChange, StoreInWidgetMut, WidgetMut, WidgetState};
 use crate::{
-    Affine, Env, Insets, Point, Rect, Size, Target, Vec2, Widget, WidgetId, WidgetPod, WindowId,
+    Affine, Env, Insets, KeyRepeat, LifeCycle, PanAlignment, Point, Rect, Size, Target, Vec2,
+    Widget, WidgetId, WidgetPod, WindowId,
 };
 
 /// A macro for implementing methods on multiple contexts.
@@ -51,17 +59,65 @@ pub(crate) struct GlobalPassCtx<'a> {
     pub(crate) command_queue: &'a mut CommandQueue,
     pub(crate) action_queue: &'a mut ActionQueue,
     // TODO - merge queues
-    // Associate timers with widgets that requested them.
-    pub(crate) timers: &'a mut HashMap<TimerToken, WidgetId>,
+    // Associate timers with widgets that requested them, and when they're due.
+    pub(crate) timers: &'a mut HashMap<TimerToken, (WidgetId, Instant)>,
     // Used in Harness for unit tests - see `src/testing/mock_timer_queue.rs`
     pub(crate) mock_timer_queue: Option<&'a mut MockTimerQueue>,
     pub(crate) window_id: WindowId,
     pub(crate) window: &'a WindowHandle,
     pub(crate) text: PietText,
+    /// Device pixels per logical pixel of the target being drawn to.
+    pub(crate) scale: f64,
     /// The id of the widget that currently has focus.
     pub(crate) focus_widget: Option<WidgetId>,
+    /// Whether the window has focus from the platform.
+    pub(crate) window_focused: bool,
+    /// Whether focus should be shown, because the keyboard was used last.
+    pub(crate) focus_visible: bool,
+    /// The widget under the cursor of assistive technologies, if any.
+    pub(crate) accessibility_focus: Option<WidgetId>,
+    /// A drag started during the current event pass.
+    pub(crate) drag_request: Option<DragRequest>,
+    /// The key event of the current pass, if it's a `KeyDown` or `KeyUp`.
+    pub(crate) key_event: Option<KeyEvent>,
+    /// How many times the key of `key_event` was repeated since it was pressed.
+    pub(crate) key_repeat_count: u32,
+    /// The size of the window, during layout passes.
+    pub(crate) window_size: Size,
+    /// When the layout pass runs out of budget, see
+    /// [`AppLauncher::with_layout_budget`](crate::AppLauncher::with_layout_budget).
+    pub(crate) layout_deadline: Option<Instant>,
+    /// Callbacks registered during the current pass, to run after the next layout.
+    pub(crate) after_layout_requests: Vec<(WidgetId, AfterLayoutCallback)>,
+    /// The callback that a `RouteAfterLayout` event is delivering.
+    pub(crate) after_layout_callback: Option<AfterLayoutCallback>,
+    /// Whether hot state changes are suppressed in the window, see
+    /// [`EventCtx::suppress_hot_changes`].
+    pub(crate) hot_suppressed: bool,
+    /// Set when a hover delay elapses and changes the hot state of a widget,
+    /// so that its ancestors recompute `has_hot`.
+    pub(crate) hover_committed: bool,
+    /// Layout rect observers added or removed during the current pass.
+    pub(crate) layout_rect_requests: Vec<LayoutRectRequest>,
+    /// Whether the current paint pass draws into an image rather than the
+    /// window's surface, so layers can't use or update their caches.
+    pub(crate) paint_offscreen: bool,
+}
+
+/// A request to start or stop observing the layout rect of a widget, see
+/// [`EventCtx::observe_layout_rect`].
+pub(crate) struct LayoutRectRequest {
+    pub(crate) observer: WidgetId,
+    pub(crate) target: WidgetId,
+    pub(crate) observe: bool,
 }
 
+/// A callback registered with [`EventCtx::run_after_layout`].
+pub(crate) type AfterLayoutCallback = Box<dyn FnOnce(&mut dyn Any, &mut EventCtx, &Env)>;
+
+/// How far back pointer moves are taken into account by [`EventCtx::pointer_velocity`].
+const POINTER_VELOCITY_WINDOW: Duration = Duration::from_millis(100);
+
 /// A context provided to implementors of [`StoreInWidgetMut`].
 ///
 /// When you declare a mutable reference type for your widget, methods of this type
@@ -87,7 +143,7 @@ pub struct EventCtx<'a, 'b> {
     pub(crate) notifications: &'a mut VecDeque<Notification>,
     pub(crate) is_handled: bool,
     pub(crate) is_root: bool,
-    pub(crate) request_pan_to_child: Option<Rect>,
+    pub(crate) request_pan_to_child: Option<(Rect, PanAlignment)>,
 }
 
 /// A context provided to the [`lifecycle`] method on widgets.
@@ -113,15 +169,53 @@ pub struct LayoutCtx<'a, 'b> {
     pub(crate) global_state: &'a mut GlobalPassCtx<'b>,
     pub(crate) widget_state: &'a mut WidgetState,
     pub(crate) mouse_pos: Option<Point>,
+    pub(crate) resize_pending: bool,
 }
 
 /// Z-order paint operations with transformations.
 pub(crate) struct ZOrderPaintOp {
     pub z_index: u32,
+    /// The paint depth of the widget that submitted the op.
+    pub depth: u32,
     pub paint_func: Box<dyn FnOnce(&mut PaintCtx) + 'static>,
     pub transform: Affine,
 }
 
+/// Sort z-ordered ops collected from the whole tree into paint order.
+///
+/// Ops are ordered by `z_index`, then by depth, so that at equal `z_index`
+/// descendants paint over their ancestors. The sort is stable: remaining ties
+/// are painted in tree order.
+pub(crate) fn sort_z_ops(z_ops: &mut [ZOrderPaintOp]) {
+    z_ops.sort_by_key(|op| (op.z_index, op.depth));
+}
+
+/// Gradient brushes built by [`PaintCtx`], kept across frames.
+///
+/// Brushes are device resources, so the cache is owned by the window and
+/// cleared whenever the window is resized.
+#[derive(Default)]
+pub(crate) struct BrushCache {
+    brushes: HashMap<GradientKey, Brush>,
+}
+
+/// The parameters a cached gradient brush was built from.
+///
+/// Floats are stored as their bit patterns so the key can be hashed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum GradientKey {
+    Linear {
+        start: (u64, u64),
+        end: (u64, u64),
+        stops: Vec<(u32, u32)>,
+    },
+    Radial {
+        center: (u64, u64),
+        radius: u64,
+        stops: Vec<(u32, u32)>,
+    },
+}
+
 /// A context passed to paint methods of widgets.
 ///
 /// In addition to the API below, [`PaintCtx`] derefs to an implemention of
@@ -138,6 +232,8 @@ pub struct PaintCtx<'a, 'b, 'c> {
     pub(crate) region: Region,
     /// The approximate depth in the tree at the time of painting.
     pub(crate) depth: u32,
+    /// Gradient brushes reused between frames.
+    pub(crate) brush_cache: &'a mut BrushCache,
 }
 
 impl_context_method!(
@@ -167,6 +263,19 @@ impl_context_method!(
             &mut self.global_state.text
         }
 
+        /// The number of device pixels per logical pixel of the surface being
+        /// drawn to.
+        ///
+        /// This is the window's scale, except during an export with
+        /// [`AppRoot::render_widget_to_with_scale`], where it's the scale of the export.
+        /// Widgets that cache rasterized content, eg images decoded at a
+        /// given resolution, can use it to pick a resolution.
+        ///
+        /// [`AppRoot::render_widget_to_with_scale`]: crate::AppRoot::render_widget_to_with_scale
+        pub fn scale(&self) -> f64 {
+            self.global_state.scale
+        }
+
         /// Skip iterating over the given child.
         ///
         /// Normally, container widgets are supposed to iterate over each of their
@@ -203,6 +312,9 @@ impl_context_method!(
 
         /// The origin of the widget in window coordinates, relative to the top left corner of the
         /// content area.
+        ///
+        /// This is only up to date after the widget has been laid out; see
+        /// [`WidgetPod::window_origin`] for details.
         pub fn window_origin(&self) -> Point {
             self.widget_state.window_origin()
         }
@@ -289,6 +401,68 @@ impl_context_method!(
             self.widget_state.has_focus
         }
 
+        /// Whether focus is within the subtree of this widget.
+        ///
+        /// This is the same as [`has_focus`], under the name of the
+        /// `:focus-within` CSS pseudo-class. Widgets receive
+        /// [`StatusChange::FocusWithinChanged`] when this changes, so a
+        /// container can repaint itself when focus enters or leaves its
+        /// children, without tracking them.
+        ///
+        /// [`has_focus`]: #method.has_focus
+        /// [`StatusChange::FocusWithinChanged`]: crate::StatusChange::FocusWithinChanged
+        pub fn has_focus_within(&self) -> bool {
+            self.widget_state.has_focus
+        }
+
+        /// Whether the window this widget is in has focus from the platform.
+        ///
+        /// This is `false` when the user switched to another window or
+        /// application. Widgets that show focus, such as text carets and
+        /// selections, should then render an inactive style.
+        ///
+        /// A new window is considered focused until the platform reports
+        /// otherwise.
+        ///
+        /// Widgets receive [`StatusChange::WindowFocusChanged`] when this changes.
+        ///
+        /// [`StatusChange::WindowFocusChanged`]: crate::StatusChange::WindowFocusChanged
+        pub fn is_window_focused(&self) -> bool {
+            self.global_state.window_focused
+        }
+
+        /// Whether this widget is focused, and should draw a focus indicator.
+        ///
+        /// Like the `:focus-visible` CSS pseudo-class, this is only `true` when
+        /// the keyboard was used since the last mouse press, so that clicking a
+        /// widget doesn't show a focus ring around it.
+        pub fn is_focus_visible(&self) -> bool {
+            self.is_focused() && self.global_state.focus_visible
+        }
+
+        /// Whether the cursor of an assistive technology, such as a screen
+        /// reader, is on this widget.
+        ///
+        /// Screen readers move their own cursor through the widgets, to read
+        /// them, independently of keyboard focus. Which of the two follows the
+        /// other depends on the assistive technology:
+        ///
+        /// - By default, moving the cursor doesn't change keyboard focus. The
+        ///   user can activate the widget under the cursor, which sends it
+        ///   [`ACTIVATE`], and keyboard events still go to the focused widget.
+        /// - With [`Env::focus_follows_accessibility_focus`], moving the cursor
+        ///   to a focusable widget focuses it too.
+        ///
+        /// Widgets receive [`StatusChange::AccessibilityFocusChanged`] when
+        /// this changes, and are scrolled into view when they gain it.
+        ///
+        /// [`ACTIVATE`]: crate::command::ACTIVATE
+        /// [`Env::focus_follows_accessibility_focus`]: crate::Env::focus_follows_accessibility_focus
+        /// [`StatusChange::AccessibilityFocusChanged`]: crate::StatusChange::AccessibilityFocusChanged
+        pub fn is_accessibility_focused(&self) -> bool {
+            self.global_state.accessibility_focus == Some(self.widget_id())
+        }
+
         /// The disabled state of a widget.
         ///
         /// Returns `true` if this widget or any of its ancestors is explicitly disabled.
@@ -448,7 +622,13 @@ impl_context_method!(WidgetCtx<'_, '_>, EventCtx<'_, '_>, LifeCycleCtx<'_, '_>,
     /// response to some event) it must call this method.
     ///
     /// [`layout`]: trait.Widget.html#tymethod.layout
+    ///
+    /// Calling this several times before the next layout pass is cheap: the
+    /// request is only recorded once.
     pub fn request_layout(&mut self) {
+        if self.widget_state.needs_layout {
+            return;
+        }
         trace!("request_layout");
         self.widget_state.needs_layout = true;
     }
@@ -465,10 +645,32 @@ impl_context_method!(WidgetCtx<'_, '_>, EventCtx<'_, '_>, LifeCycleCtx<'_, '_>,
     pub fn children_changed(&mut self) {
         trace!("children_changed");
         self.widget_state.children_changed = true;
+        self.widget_state.hit_child = None;
         self.widget_state.update_focus_chain = true;
+        self.widget_state.subtree_dirty = true;
+        self.widget_state.thumbnail_content_changed = true;
         self.request_layout();
     }
 
+    /// Indicate that the return value of [`Widget::can_receive_focus_now`] has changed.
+    ///
+    /// This rebuilds the focus chain at the end of the current pass.
+    pub fn focusability_changed(&mut self) {
+        trace!("focusability_changed");
+        self.widget_state.update_focus_chain = true;
+    }
+
+    /// Send [`LifeCycle::WidgetAdded`] again to this widget and all its descendants.
+    ///
+    /// This replays their initialization, for instance to re-read values from the
+    /// [`Env`] or rebuild caches after a theme or locale change. The widgets keep
+    /// their ids and focus, and the focus chain is rebuilt afterwards.
+    pub fn reinit_subtree(&mut self) {
+        trace!("reinit_subtree");
+        self.widget_state.needs_reinit = true;
+        self.children_changed();
+    }
+
     /// Set the disabled state for this widget.
     ///
     /// Setting this to `false` does not mean a widget is not still disabled; for instance it may
@@ -484,6 +686,68 @@ impl_context_method!(WidgetCtx<'_, '_>, EventCtx<'_, '_>, LifeCycleCtx<'_, '_>,
         self.widget_state.is_explicitly_disabled_new = disabled;
     }
 
+    /// Set how long the pointer must stay over or away from this widget before
+    /// its hot state changes.
+    ///
+    /// By default hot state changes as soon as the pointer moves, which can make
+    /// eg submenus flash open when the pointer passes over their menu item. With
+    /// a delay, the change is only committed if the pointer stays; if it goes back
+    /// before the delay elapses, the change is cancelled. Until then, the widget
+    /// doesn't receive `MouseMove` events. Button presses always update hot state
+    /// right away.
+    ///
+    /// Pass `None` to go back to immediate changes.
+    pub fn set_hover_delay(&mut self, delay: Option<Duration>) {
+        self.widget_state.hover_delay = delay;
+        if delay.is_none() {
+            self.widget_state.pending_hot = None;
+        }
+    }
+
+    /// Set how this widget receives the `KeyDown` events repeated while a key
+    /// is held.
+    ///
+    /// This only applies while the widget has focus itself; it doesn't change
+    /// the events its descendants receive. See [`KeyRepeat`].
+    pub fn set_key_repeat(&mut self, key_repeat: KeyRepeat) {
+        self.widget_state.key_repeat = key_repeat;
+    }
+
+    /// Tear down a child widget that has been taken out of the widget tree.
+    ///
+    /// The child and its descendants receive [`LifeCycle::WidgetRemoved`] before
+    /// the child is dropped. Container widgets should call this method with children
+    /// they remove, instead of simply dropping them.
+    ///
+    /// If the removed subtree holds the focus, the focus is resigned, unless a new
+    /// focus was already requested. The accessibility focus is cleared the same
+    /// way. If it holds the active widget, the pointer is released.
+    pub fn remove_child(&mut self, mut child: WidgetPod<impl Widget>) {
+        trace!("remove_child");
+        if child.state.has_focus && self.widget_state.request_focus.is_none() {
+            self.widget_state.request_focus = Some(FocusChange::Resign);
+        }
+        if let Some(id) = self.global_state.accessibility_focus {
+            if child.as_dyn().find_widget_by_id(id).is_some() {
+                self.global_state
+                    .submit_command(crate::command::SET_ACCESSIBILITY_FOCUS.with(None));
+            }
+        }
+        if child.state.has_active {
+            // The remaining children add theirs back on the next pointer event.
+            self.widget_state.has_active = self.widget_state.is_active;
+        }
+        // A child that was never added to the tree has nothing to tear down.
+        if let Some(env) = child.env.clone() {
+            let mut child_ctx = LifeCycleCtx {
+                global_state: self.global_state,
+                widget_state: self.widget_state,
+            };
+            child.lifecycle(&mut child_ctx, &LifeCycle::WidgetRemoved, &env);
+        }
+        self.children_changed();
+    }
+
     /// Mark child widget as stashed.
     ///
     /// **Note:** Stashed widgets are a WIP feature
@@ -509,6 +773,43 @@ impl_context_method!(WidgetCtx<'_, '_>, EventCtx<'_, '_>, LifeCycleCtx<'_, '_>,
     }
 });
 
+impl_context_method!(EventCtx<'_, '_>, LifeCycleCtx<'_, '_>, {
+    /// Get notified when the layout rect of the `target` widget changes.
+    ///
+    /// After each layout pass where the target's rect in window coordinates
+    /// changed, this widget receives a
+    /// [`LAYOUT_RECT_CHANGED`](crate::command::LAYOUT_RECT_CHANGED) command.
+    /// The first one is sent after the next layout pass, with the current rect.
+    ///
+    /// This is meant to keep things outside of the widget tree, such as a
+    /// native surface, positioned over a widget. Observing a widget that's
+    /// already observed does nothing. The observation ends when this widget
+    /// is removed.
+    pub fn observe_layout_rect(&mut self, target: WidgetId) {
+        trace!("observe_layout_rect {:?}", target);
+        self.global_state
+            .layout_rect_requests
+            .push(LayoutRectRequest {
+                observer: self.widget_state.id,
+                target,
+                observe: true,
+            });
+    }
+
+    /// Stop observing the layout rect of `target`, see
+    /// [`observe_layout_rect`](Self::observe_layout_rect).
+    pub fn stop_observing_layout_rect(&mut self, target: WidgetId) {
+        trace!("stop_observing_layout_rect {:?}", target);
+        self.global_state
+            .layout_rect_requests
+            .push(LayoutRectRequest {
+                observer: self.widget_state.id,
+                target,
+                observe: false,
+            });
+    }
+});
+
 // methods on everyone but paintctx
 impl_context_method!(
     WidgetCtx<'_, '_>,
@@ -532,6 +833,20 @@ impl_context_method!(
             self.global_state.submit_command(cmd.into())
         }
 
+        /// Submit several [`Command`]s to be run after this event is handled.
+        ///
+        /// Commands targeting widgets are delivered together, in a single traversal
+        /// of the widget tree, instead of one traversal per command. This is
+        /// noticeably faster when sending many commands at once, eg to update
+        /// every item of a list. Commands with the same target are delivered in
+        /// the order they were submitted.
+        ///
+        /// [`Target::Auto`] commands will be sent to the window containing the widget.
+        pub fn submit_commands(&mut self, cmds: Vec<Command>) {
+            trace!("submit_commands");
+            self.global_state.submit_commands(cmds)
+        }
+
         /// Submit an [`Action`].
         ///
         /// Note: Actions are still a WIP feature.
@@ -585,6 +900,25 @@ impl_context_method!(
             token
         }
 
+        /// Render the current frame of the window into an image.
+        ///
+        /// The image covers the whole window at its physical resolution, and
+        /// is sent back to this widget as an
+        /// [`Event::PromiseResult`](crate::Event::PromiseResult) once the
+        /// current event is handled. See [`CAPTURE_WINDOW`](crate::command::CAPTURE_WINDOW).
+        pub fn capture_window(&mut self) -> PromiseToken<ImageBuf> {
+            let token = PromiseToken::new();
+            let request = crate::command::CaptureRequest {
+                token,
+                widget: self.widget_state.id,
+            };
+            let cmd = crate::command::CAPTURE_WINDOW
+                .with(request)
+                .to(Target::Window(self.global_state.window_id));
+            self.submit_command(cmd);
+            token
+        }
+
         /// Request a timer event.
         ///
         /// The return value is a token, which can be used to associate the
@@ -639,7 +973,14 @@ impl EventCtx<'_, '_> {
 
     /// Send a signal to parent widgets to scroll this widget into view.
     pub fn request_pan_to_this(&mut self) {
-        self.request_pan_to_child = Some(self.widget_state.layout_rect());
+        self.request_pan_to_this_aligned(PanAlignment::Nearest);
+    }
+
+    /// Send a signal to parent widgets to scroll this widget into view,
+    /// placing it in the viewport according to `alignment`.
+    pub fn request_pan_to_this_aligned(&mut self, alignment: PanAlignment) {
+        let rect = self.widget_state.size.to_rect();
+        self.request_pan_to_child = Some((rect, alignment));
     }
 
     /// Set the "active" state of the widget.
@@ -651,6 +992,184 @@ impl EventCtx<'_, '_> {
         // TODO: plumb mouse grab through to platform (through druid-shell)
     }
 
+    /// The velocity of the pointer, in pixels per second, while this widget is active.
+    ///
+    /// This is computed from the pointer positions recorded since the last
+    /// [`MouseDown`](crate::Event::MouseDown) this widget received, and only
+    /// accounts for recent moves. It's meant for gestures like flings and swipes.
+    ///
+    /// Returns [`Vec2::ZERO`] if there aren't enough recent moves.
+    pub fn pointer_velocity(&self) -> Vec2 {
+        let history = &self.widget_state.pointer_history;
+        let (last_time, last_pos) = match history.back() {
+            Some(sample) => *sample,
+            None => return Vec2::ZERO,
+        };
+        let first = history
+            .iter()
+            .find(|(time, _)| last_time.duration_since(*time) <= POINTER_VELOCITY_WINDOW);
+        match first {
+            Some((first_time, first_pos)) => {
+                let elapsed = last_time.duration_since(*first_time).as_secs_f64();
+                if elapsed > 0.0 {
+                    (last_pos - *first_pos) / elapsed
+                } else {
+                    Vec2::ZERO
+                }
+            }
+            None => Vec2::ZERO,
+        }
+    }
+
+    /// The logical key of the [`KeyDown`] or [`KeyUp`] event being handled.
+    ///
+    /// This is the key as interpreted by the current keyboard layout, for
+    /// example `KbKey::Character("z")` for the key left of X on a QWERTY
+    /// keyboard, but `KbKey::Character("w")` on an AZERTY one. Use it for text
+    /// input and for shortcuts named after a letter. It's the same as the
+    /// event's `key` field.
+    ///
+    /// Returns `None` while handling other events.
+    ///
+    /// [`KeyDown`]: crate::Event::KeyDown
+    /// [`KeyUp`]: crate::Event::KeyUp
+    pub fn logical_key(&self) -> Option<&KbKey> {
+        self.global_state.key_event.as_ref().map(|event| &event.key)
+    }
+
+    /// The physical key of the [`KeyDown`] or [`KeyUp`] event being handled.
+    ///
+    /// This identifies the position of the key on the keyboard, regardless of
+    /// the keyboard layout; codes are named after the US QWERTY layout, so
+    /// `Code::KeyW` is the key above S on every keyboard. Use it for
+    /// position-based bindings, such as WASD movement in games. It's the same
+    /// as the event's `code` field.
+    ///
+    /// Platforms that don't report physical keys, and synthesized events, use
+    /// `Code::Unidentified`. Returns `None` while handling other events.
+    ///
+    /// [`KeyDown`]: crate::Event::KeyDown
+    /// [`KeyUp`]: crate::Event::KeyUp
+    pub fn physical_key(&self) -> Option<Code> {
+        self.global_state.key_event.as_ref().map(|event| event.code)
+    }
+
+    /// How many times the key of the [`KeyDown`] event being handled was
+    /// repeated since it was pressed.
+    ///
+    /// This is 0 for the first press, and while handling other events.
+    /// Whether the event is a repeat is also in its `repeat` field.
+    ///
+    /// [`KeyDown`]: crate::Event::KeyDown
+    pub fn key_repeat_count(&self) -> u32 {
+        self.global_state.key_repeat_count
+    }
+
+    /// How much a widget should scale the effect of the key press being handled,
+    /// if it uses [`KeyRepeat::Accelerate`].
+    ///
+    /// This is 1 for the first press, and for widgets with other
+    /// [key repeat settings](Self::set_key_repeat).
+    pub fn key_repeat_factor(&self) -> f64 {
+        match self.widget_state.key_repeat {
+            KeyRepeat::Accelerate { step, max } => {
+                let factor = 1.0 + step * self.global_state.key_repeat_count as f64;
+                factor.min(max).max(1.0)
+            }
+            KeyRepeat::Allow | KeyRepeat::Ignore => 1.0,
+        }
+    }
+
+    /// Start a drag-and-drop interaction carrying `payload`.
+    ///
+    /// While the mouse button is held, `preview` is painted at the pointer
+    /// above the rest of the window, and the widgets under the pointer receive
+    /// [`Event::Drag`] events. Drop targets can read the payload with
+    /// [`DragEvent::payload`]. The drag ends when the mouse button is released,
+    /// or when the user presses Escape, which sends [`DragPhase::Cancel`] to
+    /// this widget and to the drop target.
+    ///
+    /// This should be called while handling a [`MouseDown`] or [`MouseMove`] event.
+    /// The preview is laid out once, with loose constraints, and isn't updated
+    /// during the drag.
+    ///
+    /// [`Event::Drag`]: crate::Event::Drag
+    /// [`DragEvent::payload`]: crate::DragEvent::payload
+    /// [`DragPhase::Cancel`]: crate::DragPhase::Cancel
+    /// [`MouseDown`]: crate::Event::MouseDown
+    /// [`MouseMove`]: crate::Event::MouseMove
+    pub fn begin_drag(&mut self, payload: impl Any, preview: impl Widget) {
+        trace!("begin_drag");
+        self.global_state.drag_request = Some(DragRequest {
+            source: self.widget_id(),
+            payload: Rc::new(payload),
+            preview: Box::new(preview),
+        });
+    }
+
+    /// Run `callback` once the next layout pass of the window is complete.
+    ///
+    /// The callback runs at the end of the update cycle that did the layout,
+    /// not from within the layout, so it doesn't matter what triggered it.
+    ///
+    /// This is for work that needs the new layout of the widget, such as
+    /// scrolling to the bottom of a list after adding items to it. The callback
+    /// is given the widget and an `EventCtx` for it, whose [`size`] and
+    /// [`window_origin`] are those of the new layout.
+    ///
+    /// `W` must be the type of the widget calling this method. This doesn't
+    /// request a layout pass; if nothing else does, the callback waits until
+    /// one happens. Changes made by the callback that need a new layout are
+    /// laid out right away, but callbacks registered by the callback wait
+    /// for the next layout pass. Commands and actions it submits are run in
+    /// the same update cycle.
+    ///
+    /// [`size`]: EventCtx::size
+    /// [`window_origin`]: EventCtx::window_origin
+    pub fn run_after_layout<W: Widget>(
+        &mut self,
+        callback: impl FnOnce(&mut W, &mut EventCtx, &Env) + 'static,
+    ) {
+        trace!("run_after_layout");
+        let callback = move |widget: &mut dyn Any, ctx: &mut EventCtx, env: &Env| match widget
+            .downcast_mut::<W>()
+        {
+            Some(widget) => callback(widget, ctx, env),
+            None => error!(
+                "run_after_layout: widget {:?} isn't a {}",
+                ctx.widget_id(),
+                std::any::type_name::<W>()
+            ),
+        };
+        self.global_state
+            .after_layout_requests
+            .push((self.widget_id(), Box::new(callback)));
+    }
+
+    /// Stop updating the hot state of the window's widgets, until
+    /// [`resume_hot_changes`](Self::resume_hot_changes) is called.
+    ///
+    /// This is meant for scrolling containers: while content scrolls under a
+    /// stationary pointer, the widgets passing beneath it would otherwise
+    /// flicker between hot and not hot. Hot state still follows button
+    /// presses, and suppression ends on its own when the pointer moves, is
+    /// pressed or leaves the window.
+    ///
+    /// While hot changes are suppressed, no `MouseMove` is synthesized after
+    /// layout. Once they resume, a single synthetic `MouseMove` resolves the
+    /// hot state of the widgets under the pointer.
+    pub fn suppress_hot_changes(&mut self) {
+        trace!("suppress_hot_changes");
+        self.global_state.hot_suppressed = true;
+    }
+
+    /// Resume the hot state updates stopped by
+    /// [`suppress_hot_changes`](Self::suppress_hot_changes).
+    pub fn resume_hot_changes(&mut self) {
+        trace!("resume_hot_changes");
+        self.global_state.hot_suppressed = false;
+    }
+
     /// Set the event as "handled", which stops its propagation to other
     /// widgets.
     pub fn set_handled(&mut self) {
@@ -777,6 +1296,19 @@ impl LifeCycleCtx<'_, '_> {
         self.widget_state.focus_chain.push(self.widget_id());
     }
 
+    /// Tell Masonry that this widget paints its own focus ring.
+    ///
+    /// The ring painted when [`Env::AUTO_FOCUS_RING`] is set is then skipped
+    /// for this widget.
+    ///
+    /// This should be called in response to [`LifeCycle::BuildFocusChain`],
+    /// before which it's reset. To start painting the automatic ring again,
+    /// call [`focusability_changed`](Self::focusability_changed) and stop
+    /// calling this method.
+    pub fn suppress_focus_ring(&mut self) {
+        self.widget_state.draws_own_focus_ring = true;
+    }
+
     /// Register this widget as accepting text input.
     pub fn register_text_input(&mut self, document: impl ImeHandlerRef + 'static) {
         let registration = TextFieldRegistration {
@@ -789,13 +1321,66 @@ impl LifeCycleCtx<'_, '_> {
     // TODO - remove - See issue #15
     /// Register this widget as a portal.
     ///
-    /// This should only be used by scroll areas.
+    /// This should only be used by scroll areas. It disables the debug check that
+    /// children paint within their parent for all children of the widget; to allow
+    /// some children to paint outside a container, see
+    /// [`LayoutCtx::expand_paint_rect_to_include`] instead.
     pub fn register_as_portal(&mut self) {
         self.widget_state.is_portal = true;
     }
+
+    /// Promote this widget to its own compositor layer, or demote it.
+    ///
+    /// A layer's content is painted once and cached; on later frames, if the
+    /// widget only moved, the cached image is drawn at its new position instead
+    /// of calling [`Widget::paint`] on it and its descendants.
+    ///
+    /// The content is re-rendered when the widget or one of its descendants
+    /// requests a paint or a layout, or has children added or removed, or when
+    /// its paint rect changes size.
+    ///
+    /// The cache is captured from the render target, so a layer should paint
+    /// an opaque background over its whole paint rect. Widgets in a layer
+    /// that use [`PaintCtx::paint_with_z_index`] prevent it from being cached.
+    pub fn set_is_layer(&mut self, is_layer: bool) {
+        self.widget_state.is_layer = is_layer;
+        self.widget_state.subtree_dirty = true;
+    }
 }
 
 impl LayoutCtx<'_, '_> {
+    /// Return whether the window has already been resized to a size other than
+    /// the one this layout pass is running for.
+    ///
+    /// When that's the case, a new layout pass with fresh constraints is imminent,
+    /// so a widget doing expensive layout work may return a quick approximate size
+    /// instead.
+    ///
+    /// This is a best-effort optimization: it depends on the platform reporting
+    /// the new size early, and returning `false` doesn't guarantee that no resize
+    /// is pending.
+    pub fn is_resize_pending(&self) -> bool {
+        self.resize_pending
+    }
+
+    /// The size of the window's content area.
+    ///
+    /// The constraints passed to [`Widget::layout`] are decided by the parent;
+    /// this lets a widget size itself relative to the window instead, such as a
+    /// sidebar that takes 30% of the window's width.
+    ///
+    /// A widget using this depends on the window size, and its layout should be
+    /// computed again when the window is resized. This is always the case,
+    /// since a resize lays out the whole widget tree; but the widget shouldn't
+    /// cache its size across layout passes.
+    ///
+    /// For windows sized from their content (see
+    /// [`WindowSizePolicy::Content`](crate::WindowSizePolicy::Content)), this is
+    /// the size computed during the previous layout pass.
+    pub fn window_size(&self) -> Size {
+        self.global_state.window_size
+    }
+
     /// Set explicit paint [`Insets`] for this widget.
     ///
     /// You are not required to set explicit paint bounds unless you need
@@ -813,6 +1398,119 @@ impl LayoutCtx<'_, '_> {
         self.widget_state.paint_insets = insets.nonnegative();
     }
 
+    /// Clip the painting of this widget's children to `clip`, in this widget's
+    /// coordinate space, or stop clipping them with `None`.
+    ///
+    /// This is for containers whose children are larger than the area they're
+    /// visible in, such as the content of a scroll viewport. The clip doesn't
+    /// need to be inside this widget's layout rect, and the widget's own
+    /// painting isn't clipped. Ancestors that clip their children still do.
+    ///
+    /// All children are clipped, when they're painted and when they receive
+    /// pointer events: parts of them outside the clip aren't painted and don't
+    /// receive pointer events. Children placed after the call only count in
+    /// this widget's paint rect inside the clip.
+    ///
+    /// The clip is kept until it's changed; it doesn't need to be set on every
+    /// layout pass. See [`WidgetState::paint_clip`](crate::WidgetState::paint_clip).
+    pub fn set_paint_clip(&mut self, clip: Option<Rect>) {
+        trace!("set_paint_clip {:?}", clip);
+        self.widget_state.paint_clip = clip;
+    }
+
+    /// Set the z-index this widget paints at.
+    ///
+    /// Hit-testing uses it to resolve overlapping siblings: the pointer goes to
+    /// the child with the highest z-index, and at equal z-index to the last
+    /// child, which containers paint last. See [`Widget::get_child_at_pos`].
+    ///
+    /// This doesn't change when the widget is painted; a widget that sets a
+    /// z-index should paint with [`PaintCtx::paint_with_z_index`] at
+    /// [`PaintCtx::z_index`], so that the widget receiving the pointer is the
+    /// one on top. In debug builds, painting only at other indices panics.
+    pub fn set_z_index(&mut self, z_index: u32) {
+        trace!("set_z_index {}", z_index);
+        self.widget_state.z_index = z_index;
+    }
+
+    /// Acknowledge that something paints in `rect`, outside this widget's layout
+    /// rect and paint insets, in this widget's coordinate space.
+    ///
+    /// This is for deliberate overflow, such as a badge or a shadow painted by
+    /// a child beyond the edges of its container. The rect is added to this
+    /// widget's paint rect, so that it's invalidated and painted correctly.
+    ///
+    /// In debug builds, Masonry checks that children paint within their parent.
+    /// Once a widget calls this method during a layout pass, the check becomes
+    /// strict for that widget: each child must paint within the widget's own
+    /// layout rect and paint insets, or within an acknowledged rect, so
+    /// overflow that wasn't acknowledged is still reported. Unlike
+    /// [`register_as_portal`], which disables the check for every child, this
+    /// only allows the overflow it names.
+    ///
+    /// This must be called on every layout pass; acknowledged rects are reset
+    /// before [`Widget::layout`] is called.
+    ///
+    /// [`register_as_portal`]: LifeCycleCtx::register_as_portal
+    pub fn expand_paint_rect_to_include(&mut self, rect: Rect) {
+        trace!("expand_paint_rect_to_include {:?}", rect);
+        self.widget_state.local_paint_rect = self.widget_state.local_paint_rect.union(rect);
+        self.widget_state.acknowledged_paint_rect = Some(
+            self.widget_state
+                .acknowledged_paint_rect
+                .map_or(rect, |acknowledged| acknowledged.union(rect)),
+        );
+    }
+
+    /// Build `layout` now if it needs rebuilding, so painting it is cheap.
+    ///
+    /// Text layouts are usually built lazily, which makes the first frame showing
+    /// some text slower. Containers can use this to build the layouts of text that
+    /// isn't visible yet, eg rows that are about to scroll into view.
+    ///
+    /// This is the same as calling [`TextLayout::rebuild_if_needed`] with this
+    /// context's text factory.
+    pub fn prewarm_text_layout<T: TextStorage>(&mut self, layout: &mut TextLayout<T>, env: &Env) {
+        layout.rebuild_if_needed(&mut self.global_state.text, env);
+    }
+
+    /// Measure `text` drawn with `font` at `size`, wrapped at `max_width`.
+    ///
+    /// This is meant for trial measurements, such as deciding whether a string
+    /// needs to be truncated: no layout object is kept, so text that is drawn
+    /// should still use a retained [`TextLayout`], which is only rebuilt when
+    /// it changes.
+    ///
+    /// `size` replaces the size of `font`, and is used as is; unlike
+    /// [`TextLayout`], it isn't multiplied by [`theme::TEXT_SIZE_SCALE`].
+    /// Pass `f64::INFINITY` as `max_width` to measure a single line.
+    ///
+    /// [`theme::TEXT_SIZE_SCALE`]: crate::theme::TEXT_SIZE_SCALE
+    pub fn measure_text(
+        &mut self,
+        text: &str,
+        font: &FontDescriptor,
+        size: f64,
+        max_width: f64,
+    ) -> Size {
+        let layout = self
+            .global_state
+            .text
+            .new_text_layout(text.to_string())
+            .max_width(max_width)
+            .font(font.family.clone(), size)
+            .default_attribute(font.weight)
+            .default_attribute(font.style)
+            .build();
+        match layout {
+            Ok(layout) => layout.size(),
+            Err(err) => {
+                warn!("failed to measure text: {}", err);
+                Size::ZERO
+            }
+        }
+    }
+
     /// Set an explicit baseline position for this widget.
     ///
     /// The baseline position is used to align widgets that contain text,
@@ -837,8 +1535,19 @@ impl LayoutCtx<'_, '_> {
         child.state.is_expecting_place_child_call = false;
         let layout_rect = child.layout_rect();
 
-        self.widget_state.local_paint_rect =
-            self.widget_state.local_paint_rect.union(child.paint_rect());
+        match self.widget_state.paint_clip {
+            Some(clip) => {
+                let visible_rect = child.paint_rect().intersect(clip);
+                if visible_rect.area() > 0.0 {
+                    self.widget_state.local_paint_rect =
+                        self.widget_state.local_paint_rect.union(visible_rect);
+                }
+            }
+            None => {
+                self.widget_state.local_paint_rect =
+                    self.widget_state.local_paint_rect.union(child.paint_rect());
+            }
+        }
 
         // if the widget has moved, it may have moved under the mouse, in which
         // case we need to handle that.
@@ -847,12 +1556,72 @@ impl LayoutCtx<'_, '_> {
             &mut child.state,
             self.global_state,
             layout_rect,
-            self.mouse_pos,
+            self.widget_state
+                .child_pointer_pos(child.state.id, self.mouse_pos),
             env,
         ) {
             self.widget_state.merge_up(&mut child.state);
         }
     }
+
+    /// Set the position of a child widget so that its baseline is at `baseline_y`,
+    /// and its left edge at `x`, in the parent's coordinate space.
+    ///
+    /// The child's origin is computed from its size and
+    /// [`baseline_offset`](WidgetPod::baseline_offset), so this must be called
+    /// after `child.layout(...)`. It otherwise behaves like [`place_child`](Self::place_child).
+    pub fn place_child_on_baseline(
+        &mut self,
+        child: &mut WidgetPod<impl Widget>,
+        x: f64,
+        baseline_y: f64,
+        env: &Env,
+    ) {
+        let height = child.layout_rect().height();
+        let y = baseline_y - (height - child.baseline_offset());
+        self.place_child(child, Point::new(x, y), env);
+    }
+
+    /// Round each dimension of `size` up to a multiple of `increment`, so
+    /// that content of that size still fits.
+    ///
+    /// The size is returned unchanged if `increment` isn't positive.
+    pub fn snap_size_to_grid(&self, size: Size, increment: f64) -> Size {
+        if increment > 0.0 {
+            Size::new(
+                (size.width / increment).ceil() * increment,
+                (size.height / increment).ceil() * increment,
+            )
+        } else {
+            size
+        }
+    }
+
+    /// Set the position of a child widget like [`place_child`](Self::place_child),
+    /// with `origin` rounded to the nearest multiple of [`Env::GRID_INCREMENT`].
+    ///
+    /// The grid is in logical units, like all layout coordinates, and is
+    /// unrelated to device pixels: with a scale factor of 1.5, an increment
+    /// of 8 puts children on device pixels, but an increment of 5 doesn't.
+    /// Children may still be painted between device pixels, since Masonry
+    /// doesn't snap positions to them.
+    pub fn place_child_snapped(
+        &mut self,
+        child: &mut WidgetPod<impl Widget>,
+        origin: Point,
+        env: &Env,
+    ) {
+        let increment = env.get(Env::GRID_INCREMENT);
+        let origin = if increment > 0.0 {
+            Point::new(
+                (origin.x / increment).round() * increment,
+                (origin.y / increment).round() * increment,
+            )
+        } else {
+            origin
+        };
+        self.place_child(child, origin, env);
+    }
 }
 
 impl PaintCtx<'_, '_, '_> {
@@ -870,6 +1639,17 @@ impl PaintCtx<'_, '_, '_> {
         self.depth
     }
 
+    /// The z-index set with [`LayoutCtx::set_z_index`] during the last layout.
+    ///
+    /// Pass it to [`paint_with_z_index`] to paint where hit-testing expects
+    /// this widget to be.
+    ///
+    /// [`paint_with_z_index`]: #method.paint_with_z_index
+    #[inline]
+    pub fn z_index(&self) -> u32 {
+        self.widget_state.z_index
+    }
+
     /// Returns the region that needs to be repainted.
     #[inline]
     pub fn region(&self) -> &Region {
@@ -889,6 +1669,7 @@ impl PaintCtx<'_, '_, '_> {
             z_ops: Vec::new(),
             region: region.into(),
             depth: self.depth + 1,
+            brush_cache: self.brush_cache,
         };
         f(&mut child_ctx);
         self.z_ops.append(&mut child_ctx.z_ops);
@@ -931,6 +1712,13 @@ impl PaintCtx<'_, '_, '_> {
     /// Allows to specify order for paint operations.
     ///
     /// Larger `z_index` indicate that an operation will be executed later.
+    ///
+    /// Operations from the whole window are collected, and executed after the
+    /// rest of the window is painted, regardless of which subtree submitted
+    /// them. Operations with the same `z_index` are executed from the
+    /// shallowest widget to the deepest, then in tree order. Operations
+    /// submitted from inside another operation are executed after all the
+    /// operations of the current pass.
     pub fn paint_with_z_index(
         &mut self,
         z_index: u32,
@@ -939,10 +1727,158 @@ impl PaintCtx<'_, '_, '_> {
         let current_transform = self.render_ctx.current_transform();
         self.z_ops.push(ZOrderPaintOp {
             z_index,
+            depth: self.depth,
             paint_func: Box::new(paint_func),
             transform: current_transform,
         })
     }
+
+    /// Fills a shape with a linear gradient going from `start` to `end`.
+    ///
+    /// Points are in the widget's coordinate space. The brush is cached by its
+    /// parameters, so calling this every frame with the same arguments doesn't
+    /// rebuild it.
+    ///
+    /// With a single stop, the shape is filled with that stop's color. With no
+    /// stops, nothing is painted.
+    pub fn fill_linear_gradient(
+        &mut self,
+        shape: impl Shape,
+        start: Point,
+        end: Point,
+        stops: impl GradientStops,
+    ) {
+        let stops = stops.to_vec();
+        match stops.as_slice() {
+            [] => warn!("fill_linear_gradient called without any gradient stops"),
+            [stop] => self.render_ctx.fill(shape, &stop.color),
+            _ => {
+                let key = GradientKey::Linear {
+                    start: point_key(start),
+                    end: point_key(end),
+                    stops: stops_key(&stops),
+                };
+                let gradient = FixedLinearGradient { start, end, stops };
+                if let Some(brush) = self
+                    .brush_cache
+                    .get_or_create(self.render_ctx, key, gradient)
+                {
+                    self.render_ctx.fill(shape, brush);
+                }
+            }
+        }
+    }
+
+    /// Fills a shape with a radial gradient centered on `center`.
+    ///
+    /// This behaves like [`fill_linear_gradient`], including its handling of
+    /// degenerate stops.
+    ///
+    /// [`fill_linear_gradient`]: #method.fill_linear_gradient
+    pub fn fill_radial_gradient(
+        &mut self,
+        shape: impl Shape,
+        center: Point,
+        radius: f64,
+        stops: impl GradientStops,
+    ) {
+        let stops = stops.to_vec();
+        match stops.as_slice() {
+            [] => warn!("fill_radial_gradient called without any gradient stops"),
+            [stop] => self.render_ctx.fill(shape, &stop.color),
+            _ => {
+                let key = GradientKey::Radial {
+                    center: point_key(center),
+                    radius: radius.to_bits(),
+                    stops: stops_key(&stops),
+                };
+                let gradient = FixedRadialGradient {
+                    center,
+                    origin_offset: Vec2::ZERO,
+                    radius,
+                    stops,
+                };
+                if let Some(brush) = self
+                    .brush_cache
+                    .get_or_create(self.render_ctx, key, gradient)
+                {
+                    self.render_ctx.fill(shape, brush);
+                }
+            }
+        }
+    }
+}
+
+impl<'c> PaintCtx<'_, '_, 'c> {
+    /// Strokes a shape with a dashed line.
+    ///
+    /// `dashes` alternates the lengths of dashes and gaps, and `offset` shifts
+    /// the pattern along the shape. Dashes have flat ends. If the pattern has no
+    /// positive length, this is a plain [`stroke`](RenderContext::stroke), so
+    /// solid lines don't pay for dashing.
+    ///
+    /// For control over caps and joins, use
+    /// [`stroke_styled`](RenderContext::stroke_styled) with a [`StrokeStyle`].
+    pub fn stroke_dashed(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Piet<'c>>,
+        width: f64,
+        dashes: &[f64],
+        offset: f64,
+    ) {
+        if dashes.iter().all(|length| *length <= 0.0) {
+            self.render_ctx.stroke(shape, brush, width);
+            return;
+        }
+        let mut style = StrokeStyle::new().line_cap(LineCap::Butt);
+        style.set_dash_pattern(dashes.to_vec());
+        style.set_dash_offset(offset);
+        self.render_ctx.stroke_styled(shape, brush, width, &style);
+    }
+}
+
+impl BrushCache {
+    /// Past this many entries the cache is flushed, so that animated
+    /// gradients don't make it grow forever.
+    const MAX_ENTRIES: usize = 64;
+
+    pub(crate) fn clear(&mut self) {
+        self.brushes.clear();
+    }
+
+    fn get_or_create(
+        &mut self,
+        render_ctx: &mut Piet,
+        key: GradientKey,
+        gradient: impl Into<FixedGradient>,
+    ) -> Option<&Brush> {
+        if !self.brushes.contains_key(&key) {
+            let brush = match render_ctx.gradient(gradient) {
+                Ok(brush) => brush,
+                Err(e) => {
+                    error!("Failed to create gradient brush: '{}'", e);
+                    return None;
+                }
+            };
+            if self.brushes.len() >= Self::MAX_ENTRIES {
+                self.brushes.clear();
+            }
+            self.brushes.insert(key.clone(), brush);
+        }
+        self.brushes.get(&key)
+    }
+}
+
+fn point_key(point: Point) -> (u64, u64) {
+    (point.x.to_bits(), point.y.to_bits())
+}
+
+fn stops_key(stops: &[GradientStop]) -> Vec<(u32, u32)> {
+    stops
+        .iter()
+        .map(|stop| (stop.pos.to_bits(), stop.color.as_rgba_u32()))
+        .collect()
 }
 
 impl<'a> GlobalPassCtx<'a> {
@@ -951,11 +1887,14 @@ impl<'a> GlobalPassCtx<'a> {
         debug_logger: &'a mut DebugLogger,
         command_queue: &'a mut CommandQueue,
         action_queue: &'a mut ActionQueue,
-        timers: &'a mut HashMap<TimerToken, WidgetId>,
+        timers: &'a mut HashMap<TimerToken, (WidgetId, Instant)>,
         mock_timer_queue: Option<&'a mut MockTimerQueue>,
         window: &'a WindowHandle,
         window_id: WindowId,
         focus_widget: Option<WidgetId>,
+        window_focused: bool,
+        focus_visible: bool,
+        accessibility_focus: Option<WidgetId>,
     ) -> Self {
         GlobalPassCtx {
             ext_event_sink,
@@ -967,14 +1906,55 @@ impl<'a> GlobalPassCtx<'a> {
             window,
             window_id,
             focus_widget,
+            window_focused,
+            focus_visible,
+            accessibility_focus,
             text: window.text(),
+            scale: window.get_scale().map_or(1.0, |scale| scale.x()),
+            drag_request: None,
+            key_event: None,
+            key_repeat_count: 0,
+            window_size: Size::ZERO,
+            layout_deadline: None,
+            after_layout_requests: Vec::new(),
+            after_layout_callback: None,
+            hot_suppressed: false,
+            hover_committed: false,
+            layout_rect_requests: Vec::new(),
+            paint_offscreen: false,
         }
     }
 
     pub(crate) fn submit_command(&mut self, command: Command) {
-        trace!("submit_command");
-        self.command_queue
-            .push_back(command.default_to(self.window_id.into()));
+        self.submit_commands(vec![command]);
+    }
+
+    pub(crate) fn submit_commands(&mut self, commands: Vec<Command>) {
+        trace!("submit_commands count={}", commands.len());
+        let target = Target::Window(self.window_id);
+        let mut commands: Vec<_> = commands
+            .into_iter()
+            .map(|command| command.default_to(target))
+            .collect();
+        if commands.len() == 1 {
+            self.command_queue.push_back(commands.pop().unwrap());
+        } else if !commands.is_empty() {
+            // The batch is delivered as soon as its most urgent command.
+            let priority = if commands
+                .iter()
+                .any(|command| command.priority() == CommandPriority::High)
+            {
+                CommandPriority::High
+            } else {
+                CommandPriority::Normal
+            };
+            self.command_queue.push_back(
+                crate::command::COMMAND_BATCH
+                    .with(commands)
+                    .to(target)
+                    .with_priority(priority),
+            );
+        }
     }
 
     pub(crate) fn submit_action(&mut self, action: Action, widget_id: WidgetId) {
@@ -983,6 +1963,22 @@ impl<'a> GlobalPassCtx<'a> {
             .push_back((action, widget_id, self.window_id));
     }
 
+    /// The current time, or the mocked time in unit tests.
+    pub(crate) fn now(&self) -> Instant {
+        match &self.mock_timer_queue {
+            Some(timer_queue) => timer_queue.now(),
+            None => Instant::now(),
+        }
+    }
+
+    /// Whether the layout pass is over its budget.
+    pub(crate) fn layout_budget_spent(&self) -> bool {
+        match self.layout_deadline {
+            Some(deadline) => self.now() >= deadline,
+            None => false,
+        }
+    }
+
     pub(crate) fn request_timer(&mut self, duration: Duration, widget_id: WidgetId) -> TimerToken {
         trace!("request_timer duration={:?}", duration);
 
@@ -994,7 +1990,8 @@ impl<'a> GlobalPassCtx<'a> {
             self.window.request_timer(duration)
         };
 
-        self.timers.insert(timer_token, widget_id);
+        let deadline = self.now() + duration;
+        self.timers.insert(timer_token, (widget_id, deadline));
         timer_token
     }
 }
@@ -0,0 +1,128 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! Helpers for dashed strokes.
+
+use crate::kurbo::Shape;
+use crate::piet::{IntoBrush, Piet};
+use crate::PaintCtx;
+
+/// Animates the offset of a dashed stroke, for "marching ants" outlines
+/// around selections.
+///
+/// Advance it from [`Widget::on_anim_frame`](crate::Widget::on_anim_frame),
+/// and paint with [`stroke`](Self::stroke):
+///
+/// ```
+/// # use masonry::{Color, EventCtx, MarchingAnts, PaintCtx, Rect};
+/// # fn on_anim_frame(ants: &mut MarchingAnts, ctx: &mut EventCtx, interval: u64) -> bool {
+/// ctx.request_paint();
+/// ants.advance(interval)
+/// # }
+/// # fn paint(ants: &MarchingAnts, ctx: &mut PaintCtx, selection: Rect) {
+/// ants.stroke(ctx, selection, &Color::BLACK, 1.0);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarchingAnts {
+    dashes: Vec<f64>,
+    speed: f64,
+    offset: f64,
+}
+
+impl MarchingAnts {
+    /// Create an animation of the given dash pattern, moving at `speed`
+    /// in pixels per second.
+    ///
+    /// See [`PaintCtx::stroke_dashed`] for the meaning of `dashes`.
+    pub fn new(dashes: impl Into<Vec<f64>>, speed: f64) -> Self {
+        MarchingAnts {
+            dashes: dashes.into(),
+            speed,
+            offset: 0.0,
+        }
+    }
+
+    /// Move the dashes forward by `interval` nanoseconds.
+    ///
+    /// This returns `true`, so that it can be returned from `on_anim_frame`
+    /// to keep animating.
+    pub fn advance(&mut self, interval: u64) -> bool {
+        let period: f64 = self.dashes.iter().sum();
+        if period > 0.0 {
+            self.offset = (self.offset + self.speed * interval as f64 * 1e-9).rem_euclid(period);
+        }
+        true
+    }
+
+    /// The current dash offset.
+    pub fn offset(&self) -> f64 {
+        self.offset
+    }
+
+    /// The dash pattern.
+    pub fn dashes(&self) -> &[f64] {
+        &self.dashes
+    }
+
+    /// Stroke `shape` with the dash pattern at its current offset.
+    pub fn stroke<'c>(
+        &self,
+        ctx: &mut PaintCtx<'_, '_, 'c>,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Piet<'c>>,
+        width: f64,
+    ) {
+        ctx.stroke_dashed(shape, brush, width, &self.dashes, self.offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ModularWidget, TestHarness};
+    use crate::{Color, Rect, Size};
+
+    fn render_stroke(dashes: &'static [f64]) -> std::sync::Arc<[u8]> {
+        let widget = ModularWidget::new(())
+            .layout_fn(|_, _, _, _| Size::new(40.0, 40.0))
+            .paint_fn(move |_, ctx, _| {
+                let rect = Rect::new(5.0, 5.0, 35.0, 35.0);
+                ctx.stroke_dashed(rect, &Color::WHITE, 2.0, dashes, 0.0);
+            });
+        let mut harness = TestHarness::create_with_size(widget, Size::new(40.0, 40.0));
+        harness.render()
+    }
+
+    #[test]
+    fn dashed_rect() {
+        let solid = render_stroke(&[]);
+        let dashed = render_stroke(&[4.0, 4.0]);
+        let zero = render_stroke(&[0.0, 0.0]);
+
+        assert!(solid != dashed);
+        // Degenerate patterns are drawn solid.
+        assert!(solid == zero);
+
+        // The gaps leave fewer painted pixels.
+        let painted = |image: &[u8]| image.chunks(4).filter(|px| px[0] > 128).count();
+        assert!(painted(&dashed) < painted(&solid));
+        assert!(painted(&dashed) > 0);
+    }
+
+    #[test]
+    fn marching_ants_wrap() {
+        let mut ants = MarchingAnts::new([3.0, 2.0], 10.0);
+        // Half a second moves the dashes by 5px, a whole period.
+        assert!(ants.advance(500_000_000));
+        assert!(ants.offset().abs() < 1e-9);
+        ants.advance(200_000_000);
+        assert!((ants.offset() - 2.0).abs() < 1e-9);
+
+        // An empty pattern doesn't move.
+        let mut solid = MarchingAnts::new(Vec::new(), 10.0);
+        solid.advance(1_000_000_000);
+        assert_eq!(solid.offset(), 0.0);
+    }
+}
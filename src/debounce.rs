@@ -0,0 +1,139 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! Helpers to rate-limit reactions to rapid input.
+
+use std::time::Duration;
+
+use druid_shell::TimerToken;
+use instant::Instant;
+
+use crate::{Event, EventCtx};
+
+/// Runs an action once input has stopped for a given delay.
+///
+/// Each call to [`trigger`](Self::trigger) restarts the delay, so in a burst
+/// of triggers only the last one fires. This is useful for search-as-you-type,
+/// where a query should only be run once the user stops typing.
+///
+/// The debouncer is driven by timers requested by the owning widget. Forward
+/// your widget's events to [`fired`](Self::fired) to know when to run the action:
+///
+/// ```
+/// # use masonry::{Debouncer, Event, EventCtx};
+/// # fn run_search() {}
+/// # fn on_event(debouncer: &mut Debouncer, ctx: &mut EventCtx, event: &Event) {
+/// match event {
+///     Event::KeyDown(_) => debouncer.trigger(ctx),
+///     event if debouncer.fired(event) => run_search(),
+///     _ => (),
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Debouncer {
+    delay: Duration,
+    pending: Option<TimerToken>,
+}
+
+/// Runs an action at most once per interval.
+///
+/// The first call to [`trigger`](Self::trigger) in an interval returns `true`,
+/// and the action should run immediately. Later calls in the same interval
+/// return `false`, and schedule a single trailing run at the end of the interval,
+/// signaled by [`fired`](Self::fired), so the last input is never lost.
+#[derive(Debug, Clone)]
+pub struct Throttler {
+    interval: Duration,
+    last_run: Option<Instant>,
+    trailing: Option<TimerToken>,
+}
+
+impl Debouncer {
+    /// Create a debouncer that fires after `delay` without triggers.
+    pub fn new(delay: Duration) -> Self {
+        Debouncer {
+            delay,
+            pending: None,
+        }
+    }
+
+    /// Restart the delay.
+    ///
+    /// Any previously pending fire is dropped.
+    pub fn trigger(&mut self, ctx: &mut EventCtx) {
+        // Platform timers can't be cancelled; the old timer will still
+        // arrive, but `fired` won't recognize it.
+        self.pending = Some(ctx.request_timer(self.delay));
+    }
+
+    /// Drop the pending fire, if any.
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    /// Whether a fire is pending.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Return `true` if `event` is the timer of the last trigger.
+    ///
+    /// The caller should then run the debounced action.
+    pub fn fired(&mut self, event: &Event) -> bool {
+        match event {
+            Event::Timer(token) if self.pending == Some(*token) => {
+                self.pending = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Throttler {
+    /// Create a throttler that runs at most once per `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Throttler {
+            interval,
+            last_run: None,
+            trailing: None,
+        }
+    }
+
+    /// Return `true` if the throttled action should run now.
+    ///
+    /// If it returns `false`, a trailing run is scheduled for the end of the
+    /// current interval.
+    pub fn trigger(&mut self, ctx: &mut EventCtx) -> bool {
+        let now = ctx.global_state.now();
+        match self.last_run {
+            Some(last_run) if now.duration_since(last_run) < self.interval => {
+                if self.trailing.is_none() {
+                    let remaining = self.interval - now.duration_since(last_run);
+                    self.trailing = Some(ctx.request_timer(remaining));
+                }
+                false
+            }
+            _ => {
+                self.last_run = Some(now);
+                true
+            }
+        }
+    }
+
+    /// Return `true` if `event` is the timer of a trailing run.
+    ///
+    /// The caller should then run the throttled action.
+    pub fn fired(&mut self, ctx: &EventCtx, event: &Event) -> bool {
+        match event {
+            Event::Timer(token) if self.trailing == Some(*token) => {
+                self.trailing = None;
+                self.last_run = Some(ctx.global_state.now());
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -10,8 +10,8 @@ use std::sync::Arc;
 use crate::debug_values::{
     LayoutInfo, LayoutTree, LogId, MyWidgetId, Snapshot, StateTree, Timeline, Value,
 };
-use crate::widget::WidgetRef;
-use crate::Widget;
+use crate::widget::{DiffedState, WidgetRef};
+use crate::{StateDelta, Widget, WidgetId};
 
 #[derive(Debug)]
 pub struct DebugLog {
@@ -20,12 +20,17 @@ pub struct DebugLog {
     children: Vec<LogId>,
 }
 
+/// Whether the logger is compiled in, see the `debug_logger` feature.
+const COMPILED_IN: bool = cfg!(feature = "debug_logger");
+
 #[derive(Debug)]
 pub struct DebugLogger {
     pub activated: bool,
 
     pub layout_tree: LayoutTree,
     pub widget_states: HashMap<MyWidgetId, StateTree>,
+    /// The state of each widget when it was last recorded, to diff against.
+    pub(crate) last_widget_states: HashMap<MyWidgetId, DiffedState>,
     pub global_state: StateTree,
     pub event_state: StateTree,
 
@@ -44,6 +49,7 @@ impl DebugLogger {
             activated,
             layout_tree: Default::default(),
             widget_states: Default::default(),
+            last_widget_states: Default::default(),
             global_state: Default::default(),
             event_state: Default::default(),
             logs: HashMap::new(),
@@ -56,6 +62,15 @@ impl DebugLogger {
         new_self
     }
 
+    /// Whether passes are being recorded.
+    ///
+    /// This is always `false` without the `debug_logger` feature, so that the
+    /// calls made by every pass compile to nothing.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        COMPILED_IN && self.activated
+    }
+
     pub fn write_to_file(&self, path: &str) {
         use std::fs::File;
         use std::io::{BufWriter, Write};
@@ -97,7 +112,7 @@ impl DebugLogger {
     }
 
     pub fn push_log(&mut self, important: bool, message: &str) {
-        if !self.activated {
+        if !self.is_active() {
             return;
         }
 
@@ -122,7 +137,7 @@ impl DebugLogger {
     }
 
     pub fn push_span(&mut self, message: &str) {
-        if !self.activated {
+        if !self.is_active() {
             return;
         }
         self.push_log(false, message);
@@ -130,7 +145,7 @@ impl DebugLogger {
     }
 
     pub fn push_important_span(&mut self, message: &str) {
-        if !self.activated {
+        if !self.is_active() {
             return;
         }
         self.push_log(true, message);
@@ -138,14 +153,14 @@ impl DebugLogger {
     }
 
     pub fn pop_span(&mut self) {
-        if !self.activated {
+        if !self.is_active() {
             return;
         }
         self.span_stack.pop();
     }
 
     fn push_snapshot(&mut self) {
-        if !self.activated {
+        if !self.is_active() {
             return;
         }
         self.log_id_counter.0 += 1;
@@ -161,10 +176,31 @@ impl DebugLogger {
         );
     }
 
-    pub fn update_widget_state(&mut self, widget: WidgetRef<'_, dyn Widget>) {
-        if !self.activated {
+    /// Record the state of `widget`, and log what changed since it was last
+    /// recorded.
+    ///
+    /// The full state is still recorded; the log only summarizes the changes,
+    /// eg "changed is_hot, layout_rect", and is skipped if nothing changed.
+    pub fn log_widget_state(&mut self, widget: WidgetRef<'_, dyn Widget>) {
+        if !self.is_active() {
             return;
         }
+        match self.update_widget_state(widget) {
+            Some(delta) if delta.is_empty() => {}
+            Some(delta) => {
+                let message = format!("changed {}", delta.changed().join(", "));
+                self.push_log(false, &message);
+            }
+            None => self.push_log(false, "updated state"),
+        }
+    }
+
+    /// Record the state of `widget`, and return what changed since it was
+    /// last recorded, or `None` if it's the first record.
+    pub fn update_widget_state(&mut self, widget: WidgetRef<'_, dyn Widget>) -> Option<StateDelta> {
+        if !self.is_active() {
+            return None;
+        }
         let widget_id = widget.state().id.to_raw() as u32;
         let layout_info = LayoutInfo {
             layout_rect: widget.state().layout_rect(),
@@ -183,6 +219,19 @@ impl DebugLogger {
         let mut widgets = (*self.layout_tree.widgets).clone();
         widgets.insert(widget_id, layout_info);
         self.layout_tree.widgets = widgets.into();
+
+        let state = widget.state().diffed_state();
+        let delta = self
+            .last_widget_states
+            .get(&widget_id)
+            .map(|previous| state.diff(previous));
+        self.last_widget_states.insert(widget_id, state);
+        delta
+    }
+
+    /// Forget the last recorded state of a widget removed from the tree.
+    pub fn forget_widget_state(&mut self, widget_id: WidgetId) {
+        self.last_widget_states.remove(&(widget_id.to_raw() as u32));
     }
 
     pub fn get_widget_state(widget: WidgetRef<'_, dyn Widget>) -> StateTree {
@@ -195,33 +244,12 @@ impl DebugLogger {
             state.name = w_state.widget_name.to_string();
         }
 
-        state.children = vec![
-            StateTree::new(
-                "is_expecting_place_child_call",
-                w_state.is_expecting_place_child_call,
-            ),
-            StateTree::new("is_new", w_state.is_new),
-            StateTree::new(
-                "children_disabled_changed",
-                w_state.children_disabled_changed,
-            ),
-            StateTree::new("ancestor_disabled", w_state.ancestor_disabled),
-            StateTree::new("is_explicitly_disabled", w_state.is_explicitly_disabled),
-            StateTree::new("is_hot", w_state.is_hot),
-            StateTree::new("needs_layout", w_state.needs_layout),
-            StateTree::new("needs_window_origin", w_state.needs_window_origin),
-            StateTree::new("is_active", w_state.is_active),
-            StateTree::new("has_active", w_state.has_active),
-            StateTree::new("has_focus", w_state.has_focus),
-            StateTree::new("request_anim", w_state.request_anim),
-            StateTree::new("children_changed", w_state.children_changed),
-            StateTree::new(
-                "is_explicitly_disabled_new",
-                w_state.is_explicitly_disabled_new,
-            ),
-            StateTree::new("update_focus_chain", w_state.update_focus_chain),
-        ]
-        .into();
+        state.children = w_state
+            .flags()
+            .into_iter()
+            .map(|(name, value)| StateTree::new(name, value))
+            .collect::<Vec<_>>()
+            .into();
         state
     }
 
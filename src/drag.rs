@@ -0,0 +1,154 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! Types for in-window drag-and-drop.
+
+use std::any::Any;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::widget::WidgetRef;
+use crate::{Point, Widget, WidgetId, WidgetPod};
+
+/// The stage of a drag-and-drop interaction, see [`DragEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragPhase {
+    /// The pointer entered the widget while dragging.
+    ///
+    /// Widgets that accept the payload must call
+    /// [`EventCtx::set_handled`](crate::EventCtx::set_handled); they then become
+    /// the drop target. Otherwise the ancestors of the widget are asked next.
+    Enter,
+    /// The pointer moved over the current drop target.
+    Over,
+    /// The pointer left the current drop target, or another widget became
+    /// the target.
+    Leave,
+    /// The payload was dropped on the current drop target.
+    Drop,
+    /// The drag was cancelled, because the user pressed Escape or the source
+    /// widget was removed.
+    ///
+    /// This is sent to the source and to the current drop target, if any. The
+    /// preview is removed, and the payload isn't dropped. A source that moved
+    /// its item away while dragging, as in a reorderable list, should put it
+    /// back, and release the pointer if it was [active](crate::EventCtx::set_active).
+    Cancel,
+}
+
+/// An event sent to potential drop targets during drag-and-drop.
+///
+/// A drag is started by a widget calling [`EventCtx::begin_drag`], and ends
+/// when the mouse button is released, or when it's
+/// [cancelled](DragPhase::Cancel). While the drag is active, the widgets under
+/// the pointer receive [`Event::Drag`] events, from the deepest widget up to
+/// the root.
+///
+/// [`EventCtx::begin_drag`]: crate::EventCtx::begin_drag
+/// [`Event::Drag`]: crate::Event::Drag
+#[derive(Clone)]
+pub struct DragEvent {
+    /// The stage of the interaction.
+    pub phase: DragPhase,
+    /// The position of the pointer, relative to the receiving widget.
+    pub pos: Point,
+    /// The position of the pointer, relative to the window.
+    pub window_pos: Point,
+    source: WidgetId,
+    payload: Rc<dyn Any>,
+}
+
+/// A drag requested by a widget, started once the current event is handled.
+pub(crate) struct DragRequest {
+    pub(crate) source: WidgetId,
+    pub(crate) payload: Rc<dyn Any>,
+    pub(crate) preview: Box<dyn Widget>,
+}
+
+/// An active drag in a window.
+pub(crate) struct DragSession {
+    pub(crate) source: WidgetId,
+    pub(crate) payload: Rc<dyn Any>,
+    pub(crate) preview: WidgetPod<Box<dyn Widget>>,
+    /// The widget that accepted the payload, if any.
+    pub(crate) target: Option<WidgetId>,
+    /// The widgets under the pointer at the last move, deepest first.
+    pub(crate) hovered: Vec<WidgetId>,
+}
+
+// ---
+
+impl DragEvent {
+    /// The widget that started the drag.
+    pub fn source(&self) -> WidgetId {
+        self.source
+    }
+
+    /// The dragged payload, if it's of type `T`.
+    pub fn payload<T: Any>(&self) -> Option<&T> {
+        self.payload.downcast_ref()
+    }
+}
+
+impl fmt::Debug for DragEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DragEvent")
+            .field("phase", &self.phase)
+            .field("pos", &self.pos)
+            .field("window_pos", &self.window_pos)
+            .field("source", &self.source)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DragSession {
+    pub(crate) fn new(request: DragRequest) -> Self {
+        DragSession {
+            source: request.source,
+            payload: request.payload,
+            preview: WidgetPod::new(request.preview),
+            target: None,
+            hovered: Vec::new(),
+        }
+    }
+
+    /// Build an event for the given phase, at the given window position.
+    ///
+    /// `pos` is set to `window_pos`; it's made relative to the receiving widget
+    /// when the event reaches it.
+    pub(crate) fn make_event(&self, phase: DragPhase, window_pos: Point) -> DragEvent {
+        DragEvent {
+            phase,
+            pos: window_pos,
+            window_pos,
+            source: self.source,
+            payload: self.payload.clone(),
+        }
+    }
+}
+
+/// Return the enabled, visible widgets whose layout rect contains `pos`,
+/// deepest first.
+pub(crate) fn drop_candidates(root: WidgetRef<'_, dyn Widget>, pos: Point) -> Vec<WidgetId> {
+    let mut candidates = Vec::new();
+    let mut current = Some(root);
+    while let Some(widget) = current.take() {
+        let state = widget.state();
+        if state.is_stashed || state.is_disabled() || !state.window_layout_rect().contains(pos) {
+            break;
+        }
+        candidates.push(state.id);
+        // The topmost child wins, as in `Widget::get_child_at_pos`.
+        current = widget
+            .children()
+            .into_iter()
+            .filter(|child| {
+                let state = child.state();
+                !state.is_stashed && state.window_layout_rect().contains(pos)
+            })
+            .max_by_key(|child| child.state().z_index);
+    }
+    candidates.reverse();
+    candidates
+}
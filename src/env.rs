@@ -7,13 +7,16 @@
 use std::any::{self, Any};
 use std::borrow::Borrow;
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Debug, Formatter};
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::sync::Arc;
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 use crate::kurbo::RoundedRectRadii;
+use crate::piet::{FontFamily, FontStyle, FontWeight};
 use crate::text::FontDescriptor;
 use crate::{ArcStr, Color, Data, Insets, Point, Rect, Size};
 
@@ -37,6 +40,14 @@ use crate::{ArcStr, Color, Data, Insets, Point, Rect, Size};
 /// - [`Key`]s must always be set before they are used.
 /// - Values can only be overwritten by values of the same type.
 ///
+/// # Serialization
+///
+/// `Env` implements serde's `Serialize` and `Deserialize`, so the exact
+/// environment used to render can be saved, eg to pin it in a snapshot test
+/// or attach it to a bug report, and loaded back on another machine. Keys are
+/// written in sorted order. Values of custom types (stored as [`Value::Other`])
+/// can't be serialized; they're skipped with a warning.
+///
 /// [`EnvScope`]: widget/struct.EnvScope.html
 /// [`Key`]: struct.Key.html
 #[derive(Clone)]
@@ -197,6 +208,27 @@ pub struct MissingKeyError {
     key: Arc<str>,
 }
 
+/// The accessibility settings of the OS that affect how widgets render and behave.
+///
+/// They're stored in the root [`Env`], and read with [`Env::reduced_transparency`],
+/// [`Env::high_contrast`], [`Env::increased_contrast`] and
+/// [`Env::focus_follows_accessibility_focus`]. All settings are off by default,
+/// and widgets opt in to respecting the visual ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AccessibilitySettings {
+    /// The user asked for fewer translucent surfaces, such as blurred backgrounds.
+    pub reduced_transparency: bool,
+    /// A high-contrast theme is active.
+    pub high_contrast: bool,
+    /// The user asked for more contrast, without a full high-contrast theme.
+    pub increased_contrast: bool,
+    /// Keyboard focus moves along with the cursor of the screen reader.
+    ///
+    /// Some screen readers have this as an option, such as VoiceOver's
+    /// "keyboard focus follows VoiceOver cursor".
+    pub focus_follows_accessibility_focus: bool,
+}
+
 impl Env {
     /// State for whether or not to paint colorful rectangles for layout
     /// debugging.
@@ -238,6 +270,114 @@ impl Env {
     /// [`WidgetExt::debug_widget`]: trait.WidgetExt.html#method.debug_widget
     pub const DEBUG_WIDGET: Key<bool> = Key::new("org.linebender.masonry.built-in.debug-widget");
 
+    /// Whether pressing F12 logs a dump of the window's layout.
+    ///
+    /// The dump is the output of [`WidgetRef::serialize_layout`] for the root
+    /// widget, logged at the `info` level. It's meant to be pasted into bug reports.
+    ///
+    /// [`WidgetRef::serialize_layout`]: crate::widget::WidgetRef::serialize_layout
+    pub const DEBUG_LAYOUT_DUMP: Key<bool> =
+        Key::new("org.linebender.masonry.built-in.debug-layout-dump");
+
+    /// Whether to highlight the widget under the pointer, as resolved by hit
+    /// testing.
+    ///
+    /// On each mouse move, the innermost widget found by
+    /// [`WidgetRef::find_widget_at_pos`] is outlined over everything else in
+    /// the window, including the widget ids of [`WidgetExt::debug_widget_id`],
+    /// and its type and id are logged at the `info` level when it changes.
+    /// Hit testing goes through [`Widget::get_child_at_pos`], so z-indices,
+    /// paint clips and widgets with custom hit shapes are accounted for.
+    /// This helps finding out why a click went to an unexpected widget.
+    ///
+    /// [`WidgetRef::find_widget_at_pos`]: crate::widget::WidgetRef::find_widget_at_pos
+    /// [`WidgetExt::debug_widget_id`]: crate::WidgetExt::debug_widget_id
+    /// [`Widget::get_child_at_pos`]: crate::Widget::get_child_at_pos
+    pub const DEBUG_HIT_TEST: Key<bool> =
+        Key::new("org.linebender.masonry.built-in.debug-hit-test");
+
+    /// The locale used to format numbers and dates, as a language tag such as
+    /// `"en-US"`.
+    ///
+    /// Applications start with the locale of the OS. It can be changed at runtime
+    /// with [`SET_LOCALE`](crate::command::SET_LOCALE). See the [`locale`](crate::locale)
+    /// module for the formatting helpers.
+    pub const LOCALE: Key<ArcStr> = Key::new("org.linebender.masonry.built-in.locale");
+
+    /// Whether the OS asks for reduced transparency.
+    ///
+    /// See [`AccessibilitySettings`].
+    pub const REDUCED_TRANSPARENCY: Key<bool> =
+        Key::new("org.linebender.masonry.built-in.reduced-transparency");
+
+    /// Whether a high-contrast theme is active in the OS.
+    ///
+    /// See [`AccessibilitySettings`].
+    pub const HIGH_CONTRAST: Key<bool> = Key::new("org.linebender.masonry.built-in.high-contrast");
+
+    /// Whether the OS asks for increased contrast.
+    ///
+    /// See [`AccessibilitySettings`].
+    pub const INCREASED_CONTRAST: Key<bool> =
+        Key::new("org.linebender.masonry.built-in.increased-contrast");
+
+    /// Whether keyboard focus follows the cursor of assistive technologies.
+    ///
+    /// See [`AccessibilitySettings`].
+    pub const FOCUS_FOLLOWS_ACCESSIBILITY_FOCUS: Key<bool> =
+        Key::new("org.linebender.masonry.built-in.focus-follows-accessibility-focus");
+
+    /// Whether a focus ring is painted automatically around the focused widget.
+    ///
+    /// The ring is a dashed outline drawn inside the widget's layout rect with
+    /// [`theme::FOCUS_RING_COLOR`] and [`theme::FOCUS_RING_WIDTH`], when the focus should be visible (see
+    /// [`is_focus_visible`](crate::EventCtx::is_focus_visible)). It can be
+    /// turned on or off for a subtree by overriding this key, and widgets that
+    /// paint their own ring opt out with
+    /// [`suppress_focus_ring`](crate::LifeCycleCtx::suppress_focus_ring).
+    ///
+    /// [`theme::FOCUS_RING_COLOR`]: crate::theme::FOCUS_RING_COLOR
+    /// [`theme::FOCUS_RING_WIDTH`]: crate::theme::FOCUS_RING_WIDTH
+    pub const AUTO_FOCUS_RING: Key<bool> =
+        Key::new("org.linebender.masonry.built-in.auto-focus-ring");
+
+    /// The longest time between two presses of a mouse button for them to
+    /// count as a multi-click, in milliseconds.
+    ///
+    /// Masonry counts clicks itself, and sets [`MouseEvent::count`] of
+    /// [`Event::MouseDown`]: `1` for a single click, `2` for a double click,
+    /// and so on. The count goes back to `1` after this interval, or when the
+    /// pointer moves further than [`MULTI_CLICK_DISTANCE`](Self::MULTI_CLICK_DISTANCE)
+    /// from the last press.
+    ///
+    /// [`MouseEvent::count`]: crate::MouseEvent::count
+    /// [`Event::MouseDown`]: crate::Event::MouseDown
+    pub const MULTI_CLICK_INTERVAL: Key<u64> =
+        Key::new("org.linebender.masonry.built-in.multi-click-interval");
+
+    /// How far the pointer can move from a press, in logical units, before
+    /// the next press stops counting as a multi-click.
+    ///
+    /// See [`MULTI_CLICK_INTERVAL`](Self::MULTI_CLICK_INTERVAL).
+    pub const MULTI_CLICK_DISTANCE: Key<f64> =
+        Key::new("org.linebender.masonry.built-in.multi-click-distance");
+
+    /// The grid increment used by
+    /// [`LayoutCtx::place_child_snapped`](crate::LayoutCtx::place_child_snapped),
+    /// in logical units.
+    ///
+    /// Values that aren't positive disable snapping.
+    pub const GRID_INCREMENT: Key<f64> = Key::new("org.linebender.masonry.built-in.grid-increment");
+
+    /// The scale an export is rendered at, set by the window for the passes of
+    /// [`AppRoot::render_widget_to_with_scale`](crate::AppRoot::render_widget_to_with_scale).
+    ///
+    /// Unset in normal passes. [`TextLayout`](crate::TextLayout) rebuilds when
+    /// it changes, so text is built for the export's resolution, and again
+    /// for the window's once the export is done.
+    pub(crate) const RENDER_SCALE: Key<f64> =
+        Key::new("org.linebender.masonry.built-in.render-scale");
+
     /// Gets a value from the environment, expecting it to be present.
     ///
     /// Note that the return value is a reference for "expensive" types such
@@ -311,6 +451,15 @@ impl Env {
         self.0.map.iter()
     }
 
+    /// Return whether `key` has a different value in `self` and `other`.
+    pub(crate) fn key_changed(&self, other: &Env, key: &Key<()>) -> bool {
+        match (self.0.map.get(key.key), other.0.map.get(key.key)) {
+            (Some(value), Some(other_value)) => !value.same(other_value),
+            (None, None) => false,
+            _ => true,
+        }
+    }
+
     /// Adds a key/value, acting like a builder.
     pub fn adding<V: ValueType>(mut self, key: Key<V>, value: impl Into<V>) -> Env {
         let env = Arc::make_mut(&mut self.0);
@@ -355,6 +504,56 @@ impl Env {
         Ok(())
     }
 
+    /// Whether the OS asks for reduced transparency.
+    ///
+    /// Returns `false` if the key isn't set.
+    pub fn reduced_transparency(&self) -> bool {
+        self.try_get(Env::REDUCED_TRANSPARENCY).unwrap_or(false)
+    }
+
+    /// Whether a high-contrast theme is active in the OS.
+    ///
+    /// Returns `false` if the key isn't set.
+    pub fn high_contrast(&self) -> bool {
+        self.try_get(Env::HIGH_CONTRAST).unwrap_or(false)
+    }
+
+    /// Whether the OS asks for increased contrast.
+    ///
+    /// Returns `false` if the key isn't set.
+    pub fn increased_contrast(&self) -> bool {
+        self.try_get(Env::INCREASED_CONTRAST).unwrap_or(false)
+    }
+
+    /// Whether keyboard focus follows the cursor of assistive technologies.
+    ///
+    /// Returns `false` if the key isn't set.
+    pub fn focus_follows_accessibility_focus(&self) -> bool {
+        self.try_get(Env::FOCUS_FOLLOWS_ACCESSIBILITY_FOCUS)
+            .unwrap_or(false)
+    }
+
+    /// All the accessibility settings stored in this environment.
+    pub fn accessibility_settings(&self) -> AccessibilitySettings {
+        AccessibilitySettings {
+            reduced_transparency: self.reduced_transparency(),
+            high_contrast: self.high_contrast(),
+            increased_contrast: self.increased_contrast(),
+            focus_follows_accessibility_focus: self.focus_follows_accessibility_focus(),
+        }
+    }
+
+    /// Store accessibility settings in this environment.
+    pub fn set_accessibility_settings(&mut self, settings: AccessibilitySettings) {
+        self.set(Env::REDUCED_TRANSPARENCY, settings.reduced_transparency);
+        self.set(Env::HIGH_CONTRAST, settings.high_contrast);
+        self.set(Env::INCREASED_CONTRAST, settings.increased_contrast);
+        self.set(
+            Env::FOCUS_FOLLOWS_ACCESSIBILITY_FOCUS,
+            settings.focus_follows_accessibility_focus,
+        );
+    }
+
     /// Given an id, returns one of 18 distinct colors
     #[doc(hidden)]
     pub fn get_debug_color(&self, id: u64) -> Color {
@@ -388,6 +587,14 @@ impl<T> Key<T> {
             value_type: PhantomData,
         }
     }
+
+    /// Return this key without its value type.
+    ///
+    /// This is used to list keys of different types together, for instance in
+    /// [`Widget::env_keys_affecting_layout`](crate::Widget::env_keys_affecting_layout).
+    pub const fn erased(&self) -> Key<()> {
+        Key::untyped(self.key)
+    }
 }
 
 impl Key<()> {
@@ -465,6 +672,135 @@ impl Debug for Value {
     }
 }
 
+/// The serialized form of a [`Value`], made of plain numbers and strings.
+#[derive(Serialize, Deserialize)]
+enum SerializedValue {
+    Point(f64, f64),
+    Size(f64, f64),
+    Rect(f64, f64, f64, f64),
+    Insets(f64, f64, f64, f64),
+    /// RGBA, 8 bits per component.
+    Color(u32),
+    Float(f64),
+    Bool(bool),
+    UnsignedInt(u64),
+    String(String),
+    Font {
+        family: String,
+        size: f64,
+        weight: u16,
+        italic: bool,
+    },
+    /// Top-left, top-right, bottom-right and bottom-left radii.
+    RoundedRectRadii(f64, f64, f64, f64),
+}
+
+impl SerializedValue {
+    fn from_value(value: &Value) -> Option<Self> {
+        let value = match value {
+            Value::Point(p) => SerializedValue::Point(p.x, p.y),
+            Value::Size(s) => SerializedValue::Size(s.width, s.height),
+            Value::Rect(r) => SerializedValue::Rect(r.x0, r.y0, r.x1, r.y1),
+            Value::Insets(i) => SerializedValue::Insets(i.x0, i.y0, i.x1, i.y1),
+            Value::Color(c) => SerializedValue::Color(c.as_rgba_u32()),
+            Value::Float(x) => SerializedValue::Float(*x),
+            Value::Bool(b) => SerializedValue::Bool(*b),
+            Value::UnsignedInt(x) => SerializedValue::UnsignedInt(*x),
+            Value::String(s) => SerializedValue::String(s.to_string()),
+            Value::Font(font) => SerializedValue::Font {
+                family: font.family.name().to_string(),
+                size: font.size,
+                weight: font.weight.to_raw(),
+                italic: font.style == FontStyle::Italic,
+            },
+            Value::RoundedRectRadii(r) => SerializedValue::RoundedRectRadii(
+                r.top_left,
+                r.top_right,
+                r.bottom_right,
+                r.bottom_left,
+            ),
+            Value::Other(_) => return None,
+        };
+        Some(value)
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            SerializedValue::Point(x, y) => Value::Point(Point::new(x, y)),
+            SerializedValue::Size(width, height) => Value::Size(Size::new(width, height)),
+            SerializedValue::Rect(x0, y0, x1, y1) => Value::Rect(Rect::new(x0, y0, x1, y1)),
+            SerializedValue::Insets(x0, y0, x1, y1) => Value::Insets(Insets::new(x0, y0, x1, y1)),
+            SerializedValue::Color(rgba) => Value::Color(Color::from_rgba32_u32(rgba)),
+            SerializedValue::Float(x) => Value::Float(x),
+            SerializedValue::Bool(b) => Value::Bool(b),
+            SerializedValue::UnsignedInt(x) => Value::UnsignedInt(x),
+            SerializedValue::String(s) => Value::String(s.into()),
+            SerializedValue::Font {
+                family,
+                size,
+                weight,
+                italic,
+            } => {
+                // Generic families are written with their CSS names.
+                let family = match family.as_str() {
+                    "serif" => FontFamily::SERIF,
+                    "sans-serif" => FontFamily::SANS_SERIF,
+                    "monospace" => FontFamily::MONOSPACE,
+                    "system-ui" => FontFamily::SYSTEM_UI,
+                    _ => FontFamily::new_unchecked(family),
+                };
+                let style = if italic {
+                    FontStyle::Italic
+                } else {
+                    FontStyle::Regular
+                };
+                Value::Font(
+                    FontDescriptor::new(family)
+                        .with_size(size)
+                        .with_weight(FontWeight::new(weight))
+                        .with_style(style),
+                )
+            }
+            SerializedValue::RoundedRectRadii(top_left, top_right, bottom_right, bottom_left) => {
+                Value::RoundedRectRadii(RoundedRectRadii::new(
+                    top_left,
+                    top_right,
+                    bottom_right,
+                    bottom_left,
+                ))
+            }
+        }
+    }
+}
+
+impl Serialize for Env {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = BTreeMap::new();
+        for (key, value) in &self.0.map {
+            match SerializedValue::from_value(value) {
+                Some(value) => {
+                    map.insert(key.as_ref(), value);
+                }
+                None => {
+                    tracing::warn!("skipping env key '{}': its value can't be serialized", key);
+                }
+            }
+        }
+        map.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Env {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Env, D::Error> {
+        let map = HashMap::<String, SerializedValue>::deserialize(deserializer)?;
+        let map = map
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into_value()))
+            .collect();
+        Ok(Env(Arc::new(EnvImpl { map })))
+    }
+}
+
 impl Data for Env {
     fn same(&self, other: &Env) -> bool {
         Arc::ptr_eq(&self.0, &other.0) || self.0.deref().same(other.0.deref())
@@ -518,7 +854,18 @@ impl Env {
         let env = Env::empty()
             .adding(Env::DEBUG_PAINT, false)
             .adding(Env::DEBUG_WIDGET_ID, false)
-            .adding(Env::DEBUG_WIDGET, false);
+            .adding(Env::DEBUG_WIDGET, false)
+            .adding(Env::DEBUG_LAYOUT_DUMP, false)
+            .adding(Env::DEBUG_HIT_TEST, false)
+            .adding(Env::LOCALE, "en-US")
+            .adding(Env::REDUCED_TRANSPARENCY, false)
+            .adding(Env::HIGH_CONTRAST, false)
+            .adding(Env::INCREASED_CONTRAST, false)
+            .adding(Env::FOCUS_FOLLOWS_ACCESSIBILITY_FOCUS, false)
+            .adding(Env::AUTO_FOCUS_RING, false)
+            .adding(Env::MULTI_CLICK_INTERVAL, 500u64)
+            .adding(Env::MULTI_CLICK_DISTANCE, 4.0)
+            .adding(Env::GRID_INCREMENT, 8.0);
 
         crate::theme::add_to_env(env)
     }
@@ -689,4 +1036,33 @@ mod tests {
 
         assert_send_sync::<Key<()>>();
     }
+
+    #[test]
+    fn serialization_round_trip() {
+        const POINT: Key<Point> = Key::new("org.linebender.test.point");
+        const INSETS: Key<Insets> = Key::new("org.linebender.test.insets");
+        const RADII: Key<RoundedRectRadii> = Key::new("org.linebender.test.radii");
+        const FONT: Key<FontDescriptor> = Key::new("org.linebender.test.font");
+        const OTHER: Key<Arc<u32>> = Key::new("org.linebender.test.other");
+
+        let font = FontDescriptor::new(FontFamily::new_unchecked("Fira Sans"))
+            .with_size(17.0)
+            .with_weight(FontWeight::BOLD)
+            .with_style(FontStyle::Italic);
+        let env = Env::with_theme()
+            .adding(POINT, Point::new(1.5, -2.0))
+            .adding(INSETS, Insets::new(1.0, 2.0, 3.0, 4.0))
+            .adding(RADII, RoundedRectRadii::new(1.0, 2.0, 3.0, 4.0))
+            .adding(FONT, font)
+            .adding(Env::LOCALE, "fr-FR");
+        let with_other = env.clone().adding(OTHER, Arc::new(42));
+
+        let json = serde_json::to_string(&with_other).unwrap();
+        let loaded: Env = serde_json::from_str(&json).unwrap();
+
+        assert!(loaded.same(&env));
+        assert!(loaded.try_get(OTHER).is_err());
+        // Keys are sorted, so the output is stable.
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+    }
 }
@@ -4,9 +4,10 @@
 
 //! Events.
 
-use druid_shell::{Clipboard, KeyEvent, TimerToken};
+use druid_shell::{Clipboard, KeyEvent, TimerToken, WindowState};
 
-use crate::kurbo::{Rect, Size};
+use crate::drag::DragEvent;
+use crate::kurbo::{Point, Rect, Size};
 use crate::mouse::MouseEvent;
 // TODO - See issue #14
 use crate::promise::PromiseResult;
@@ -64,6 +65,17 @@ pub enum Event {
     /// widgets. It might be better to just handle it in `layout`.
     WindowSize(Size),
 
+    /// Sent to all widgets in a given window when it is minimized, maximized or restored.
+    ///
+    /// Windows aren't painted while minimized, so widgets may use this event to
+    /// pause animations or other background work until the window is restored.
+    WindowStateChanged {
+        /// The state the window was in before the change.
+        old: WindowState,
+        /// The state the window is now in.
+        new: WindowState,
+    },
+
     /// Called when a mouse button is pressed.
     MouseDown(MouseEvent),
 
@@ -91,6 +103,13 @@ pub enum Event {
     Wheel(MouseEvent),
 
     /// Called when a key is pressed.
+    ///
+    /// The event carries both the logical key (`key`, which depends on the
+    /// keyboard layout) and the physical key (`code`, the key's position).
+    /// See [`EventCtx::logical_key`] and [`EventCtx::physical_key`].
+    ///
+    /// [`EventCtx::logical_key`]: crate::EventCtx::logical_key
+    /// [`EventCtx::physical_key`]: crate::EventCtx::physical_key
     KeyDown(KeyEvent),
 
     /// Called when a key is released.
@@ -118,6 +137,20 @@ pub enum Event {
     /// The value is a delta.
     Zoom(f64),
 
+    /// Sent when the user asks for a context menu, with a secondary click or
+    /// with the keyboard.
+    ///
+    /// Pointer requests are sent to the widgets under the pointer, after the
+    /// `MouseDown` that triggered them, if no widget handled it. Keyboard
+    /// requests, from the context menu key or Shift+F10, are sent to the
+    /// focused widget and its ancestors.
+    ///
+    /// A widget that opens a menu should call
+    /// [`EventCtx::set_handled`](crate::EventCtx::set_handled). Containers
+    /// with a menu of their own should pass the event to their children
+    /// first, and only open their menu if it wasn't handled.
+    ContextMenuRequested(ContextMenuRequest),
+
     /// Called at the beginning of a new animation frame.
     ///
     /// On the first frame when transitioning from idle to animating, `interval`
@@ -164,6 +197,11 @@ pub enum Event {
     /// to stop the notification from being delivered to further ancestors.
     Notification(Notification),
 
+    /// Sent to widgets under the pointer during drag-and-drop.
+    ///
+    /// See [`DragEvent`] for details.
+    Drag(DragEvent),
+
     /// Internal Masonry event.
     ///
     /// This should always be passed down to descendant [`WidgetPod`]s.
@@ -172,6 +210,18 @@ pub enum Event {
     Internal(InternalEvent),
 }
 
+/// The payload of [`Event::ContextMenuRequested`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContextMenuRequest {
+    /// Where the menu should be placed, in window coordinates.
+    ///
+    /// This is the pointer position, or the bottom-left corner of the focused
+    /// widget for keyboard requests.
+    pub window_pos: Point,
+    /// Whether the menu was requested with the keyboard.
+    pub from_keyboard: bool,
+}
+
 /// Internal events used by Masonry inside [`WidgetPod`].
 ///
 /// These events are translated into regular [`Event`]s
@@ -190,14 +240,38 @@ pub enum InternalEvent {
     /// A command still in the process of being dispatched.
     TargetedCommand(Command),
 
+    /// A batch of widget-targeted commands still in the process of being dispatched.
+    ///
+    /// The batch is routed in a single traversal; each widget only receives the
+    /// commands that may target it or its descendants.
+    TargetedCommands(Vec<Command>),
+
     /// Used for routing timer events.
     RouteTimer(TimerToken, WidgetId),
 
     /// Used for routing promise results.
     RoutePromiseResult(PromiseResult, WidgetId),
 
+    /// Used for routing drag-and-drop events.
+    RouteDrag(WidgetId, DragEvent),
+
     /// Route an IME change event.
     RouteImeStateChange(WidgetId),
+
+    /// Used for running a callback registered with
+    /// [`EventCtx::run_after_layout`](crate::EventCtx::run_after_layout).
+    RouteAfterLayout(WidgetId),
+
+    /// Used for scrolling a widget into view, as if it had called
+    /// [`EventCtx::request_pan_to_this`](crate::EventCtx::request_pan_to_this).
+    RoutePanToWidget(WidgetId),
+
+    /// Sent to the descendants of a widget that claimed a pointer event in
+    /// [`Widget::on_event_capture`](crate::Widget::on_event_capture), so that
+    /// they stop being active.
+    ///
+    /// Also sent to the whole tree when a drag is cancelled.
+    PointerClaimed,
 }
 
 /// Application life cycle events.
@@ -228,6 +302,18 @@ pub enum LifeCycle {
     /// of events.
     WidgetAdded,
 
+    /// Sent to a `Widget` and its descendants when it is removed from the widget tree,
+    /// right before it's dropped. This is the last message that each widget receives.
+    ///
+    /// Widgets can handle this event to save state or deregister themselves.
+    /// Commands submitted while handling it are delivered after the widget
+    /// has been dropped, so they shouldn't target the removed widget itself.
+    ///
+    /// Container widgets send this event by removing children with
+    /// [`remove_child`](crate::EventCtx::remove_child), and must forward it
+    /// to their own children.
+    WidgetRemoved,
+
     // TODO - Put in StatusChange
     /// Called when the Disabled state of the widgets is changed.
     ///
@@ -250,9 +336,29 @@ pub enum LifeCycle {
     /// [`focus_prev`]: crate::EventCtx::focus_prev
     BuildFocusChain,
 
+    /// Called on every widget when the root [`Env`] of the window changed, for
+    /// instance after a [`SET_LOCALE`] command.
+    ///
+    /// Widgets that cache values derived from the environment, such as
+    /// formatted text, should compute them again. Every widget is laid out
+    /// and painted again after this event, unless it declared which keys it
+    /// depends on with [`Widget::env_keys_affecting_layout`] and
+    /// [`Widget::env_keys_affecting_paint`].
+    ///
+    /// [`Widget::env_keys_affecting_layout`]: crate::Widget::env_keys_affecting_layout
+    /// [`Widget::env_keys_affecting_paint`]: crate::Widget::env_keys_affecting_paint
+    ///
+    /// [`Env`]: crate::Env
+    /// [`SET_LOCALE`]: crate::command::SET_LOCALE
+    EnvChanged,
+
     /// Called when a child widgets uses
     /// [`EventCtx::request_pan_to_this`](crate::EventCtx::request_pan_to_this).
-    RequestPanToChild(Rect),
+    ///
+    /// The rect is in the receiving widget's coordinate space. Scrolling
+    /// containers should adjust their offset so the rect is visible, placed
+    /// according to the [`PanAlignment`].
+    RequestPanToChild(Rect, PanAlignment),
 
     /// Internal Masonry lifecycle event.
     ///
@@ -287,6 +393,56 @@ pub enum InternalLifeCycle {
 
     /// The parents widget origin in window coordinate space has changed.
     ParentWindowOrigin,
+
+    /// Used to route the `WindowFocusChanged` status change to all widgets.
+    RouteWindowFocusChanged(bool),
+
+    /// Used to route the `AccessibilityFocusChanged` status change.
+    RouteAccessibilityFocusChanged {
+        /// the widget that is losing accessibility focus, if any
+        old: Option<WidgetId>,
+        /// the widget that is gaining accessibility focus, if any
+        new: Option<WidgetId>,
+    },
+}
+
+/// Where a scrolling container should place a target when panning to it.
+///
+/// See [`EventCtx::request_pan_to_this_aligned`](crate::EventCtx::request_pan_to_this_aligned).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PanAlignment {
+    /// Move the viewport as little as possible for the target to be visible.
+    #[default]
+    Nearest,
+    /// Align the start (top or left) of the target with the start of the viewport.
+    Start,
+    /// Center the target in the viewport.
+    Center,
+    /// Align the end (bottom or right) of the target with the end of the viewport.
+    End,
+}
+
+/// How a widget receives the `KeyDown` events repeated while a key is held.
+///
+/// See [`EventCtx::set_key_repeat`](crate::EventCtx::set_key_repeat).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum KeyRepeat {
+    /// Repeated key presses are delivered like the first one.
+    #[default]
+    Allow,
+    /// Only the first `KeyDown` of each key press is delivered.
+    Ignore,
+    /// Repeated key presses are delivered, and
+    /// [`EventCtx::key_repeat_factor`](crate::EventCtx::key_repeat_factor)
+    /// grows the longer the key is held.
+    ///
+    /// The factor starts at 1 and grows by `step` with each repeat, up to `max`.
+    Accelerate {
+        /// How much the factor grows with each repeat.
+        step: f64,
+        /// The largest factor.
+        max: f64,
+    },
 }
 
 /// Event indicating status changes within the widget hierarchy.
@@ -313,6 +469,52 @@ pub enum StatusChange {
     ///
     /// [`EventCtx::is_focused`]: struct.EventCtx.html#method.is_focused
     FocusChanged(bool),
+
+    /// Called when focus enters or leaves the subtree of this widget.
+    ///
+    /// This is sent to the focused widget and all its ancestors, after
+    /// [`FocusChanged`](Self::FocusChanged). Containers can use it to
+    /// highlight themselves while one of their descendants is focused, like
+    /// the `:focus-within` CSS pseudo-class.
+    ///
+    /// See [`EventCtx::has_focus_within`] for more information.
+    ///
+    /// [`EventCtx::has_focus_within`]: crate::EventCtx::has_focus_within
+    FocusWithinChanged(bool),
+
+    /// Called when the pointer leaves the subtree of this widget.
+    ///
+    /// Unlike [`HotChanged`](Self::HotChanged), which is about the widget's
+    /// own layout rect, this is sent once neither the widget nor any of its
+    /// descendants is hot or active anymore. Moving the pointer from a
+    /// container to one of its children, even one laid out outside of the
+    /// container's bounds, doesn't trigger it. Containers can use it to close
+    /// a submenu or a hover card opened by hovering them.
+    ///
+    /// This is sent after the pointer event that caused it went through the
+    /// subtree, or when the [hover delay](crate::LifeCycleCtx::set_hover_delay)
+    /// of the last hot descendant elapses.
+    PointerLeftSubtree,
+
+    /// Called on every widget when the window gains or loses focus from the
+    /// platform, eg when the user switches to another application.
+    ///
+    /// Widgets can use this to render an inactive style, such as hiding the
+    /// caret of a text box.
+    ///
+    /// See [`EventCtx::is_window_focused`] for more information.
+    ///
+    /// [`EventCtx::is_window_focused`]: crate::EventCtx::is_window_focused
+    WindowFocusChanged(bool),
+
+    /// Called when the cursor of an assistive technology, such as a screen
+    /// reader, moves to or away from this widget.
+    ///
+    /// See [`EventCtx::is_accessibility_focused`] for how this relates to
+    /// keyboard focus.
+    ///
+    /// [`EventCtx::is_accessibility_focused`]: crate::EventCtx::is_accessibility_focused
+    AccessibilityFocusChanged(bool),
 }
 
 impl Event {
@@ -339,6 +541,7 @@ impl Event {
             | Event::WindowCloseRequested
             | Event::WindowDisconnected
             | Event::WindowSize(_)
+            | Event::WindowStateChanged { .. }
             | Event::Timer(_)
             | Event::AnimFrame(_)
             | Event::Command(_)
@@ -353,7 +556,40 @@ impl Event {
             | Event::KeyUp(_)
             | Event::Paste(_)
             | Event::ImeStateChange
-            | Event::Zoom(_) => false,
+            | Event::Zoom(_)
+            | Event::ContextMenuRequested(_)
+            | Event::Drag(_) => false,
+        }
+    }
+
+    /// Whether this event comes from the user's input devices.
+    ///
+    /// These are the pointer, keyboard, text input and drag-and-drop events.
+    /// The other events come from the platform, the app or Masonry itself.
+    pub fn is_user_input(&self) -> bool {
+        match self {
+            Event::WindowConnected
+            | Event::WindowCloseRequested
+            | Event::WindowDisconnected
+            | Event::WindowSize(_)
+            | Event::WindowStateChanged { .. }
+            | Event::Timer(_)
+            | Event::AnimFrame(_)
+            | Event::Command(_)
+            | Event::PromiseResult(_)
+            | Event::Notification(_)
+            | Event::Internal(_) => false,
+            Event::MouseDown(_)
+            | Event::MouseUp(_)
+            | Event::MouseMove(_)
+            | Event::Wheel(_)
+            | Event::KeyDown(_)
+            | Event::KeyUp(_)
+            | Event::Paste(_)
+            | Event::ImeStateChange
+            | Event::Zoom(_)
+            | Event::ContextMenuRequested(_)
+            | Event::Drag(_) => true,
         }
     }
 
@@ -365,14 +601,20 @@ impl Event {
             Event::Internal(internal) => match internal {
                 InternalEvent::MouseLeave => "MouseLeave",
                 InternalEvent::TargetedCommand(_) => "TargetedCommand",
+                InternalEvent::TargetedCommands(_) => "TargetedCommands",
                 InternalEvent::RouteTimer(_, _) => "RouteTimer",
                 InternalEvent::RoutePromiseResult(_, _) => "RoutePromiseResult",
+                InternalEvent::RouteDrag(_, _) => "RouteDrag",
                 InternalEvent::RouteImeStateChange(_) => "RouteImeStateChange",
+                InternalEvent::RouteAfterLayout(_) => "RouteAfterLayout",
+                InternalEvent::RoutePanToWidget(_) => "RoutePanToWidget",
+                InternalEvent::PointerClaimed => "PointerClaimed",
             },
             Event::WindowConnected => "WindowConnected",
             Event::WindowCloseRequested => "WindowCloseRequested",
             Event::WindowDisconnected => "WindowDisconnected",
             Event::WindowSize(_) => "WindowSize",
+            Event::WindowStateChanged { .. } => "WindowStateChanged",
             Event::Timer(_) => "Timer",
             Event::AnimFrame(_) => "AnimFrame",
             Event::Command(_) => "Command",
@@ -387,6 +629,8 @@ impl Event {
             Event::Paste(_) => "Paste",
             Event::ImeStateChange => "ImeStateChange",
             Event::Zoom(_) => "Zoom",
+            Event::ContextMenuRequested(_) => "ContextMenuRequested",
+            Event::Drag(_) => "Drag",
         }
     }
 }
@@ -405,9 +649,11 @@ impl LifeCycle {
         match self {
             LifeCycle::Internal(internal) => internal.should_propagate_to_hidden(),
             LifeCycle::WidgetAdded => true,
+            LifeCycle::WidgetRemoved => true,
             LifeCycle::DisabledChanged(_) => true,
             LifeCycle::BuildFocusChain => false,
-            LifeCycle::RequestPanToChild(_) => false,
+            LifeCycle::EnvChanged => true,
+            LifeCycle::RequestPanToChild(..) => false,
         }
     }
 
@@ -421,11 +667,17 @@ impl LifeCycle {
                 InternalLifeCycle::RouteFocusChanged { .. } => "RouteFocusChanged",
                 InternalLifeCycle::RouteDisabledChanged => "RouteDisabledChanged",
                 InternalLifeCycle::ParentWindowOrigin => "ParentWindowOrigin",
+                InternalLifeCycle::RouteWindowFocusChanged(_) => "RouteWindowFocusChanged",
+                InternalLifeCycle::RouteAccessibilityFocusChanged { .. } => {
+                    "RouteAccessibilityFocusChanged"
+                }
             },
             LifeCycle::WidgetAdded => "WidgetAdded",
+            LifeCycle::WidgetRemoved => "WidgetRemoved",
             LifeCycle::DisabledChanged(_) => "DisabledChanged",
             LifeCycle::BuildFocusChain => "BuildFocusChain",
-            LifeCycle::RequestPanToChild(_) => "RequestPanToChild",
+            LifeCycle::EnvChanged => "EnvChanged",
+            LifeCycle::RequestPanToChild(..) => "RequestPanToChild",
         }
     }
 }
@@ -443,7 +695,9 @@ impl InternalLifeCycle {
         match self {
             InternalLifeCycle::RouteWidgetAdded
             | InternalLifeCycle::RouteFocusChanged { .. }
-            | InternalLifeCycle::RouteDisabledChanged => true,
+            | InternalLifeCycle::RouteDisabledChanged
+            | InternalLifeCycle::RouteWindowFocusChanged(_)
+            | InternalLifeCycle::RouteAccessibilityFocusChanged { .. } => true,
             InternalLifeCycle::ParentWindowOrigin => false,
         }
     }
@@ -9,7 +9,9 @@ use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 
 use druid_shell::IdleHandle;
+use instant::Instant;
 
+use crate::app_root::deadline_passed;
 use crate::command::SelectorSymbol;
 use crate::platform::EXT_EVENT_IDLE_TOKEN;
 use crate::promise::PromiseResult;
@@ -21,6 +23,17 @@ pub(crate) enum ExtMessage {
     Promise(PromiseResult, WidgetId, WindowId),
 }
 
+/// The owner of the event loop external events are delivered to.
+///
+/// Implemented by [`AppRoot`](crate::AppRoot) and by the test harness, so that
+/// both go through [`process_ext_events`].
+pub(crate) trait ExtEventHost {
+    fn recv_ext_message(&mut self) -> Option<ExtMessage>;
+    fn has_pending_ext_messages(&self) -> bool;
+    fn now(&self) -> Instant;
+    fn deliver_ext_message(&mut self, message: ExtMessage);
+}
+
 /// A thing that can move into other threads and be used to submit commands back
 /// to the running application.
 ///
@@ -125,6 +138,27 @@ impl ExtEventSink {
     }
 }
 
+/// Deliver events from the background event queue until it's empty, or
+/// `deadline` has passed. At least one event is delivered per call.
+///
+/// Returns `false` if events are left in the queue.
+pub(crate) fn process_ext_events(host: &mut impl ExtEventHost, deadline: Option<Instant>) -> bool {
+    let mut first = true;
+    loop {
+        if !std::mem::take(&mut first)
+            && deadline_passed(deadline, host.now())
+            && host.has_pending_ext_messages()
+        {
+            return false;
+        }
+
+        match host.recv_ext_message() {
+            Some(message) => host.deliver_ext_message(message),
+            None => return true,
+        }
+    }
+}
+
 impl std::fmt::Display for ExtEventError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "Window missing for external event")
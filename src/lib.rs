@@ -96,6 +96,8 @@ pub use druid_shell::{kurbo, piet};
 mod util;
 
 mod action;
+#[cfg(feature = "diagnostics")]
+pub mod alloc_counter;
 mod app_delegate;
 mod app_launcher;
 mod app_root;
@@ -103,16 +105,22 @@ mod bloom;
 mod box_constraints;
 pub mod command;
 mod contexts;
+mod dash;
 mod data;
+mod debounce;
+mod drag;
 pub mod env;
 mod event;
 pub mod ext_event;
+pub mod locale;
 mod mouse;
 mod platform;
 pub mod promise;
+mod spring;
 pub mod testing;
 pub mod text;
 pub mod theme;
+mod undo;
 pub mod widget;
 
 // TODO
@@ -122,20 +130,31 @@ pub mod debug_values;
 pub use action::Action;
 pub use app_delegate::{AppDelegate, DelegateCtx};
 pub use app_launcher::AppLauncher;
-pub use app_root::{AppRoot, WindowRoot};
+#[cfg(any(test, feature = "diagnostics"))]
+pub use app_root::PendingTimer;
+pub use app_root::{AppRoot, FocusChainEntry, Interception, WindowRoot};
 pub use box_constraints::BoxConstraints;
-pub use command::{Command, Notification, Selector, SingleUse, Target};
+pub use command::{Command, CommandPriority, Notification, Selector, SingleUse, Target};
 pub use contexts::{EventCtx, LayoutCtx, LifeCycleCtx, PaintCtx, WidgetCtx};
+pub use dash::MarchingAnts;
 pub use data::Data;
+pub use debounce::{Debouncer, Throttler};
+pub use drag::{DragEvent, DragPhase};
 pub use druid_shell::Error as PlatformError;
-pub use env::{Env, Key, KeyOrValue, Value, ValueType, ValueTypeError};
-pub use event::{Event, InternalEvent, InternalLifeCycle, LifeCycle, StatusChange};
+pub use env::{AccessibilitySettings, Env, Key, KeyOrValue, Value, ValueType, ValueTypeError};
+pub use event::{
+    ContextMenuRequest, Event, InternalEvent, InternalLifeCycle, KeyRepeat, LifeCycle,
+    PanAlignment, StatusChange,
+};
 pub use kurbo::{Affine, Insets, Point, Rect, Size, Vec2};
 pub use mouse::MouseEvent;
 pub use piet::{Color, ImageBuf, LinearGradient, RadialGradient, RenderContext, UnitPoint};
 pub use platform::{
-    MasonryWinHandler, WindowConfig, WindowDescription, WindowId, WindowSizePolicy,
+    IdleWork, MasonryWinHandler, PlatformBackend, WindowConfig, WindowDescription, WindowId,
+    WindowSizePolicy,
 };
+pub use spring::Spring;
 pub use text::ArcStr;
-pub use util::{AsAny, Handled};
-pub use widget::{BackgroundBrush, Widget, WidgetId, WidgetPod, WidgetState};
+pub use undo::{EditDelta, UndoStack, UndoableEdit, DEFAULT_COALESCE_INTERVAL};
+pub use util::{clear_debug_panic_handler, set_debug_panic_handler, AsAny, Handled};
+pub use widget::{BackgroundBrush, StateDelta, Widget, WidgetId, WidgetPod, WidgetState};
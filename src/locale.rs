@@ -0,0 +1,232 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! Locale-aware formatting of numbers and dates.
+//!
+//! The current locale is stored in the [`Env`] under [`Env::LOCALE`], as a
+//! language tag such as `"en-US"` or `"fr-FR"`. Applications start with the
+//! locale of the OS, and can change it with [`SET_LOCALE`]; widgets are then
+//! notified with [`LifeCycle::EnvChanged`] so they can format their values again.
+//!
+//! Only the most common conventions are covered: the decimal and grouping
+//! separators, and the order of date components.
+//!
+//! [`SET_LOCALE`]: crate::command::SET_LOCALE
+//! [`LifeCycle::EnvChanged`]: crate::LifeCycle::EnvChanged
+
+use crate::Env;
+
+/// The order of the components of a short date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateOrder {
+    DayMonthYear,
+    MonthDayYear,
+    YearMonthDay,
+}
+
+/// The formatting conventions of a locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Conventions {
+    decimal_separator: char,
+    group_separator: char,
+    date_order: DateOrder,
+    date_separator: char,
+}
+
+/// Format `value` with `decimals` digits after the decimal separator,
+/// using the locale in `env`.
+///
+/// Digits before the decimal separator are grouped by thousands.
+pub fn format_number(value: f64, decimals: usize, env: &Env) -> String {
+    format_number_in(value, decimals, &env.get(Env::LOCALE))
+}
+
+/// Format `value`, using the locale in `env`.
+///
+/// Digits are grouped by thousands.
+pub fn format_integer(value: i64, env: &Env) -> String {
+    format_integer_in(value, &env.get(Env::LOCALE))
+}
+
+/// Format a date as a short numeric date, using the locale in `env`.
+///
+/// `month` and `day` start at 1. The date isn't validated.
+pub fn format_date(year: i32, month: u32, day: u32, env: &Env) -> String {
+    format_date_in(year, month, day, &env.get(Env::LOCALE))
+}
+
+/// Format a date as a short numeric date, using the locale in `env`.
+///
+/// See [`format_date`].
+#[cfg(feature = "chrono")]
+pub fn format_naive_date(date: chrono::NaiveDate, env: &Env) -> String {
+    use chrono::Datelike;
+    format_date(date.year(), date.month(), date.day(), env)
+}
+
+// ---
+
+fn format_number_in(value: f64, decimals: usize, locale: &str) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    let conventions = Conventions::for_locale(locale);
+
+    let digits = format!("{:.*}", decimals, value.abs());
+    let (integer, fraction) = match digits.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (digits.as_str(), None),
+    };
+    // Don't print "-0.00".
+    let is_zero = digits.bytes().all(|b| b == b'0' || b == b'.');
+
+    let mut formatted = String::new();
+    if value.is_sign_negative() && !is_zero {
+        formatted.push('-');
+    }
+    push_grouped(&mut formatted, integer, conventions.group_separator);
+    if let Some(fraction) = fraction {
+        formatted.push(conventions.decimal_separator);
+        formatted.push_str(fraction);
+    }
+    formatted
+}
+
+fn format_integer_in(value: i64, locale: &str) -> String {
+    let conventions = Conventions::for_locale(locale);
+    let mut formatted = String::new();
+    if value < 0 {
+        formatted.push('-');
+    }
+    push_grouped(
+        &mut formatted,
+        &value.unsigned_abs().to_string(),
+        conventions.group_separator,
+    );
+    formatted
+}
+
+fn format_date_in(year: i32, month: u32, day: u32, locale: &str) -> String {
+    let conventions = Conventions::for_locale(locale);
+    let sep = conventions.date_separator;
+    match conventions.date_order {
+        DateOrder::DayMonthYear => format!("{day:02}{sep}{month:02}{sep}{year}"),
+        DateOrder::MonthDayYear => format!("{month}{sep}{day}{sep}{year}"),
+        DateOrder::YearMonthDay => format!("{year}{sep}{month:02}{sep}{day:02}"),
+    }
+}
+
+/// Append `digits` to `out`, with `separator` between groups of three digits.
+fn push_grouped(out: &mut String, digits: &str, separator: char) {
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            out.push(separator);
+        }
+        out.push(digit);
+    }
+}
+
+impl Conventions {
+    /// Return the conventions of a language tag such as `"en-US"`.
+    ///
+    /// POSIX-style tags such as `"de_DE.UTF-8"` are accepted too. Unknown
+    /// languages use English conventions.
+    fn for_locale(locale: &str) -> Conventions {
+        let tag = locale
+            .split(|c: char| c == '.' || c == '@')
+            .next()
+            .unwrap_or("");
+        let mut subtags = tag.split(|c: char| c == '-' || c == '_');
+        let language = subtags.next().unwrap_or("").to_ascii_lowercase();
+        let region = subtags
+            .find(|subtag| subtag.len() == 2)
+            .unwrap_or("")
+            .to_ascii_uppercase();
+
+        let (decimal_separator, group_separator) = match (language.as_str(), region.as_str()) {
+            ("de" | "it" | "fr", "CH") => ('.', '\u{2019}'),
+            ("es", "MX" | "US") => ('.', ','),
+            ("fr", _) => (',', '\u{202F}'),
+            (
+                "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "no" | "uk" | "hu" | "bg" | "lt"
+                | "lv" | "et",
+                _,
+            ) => (',', '\u{A0}'),
+            (
+                "de" | "es" | "it" | "pt" | "nl" | "da" | "tr" | "el" | "id" | "ro" | "hr" | "sl"
+                | "sr" | "vi" | "ca",
+                _,
+            ) => (',', '.'),
+            _ => ('.', ','),
+        };
+
+        let (date_order, date_separator) = match (language.as_str(), region.as_str()) {
+            ("en", "US" | "PH") | ("es", "US") => (DateOrder::MonthDayYear, '/'),
+            ("ja" | "zh", _) => (DateOrder::YearMonthDay, '/'),
+            ("ko" | "hu", _) => (DateOrder::YearMonthDay, '.'),
+            ("sv" | "lt", _) => (DateOrder::YearMonthDay, '-'),
+            ("nl", _) => (DateOrder::DayMonthYear, '-'),
+            (
+                "de" | "ru" | "pl" | "cs" | "sk" | "fi" | "nb" | "no" | "da" | "tr" | "uk" | "ro"
+                | "hr" | "sl" | "sr" | "bg" | "et" | "lv",
+                _,
+            ) => (DateOrder::DayMonthYear, '.'),
+            // Bare "en" is most often US English.
+            ("en", "") => (DateOrder::MonthDayYear, '/'),
+            _ => (DateOrder::DayMonthYear, '/'),
+        };
+
+        Conventions {
+            decimal_separator,
+            group_separator,
+            date_order,
+            date_separator,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(format_number_in(1234567.891, 2, "en-US"), "1,234,567.89");
+        assert_eq!(format_number_in(1234567.891, 2, "de-DE"), "1.234.567,89");
+        assert_eq!(format_number_in(1234.5, 1, "fr-FR"), "1\u{202F}234,5");
+        assert_eq!(format_number_in(1234.5, 1, "de-CH"), "1\u{2019}234.5");
+        assert_eq!(format_number_in(-999.0, 0, "en-US"), "-999");
+        assert_eq!(format_number_in(-0.001, 2, "en-US"), "0.00");
+        assert_eq!(format_number_in(f64::NAN, 2, "en-US"), "NaN");
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(format_integer_in(0, "en-US"), "0");
+        assert_eq!(format_integer_in(-1234, "ru-RU"), "-1\u{A0}234");
+        assert_eq!(
+            format_integer_in(i64::MIN, "en"),
+            "-9,223,372,036,854,775,808"
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(format_date_in(2026, 3, 7, "en-US"), "3/7/2026");
+        assert_eq!(format_date_in(2026, 3, 7, "en-GB"), "07/03/2026");
+        assert_eq!(format_date_in(2026, 3, 7, "de-DE"), "07.03.2026");
+        assert_eq!(format_date_in(2026, 3, 7, "ja-JP"), "2026/03/07");
+        assert_eq!(format_date_in(2026, 3, 7, "sv-SE"), "2026-03-07");
+    }
+
+    #[test]
+    fn posix_locale_names() {
+        assert_eq!(
+            Conventions::for_locale("de_DE.UTF-8"),
+            Conventions::for_locale("de-DE")
+        );
+        // Unknown languages fall back to English conventions.
+        assert_eq!(format_number_in(1234.5, 1, "xx"), "1,234.5");
+    }
+}
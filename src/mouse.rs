@@ -37,8 +37,12 @@ pub struct MouseEvent {
     pub buttons: MouseButtons,
     /// Keyboard modifiers at the time of the event.
     pub mods: Modifiers,
-    /// The number of mouse clicks associated with this event. This will always
-    /// be `0` for a mouse-up and mouse-move events.
+    /// The number of mouse clicks associated with this event: `1` for a single
+    /// click, `2` for a double click, and so on. This will always be `0` for
+    /// mouse-up and mouse-move events.
+    ///
+    /// Masonry counts clicks itself for mouse-down events, see
+    /// [`Env::MULTI_CLICK_INTERVAL`](crate::Env::MULTI_CLICK_INTERVAL).
     pub count: u8,
     /// Focus is `true` on macOS when the mouse-down event (or its companion mouse-up event)
     /// with `MouseButton::Left` was the event that caused the window to gain focus.
@@ -0,0 +1,32 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+use crate::AccessibilitySettings;
+
+/// Query the accessibility settings of the OS, at startup.
+///
+/// druid-shell doesn't expose these settings, so only the signals that don't
+/// need platform APIs are read: on Linux, whether the GTK theme is a
+/// high-contrast theme. Applications that know more, or that are notified of
+/// changes, can report them with
+/// [`SET_ACCESSIBILITY_SETTINGS`](crate::command::SET_ACCESSIBILITY_SETTINGS).
+pub(crate) fn query_accessibility_settings() -> AccessibilitySettings {
+    AccessibilitySettings {
+        high_contrast: gtk_theme_is_high_contrast(),
+        ..Default::default()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn gtk_theme_is_high_contrast() -> bool {
+    // GTK_THEME is a theme name, optionally followed by a variant: "Adwaita:dark".
+    std::env::var("GTK_THEME")
+        .map(|theme| theme.to_ascii_lowercase().starts_with("highcontrast"))
+        .unwrap_or(false)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn gtk_theme_is_high_contrast() -> bool {
+    false
+}
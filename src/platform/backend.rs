@@ -0,0 +1,213 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! The interface through which a platform backend drives Masonry.
+
+use druid_shell::{IdleToken, Region};
+
+use crate::app_root::AppRoot;
+use crate::kurbo::{Rect, Size};
+use crate::piet::Piet;
+use crate::platform::{EXT_EVENT_IDLE_TOKEN, RUN_COMMANDS_TOKEN};
+use crate::{command as sys_cmd, Event, Handled, WindowId};
+
+/// Deferred work that a backend runs when its event loop is idle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleWork {
+    /// Run the commands left over from previous events.
+    Commands,
+    /// Run the events submitted through an [`ExtEventSink`](crate::ext_event::ExtEventSink).
+    ExtEvents,
+}
+
+/// The entry points of the Masonry event loop.
+///
+/// [`AppRoot`] implements this trait. The default backend, druid-shell, calls
+/// it from [`MasonryWinHandler`](crate::MasonryWinHandler); a host with its own
+/// event loop (a game engine, a web canvas) can translate its events and call
+/// the same methods.
+///
+/// The trait hides druid-shell's windowing glue (`WinHandler`, `Region`,
+/// `IdleToken`), but it isn't independent from druid-shell:
+/// - events carry druid-shell's keyboard and mouse types, and painting goes
+///   through piet;
+/// - [`AppRoot`] windows are still connected with a druid-shell `WindowHandle`
+///   (see [`AppRoot::window_connected`]), which Masonry uses to request
+///   repaints, timers and idle callbacks.
+///
+/// [`TestHarness`](crate::testing::TestHarness) also implements this trait,
+/// without a platform window.
+pub trait PlatformBackend {
+    /// Send an event to the widgets of a window.
+    ///
+    /// Returns [`Handled::Yes`] if the event was handled, which some platforms
+    /// need to know for key events.
+    fn window_event(&mut self, window_id: WindowId, event: Event) -> Handled;
+
+    /// Notify Masonry that a window was resized, minimized or maximized.
+    fn window_resized(&mut self, window_id: WindowId, size: Size);
+
+    /// Notify Masonry that a window gained or lost focus.
+    fn window_focus_changed(&mut self, window_id: WindowId, focused: bool);
+
+    /// Notify Masonry that the user asked to close a window.
+    ///
+    /// The window is closed once the platform reports it with
+    /// [`window_closed`](Self::window_closed).
+    fn close_requested(&mut self, window_id: WindowId);
+
+    /// Notify Masonry that a window was closed by the platform.
+    fn window_closed(&mut self, window_id: WindowId);
+
+    /// Run layout and animations before painting a window.
+    ///
+    /// Must be called once per frame, before [`paint`](Self::paint).
+    fn prepare_paint(&mut self, window_id: WindowId);
+
+    /// Paint the parts of a window covered by `invalid`.
+    fn paint(&mut self, window_id: WindowId, piet: &mut Piet, invalid: &[Rect]);
+
+    /// Run deferred work, after the backend was asked to schedule it.
+    fn idle(&mut self, work: IdleWork);
+}
+
+impl IdleWork {
+    /// The work a druid-shell idle token stands for.
+    pub(crate) fn from_token(token: IdleToken) -> Option<Self> {
+        match token {
+            RUN_COMMANDS_TOKEN => Some(IdleWork::Commands),
+            EXT_EVENT_IDLE_TOKEN => Some(IdleWork::ExtEvents),
+            _ => None,
+        }
+    }
+}
+
+impl PlatformBackend for AppRoot {
+    fn window_event(&mut self, window_id: WindowId, event: Event) -> Handled {
+        self.handle_event(event, window_id)
+    }
+
+    fn window_resized(&mut self, window_id: WindowId, size: Size) {
+        self.handle_event(Event::WindowSize(size), window_id);
+        // Minimizing or maximizing a window is reported as a resize.
+        self.update_window_state(window_id);
+    }
+
+    fn window_focus_changed(&mut self, window_id: WindowId, focused: bool) {
+        if focused {
+            self.window_got_focus(window_id);
+        } else {
+            self.window_lost_focus(window_id);
+        }
+    }
+
+    fn close_requested(&mut self, window_id: WindowId) {
+        let event = Event::Command(sys_cmd::CLOSE_WINDOW.to(window_id));
+        self.handle_event(event, window_id);
+    }
+
+    fn window_closed(&mut self, window_id: WindowId) {
+        self.window_removed(window_id);
+    }
+
+    fn prepare_paint(&mut self, window_id: WindowId) {
+        AppRoot::prepare_paint(self, window_id);
+    }
+
+    fn paint(&mut self, window_id: WindowId, piet: &mut Piet, invalid: &[Rect]) {
+        let mut region = Region::EMPTY;
+        for rect in invalid {
+            region.add_rect(*rect);
+        }
+        AppRoot::paint(self, window_id, piet, &region);
+    }
+
+    fn idle(&mut self, work: IdleWork) {
+        match work {
+            IdleWork::Commands => self.run_commands(),
+            IdleWork::ExtEvents => self.run_ext_events(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use druid_shell::{Modifiers, MouseButton, MouseButtons};
+
+    use super::*;
+    use crate::piet::{Device, RenderContext};
+    use crate::testing::{widget_ids, Record, Recording, TestHarness, TestWidgetExt as _};
+    use crate::widget::Button;
+    use crate::{Action, MouseEvent, Point, Vec2};
+
+    fn mouse_event(pos: Point, button: MouseButton, buttons: MouseButtons) -> MouseEvent {
+        MouseEvent {
+            pos,
+            window_pos: pos,
+            buttons,
+            mods: Modifiers::default(),
+            count: 1,
+            focus: false,
+            button,
+            wheel_delta: Vec2::ZERO,
+        }
+    }
+
+    /// A host with its own event loop, which only knows about the trait.
+    fn run_host_frame(
+        backend: &mut impl PlatformBackend,
+        window_id: WindowId,
+        size: Size,
+        click: Point,
+    ) {
+        backend.window_resized(window_id, size);
+        backend.window_focus_changed(window_id, true);
+
+        let mut pressed = MouseButtons::default();
+        pressed.insert(MouseButton::Left);
+        let mouse_move = mouse_event(click, MouseButton::None, MouseButtons::default());
+        backend.window_event(window_id, Event::MouseMove(mouse_move));
+        let mouse_down = mouse_event(click, MouseButton::Left, pressed);
+        backend.window_event(window_id, Event::MouseDown(mouse_down));
+        let mouse_up = mouse_event(click, MouseButton::Left, MouseButtons::default());
+        backend.window_event(window_id, Event::MouseUp(mouse_up));
+        backend.idle(IdleWork::Commands);
+
+        let mut device = Device::new().unwrap();
+        let mut target = device
+            .bitmap_target(size.width as usize, size.height as usize, 1.0)
+            .unwrap();
+        let mut piet = target.render_context();
+        backend.prepare_paint(window_id);
+        backend.paint(window_id, &mut piet, &[size.to_rect()]);
+        piet.finish().unwrap();
+    }
+
+    #[test]
+    fn host_drives_masonry_through_backend() {
+        let [button_id] = widget_ids();
+        let record = Recording::default();
+        let button = Button::new("Hello").record(&record).with_id(button_id);
+
+        let mut harness = TestHarness::create(button);
+        let window_id = harness.window().id;
+        let click = harness
+            .get_widget(button_id)
+            .state()
+            .window_layout_rect()
+            .center();
+        record.clear();
+
+        run_host_frame(&mut harness, window_id, Size::new(200.0, 100.0), click);
+
+        assert_eq!(
+            harness.pop_action(),
+            Some((Action::ButtonPressed, button_id))
+        );
+        assert!(record
+            .drain()
+            .iter()
+            .any(|record| matches!(record, Record::Paint)));
+    }
+}
@@ -2,11 +2,18 @@
 // "as-is" basis without warranties of any kind. See the LICENSE file for
 // details.
 
+#[cfg(not(tarpaulin_include))]
+mod accessibility;
+#[cfg(not(tarpaulin_include))]
+mod backend;
 #[cfg(not(tarpaulin_include))]
 mod win_handler;
 #[cfg(not(tarpaulin_include))]
 mod window_description;
 
+pub(crate) use accessibility::query_accessibility_settings;
+pub use backend::{IdleWork, PlatformBackend};
 pub use win_handler::{DialogInfo, MasonryAppHandler, MasonryWinHandler};
 pub(crate) use win_handler::{EXT_EVENT_IDLE_TOKEN, RUN_COMMANDS_TOKEN};
+pub(crate) use window_description::{aspect_corrected_size, content_constraints};
 pub use window_description::{WindowConfig, WindowDescription, WindowId, WindowSizePolicy};
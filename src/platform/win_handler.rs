@@ -15,7 +15,8 @@ use druid_shell::{
 use crate::app_root::AppRoot;
 use crate::kurbo::Size;
 use crate::piet::Piet;
-use crate::{command as sys_cmd, Event, InternalEvent, Selector, WindowId};
+use crate::platform::{IdleWork, PlatformBackend};
+use crate::{Event, InternalEvent, Selector, WindowId};
 
 pub(crate) const RUN_COMMANDS_TOKEN: IdleToken = IdleToken::new(1);
 
@@ -75,6 +76,11 @@ impl MasonryWinHandler {
             window_id,
         }
     }
+
+    /// The app state, seen through the backend-independent interface.
+    fn backend(&mut self) -> &mut impl PlatformBackend {
+        &mut self.app_state
+    }
 }
 
 impl AppHandler for MasonryAppHandler {
@@ -85,9 +91,10 @@ impl AppHandler for MasonryAppHandler {
 
 // Every WinHandler method is triggered by some sort of platform event.
 //
-// The method implementations should be short (two lines at most, usually), and call
-// AppRoot methods directly. MasonryWinHandler methods should never break or check
-// invariants: that's AppRoot's job.
+// The method implementations should be short (two lines at most, usually), and go
+// through the PlatformBackend trait wherever it covers the event, so that druid-shell
+// drives Masonry the same way other backends would. MasonryWinHandler methods should
+// never break or check invariants: that's AppRoot's job.
 impl WinHandler for MasonryWinHandler {
     fn connect(&mut self, handle: &WindowHandle) {
         self.app_state
@@ -95,29 +102,38 @@ impl WinHandler for MasonryWinHandler {
     }
 
     fn request_close(&mut self) {
-        let event = Event::Command(sys_cmd::CLOSE_WINDOW.to(self.window_id));
-        self.app_state.handle_event(event, self.window_id);
+        let window_id = self.window_id;
+        self.backend().close_requested(window_id);
     }
 
     fn destroy(&mut self) {
-        self.app_state.window_removed(self.window_id);
+        let window_id = self.window_id;
+        self.backend().window_closed(window_id);
     }
 
     fn got_focus(&mut self) {
-        self.app_state.window_got_focus(self.window_id);
+        let window_id = self.window_id;
+        self.backend().window_focus_changed(window_id, true);
+    }
+
+    fn lost_focus(&mut self) {
+        let window_id = self.window_id;
+        self.backend().window_focus_changed(window_id, false);
     }
 
     fn prepare_paint(&mut self) {
-        self.app_state.prepare_paint(self.window_id);
+        let window_id = self.window_id;
+        self.backend().prepare_paint(window_id);
     }
 
     fn paint(&mut self, piet: &mut Piet, region: &Region) {
-        self.app_state.paint(self.window_id, piet, region);
+        let window_id = self.window_id;
+        self.backend().paint(window_id, piet, region.rects());
     }
 
     fn size(&mut self, size: Size) {
-        let event = Event::WindowSize(size);
-        self.app_state.handle_event(event, self.window_id);
+        let window_id = self.window_id;
+        self.backend().window_resized(window_id, size);
     }
 
     fn scale(&mut self, _scale: Scale) {
@@ -137,63 +153,63 @@ impl WinHandler for MasonryWinHandler {
     }
 
     fn mouse_down(&mut self, event: &MouseEvent) {
-        // Note that double-click detection is done in druid-shell.
+        // The click count is recomputed by WindowRoot, as platforms disagree on it.
         let event = Event::MouseDown(event.clone().into());
-        self.app_state.handle_event(event, self.window_id);
+        let window_id = self.window_id;
+        self.backend().window_event(window_id, event);
     }
 
     fn mouse_up(&mut self, event: &MouseEvent) {
         let event = Event::MouseUp(event.clone().into());
-        self.app_state.handle_event(event, self.window_id);
+        let window_id = self.window_id;
+        self.backend().window_event(window_id, event);
     }
 
     fn mouse_move(&mut self, event: &MouseEvent) {
         let event = Event::MouseMove(event.clone().into());
-        self.app_state.handle_event(event, self.window_id);
+        let window_id = self.window_id;
+        self.backend().window_event(window_id, event);
     }
 
     fn mouse_leave(&mut self) {
-        self.app_state
-            .handle_event(Event::Internal(InternalEvent::MouseLeave), self.window_id);
+        let window_id = self.window_id;
+        self.backend()
+            .window_event(window_id, Event::Internal(InternalEvent::MouseLeave));
     }
 
     fn key_down(&mut self, event: KeyEvent) -> bool {
-        self.app_state
-            .handle_event(Event::KeyDown(event), self.window_id)
+        let window_id = self.window_id;
+        self.backend()
+            .window_event(window_id, Event::KeyDown(event))
             .is_handled()
     }
 
     fn key_up(&mut self, event: KeyEvent) {
-        self.app_state
-            .handle_event(Event::KeyUp(event), self.window_id);
+        let window_id = self.window_id;
+        self.backend().window_event(window_id, Event::KeyUp(event));
     }
 
     fn wheel(&mut self, event: &MouseEvent) {
-        self.app_state
-            .handle_event(Event::Wheel(event.clone().into()), self.window_id);
+        let window_id = self.window_id;
+        self.backend()
+            .window_event(window_id, Event::Wheel(event.clone().into()));
     }
 
     fn zoom(&mut self, delta: f64) {
         let event = Event::Zoom(delta);
-        self.app_state.handle_event(event, self.window_id);
+        let window_id = self.window_id;
+        self.backend().window_event(window_id, event);
     }
 
     fn timer(&mut self, token: TimerToken) {
-        self.app_state
-            .handle_event(Event::Timer(token), self.window_id);
+        let window_id = self.window_id;
+        self.backend().window_event(window_id, Event::Timer(token));
     }
 
     fn idle(&mut self, token: IdleToken) {
-        match token {
-            RUN_COMMANDS_TOKEN => {
-                self.app_state.run_commands();
-            }
-            EXT_EVENT_IDLE_TOKEN => {
-                self.app_state.run_ext_events();
-            }
-            other => {
-                tracing::warn!("unexpected idle token {:?}", other);
-            }
+        match IdleWork::from_token(token) {
+            Some(work) => self.backend().idle(work),
+            None => tracing::warn!("unexpected idle token {:?}", token),
         }
     }
 
@@ -213,7 +229,8 @@ impl WinHandler for MasonryWinHandler {
         let needs_update = self.app_state.release_ime_lock(self.window_id, token);
         if let Some(widget) = needs_update {
             let event = Event::Internal(InternalEvent::RouteImeStateChange(widget));
-            self.app_state.handle_event(event, self.window_id);
+            let window_id = self.window_id;
+            self.backend().window_event(window_id, event);
         }
     }
 }
@@ -5,7 +5,8 @@
 use druid_shell::{Counter, WindowBuilder, WindowHandle, WindowLevel, WindowState};
 
 use crate::kurbo::{Point, Size};
-use crate::{ArcStr, Widget};
+use crate::piet::Color;
+use crate::{ArcStr, BoxConstraints, Widget};
 
 /// A unique identifier for a window.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -38,8 +39,16 @@ pub struct WindowDescription {
 pub enum WindowSizePolicy {
     /// Use the content of the window to determine the size.
     ///
-    /// If you use this option, your root widget will be passed infinite constraints;
-    /// you are responsible for ensuring that your content picks an appropriate size.
+    /// The root widget is laid out with loose constraints, and the window is
+    /// resized to fit it whenever its size changes. This suits dialogs and
+    /// tooltips shown as windows.
+    ///
+    /// The constraints range from the [`min_size`](WindowConfig::min_size), if
+    /// any, to the work area of the screen, so that the window never grows past
+    /// the screen; a minimum size larger than the screen is reduced to it. The
+    /// initial [`window_size`](WindowConfig::window_size) is ignored once the
+    /// content is laid out, which happens before the window is first shown on
+    /// platforms that connect windows when building them.
     Content,
     /// Use the provided window size.
     User,
@@ -60,6 +69,11 @@ pub struct WindowConfig {
     pub(crate) level: Option<WindowLevel>,
     // TODO - Remove?
     pub(crate) state: Option<WindowState>,
+    pub(crate) keep_aspect_ratio: Option<f64>,
+    pub(crate) background_color: Option<Color>,
+    pub(crate) clear: Option<bool>,
+    pub(crate) replay_last_frame: Option<bool>,
+    pub(crate) layout_on_connect: Option<bool>,
 }
 
 // ---
@@ -176,6 +190,48 @@ impl WindowDescription {
         self
     }
 
+    /// Keep the window's drawing area at the given `width / height` ratio when resized.
+    ///
+    /// See [`WindowConfig::keep_aspect_ratio`].
+    pub fn keep_aspect_ratio(mut self, ratio: f64) -> Self {
+        self.config = self.config.keep_aspect_ratio(ratio);
+        self
+    }
+
+    /// Set the color painted behind this window's widgets.
+    ///
+    /// See [`WindowConfig::background_color`].
+    pub fn background_color(mut self, color: Color) -> Self {
+        self.config = self.config.background_color(color);
+        self
+    }
+
+    /// Set whether this window's surface is cleared before painting.
+    ///
+    /// See [`WindowConfig::clear`].
+    pub fn clear(mut self, clear: bool) -> Self {
+        self.config = self.config.clear(clear);
+        self
+    }
+
+    /// Set whether this window keeps an image of its last frame, to repaint
+    /// without painting its widgets when nothing changed.
+    ///
+    /// See [`WindowConfig::replay_last_frame`].
+    pub fn replay_last_frame(mut self, replay: bool) -> Self {
+        self.config = self.config.replay_last_frame(replay);
+        self
+    }
+
+    /// Set whether this window is laid out as soon as it's connected, before
+    /// it's shown.
+    ///
+    /// See [`WindowConfig::layout_on_connect`].
+    pub fn layout_on_connect(mut self, layout_on_connect: bool) -> Self {
+        self.config = self.config.layout_on_connect(layout_on_connect);
+        self
+    }
+
     /// Set the [`WindowConfig`] of the window.
     pub fn with_config(mut self, config: WindowConfig) -> Self {
         self.config = config;
@@ -278,6 +334,85 @@ impl WindowConfig {
         self
     }
 
+    /// Keep the window's drawing area at the given `width / height` ratio when resized.
+    ///
+    /// The platform layer doesn't expose native aspect-ratio constraints, so
+    /// this is done after the fact: when the window receives a size that doesn't
+    /// match the ratio, Masonry asks the platform for a corrected size. Only the
+    /// axis that changed the least is corrected, so that the axis the user is
+    /// dragging follows the pointer. The window may briefly show the uncorrected
+    /// size.
+    ///
+    /// This has no effect on windows with the [`Content`](WindowSizePolicy::Content)
+    /// size policy.
+    pub fn keep_aspect_ratio(mut self, ratio: f64) -> Self {
+        if ratio > 0.0 && ratio.is_finite() {
+            self.keep_aspect_ratio = Some(ratio);
+        } else {
+            tracing::warn!("keep_aspect_ratio called with invalid ratio {}", ratio);
+        }
+        self
+    }
+
+    /// Set the color painted behind the window's widgets.
+    ///
+    /// Before painting, the invalid parts of the window are cleared to this
+    /// color, so it shows through wherever widgets don't fill their area. It
+    /// defaults to [`WINDOW_BACKGROUND_COLOR`] for opaque windows, and to
+    /// [`Color::TRANSPARENT`] for [transparent](Self::transparent) ones.
+    ///
+    /// [`WINDOW_BACKGROUND_COLOR`]: crate::theme::WINDOW_BACKGROUND_COLOR
+    pub fn background_color(mut self, color: Color) -> Self {
+        self.background_color = Some(color);
+        self
+    }
+
+    /// Set whether the window's surface is cleared before painting.
+    ///
+    /// This is `true` by default. Without clearing, the widgets paint over
+    /// whatever the surface held, such as the previous frame; this suits
+    /// overlay windows whose widgets always fill their area.
+    pub fn clear(mut self, clear: bool) -> Self {
+        self.clear = Some(clear);
+        self
+    }
+
+    /// Set whether the window keeps an image of its last frame.
+    ///
+    /// The platform sometimes asks for a repaint when nothing changed, for
+    /// instance when the window is uncovered. With this option, if no widget
+    /// requested a paint or a layout since the last frame, the window is
+    /// repainted from that image, without calling the widgets' `paint` methods.
+    ///
+    /// The image is only captured when a frame repaints the whole window, and
+    /// is dropped as soon as anything is invalidated or the window is resized,
+    /// so a stale frame is never shown. Capturing costs a copy of the surface
+    /// on every full repaint, so this is `false` by default.
+    pub fn replay_last_frame(mut self, replay: bool) -> Self {
+        self.replay_last_frame = Some(replay);
+        self
+    }
+
+    /// Set whether the window is laid out as soon as it's connected, before
+    /// it's shown.
+    ///
+    /// By default, a window with the [`User`](WindowSizePolicy::User) size
+    /// policy is laid out once the platform reports its size, which on some
+    /// platforms comes after the window is first shown. With this option, the
+    /// window is laid out at its initial size (the size the platform gives it
+    /// when it's built, or else the size set with
+    /// [`window_size`](Self::window_size)) while it's being connected, and
+    /// its first frame is painted right away, then drawn when the platform
+    /// asks for it. If the platform then reports a different size, the window
+    /// is laid out and painted again.
+    ///
+    /// Windows with the [`Content`](WindowSizePolicy::Content) size policy
+    /// are always laid out when they're connected.
+    pub fn layout_on_connect(mut self, layout_on_connect: bool) -> Self {
+        self.layout_on_connect = Some(layout_on_connect);
+        self
+    }
+
     /// Apply this window configuration to the given WindowBuilder
     pub fn apply_to_builder(&self, builder: &mut WindowBuilder) {
         if let Some(resizable) = self.resizable {
@@ -366,6 +501,109 @@ impl std::fmt::Debug for WindowConfig {
                 },
             )
             .field("state", &self.state)
+            .field("keep_aspect_ratio", &self.keep_aspect_ratio)
+            .field("background_color", &self.background_color)
+            .field("clear", &self.clear)
+            .field("replay_last_frame", &self.replay_last_frame)
+            .field("layout_on_connect", &self.layout_on_connect)
             .finish()
     }
 }
+
+/// Return the size the window should be resized to for its drawing area to
+/// have the given `width / height` ratio, or `None` if it already does.
+///
+/// The axis that changed the most since `old_size` is assumed to be the one
+/// the user is dragging, and is left untouched.
+pub(crate) fn aspect_corrected_size(ratio: f64, old_size: Size, new_size: Size) -> Option<Size> {
+    if new_size.width <= 0.0 || new_size.height <= 0.0 {
+        return None;
+    }
+    // Sizes within half a display point of the ratio are considered correct,
+    // so that rounding by the platform can't cause oscillations.
+    if (new_size.width - new_size.height * ratio).abs() < 0.5 {
+        return None;
+    }
+
+    let width_change = (new_size.width - old_size.width).abs();
+    let height_change = (new_size.height - old_size.height).abs();
+    if width_change >= height_change {
+        Some(Size::new(new_size.width, new_size.width / ratio))
+    } else {
+        Some(Size::new(new_size.height * ratio, new_size.height))
+    }
+}
+
+/// Return the constraints of the root widget of a window with the
+/// [`Content`](WindowSizePolicy::Content) size policy, whose drawing area can't
+/// exceed `max_size`.
+pub(crate) fn content_constraints(min_size: Option<Size>, max_size: Size) -> BoxConstraints {
+    let max_size = Size::new(max_size.width.max(0.0), max_size.height.max(0.0));
+    let min_size = min_size.unwrap_or(Size::ZERO);
+    let min_size = Size::new(
+        min_size.width.clamp(0.0, max_size.width),
+        min_size.height.clamp(0.0, max_size.height),
+    );
+    BoxConstraints::new(min_size, max_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aspect_ratio_already_kept() {
+        let size = Size::new(400.0, 200.0);
+        assert_eq!(aspect_corrected_size(2.0, size, size), None);
+        assert_eq!(
+            aspect_corrected_size(2.0, Size::new(300.0, 150.0), Size::new(400.2, 200.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn aspect_ratio_follows_dragged_axis() {
+        let old_size = Size::new(400.0, 200.0);
+
+        // Dragging the right edge
+        assert_eq!(
+            aspect_corrected_size(2.0, old_size, Size::new(500.0, 200.0)),
+            Some(Size::new(500.0, 250.0))
+        );
+        // Dragging the bottom edge
+        assert_eq!(
+            aspect_corrected_size(2.0, old_size, Size::new(400.0, 300.0)),
+            Some(Size::new(600.0, 300.0))
+        );
+    }
+
+    #[test]
+    fn aspect_ratio_ignores_empty_size() {
+        let old_size = Size::new(400.0, 200.0);
+        assert_eq!(aspect_corrected_size(2.0, old_size, Size::ZERO), None);
+    }
+
+    #[test]
+    fn content_constraints_fit_screen() {
+        let screen = Size::new(800.0, 600.0);
+
+        let bc = content_constraints(None, screen);
+        assert_eq!(bc.min(), Size::ZERO);
+        assert_eq!(bc.max(), screen);
+        // Content larger than the screen is clamped to it.
+        assert_eq!(
+            bc.constrain(Size::new(1000.0, 100.0)),
+            Size::new(800.0, 100.0)
+        );
+
+        let bc = content_constraints(Some(Size::new(200.0, 900.0)), screen);
+        assert_eq!(bc.min(), Size::new(200.0, 600.0));
+
+        let bc = content_constraints(
+            Some(Size::new(200.0, 100.0)),
+            Size::new(f64::INFINITY, f64::INFINITY),
+        );
+        assert!(!bc.is_width_bounded());
+        assert_eq!(bc.constrain(Size::ZERO), Size::new(200.0, 100.0));
+    }
+}
@@ -0,0 +1,244 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! Physics-based animation of a value.
+
+use std::time::Duration;
+
+use crate::EventCtx;
+
+// The integration step. Springs are stiff systems; a fixed small step keeps
+// them stable regardless of the frame rate.
+const STEP: Duration = Duration::from_millis(1);
+// Frames further apart than this are treated as this long, so the spring
+// doesn't jump after the app was paused.
+const MAX_FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A value that moves towards a target like a mass attached to a spring.
+///
+/// Unlike an animation with a duration and an easing curve, a spring keeps its
+/// velocity when its target changes, which makes interrupted motion look
+/// natural, eg a sheet that's dismissed while it's still opening, or a fling
+/// that's caught mid-way.
+///
+/// The spring is driven by the animation frames of the owning widget. Forward
+/// them to [`on_anim_frame`](Self::on_anim_frame), which requests the next
+/// frame until the spring settles:
+///
+/// ```
+/// # use masonry::{EventCtx, Spring};
+/// # fn on_anim_frame(offset: &mut Spring, ctx: &mut EventCtx, interval: u64) -> bool {
+/// if offset.on_anim_frame(ctx, interval) {
+///     ctx.request_paint();
+/// }
+/// # false
+/// # }
+/// ```
+///
+/// There is no global animation setting; a widget honoring a preference for
+/// reduced motion can call [`snap_to_target`](Self::snap_to_target) instead of
+/// animating.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spring {
+    stiffness: f64,
+    damping: f64,
+    mass: f64,
+    rest_displacement: f64,
+    rest_velocity: f64,
+    value: f64,
+    velocity: f64,
+    target: f64,
+}
+
+impl Spring {
+    /// Create a spring at rest at `value`.
+    ///
+    /// The default parameters (a stiffness of 170, a damping of 26 and a mass
+    /// of 1) give a quick motion with very little overshoot. The spring settles
+    /// once it's within 0.01 of its target, at a speed under 0.01 per second.
+    pub fn new(value: f64) -> Self {
+        Spring {
+            stiffness: 170.0,
+            damping: 26.0,
+            mass: 1.0,
+            rest_displacement: 0.01,
+            rest_velocity: 0.01,
+            value,
+            velocity: 0.0,
+            target: value,
+        }
+    }
+
+    /// Builder-style method to set the stiffness of the spring.
+    ///
+    /// A stiffer spring moves faster.
+    pub fn stiffness(mut self, stiffness: f64) -> Self {
+        self.stiffness = stiffness;
+        self
+    }
+
+    /// Builder-style method to set the damping of the spring.
+    ///
+    /// Less damping makes the spring overshoot its target and oscillate.
+    pub fn damping(mut self, damping: f64) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    /// Builder-style method to set the mass attached to the spring.
+    ///
+    /// A heavier mass moves slower, and overshoots more.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mass` isn't positive.
+    pub fn mass(mut self, mass: f64) -> Self {
+        assert!(mass > 0.0, "spring mass must be positive, got {}", mass);
+        self.mass = mass;
+        self
+    }
+
+    /// Builder-style method to set how close to its target, and how slow, the
+    /// spring must be to settle.
+    ///
+    /// `velocity` is in units per second.
+    pub fn rest_thresholds(mut self, displacement: f64, velocity: f64) -> Self {
+        self.rest_displacement = displacement;
+        self.rest_velocity = velocity;
+        self
+    }
+
+    /// The current value.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// The current velocity, in units per second.
+    pub fn velocity(&self) -> f64 {
+        self.velocity
+    }
+
+    /// The value the spring moves towards.
+    pub fn target(&self) -> f64 {
+        self.target
+    }
+
+    /// Set the value the spring moves towards.
+    ///
+    /// The spring keeps its current velocity.
+    pub fn set_target(&mut self, target: f64) {
+        self.target = target;
+    }
+
+    /// Set the current velocity, in units per second, eg the velocity of a
+    /// fling when the pointer is released.
+    pub fn set_velocity(&mut self, velocity: f64) {
+        self.velocity = velocity;
+    }
+
+    /// Move the spring to its target, and stop it.
+    pub fn snap_to_target(&mut self) {
+        self.value = self.target;
+        self.velocity = 0.0;
+    }
+
+    /// Whether the spring is at rest at its target.
+    pub fn is_settled(&self) -> bool {
+        self.value == self.target && self.velocity == 0.0
+    }
+
+    /// Advance the spring by `interval`.
+    ///
+    /// Once the spring is close enough to its target, and slow enough (see
+    /// [`rest_thresholds`](Self::rest_thresholds)), it snaps to the target and
+    /// stops. Return `true` if the value changed.
+    pub fn step(&mut self, interval: Duration) -> bool {
+        if self.is_settled() {
+            return false;
+        }
+        let old_value = self.value;
+        let mut remaining = interval.min(MAX_FRAME_INTERVAL);
+        while !remaining.is_zero() {
+            let dt = remaining.min(STEP);
+            remaining -= dt;
+            // Semi-implicit Euler: update the velocity first, then move with it.
+            let dt = dt.as_secs_f64();
+            let force = -self.stiffness * (self.value - self.target) - self.damping * self.velocity;
+            self.velocity += force / self.mass * dt;
+            self.value += self.velocity * dt;
+
+            if (self.value - self.target).abs() < self.rest_displacement
+                && self.velocity.abs() < self.rest_velocity
+            {
+                self.snap_to_target();
+                break;
+            }
+        }
+        self.value != old_value
+    }
+
+    /// Advance the spring by the `interval` of an [`Event::AnimFrame`], in
+    /// nanoseconds, and request another frame if it hasn't settled.
+    ///
+    /// Return `true` if the value changed, in which case the widget should
+    /// request a paint or a layout.
+    ///
+    /// [`Event::AnimFrame`]: crate::Event::AnimFrame
+    pub fn on_anim_frame(&mut self, ctx: &mut EventCtx, interval: u64) -> bool {
+        let changed = self.step(Duration::from_nanos(interval));
+        if !self.is_settled() {
+            ctx.request_anim_frame();
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_nanos(16_666_667);
+
+    #[test]
+    fn settles_at_target() {
+        let mut spring = Spring::new(0.0);
+        spring.set_target(100.0);
+
+        let mut frames = 0;
+        while !spring.is_settled() {
+            assert!(frames < 120, "spring didn't settle in 2 seconds");
+            spring.step(FRAME);
+            frames += 1;
+        }
+        assert_eq!(spring.value(), 100.0);
+        assert_eq!(spring.velocity(), 0.0);
+        assert!(!spring.step(FRAME));
+    }
+
+    #[test]
+    fn underdamped_spring_overshoots() {
+        let mut spring = Spring::new(0.0).damping(5.0);
+        spring.set_target(1.0);
+
+        let mut max_value: f64 = 0.0;
+        for _ in 0..600 {
+            spring.step(FRAME);
+            max_value = max_value.max(spring.value());
+        }
+        assert!(max_value > 1.0);
+        assert!(spring.is_settled());
+    }
+
+    #[test]
+    fn retargeting_keeps_velocity() {
+        let mut spring = Spring::new(0.0);
+        spring.set_target(100.0);
+        spring.step(FRAME * 5);
+        let velocity = spring.velocity();
+        assert!(velocity > 0.0);
+
+        spring.set_target(0.0);
+        assert_eq!(spring.velocity(), velocity);
+    }
+}
@@ -20,18 +20,21 @@ use super::screenshots::{get_image_diff, get_rgba_image};
 use super::snapshot_utils::get_cargo_workspace;
 use super::MockTimerQueue;
 use crate::action::{Action, ActionQueue};
-//use crate::ext_event::ExtEventHost;
-use crate::command::CommandQueue;
+use crate::app_root::{filter_event, EventInterceptor, EventSpy, MAX_HIGH_PRIORITY_ROUNDS};
+use crate::command::{take_high_priority_commands, CommandQueue};
 use crate::contexts::GlobalPassCtx;
 use crate::debug_logger::DebugLogger;
-use crate::ext_event::ExtEventQueue;
-use crate::piet::{BitmapTarget, Device, ImageFormat, Piet};
+use crate::ext_event::{process_ext_events, ExtEventHost, ExtEventQueue, ExtEventSink, ExtMessage};
+use crate::piet::{BitmapTarget, Device, Error as PietError, ImageFormat, Piet};
 use crate::widget::{StoreInWidgetMut, WidgetMut, WidgetRef};
 use crate::*;
 
 /// Default screen size for tests.
 pub const HARNESS_DEFAULT_SIZE: Size = Size::new(400., 400.);
 
+/// The mocked time between two frames of [`TestHarness::run_frames`].
+pub const HARNESS_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
 /// A safe headless environment to test widgets in.
 ///
 /// `TestHarness` is a type that simulates an [`AppRoot`](crate::AppRoot)
@@ -61,10 +64,8 @@ pub const HARNESS_DEFAULT_SIZE: Size = Size::new(400., 400.);
 ///
 /// `TestHarness` tries to act like the normal masonry environment. For instance, it will dispatch every `Command` sent during event handling, handle lifecycle methods, etc.
 ///
-/// The passage of time is simulated with the [`move_timers_forward`](Self::move_timers_forward) methods. **(TODO -
-/// Doesn't move animations forward.)**
-///
-/// **(TODO - ExtEvents aren't handled.)**
+/// The passage of time is simulated with the [`move_timers_forward`](Self::move_timers_forward) methods.
+/// Animations and external events only move forward with [`run_frames`](Self::run_frames).
 ///
 /// **(TODO - Painting invalidation might not be accurate.)**
 ///
@@ -147,6 +148,10 @@ struct MockAppRoot {
     command_queue: CommandQueue,
     action_queue: ActionQueue,
     debug_logger: DebugLogger,
+    ext_event_queue: ExtEventQueue,
+    event_spies: Vec<EventSpy>,
+    event_interceptor: Option<EventInterceptor>,
+    idle_budget: Option<Duration>,
 }
 
 impl TestHarness {
@@ -159,10 +164,36 @@ impl TestHarness {
 
     /// Builds harness with given root widget and window size.
     pub fn create_with_size(root: impl Widget, window_size: Size) -> Self {
-        //let ext_host = ExtEventHost::default();
-        //let ext_handle = ext_host.make_sink();
+        let mut harness = Self::create_unconnected(root, window_size);
+
+        harness.process_event(Event::WindowConnected);
+        harness.process_event(Event::WindowSize(window_size));
+
+        harness
+    }
 
-        // FIXME
+    /// Builds harness with given root widget, laid out and painted at
+    /// `initial_size` as its window is connected, before the platform reports
+    /// `window_size`.
+    ///
+    /// This is the equivalent of [`WindowConfig::layout_on_connect`].
+    pub fn create_with_layout_on_connect(
+        root: impl Widget,
+        initial_size: Size,
+        window_size: Size,
+    ) -> Self {
+        let mut harness = Self::create_unconnected(root, window_size);
+
+        // Same as in `AppRoot::window_connected`.
+        harness.mock_app.event(Event::WindowConnected);
+        harness.mock_app.layout_on_connect(initial_size);
+        harness.process_state_after_event();
+        harness.process_event(Event::WindowSize(window_size));
+
+        harness
+    }
+
+    fn create_unconnected(root: impl Widget, window_size: Size) -> Self {
         let event_queue = ExtEventQueue::new();
 
         let window = WindowRoot::new(
@@ -194,18 +225,18 @@ impl TestHarness {
                 command_queue: VecDeque::new(),
                 action_queue: VecDeque::new(),
                 debug_logger: DebugLogger::new(false),
+                ext_event_queue: event_queue,
+                event_spies: Vec::new(),
+                event_interceptor: None,
+                idle_budget: None,
             },
             mouse_state,
             window_size,
         };
-
         // verify that all widgets are marked as having children_changed
         // (this should always be true for a new widget)
         harness.inspect_widgets(|widget| assert!(widget.state().children_changed));
 
-        harness.process_event(Event::WindowConnected);
-        harness.process_event(Event::WindowSize(window_size));
-
         harness
     }
 
@@ -215,29 +246,140 @@ impl TestHarness {
     /// as will any resulting commands. Commands created as a result of this event
     /// will also be dispatched.
     pub fn process_event(&mut self, event: Event) {
-        self.mock_app.event(event);
+        self.handle_event(event);
+    }
 
+    fn handle_event(&mut self, event: Event) -> Handled {
+        let handled = self.dispatch_event(event);
         self.process_state_after_event();
+        handled
+    }
+
+    /// Send an event to the widgets, leaving the commands it submits in the queue.
+    fn dispatch_event(&mut self, event: Event) -> Handled {
+        // Same as in `AppRoot::do_window_event`; commands don't go through
+        // the spies and the interceptor.
+        let event = if matches!(
+            event,
+            Event::Command(..)
+                | Event::Internal(InternalEvent::TargetedCommand(..))
+                | Event::Internal(InternalEvent::TargetedCommands(..))
+        ) {
+            Some(event)
+        } else {
+            filter_event(
+                &mut self.mock_app.event_spies,
+                self.mock_app.event_interceptor.as_mut(),
+                self.mock_app.window.id,
+                event,
+                self.mock_app.window.now(),
+            )
+        };
+        match event {
+            Some(event) => self.mock_app.event(event),
+            None => Handled::Yes,
+        }
     }
 
     fn process_state_after_event(&mut self) {
-        loop {
-            let cmd = self.mock_app.command_queue.pop_front();
-            match cmd {
-                Some(cmd) => self
-                    .mock_app
-                    .event(Event::Internal(InternalEvent::TargetedCommand(cmd))),
-                None => break,
-            };
+        while let Some(cmd) = self.mock_app.command_queue.pop_front() {
+            self.dispatch_command(cmd);
         }
 
-        // TODO - this might be too coarse
-        if self.root_widget().state().needs_layout {
-            self.mock_app.layout();
-            *self.window_mut().invalid_mut() = Region::from(self.window_size.to_rect());
+        // Same as in `AppRoot::run_high_priority_commands`: commands submitted
+        // during layout wait, unless they're high-priority.
+        for _ in 0..MAX_HIGH_PRIORITY_ROUNDS {
+            // TODO - this might be too coarse
+            if self.root_widget().state().needs_layout {
+                self.mock_app.layout();
+                *self.window_mut().invalid_mut() = Region::from(self.window_size.to_rect());
+            }
+            let commands = take_high_priority_commands(&mut self.mock_app.command_queue);
+            if commands.is_empty() {
+                break;
+            }
+            for cmd in commands {
+                self.dispatch_command(cmd);
+            }
+        }
+
+        // Same as in `AppRootInner::invalidate_and_finalize`.
+        if !self.window().after_layout_ready.is_empty() {
+            self.mock_app.finalize_update();
+            self.process_state_after_event();
         }
     }
 
+    /// Send a command targeting [`Target::Pointer`] to the widget under the
+    /// pointer, or drop it if there is none.
+    ///
+    /// Same as in `AppRoot::dispatch_cmd`.
+    fn resolve_pointer_target(&self, cmd: Command) -> Option<Command> {
+        if cmd.target() != Target::Pointer {
+            return Some(cmd);
+        }
+        match self.mock_app.window.widget_under_pointer() {
+            Some(widget) => Some(cmd.to(widget.id())),
+            None => {
+                tracing::warn!("{:?} dropped: no widget under the pointer", cmd);
+                None
+            }
+        }
+    }
+
+    fn dispatch_command(&mut self, cmd: Command) {
+        // Same as in `AppRoot::do_cmd_batch`: widget commands are sent in a
+        // single traversal, the others one by one.
+        if let Some(batch) = cmd.try_get(crate::command::COMMAND_BATCH) {
+            let mut widget_cmds = Vec::new();
+            for cmd in batch.clone() {
+                if let Target::Widget(_) = cmd.target() {
+                    widget_cmds.push(cmd);
+                } else {
+                    self.dispatch_command(cmd);
+                }
+            }
+            if !widget_cmds.is_empty() {
+                let event = InternalEvent::TargetedCommands(widget_cmds);
+                self.mock_app.event(Event::Internal(event));
+            }
+            return;
+        }
+
+        let cmd = match self.resolve_pointer_target(cmd) {
+            Some(cmd) => cmd,
+            None => return,
+        };
+        let cmd = match cmd.target() {
+            // Same as in `AppRoot::do_cmd`, for the window commands the
+            // harness supports.
+            Target::Window(id) if id == self.mock_app.window.id => {
+                if let Some(request) = cmd.try_get(crate::command::CAPTURE_WINDOW) {
+                    self.mock_app.capture_window(*request);
+                    return;
+                }
+                if let Some(title) = cmd.try_get(crate::command::SET_WINDOW_TITLE) {
+                    self.mock_app.window.set_title(title.clone());
+                    return;
+                }
+                if let Some(widget_id) = cmd.try_get(crate::command::SET_ACCESSIBILITY_FOCUS) {
+                    self.mock_app.window.set_accessibility_focus(
+                        *widget_id,
+                        &mut self.mock_app.debug_logger,
+                        &mut self.mock_app.command_queue,
+                        &mut self.mock_app.action_queue,
+                        &self.mock_app.env,
+                    );
+                    return;
+                }
+                cmd
+            }
+            _ => cmd,
+        };
+        let event = InternalEvent::TargetedCommand(cmd);
+        self.mock_app.event(Event::Internal(event));
+    }
+
     fn render_to(&mut self, render_target: &mut BitmapTarget) {
         /// A way to clean up resources when our render context goes out of
         /// scope, even during a panic.
@@ -256,7 +398,7 @@ impl TestHarness {
         let mut piet = RenderContextGuard(render_target.render_context());
 
         // FIXME - this doesn't make sense given we might render to a fresh surface
-        let invalid = std::mem::replace(self.window_mut().invalid_mut(), Region::EMPTY);
+        let invalid = self.window_mut().take_invalid();
         self.mock_app.paint_region(&mut piet.0, &invalid);
     }
 
@@ -280,6 +422,32 @@ impl TestHarness {
             .raw_pixels_shared()
     }
 
+    /// Lay out the root widget at `size` and paint it into `piet`.
+    ///
+    /// This is independent of the harness's window size; the window is laid out
+    /// again at its own size the next time it's rendered.
+    pub fn render_widget_to(&mut self, piet: &mut Piet, size: Size) {
+        self.mock_app.render_widget_to(piet, size, 1.0);
+    }
+
+    /// Lay out the root widget at `size` and paint it into `piet`, whose target
+    /// has `scale` device pixels per logical pixel.
+    ///
+    /// See [`AppRoot::render_widget_to_with_scale`] for details.
+    pub fn render_widget_to_with_scale(&mut self, piet: &mut Piet, size: Size, scale: f64) {
+        self.mock_app.render_widget_to(piet, size, scale);
+    }
+
+    /// Lay out the root widget at `size` and paint it into a new image, `scale`
+    /// times the size in pixels.
+    ///
+    /// See [`AppRoot::export_image`] for details.
+    pub fn export_image(&mut self, size: Size, scale: f64) -> ImageBuf {
+        self.mock_app
+            .export_image(size, scale)
+            .expect("failed to export image")
+    }
+
     // --- Event helpers ---
 
     /// Move an internal mouse state, and send a MouseMove event to the window.
@@ -293,6 +461,14 @@ impl TestHarness {
         self.process_event(Event::MouseMove(self.mouse_state.clone()));
     }
 
+    /// Simulate the mouse leaving the window.
+    ///
+    /// The internal mouse state is kept, so a following [`mouse_move`](Self::mouse_move)
+    /// can send the pointer outside the window bounds.
+    pub fn mouse_leave(&mut self) {
+        self.process_event(Event::Internal(InternalEvent::MouseLeave));
+    }
+
     /// Send a MouseDown event to the window.
     pub fn mouse_button_press(&mut self, button: MouseButton) {
         self.mouse_state.buttons.insert(button);
@@ -382,10 +558,167 @@ impl TestHarness {
     /// Send a command to a target.
     pub fn submit_command(&mut self, command: impl Into<Command>) {
         let command = command.into().default_to(self.mock_app.window.id.into());
-        let event = Event::Internal(InternalEvent::TargetedCommand(command));
+        if let Some(command) = self.resolve_pointer_target(command) {
+            let event = Event::Internal(InternalEvent::TargetedCommand(command));
+            self.process_event(event);
+        }
+    }
+
+    /// Perform the primary action of the given widget, as if it had been clicked.
+    ///
+    /// This sends [`ACTIVATE`](crate::command::ACTIVATE) to the widget, so it
+    /// works regardless of the widget's position in the window.
+    pub fn activate(&mut self, id: WidgetId) {
+        self.submit_command(crate::command::ACTIVATE.to(id));
+    }
+
+    /// Send a batch of widget-targeted commands, routed in a single traversal.
+    ///
+    /// This is the equivalent of [`EventCtx::submit_commands`].
+    pub fn submit_commands(&mut self, commands: Vec<Command>) {
+        let event = Event::Internal(InternalEvent::TargetedCommands(commands));
         self.process_event(event);
     }
 
+    /// Register a callback that observes every event sent to the window.
+    ///
+    /// This is the equivalent of [`AppRoot::add_event_spy`].
+    pub fn add_event_spy(&mut self, spy: impl FnMut(WindowId, &Event, instant::Instant) + 'static) {
+        self.mock_app.event_spies.push(Box::new(spy));
+    }
+
+    /// Set a callback that may consume or replace the user input sent to the window.
+    ///
+    /// This is the equivalent of [`AppRoot::set_event_interceptor`].
+    pub fn set_event_interceptor(
+        &mut self,
+        interceptor: impl FnMut(WindowId, Event) -> Interception + 'static,
+    ) {
+        self.mock_app.event_interceptor = Some(Box::new(interceptor));
+    }
+
+    /// Simulate the window gaining or losing focus from the platform.
+    ///
+    /// The window starts focused.
+    pub fn set_window_focused(&mut self, focused: bool) {
+        self.mock_app.window.set_window_focused(
+            focused,
+            &mut self.mock_app.debug_logger,
+            &mut self.mock_app.command_queue,
+            &mut self.mock_app.action_queue,
+            &self.mock_app.env,
+        );
+        self.process_state_after_event();
+    }
+
+    /// Change the locale of the root env, and send
+    /// [`LifeCycle::EnvChanged`](crate::LifeCycle::EnvChanged) to every widget.
+    ///
+    /// This is the equivalent of submitting [`SET_LOCALE`](crate::command::SET_LOCALE).
+    pub fn set_locale(&mut self, locale: &str) {
+        self.mock_app.env.set(Env::LOCALE, locale);
+        self.mock_app.window.env_changed(
+            &mut self.mock_app.debug_logger,
+            &mut self.mock_app.command_queue,
+            &mut self.mock_app.action_queue,
+            &self.mock_app.env,
+        );
+        self.process_state_after_event();
+    }
+
+    /// Change the root env with `update`, and send
+    /// [`LifeCycle::EnvChanged`](crate::LifeCycle::EnvChanged) to every widget.
+    pub fn update_env(&mut self, update: impl FnOnce(&mut Env)) {
+        update(&mut self.mock_app.env);
+        self.mock_app.window.env_changed(
+            &mut self.mock_app.debug_logger,
+            &mut self.mock_app.command_queue,
+            &mut self.mock_app.action_queue,
+            &self.mock_app.env,
+        );
+        self.process_state_after_event();
+    }
+
+    /// Change the accessibility settings of the root env, and send
+    /// [`LifeCycle::EnvChanged`](crate::LifeCycle::EnvChanged) to every widget.
+    ///
+    /// This is the equivalent of submitting
+    /// [`SET_ACCESSIBILITY_SETTINGS`](crate::command::SET_ACCESSIBILITY_SETTINGS).
+    pub fn set_accessibility_settings(&mut self, settings: AccessibilitySettings) {
+        self.mock_app.env.set_accessibility_settings(settings);
+        self.mock_app.window.env_changed(
+            &mut self.mock_app.debug_logger,
+            &mut self.mock_app.command_queue,
+            &mut self.mock_app.action_queue,
+            &self.mock_app.env,
+        );
+        self.process_state_after_event();
+    }
+
+    /// Move the accessibility focus to a widget, as if a screen reader's
+    /// cursor moved to it.
+    ///
+    /// This is the equivalent of submitting
+    /// [`SET_ACCESSIBILITY_FOCUS`](crate::command::SET_ACCESSIBILITY_FOCUS) to the window.
+    pub fn set_accessibility_focus(&mut self, widget_id: Option<WidgetId>) {
+        self.mock_app.window.set_accessibility_focus(
+            widget_id,
+            &mut self.mock_app.debug_logger,
+            &mut self.mock_app.command_queue,
+            &mut self.mock_app.action_queue,
+            &self.mock_app.env,
+        );
+        self.process_state_after_event();
+    }
+
+    /// Replace the root widget of the window.
+    ///
+    /// This is the equivalent of submitting
+    /// [`SET_WINDOW_ROOT`](crate::command::SET_WINDOW_ROOT) to the window.
+    pub fn set_root_widget(&mut self, root: impl Widget) {
+        self.mock_app.window.set_root(
+            Box::new(root),
+            &mut self.mock_app.debug_logger,
+            &mut self.mock_app.command_queue,
+            &mut self.mock_app.action_queue,
+            &self.mock_app.env,
+        );
+        self.process_state_after_event();
+    }
+
+    /// Set the title of the window.
+    ///
+    /// This is the equivalent of submitting
+    /// [`SET_WINDOW_TITLE`](crate::command::SET_WINDOW_TITLE) to the window.
+    pub fn set_window_title(&mut self, title: impl Into<ArcStr>) {
+        let window_id = self.mock_app.window.id;
+        let command = crate::command::SET_WINDOW_TITLE
+            .with(title.into())
+            .to(window_id);
+        self.dispatch_command(command);
+        self.process_state_after_event();
+    }
+
+    /// Set how long the layout passes of a frame may take, see
+    /// [`AppLauncher::with_layout_budget`](crate::AppLauncher::with_layout_budget).
+    ///
+    /// Layout doesn't move the mocked time forward, so with a budget of zero,
+    /// everything that can be deferred is.
+    pub fn set_layout_budget(&mut self, budget: Option<Duration>) {
+        self.window_mut().layout_budget = budget;
+    }
+
+    /// Set how long an idle callback may spend on external events, see
+    /// [`AppLauncher::with_idle_budget`](crate::AppLauncher::with_idle_budget).
+    ///
+    /// The budget only applies to idle callbacks run through
+    /// [`PlatformBackend::idle`]; [`run_frames`](Self::run_frames) runs all
+    /// pending external events. Events don't move the mocked time forward, so
+    /// with a budget of zero, each callback runs a single event.
+    pub fn set_idle_budget(&mut self, budget: Option<Duration>) {
+        self.mock_app.idle_budget = budget;
+    }
+
     /// Simulate the passage of time.
     ///
     /// If you create any timer in a widget, this method is the only way to trigger
@@ -407,8 +740,65 @@ impl TestHarness {
         }
     }
 
+    /// Run `n` frames of the event loop.
+    ///
+    /// Each frame moves the mocked clock forward by [`HARNESS_FRAME_INTERVAL`] and
+    /// fires the timers that are due, delivers the events submitted through
+    /// [`ext_event_sink`](Self::ext_event_sink), sends an [`Event::AnimFrame`] if
+    /// a widget requested one, then lays out and paints the window. Commands
+    /// submitted during the frame's layout run after the paint, unless they're
+    /// [high-priority](crate::CommandPriority::High).
+    ///
+    /// Background threads started by widgets aren't waited for: their events are
+    /// delivered by the first frame that runs after they're submitted.
+    pub fn run_frames(&mut self, n: usize) {
+        for _ in 0..n {
+            self.move_timers_forward(HARNESS_FRAME_INTERVAL);
+            self.run_ext_events(None);
+            if self.mock_app.window.wants_animation_frame() {
+                self.process_event(Event::AnimFrame(HARNESS_FRAME_INTERVAL.as_nanos() as u64));
+            }
+            self.render();
+            // Like the idle callback scheduled by a paint.
+            self.process_state_after_event();
+        }
+    }
+
+    /// Same as `AppRoot::run_ext_events`: the commands submitted by the events
+    /// run once all of them were delivered.
+    ///
+    /// Returns `false` if events are left in the queue after going over `budget`.
+    fn run_ext_events(&mut self, budget: Option<Duration>) -> bool {
+        let deadline = budget.map(|budget| self.mock_app.window.now() + budget);
+        let has_events = self.has_pending_ext_messages();
+        let done = process_ext_events(self, deadline);
+        if done {
+            self.process_state_after_event();
+        }
+        // Same as in `AppRoot::run_ext_events`.
+        if has_events {
+            self.mock_app.window.coalesce_invalid_to_bounds();
+        }
+        done
+    }
+
     // --- Getters ---
 
+    /// Return the timers that haven't fired yet, from the earliest to the latest.
+    ///
+    /// Deadlines are in the mocked time moved by [`move_timers_forward`](Self::move_timers_forward).
+    #[cfg(any(test, feature = "diagnostics"))]
+    pub fn pending_timers(&self) -> Vec<PendingTimer> {
+        self.mock_app.window.pending_timers()
+    }
+
+    /// Return a handle to submit external events, as a background thread would.
+    ///
+    /// The events are delivered by [`run_frames`](Self::run_frames).
+    pub fn ext_event_sink(&self) -> ExtEventSink {
+        self.mock_app.window.ext_event_sink.clone()
+    }
+
     /// Return the mocked window.
     pub fn window(&self) -> &WindowRoot {
         &self.mock_app.window
@@ -436,6 +826,14 @@ impl TestHarness {
             .expect("could not find widget")
     }
 
+    /// Return a snapshot of the state of the widget with the given id.
+    ///
+    /// See [`WidgetStateSnapshot`](super::WidgetStateSnapshot).
+    pub fn state_snapshot(&self, id: WidgetId) -> super::WidgetStateSnapshot {
+        let is_focused = self.mock_app.window.focus == Some(id);
+        super::WidgetStateSnapshot::new(self.get_widget(id).state(), is_focused)
+    }
+
     /// Try to return the widget with the given id.
     pub fn try_get_widget(&self, id: WidgetId) -> Option<WidgetRef<'_, dyn Widget>> {
         self.mock_app.window.find_widget_by_id(id)
@@ -484,6 +882,9 @@ impl TestHarness {
                 &window.handle,
                 window.id,
                 window.focus,
+                window.window_focused,
+                window.focus_visible,
+                window.accessibility_focus,
             );
             fake_widget_state = window.root.state.clone();
 
@@ -601,6 +1002,15 @@ impl TestHarness {
         self.mock_app.debug_logger.push_log(false, message);
     }
 
+    /// Turn the debug logger on or off.
+    ///
+    /// The logger is off by default. While it's on, every pass records the
+    /// state of the widgets it visits, for [`write_debug_logs`](Self::write_debug_logs).
+    /// Without the `debug_logger` feature, the logger stays off.
+    pub fn set_debug_logger_enabled(&mut self, enabled: bool) {
+        self.mock_app.debug_logger.activated = enabled;
+    }
+
     // ex: harness.write_debug_logs("test_log.json");
     #[allow(missing_docs)]
     pub fn write_debug_logs(&mut self, path: &str) {
@@ -608,6 +1018,90 @@ impl TestHarness {
     }
 }
 
+impl ExtEventHost for TestHarness {
+    fn recv_ext_message(&mut self) -> Option<ExtMessage> {
+        self.mock_app.ext_event_queue.recv()
+    }
+
+    fn has_pending_ext_messages(&self) -> bool {
+        self.mock_app.ext_event_queue.has_pending_items()
+    }
+
+    fn now(&self) -> instant::Instant {
+        self.mock_app.window.now()
+    }
+
+    fn deliver_ext_message(&mut self, message: ExtMessage) {
+        let event = match message {
+            ExtMessage::Command(selector, payload, target) => {
+                InternalEvent::TargetedCommand(Command::from_ext(selector, payload, target))
+            }
+            ExtMessage::Promise(promise_result, widget_id, _) => {
+                InternalEvent::RoutePromiseResult(promise_result, widget_id)
+            }
+        };
+        self.dispatch_event(Event::Internal(event));
+    }
+}
+
+/// The harness can be driven like a platform backend, with its single window.
+impl PlatformBackend for TestHarness {
+    fn window_event(&mut self, window_id: WindowId, event: Event) -> Handled {
+        assert_eq!(window_id, self.mock_app.window.id, "unknown window");
+        self.handle_event(event)
+    }
+
+    fn window_resized(&mut self, window_id: WindowId, size: Size) {
+        assert_eq!(window_id, self.mock_app.window.id, "unknown window");
+        self.window_size = size;
+        self.process_event(Event::WindowSize(size));
+    }
+
+    fn window_focus_changed(&mut self, window_id: WindowId, focused: bool) {
+        assert_eq!(window_id, self.mock_app.window.id, "unknown window");
+        self.set_window_focused(focused);
+    }
+
+    fn close_requested(&mut self, window_id: WindowId) {
+        assert_eq!(window_id, self.mock_app.window.id, "unknown window");
+        // Same as in `AppRoot::request_close_window`.
+        if !self.handle_event(Event::WindowCloseRequested).is_handled() {
+            self.process_event(Event::WindowDisconnected);
+        }
+    }
+
+    fn window_closed(&mut self, window_id: WindowId) {
+        // The harness keeps its window, so that it can still be inspected.
+        assert_eq!(window_id, self.mock_app.window.id, "unknown window");
+    }
+
+    fn prepare_paint(&mut self, window_id: WindowId) {
+        assert_eq!(window_id, self.mock_app.window.id, "unknown window");
+        if self.mock_app.window.wants_animation_frame() {
+            self.process_event(Event::AnimFrame(HARNESS_FRAME_INTERVAL.as_nanos() as u64));
+        }
+        self.process_state_after_event();
+    }
+
+    fn paint(&mut self, window_id: WindowId, piet: &mut Piet, invalid: &[Rect]) {
+        assert_eq!(window_id, self.mock_app.window.id, "unknown window");
+        let mut region = Region::EMPTY;
+        for rect in invalid {
+            region.add_rect(*rect);
+        }
+        self.mock_app.paint_region(piet, &region);
+    }
+
+    fn idle(&mut self, work: IdleWork) {
+        match work {
+            IdleWork::Commands => self.process_state_after_event(),
+            IdleWork::ExtEvents => {
+                self.run_ext_events(self.mock_app.idle_budget);
+            }
+        }
+    }
+}
+
 #[allow(dead_code)]
 impl MockAppRoot {
     fn event(&mut self, event: Event) -> Handled {
@@ -631,6 +1125,47 @@ impl MockAppRoot {
         );
     }
 
+    /// Same as `AppRoot::capture_window`. The image is delivered by the next
+    /// frame, like other promises.
+    fn capture_window(&mut self, request: crate::command::CaptureRequest) {
+        let image = self
+            .window
+            .capture(
+                &mut self.debug_logger,
+                &mut self.command_queue,
+                &mut self.action_queue,
+                &self.env,
+            )
+            .unwrap_or_else(|err| {
+                tracing::warn!("failed to capture window: {}", err);
+                ImageBuf::empty()
+            });
+        let result = request.token.make_result(image);
+        let _ = self
+            .window
+            .ext_event_sink
+            .resolve_promise(result, request.widget, self.window.id);
+    }
+
+    fn layout_on_connect(&mut self, initial_size: Size) {
+        self.window.layout_on_connect(
+            Some(initial_size),
+            &mut self.debug_logger,
+            &mut self.command_queue,
+            &mut self.action_queue,
+            &self.env,
+        );
+    }
+
+    fn finalize_update(&mut self) {
+        self.window.finalize_update(
+            &mut self.debug_logger,
+            &mut self.command_queue,
+            &mut self.action_queue,
+            &self.env,
+        );
+    }
+
     fn layout(&mut self) {
         self.window.layout(
             &mut self.debug_logger,
@@ -640,6 +1175,29 @@ impl MockAppRoot {
         );
     }
 
+    fn render_widget_to(&mut self, piet: &mut Piet, size: Size, scale: f64) {
+        self.window.render_widget_to(
+            piet,
+            size,
+            scale,
+            &mut self.debug_logger,
+            &mut self.command_queue,
+            &mut self.action_queue,
+            &self.env,
+        );
+    }
+
+    fn export_image(&mut self, size: Size, scale: f64) -> Result<ImageBuf, PietError> {
+        self.window.export_image(
+            size,
+            scale,
+            &mut self.debug_logger,
+            &mut self.command_queue,
+            &mut self.action_queue,
+            &self.env,
+        )
+    }
+
     fn paint_region(&mut self, piet: &mut Piet, invalid: &Region) {
         self.window.do_paint(
             piet,
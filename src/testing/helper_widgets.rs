@@ -22,13 +22,16 @@ use crate::widget::{SizedBox, WidgetRef};
 use crate::*;
 
 pub type EventFn<S> = dyn FnMut(&mut S, &mut EventCtx, &Event, &Env);
+pub type EventCaptureFn<S> = dyn FnMut(&mut S, &mut EventCtx, &Event, &Env) -> Handled;
 pub type StatusChangeFn<S> = dyn FnMut(&mut S, &mut LifeCycleCtx, &StatusChange, &Env);
 pub type LifeCycleFn<S> = dyn FnMut(&mut S, &mut LifeCycleCtx, &LifeCycle, &Env);
 pub type LayoutFn<S> = dyn FnMut(&mut S, &mut LayoutCtx, &BoxConstraints, &Env) -> Size;
 pub type PaintFn<S> = dyn FnMut(&mut S, &mut PaintCtx, &Env);
 pub type ChildrenFn<S> = dyn Fn(&S) -> SmallVec<[WidgetRef<'_, dyn Widget>; 16]>;
+pub type CanReceiveFocusFn<S> = dyn Fn(&S) -> bool;
 
 pub const REPLACE_CHILD: Selector = Selector::new("masonry-test.replace-child");
+pub const REQUEST_FOCUS: Selector = Selector::new("masonry-test.request-focus");
 
 /// A widget that can be constructed from individual functions, builder-style.
 ///
@@ -36,11 +39,15 @@ pub const REPLACE_CHILD: Selector = Selector::new("masonry-test.replace-child");
 pub struct ModularWidget<S> {
     state: S,
     on_event: Option<Box<EventFn<S>>>,
+    on_event_capture: Option<Box<EventCaptureFn<S>>>,
     on_status_change: Option<Box<StatusChangeFn<S>>>,
     lifecycle: Option<Box<LifeCycleFn<S>>>,
     layout: Option<Box<LayoutFn<S>>>,
     paint: Option<Box<PaintFn<S>>>,
     children: Option<Box<ChildrenFn<S>>>,
+    can_receive_focus_now: Option<Box<CanReceiveFocusFn<S>>>,
+    is_input_sink: bool,
+    wants_all_pointer_moves: bool,
 }
 
 /// A widget that can replace its child on command
@@ -69,6 +76,15 @@ pub struct Recorder<W> {
     child: W,
 }
 
+/// A widget that registers its child for focus, and requests focus when
+/// it receives [`REQUEST_FOCUS`].
+///
+/// Make one with [`TestWidgetExt::focusable`]. Children that already register
+/// for focus themselves aren't registered twice.
+pub struct Focusable<W> {
+    child: W,
+}
+
 /// A recording of widget method calls.
 #[derive(Debug, Clone, Default)]
 pub struct Recording(Rc<RefCell<VecDeque<Record>>>);
@@ -103,6 +119,10 @@ pub trait TestWidgetExt: Widget + Sized + 'static {
     fn with_id(self, id: WidgetId) -> SizedBox {
         SizedBox::new_with_id(self, id)
     }
+
+    fn focusable(self) -> Focusable<Self> {
+        Focusable { child: self }
+    }
 }
 
 impl<W: Widget + 'static> TestWidgetExt for W {}
@@ -112,11 +132,15 @@ impl<S> ModularWidget<S> {
         ModularWidget {
             state,
             on_event: None,
+            on_event_capture: None,
             on_status_change: None,
             lifecycle: None,
             layout: None,
             paint: None,
             children: None,
+            can_receive_focus_now: None,
+            is_input_sink: false,
+            wants_all_pointer_moves: false,
         }
     }
 
@@ -128,6 +152,14 @@ impl<S> ModularWidget<S> {
         self
     }
 
+    pub fn event_capture_fn(
+        mut self,
+        f: impl FnMut(&mut S, &mut EventCtx, &Event, &Env) -> Handled + 'static,
+    ) -> Self {
+        self.on_event_capture = Some(Box::new(f));
+        self
+    }
+
     pub fn status_change_fn(
         mut self,
         f: impl FnMut(&mut S, &mut LifeCycleCtx, &StatusChange, &Env) + 'static,
@@ -164,6 +196,43 @@ impl<S> ModularWidget<S> {
         self.children = Some(Box::new(children));
         self
     }
+
+    pub fn can_receive_focus_now_fn(mut self, f: impl Fn(&S) -> bool + 'static) -> Self {
+        self.can_receive_focus_now = Some(Box::new(f));
+        self
+    }
+
+    /// Make the widget an input sink, see [`Widget::is_input_sink`].
+    pub fn input_sink(mut self) -> Self {
+        self.is_input_sink = true;
+        self
+    }
+
+    /// Make the widget receive all mouse moves, see
+    /// [`Widget::wants_all_pointer_moves`].
+    pub fn all_pointer_moves(mut self) -> Self {
+        self.wants_all_pointer_moves = true;
+        self
+    }
+}
+
+impl ModularWidget<WidgetPod<Box<dyn Widget>>> {
+    /// A container with a single child, which it lays out at its origin.
+    ///
+    /// Every method is forwarded to the child; replacing one with the
+    /// builder methods replaces the forwarding too.
+    pub fn single_child(child: impl Widget) -> Self {
+        ModularWidget::new(WidgetPod::new(child).boxed())
+            .event_fn(|child, ctx, event, env| child.on_event(ctx, event, env))
+            .lifecycle_fn(|child, ctx, event, env| child.lifecycle(ctx, event, env))
+            .layout_fn(|child, ctx, bc, env| {
+                let size = child.layout(ctx, bc, env);
+                ctx.place_child(child, Point::ORIGIN, env);
+                size
+            })
+            .paint_fn(|child, ctx, env| child.paint(ctx, env))
+            .children_fn(|child| smallvec::smallvec![child.as_dyn()])
+    }
 }
 
 impl<S: 'static> Widget for ModularWidget<S> {
@@ -173,6 +242,13 @@ impl<S: 'static> Widget for ModularWidget<S> {
         }
     }
 
+    fn on_event_capture(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) -> Handled {
+        match self.on_event_capture.as_mut() {
+            Some(f) => f(&mut self.state, ctx, event, env),
+            None => Handled::No,
+        }
+    }
+
     fn on_status_change(&mut self, ctx: &mut LifeCycleCtx, event: &StatusChange, env: &Env) {
         if let Some(f) = self.on_status_change.as_mut() {
             f(&mut self.state, ctx, event, env)
@@ -210,6 +286,21 @@ impl<S: 'static> Widget for ModularWidget<S> {
             SmallVec::new()
         }
     }
+
+    fn can_receive_focus_now(&self) -> bool {
+        self.can_receive_focus_now
+            .as_ref()
+            .map(|f| f(&self.state))
+            .unwrap_or(true)
+    }
+
+    fn is_input_sink(&self) -> bool {
+        self.is_input_sink
+    }
+
+    fn wants_all_pointer_moves(&self) -> bool {
+        self.wants_all_pointer_moves
+    }
 }
 
 impl ReplaceChild {
@@ -224,8 +315,8 @@ impl Widget for ReplaceChild {
     fn on_event(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) {
         if let Event::Command(cmd) = event {
             if cmd.is(REPLACE_CHILD) {
-                self.child = (self.replacer)();
-                ctx.children_changed();
+                let old_child = std::mem::replace(&mut self.child, (self.replacer)());
+                ctx.remove_child(old_child);
                 return;
             }
         }
@@ -284,12 +375,88 @@ impl Recording {
     }
 }
 
+impl<W: Widget> Widget for Focusable<W> {
+    fn on_event(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) {
+        self.child.on_event(ctx, event, env)
+    }
+
+    fn on_command(&mut self, ctx: &mut EventCtx, cmd: &Command, env: &Env) -> Handled {
+        if cmd.is(REQUEST_FOCUS) {
+            ctx.request_focus();
+        }
+        self.child.on_command(ctx, cmd, env)
+    }
+
+    fn on_anim_frame(&mut self, ctx: &mut EventCtx, interval: u64, env: &Env) -> bool {
+        self.child.on_anim_frame(ctx, interval, env)
+    }
+
+    fn on_event_capture(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) -> Handled {
+        self.child.on_event_capture(ctx, event, env)
+    }
+
+    fn on_status_change(&mut self, ctx: &mut LifeCycleCtx, event: &StatusChange, env: &Env) {
+        self.child.on_status_change(ctx, event, env)
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, env: &Env) {
+        self.child.lifecycle(ctx, event, env);
+        if let LifeCycle::BuildFocusChain = event {
+            if !ctx.widget_state.focus_chain.contains(&ctx.widget_id()) {
+                ctx.register_for_focus();
+            }
+        }
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, env: &Env) -> Size {
+        self.child.layout(ctx, bc, env)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, env: &Env) {
+        self.child.paint(ctx, env)
+    }
+
+    fn children(&self) -> SmallVec<[WidgetRef<'_, dyn Widget>; 16]> {
+        self.child.children()
+    }
+
+    fn can_receive_focus_now(&self) -> bool {
+        self.child.can_receive_focus_now()
+    }
+
+    fn is_input_sink(&self) -> bool {
+        self.child.is_input_sink()
+    }
+
+    fn wants_all_pointer_moves(&self) -> bool {
+        self.child.wants_all_pointer_moves()
+    }
+}
+
 impl<W: Widget> Widget for Recorder<W> {
     fn on_event(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) {
         self.recording.push(Record::E(event.clone()));
         self.child.on_event(ctx, event, env)
     }
 
+    fn on_command(&mut self, ctx: &mut EventCtx, cmd: &Command, env: &Env) -> Handled {
+        let handled = self.child.on_command(ctx, cmd, env);
+        // Otherwise the command is recorded by `on_event`.
+        if handled.is_handled() {
+            self.recording.push(Record::E(Event::Command(cmd.clone())));
+        }
+        handled
+    }
+
+    fn on_anim_frame(&mut self, ctx: &mut EventCtx, interval: u64, env: &Env) -> bool {
+        // The frame is recorded by `on_event`.
+        self.child.on_anim_frame(ctx, interval, env)
+    }
+
+    fn on_event_capture(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) -> Handled {
+        self.child.on_event_capture(ctx, event, env)
+    }
+
     fn on_status_change(&mut self, ctx: &mut LifeCycleCtx, event: &StatusChange, env: &Env) {
         self.recording.push(Record::SC(event.clone()));
         self.child.on_status_change(ctx, event, env)
@@ -314,4 +481,16 @@ impl<W: Widget> Widget for Recorder<W> {
     fn children(&self) -> SmallVec<[WidgetRef<'_, dyn Widget>; 16]> {
         self.child.children()
     }
+
+    fn can_receive_focus_now(&self) -> bool {
+        self.child.can_receive_focus_now()
+    }
+
+    fn is_input_sink(&self) -> bool {
+        self.child.is_input_sink()
+    }
+
+    fn wants_all_pointer_moves(&self) -> bool {
+        self.child.wants_all_pointer_moves()
+    }
 }
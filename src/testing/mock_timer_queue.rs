@@ -5,7 +5,7 @@
 use std::collections::VecDeque;
 
 use druid_shell::TimerToken;
-use instant::Duration;
+use instant::{Duration, Instant};
 
 /// Handles timers for unit tests.
 ///
@@ -21,6 +21,7 @@ use instant::Duration;
 /// To avoid polluting the code with `#[cfg(test)]` annotations, MockTimerQueue is also
 /// present in non-test code, but it's always empty.
 pub(crate) struct MockTimerQueue {
+    pub start_time: Instant,
     pub current_time: Duration,
     pub queue: VecDeque<(Duration, TimerToken)>,
 }
@@ -28,11 +29,17 @@ pub(crate) struct MockTimerQueue {
 impl MockTimerQueue {
     pub(crate) fn new() -> Self {
         MockTimerQueue {
+            start_time: Instant::now(),
             current_time: Duration::ZERO,
             queue: VecDeque::new(),
         }
     }
 
+    /// The mocked current time, which only moves forward with [`move_forward`](Self::move_forward).
+    pub(crate) fn now(&self) -> Instant {
+        self.start_time + self.current_time
+    }
+
     #[must_use]
     pub(crate) fn add_timer(&mut self, duration: Duration) -> TimerToken {
         let deadline = self.current_time + duration;
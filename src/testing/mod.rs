@@ -16,13 +16,17 @@ mod mock_timer_queue;
 mod screenshots;
 #[cfg(not(tarpaulin_include))]
 mod snapshot_utils;
+#[cfg(not(tarpaulin_include))]
+mod state_snapshot;
 
 use druid_shell::{Modifiers, MouseButton, MouseButtons};
-pub use harness::{TestHarness, HARNESS_DEFAULT_SIZE};
+pub use harness::{TestHarness, HARNESS_DEFAULT_SIZE, HARNESS_FRAME_INTERVAL};
 pub use helper_widgets::{
-    ModularWidget, Record, Recorder, Recording, ReplaceChild, TestWidgetExt, REPLACE_CHILD,
+    Focusable, ModularWidget, Record, Recorder, Recording, ReplaceChild, TestWidgetExt,
+    REPLACE_CHILD, REQUEST_FOCUS,
 };
 pub(crate) use mock_timer_queue::MockTimerQueue;
+pub use state_snapshot::WidgetStateSnapshot;
 
 use crate::kurbo::{Point, Vec2};
 use crate::{MouseEvent, WidgetId};
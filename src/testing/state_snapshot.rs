@@ -0,0 +1,86 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+use crate::kurbo::{Point, Rect, Size};
+use crate::WidgetState;
+
+/// The parts of a widget's state that tests usually assert on.
+///
+/// This is a copy of the geometry and status of a widget at one point in
+/// time, returned by [`TestHarness::state_snapshot`]. Tests can take a
+/// snapshot before and after an action, and compare them.
+///
+/// Unlike [`WidgetState`], it only holds values with a stable meaning, so
+/// tests using it aren't affected by changes to the internal bookkeeping
+/// flags.
+///
+/// [`TestHarness::state_snapshot`]: super::TestHarness::state_snapshot
+#[derive(Clone, Copy, PartialEq)]
+pub struct WidgetStateSnapshot {
+    /// The origin of the widget, in its parent's coordinates.
+    pub origin: Point,
+    /// The origin of the widget, in window coordinates.
+    pub window_origin: Point,
+    /// The size of the widget.
+    pub size: Size,
+    /// The distance from the bottom of the widget to its baseline.
+    pub baseline_offset: f64,
+    /// Whether the pointer is over the widget.
+    pub is_hot: bool,
+    /// Whether the widget is active, eg a button being pressed.
+    pub is_active: bool,
+    /// Whether the widget has keyboard focus itself.
+    pub is_focused: bool,
+    /// Whether the widget or one of its ancestors is disabled.
+    pub is_disabled: bool,
+    /// Whether the widget is stashed.
+    pub is_stashed: bool,
+}
+
+impl WidgetStateSnapshot {
+    pub(crate) fn new(state: &WidgetState, is_focused: bool) -> Self {
+        WidgetStateSnapshot {
+            origin: state.origin,
+            window_origin: state.window_origin(),
+            size: state.size,
+            baseline_offset: state.baseline_offset,
+            is_hot: state.is_hot,
+            is_active: state.is_active,
+            is_focused,
+            is_disabled: state.is_disabled(),
+            is_stashed: state.is_stashed,
+        }
+    }
+
+    /// The widget's layout rect, in its parent's coordinates.
+    pub fn layout_rect(&self) -> Rect {
+        Rect::from_origin_size(self.origin, self.size)
+    }
+
+    /// The names of the flags that are set.
+    pub fn flags(&self) -> Vec<&'static str> {
+        [
+            ("hot", self.is_hot),
+            ("active", self.is_active),
+            ("focused", self.is_focused),
+            ("disabled", self.is_disabled),
+            ("stashed", self.is_stashed),
+        ]
+        .into_iter()
+        .filter(|(_, set)| *set)
+        .map(|(name, _)| name)
+        .collect()
+    }
+}
+
+impl std::fmt::Debug for WidgetStateSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WidgetStateSnapshot")
+            .field("layout_rect", &self.layout_rect())
+            .field("window_origin", &self.window_origin)
+            .field("baseline_offset", &self.baseline_offset)
+            .field("flags", &self.flags())
+            .finish()
+    }
+}
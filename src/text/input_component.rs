@@ -296,7 +296,9 @@ impl<T: TextStorage + EditableText> TextComponentMut<'_, '_, T> {
 impl<T: TextStorage + EditableText> Widget for TextComponent<T> {
     fn on_event(&mut self, ctx: &mut EventCtx, event: &Event, _env: &Env) {
         match event {
-            Event::MouseDown(mouse) if self.can_write() && !ctx.is_disabled() => {
+            Event::MouseDown(mouse)
+                if mouse.button.is_left() && self.can_write() && !ctx.is_disabled() =>
+            {
                 ctx.set_active(true);
                 self.borrow_mut()
                     .do_mouse_down(mouse.pos, mouse.mods, mouse.count);
@@ -452,7 +454,7 @@ impl<T: TextStorage + EditableText> Widget for TextComponent<T> {
             tracing::warn!("Text paint called with IME lock held.");
         }
 
-        let selection_color = if self.has_focus {
+        let selection_color = if self.has_focus && ctx.is_window_focused() {
             env.get(theme::SELECTED_TEXT_BACKGROUND_COLOR)
         } else {
             env.get(theme::SELECTED_TEXT_INACTIVE_BACKGROUND_COLOR)
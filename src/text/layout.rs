@@ -44,6 +44,10 @@ pub struct TextLayout<T> {
     // using a `FontDescriptor` in the `Env`.
     text_size_override: Option<KeyOrValue<f64>>,
     text_color: KeyOrValue<Color>,
+    // The value of `TEXT_SIZE_SCALE` the current layout was built with.
+    text_size_scale: f64,
+    // The export scale the current layout was built at, if any.
+    render_scale: Option<f64>,
     layout: Option<PietTextLayout>,
     wrap_width: f64,
     alignment: TextAlignment,
@@ -75,6 +79,8 @@ impl<T> TextLayout<T> {
             font: crate::theme::UI_FONT.into(),
             text_color: crate::theme::TEXT_COLOR.into(),
             text_size_override: None,
+            text_size_scale: 1.0,
+            render_scale: None,
             layout: None,
             wrap_width: f64::INFINITY,
             alignment: Default::default(),
@@ -270,6 +276,11 @@ impl<T: TextStorage> TextLayout<T> {
     /// Given a utf-8 range in the underlying text, return a `Vec` of `Rect`s
     /// representing the nominal bounding boxes of the text in that range.
     ///
+    /// If the range spans several visual lines (because of explicit line breaks
+    /// or because of wrapping), one rect is returned per line. The rects are
+    /// relative to this object's origin, and are empty if the layout hasn't
+    /// been built yet.
+    ///
     /// # Panics
     ///
     /// Panics if the range start or end is not a character boundary.
@@ -342,8 +353,26 @@ impl<T: TextStorage> TextLayout<T> {
     /// A simple way to ensure this is correct is to always call this method
     /// as part of your widget's [`layout`] method.
     ///
+    /// The effective text size is multiplied by [`TEXT_SIZE_SCALE`]; if
+    /// that value changed since the last build, the layout is rebuilt. The
+    /// layout is also rebuilt when an export at a different scale starts or
+    /// ends (see [`PaintCtx::scale`]).
+    ///
+    /// [`PaintCtx::scale`]: crate::PaintCtx::scale
     /// [`layout`]: trait.Widget.html#method.layout
+    /// [`TEXT_SIZE_SCALE`]: crate::theme::TEXT_SIZE_SCALE
     pub fn rebuild_if_needed(&mut self, factory: &mut PietText, env: &Env) {
+        let text_size_scale = env.try_get(crate::theme::TEXT_SIZE_SCALE).unwrap_or(1.0);
+        if text_size_scale != self.text_size_scale {
+            self.text_size_scale = text_size_scale;
+            self.layout = None;
+        }
+        let render_scale = env.try_get(Env::RENDER_SCALE).ok();
+        if render_scale != self.render_scale {
+            self.render_scale = render_scale;
+            self.layout = None;
+        }
+
         if let Some(text) = &self.text {
             if self.layout.is_none() {
                 let font = self.font.resolve(env);
@@ -360,7 +389,10 @@ impl<T: TextStorage> TextLayout<T> {
                     .new_text_layout(text.clone())
                     .max_width(self.wrap_width)
                     .alignment(self.alignment)
-                    .font(descriptor.family.clone(), descriptor.size)
+                    .font(
+                        descriptor.family.clone(),
+                        descriptor.size * self.text_size_scale,
+                    )
                     .default_attribute(descriptor.weight)
                     .default_attribute(descriptor.style)
                     .default_attribute(TextAttribute::TextColor(color));
@@ -404,6 +436,31 @@ impl<T: TextStorage> TextLayout<T> {
             ctx.draw_text(layout, point);
         }
     }
+
+    /// Fill the bounding boxes of the text in `range` with `color`, as a
+    /// selection highlight.
+    ///
+    /// `point` is the origin of the layout, and should be the same point
+    /// passed to [`draw`]. Call this before `draw`, so the highlight is
+    /// painted behind the glyphs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range start or end is not a character boundary.
+    ///
+    /// [`draw`]: #method.draw
+    pub fn draw_selection(
+        &self,
+        ctx: &mut PaintCtx,
+        point: impl Into<Point>,
+        range: Range<usize>,
+        color: &Color,
+    ) {
+        let offset = point.into().to_vec2();
+        for rect in self.rects_for_range(range) {
+            ctx.fill(rect + offset, color);
+        }
+    }
 }
 
 impl<T> std::fmt::Debug for TextLayout<T> {
@@ -429,3 +486,67 @@ impl<T: TextStorage> Default for TextLayout<T> {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piet::Device;
+
+    fn text_factory() -> PietText {
+        let mut device = Device::new().unwrap();
+        let mut target = device.bitmap_target(1, 1, 1.0).unwrap();
+        let mut piet = target.render_context();
+        let text = piet.text().clone();
+        piet.finish().unwrap();
+        text
+    }
+
+    fn build_layout(text: &str, wrap_width: f64) -> TextLayout<String> {
+        let mut layout = TextLayout::from_text(text.to_string());
+        layout.set_wrap_width(wrap_width);
+        layout.rebuild_if_needed(&mut text_factory(), &Env::with_theme());
+        layout
+    }
+
+    #[test]
+    fn no_rects_before_rebuild() {
+        let layout = TextLayout::<String>::from_text("hello");
+        assert!(layout.rects_for_range(0..5).is_empty());
+    }
+
+    #[test]
+    fn single_line_selection() {
+        let layout = build_layout("hello world", f64::INFINITY);
+        let rects = layout.rects_for_range(0..5);
+
+        assert_eq!(rects.len(), 1);
+        assert!(rects[0].width() > 0.0);
+        assert!(rects[0].width() < layout.size().width);
+    }
+
+    #[test]
+    fn selection_across_line_wraps() {
+        let text = "one two three four five six";
+        let layout = build_layout(text, 40.0);
+        assert!(layout.layout().unwrap().line_count() > 1);
+
+        let rects = layout.rects_for_range(0..text.len());
+        assert!(rects.len() > 1);
+        for pair in rects.windows(2) {
+            assert!(pair[0].y0 < pair[1].y0);
+            assert!(pair[0].y1 <= pair[1].y0 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn selection_across_bidi_runs() {
+        let text = "abc \u{5d0}\u{5d1}\u{5d2} def";
+        let layout = build_layout(text, f64::INFINITY);
+
+        let rects = layout.rects_for_range(0..text.len());
+        assert!(!rects.is_empty());
+        let line_top = rects[0].y0;
+        assert!(rects.iter().all(|rect| rect.y0 == line_top));
+        assert!(rects.iter().all(|rect| rect.width() > 0.0));
+    }
+}
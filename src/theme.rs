@@ -8,7 +8,7 @@
 
 use crate::piet::{Color, FontFamily, FontStyle, FontWeight};
 use crate::text::FontDescriptor;
-use crate::{Env, Insets, Key};
+use crate::{Env, Insets, Key, KeyOrValue};
 
 pub const WINDOW_BACKGROUND_COLOR: Key<Color> =
     Key::new("org.masonry.theme.window_background_color");
@@ -46,9 +46,19 @@ pub const SELECTED_TEXT_INACTIVE_BACKGROUND_COLOR: Key<Color> =
     Key::new("org.masonry.theme.selection_color_inactive");
 pub const SELECTION_TEXT_COLOR: Key<Color> = Key::new("org.masonry.theme.selection_text_color");
 pub const CURSOR_COLOR: Key<Color> = Key::new("org.masonry.theme.cursor_color");
+pub const FOCUS_RING_COLOR: Key<Color> = Key::new("org.masonry.theme.focus_ring_color");
+pub const FOCUS_RING_WIDTH: Key<f64> = Key::new("org.masonry.theme.focus_ring_width");
 
 pub const TEXT_SIZE_NORMAL: Key<f64> = Key::new("org.masonry.theme.text_size_normal");
 pub const TEXT_SIZE_LARGE: Key<f64> = Key::new("org.masonry.theme.text_size_large");
+/// A factor applied to the size of all text laid out by [`TextLayout`].
+///
+/// This is meant for accessibility: set it above `1.0` to make all text in
+/// the application (or in a subtree, with [`WidgetPod::with_env`]) larger.
+///
+/// [`TextLayout`]: crate::text::TextLayout
+/// [`WidgetPod::with_env`]: crate::WidgetPod::with_env
+pub const TEXT_SIZE_SCALE: Key<f64> = Key::new("org.masonry.theme.text_size_scale");
 pub const BASIC_WIDGET_HEIGHT: Key<f64> = Key::new("org.masonry.theme.basic_widget_height");
 
 /// The default font for labels, buttons, text boxes, and other UI elements.
@@ -89,6 +99,69 @@ pub const SCROLLBAR_EDGE_WIDTH: Key<f64> = Key::new("org.masonry.theme.scrollbar
 /// scrollbar's primary axis.
 pub const SCROLLBAR_MIN_SIZE: Key<f64> = Key::new("org.linebender.theme.scrollbar_min_size");
 
+pub const COLOR_ROLE_PRIMARY: Key<Color> = Key::new("org.masonry.theme.role.primary");
+pub const COLOR_ROLE_ON_PRIMARY: Key<Color> = Key::new("org.masonry.theme.role.on_primary");
+pub const COLOR_ROLE_SECONDARY: Key<Color> = Key::new("org.masonry.theme.role.secondary");
+pub const COLOR_ROLE_ON_SECONDARY: Key<Color> = Key::new("org.masonry.theme.role.on_secondary");
+pub const COLOR_ROLE_BACKGROUND: Key<Color> = Key::new("org.masonry.theme.role.background");
+pub const COLOR_ROLE_ON_BACKGROUND: Key<Color> = Key::new("org.masonry.theme.role.on_background");
+pub const COLOR_ROLE_SURFACE: Key<Color> = Key::new("org.masonry.theme.role.surface");
+pub const COLOR_ROLE_ON_SURFACE: Key<Color> = Key::new("org.masonry.theme.role.on_surface");
+pub const COLOR_ROLE_ERROR: Key<Color> = Key::new("org.masonry.theme.role.error");
+pub const COLOR_ROLE_ON_ERROR: Key<Color> = Key::new("org.masonry.theme.role.on_error");
+
+/// The semantic role of a color in the theme.
+///
+/// Widgets can ask for the color of a role instead of using a specific key,
+/// so that themes stay consistent across widgets. Each role is backed by an
+/// [`Env`] key, which can be overridden to customize the palette.
+///
+/// "On" roles are for content (text, icons) drawn on top of the matching role.
+///
+/// A `ColorRole` can be used anywhere a [`KeyOrValue<Color>`](crate::KeyOrValue) is expected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorRole {
+    Primary,
+    OnPrimary,
+    Secondary,
+    OnSecondary,
+    Background,
+    OnBackground,
+    Surface,
+    OnSurface,
+    Error,
+    OnError,
+}
+
+impl ColorRole {
+    /// The [`Env`] key backing this role.
+    pub fn key(self) -> Key<Color> {
+        match self {
+            ColorRole::Primary => COLOR_ROLE_PRIMARY,
+            ColorRole::OnPrimary => COLOR_ROLE_ON_PRIMARY,
+            ColorRole::Secondary => COLOR_ROLE_SECONDARY,
+            ColorRole::OnSecondary => COLOR_ROLE_ON_SECONDARY,
+            ColorRole::Background => COLOR_ROLE_BACKGROUND,
+            ColorRole::OnBackground => COLOR_ROLE_ON_BACKGROUND,
+            ColorRole::Surface => COLOR_ROLE_SURFACE,
+            ColorRole::OnSurface => COLOR_ROLE_ON_SURFACE,
+            ColorRole::Error => COLOR_ROLE_ERROR,
+            ColorRole::OnError => COLOR_ROLE_ON_ERROR,
+        }
+    }
+
+    /// Resolve the themed color for this role.
+    pub fn resolve(self, env: &Env) -> Color {
+        env.get(self.key())
+    }
+}
+
+impl From<ColorRole> for KeyOrValue<Color> {
+    fn from(role: ColorRole) -> KeyOrValue<Color> {
+        role.key().into()
+    }
+}
+
 /// An initial theme.
 pub(crate) fn add_to_env(env: Env) -> Env {
     env.adding(WINDOW_BACKGROUND_COLOR, Color::rgb8(0x29, 0x29, 0x29))
@@ -119,8 +192,22 @@ pub(crate) fn add_to_env(env: Env) -> Env {
         .adding(SELECTED_TEXT_INACTIVE_BACKGROUND_COLOR, Color::grey8(0x74))
         .adding(SELECTION_TEXT_COLOR, Color::rgb8(0x00, 0x00, 0x00))
         .adding(CURSOR_COLOR, Color::WHITE)
+        .adding(FOCUS_RING_COLOR, Color::rgb8(0x5c, 0xc4, 0xff))
+        .adding(FOCUS_RING_WIDTH, 2.0)
         .adding(TEXT_SIZE_NORMAL, 15.0)
         .adding(TEXT_SIZE_LARGE, 24.0)
+        .adding(TEXT_SIZE_SCALE, 1.0)
+        // A dark, Material-like palette
+        .adding(COLOR_ROLE_PRIMARY, Color::rgb8(0xbb, 0x86, 0xfc))
+        .adding(COLOR_ROLE_ON_PRIMARY, Color::BLACK)
+        .adding(COLOR_ROLE_SECONDARY, Color::rgb8(0x03, 0xda, 0xc6))
+        .adding(COLOR_ROLE_ON_SECONDARY, Color::BLACK)
+        .adding(COLOR_ROLE_BACKGROUND, Color::rgb8(0x12, 0x12, 0x12))
+        .adding(COLOR_ROLE_ON_BACKGROUND, Color::WHITE)
+        .adding(COLOR_ROLE_SURFACE, Color::rgb8(0x1e, 0x1e, 0x1e))
+        .adding(COLOR_ROLE_ON_SURFACE, Color::WHITE)
+        .adding(COLOR_ROLE_ERROR, Color::rgb8(0xcf, 0x66, 0x79))
+        .adding(COLOR_ROLE_ON_ERROR, Color::BLACK)
         .adding(BASIC_WIDGET_HEIGHT, 18.0)
         .adding(WIDE_WIDGET_WIDTH, 100.)
         .adding(BORDERED_WIDGET_HEIGHT, 24.0)
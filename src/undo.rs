@@ -0,0 +1,251 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! An undo stack shared by the widgets of a subtree.
+
+use std::any::Any;
+use std::rc::Rc;
+use std::time::Duration;
+
+use instant::Instant;
+
+use crate::command::{APPLY_EDIT, REDO, UNDO, UNDOABLE_EDIT};
+use crate::{Command, Event, EventCtx, WidgetId};
+
+/// How close consecutive edits with the same coalescing key must be to be
+/// undone together, by default.
+pub const DEFAULT_COALESCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An edit made by a widget, that can be undone and redone.
+///
+/// The edit carries two deltas of any type: one to undo the edit, and one to
+/// apply it again. The widget that made the edit receives them back in
+/// [`APPLY_EDIT`] commands.
+///
+/// Widgets submit edits as [`UNDOABLE_EDIT`] notifications, to be collected by
+/// an ancestor holding an [`UndoStack`].
+#[derive(Clone)]
+pub struct UndoableEdit {
+    undo: EditDelta,
+    redo: EditDelta,
+    coalesce_key: Option<&'static str>,
+}
+
+/// The delta of an [`UndoableEdit`], sent back to the widget that made it.
+#[derive(Clone)]
+pub struct EditDelta(Rc<dyn Any>);
+
+/// A stack of undoable edits, made by the widgets of a subtree.
+///
+/// The stack is held by a container, usually close to the root, which forwards
+/// its events to [`handle_event`](Self::handle_event):
+/// - [`UNDOABLE_EDIT`] notifications from descendants are recorded.
+/// - [`UNDO`] and [`REDO`] commands send [`APPLY_EDIT`] commands back to the
+/// widgets that made the edits.
+///
+/// Consecutive edits from the same widget with the same coalescing key (see
+/// [`UndoableEdit::coalescing`]) are grouped into a single undo step, as long
+/// as each one follows the previous one within the coalescing interval. This
+/// lets a burst of typing be undone at once.
+///
+/// ```
+/// # use masonry::{Event, EventCtx, UndoStack, Widget, WidgetPod};
+/// # fn on_event(stack: &mut UndoStack, child: &mut WidgetPod<Box<dyn Widget>>, ctx: &mut EventCtx, event: &Event, env: &masonry::Env) {
+/// if stack.handle_event(ctx, event) {
+///     return;
+/// }
+/// child.on_event(ctx, event, env);
+/// # }
+/// ```
+pub struct UndoStack {
+    undo_steps: Vec<UndoStep>,
+    redo_steps: Vec<UndoStep>,
+    coalesce_interval: Duration,
+}
+
+/// Edits that are undone and redone together.
+struct UndoStep {
+    edits: Vec<(WidgetId, UndoableEdit)>,
+    coalesce_key: Option<(WidgetId, &'static str)>,
+    last_edit: Instant,
+}
+
+// ---
+
+impl UndoableEdit {
+    /// Create an edit, from the delta that undoes it and the delta that
+    /// applies it again.
+    pub fn new(undo: impl Any, redo: impl Any) -> Self {
+        UndoableEdit {
+            undo: EditDelta(Rc::new(undo)),
+            redo: EditDelta(Rc::new(redo)),
+            coalesce_key: None,
+        }
+    }
+
+    /// Builder-style method to let this edit be grouped with the previous
+    /// edit of the same widget with the same `key`, such as `"typing"`.
+    pub fn coalescing(mut self, key: &'static str) -> Self {
+        self.coalesce_key = Some(key);
+        self
+    }
+}
+
+impl EditDelta {
+    /// Return the delta if it has type `T`.
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+}
+
+impl UndoStack {
+    /// Create an empty stack, with the [`DEFAULT_COALESCE_INTERVAL`].
+    pub fn new() -> Self {
+        UndoStack {
+            undo_steps: Vec::new(),
+            redo_steps: Vec::new(),
+            coalesce_interval: DEFAULT_COALESCE_INTERVAL,
+        }
+    }
+
+    /// Builder-style method to set how close consecutive edits must be to be
+    /// coalesced.
+    pub fn with_coalesce_interval(mut self, interval: Duration) -> Self {
+        self.coalesce_interval = interval;
+        self
+    }
+
+    /// Record edits and run undo and redo commands.
+    ///
+    /// Return `true` if the event was used, in which case it's marked as
+    /// handled and shouldn't be passed on.
+    pub fn handle_event(&mut self, ctx: &mut EventCtx, event: &Event) -> bool {
+        match event {
+            Event::Notification(note) => match note.try_get(UNDOABLE_EDIT) {
+                Some(edit) => {
+                    let now = ctx.global_state.now();
+                    self.record(note.source(), edit.clone(), now);
+                }
+                None => return false,
+            },
+            Event::Command(cmd) if cmd.is(UNDO) => {
+                for command in self.undo() {
+                    ctx.submit_command(command);
+                }
+            }
+            Event::Command(cmd) if cmd.is(REDO) => {
+                for command in self.redo() {
+                    ctx.submit_command(command);
+                }
+            }
+            _ => return false,
+        }
+        ctx.set_handled();
+        true
+    }
+
+    /// Record an edit made by `source` at `now`.
+    ///
+    /// This clears the redo steps.
+    pub fn record(&mut self, source: WidgetId, edit: UndoableEdit, now: Instant) {
+        self.redo_steps.clear();
+        let coalesce_key = edit.coalesce_key.map(|key| (source, key));
+        if let Some(step) = self.undo_steps.last_mut() {
+            let coalesces = coalesce_key.is_some()
+                && step.coalesce_key == coalesce_key
+                && now.saturating_duration_since(step.last_edit) <= self.coalesce_interval;
+            if coalesces {
+                step.edits.push((source, edit));
+                step.last_edit = now;
+                return;
+            }
+        }
+        self.undo_steps.push(UndoStep {
+            edits: vec![(source, edit)],
+            coalesce_key,
+            last_edit: now,
+        });
+    }
+
+    /// Undo the last step, returning the [`APPLY_EDIT`] commands to submit.
+    ///
+    /// The edits of the step are undone in reverse order.
+    pub fn undo(&mut self) -> Vec<Command> {
+        let step = match self.undo_steps.pop() {
+            Some(step) => step,
+            None => return Vec::new(),
+        };
+        let commands = step
+            .edits
+            .iter()
+            .rev()
+            .map(|(source, edit)| APPLY_EDIT.with(edit.undo.clone()).to(*source))
+            .collect();
+        self.redo_steps.push(step);
+        commands
+    }
+
+    /// Redo the last undone step, returning the [`APPLY_EDIT`] commands to submit.
+    pub fn redo(&mut self) -> Vec<Command> {
+        let mut step = match self.redo_steps.pop() {
+            Some(step) => step,
+            None => return Vec::new(),
+        };
+        let commands = step
+            .edits
+            .iter()
+            .map(|(source, edit)| APPLY_EDIT.with(edit.redo.clone()).to(*source))
+            .collect();
+        // A redone step isn't extended by later edits.
+        step.coalesce_key = None;
+        self.undo_steps.push(step);
+        commands
+    }
+
+    /// Whether there is a step to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo_steps.is_empty()
+    }
+
+    /// Whether there is a step to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo_steps.is_empty()
+    }
+
+    /// Forget all steps.
+    pub fn clear(&mut self) {
+        self.undo_steps.clear();
+        self.redo_steps.clear();
+    }
+}
+
+impl Default for UndoStack {
+    fn default() -> Self {
+        UndoStack::new()
+    }
+}
+
+impl std::fmt::Debug for UndoableEdit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UndoableEdit")
+            .field("coalesce_key", &self.coalesce_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Debug for EditDelta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EditDelta")
+    }
+}
+
+impl std::fmt::Debug for UndoStack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UndoStack")
+            .field("undo_steps", &self.undo_steps.len())
+            .field("redo_steps", &self.redo_steps.len())
+            .field("coalesce_interval", &self.coalesce_interval)
+            .finish()
+    }
+}
@@ -7,6 +7,8 @@
 #![cfg(not(tarpaulin_include))]
 
 use std::any::Any;
+use std::cell::RefCell;
+use std::fmt;
 use std::hash::Hash;
 
 /// Panic in debug and tracing::error in release mode.
@@ -15,23 +17,63 @@ use std::hash::Hash;
 /// but it will log the provided message instead of ignoring it in release builds.
 ///
 /// It's useful when a backtrace would aid debugging but a crash can be avoided in release.
+///
+/// If a handler was registered with [`set_debug_panic_handler`], the formatted
+/// message is passed to it instead, in both debug and release builds.
 macro_rules! debug_panic {
     () => { ... };
     ($msg:expr) => {
-        if cfg!(debug_assertions) {
-            panic!($msg);
-        } else {
-            tracing::error!($msg);
-        }
+        $crate::util::debug_panic_impl(format_args!($msg))
     };
     ($msg:expr,) => { debug_panic!($msg) };
     ($fmt:expr, $($arg:tt)+) => {
-        if cfg!(debug_assertions) {
-            panic!($fmt, $($arg)*);
+        $crate::util::debug_panic_impl(format_args!($fmt, $($arg)*))
+    };
+}
+
+thread_local! {
+    static DEBUG_PANIC_HANDLER: RefCell<Option<Box<dyn Fn(&str)>>> = RefCell::new(None);
+}
+
+/// Route `debug_panic!` invocations to `handler` instead of panicking.
+///
+/// Masonry uses `debug_panic!` to report widget bugs, like a container
+/// forgetting to recurse into a child. By default these panic in debug builds
+/// and log an error in release builds. Hosts that need to survive misbehaving
+/// widgets, like a live widget editor, can use this to log the message or
+/// forward it to telemetry instead.
+///
+/// The handler is set for the current thread, which should be the thread
+/// running the UI. It replaces any previously set handler.
+pub fn set_debug_panic_handler(handler: impl Fn(&str) + 'static) {
+    DEBUG_PANIC_HANDLER.with(|cell| *cell.borrow_mut() = Some(Box::new(handler)));
+}
+
+/// Remove the handler set by [`set_debug_panic_handler`], restoring the
+/// default behavior.
+pub fn clear_debug_panic_handler() {
+    DEBUG_PANIC_HANDLER.with(|cell| *cell.borrow_mut() = None);
+}
+
+#[track_caller]
+pub(crate) fn debug_panic_impl(args: fmt::Arguments<'_>) {
+    let handled = DEBUG_PANIC_HANDLER.with(|cell| {
+        if let Some(handler) = cell.borrow().as_ref() {
+            handler(&args.to_string());
+            true
         } else {
-            tracing::error!($fmt, $($arg)*);
+            false
         }
-    };
+    });
+    if handled {
+        return;
+    }
+
+    if cfg!(debug_assertions) {
+        panic!("{}", args);
+    } else {
+        tracing::error!("{}", args);
+    }
 }
 
 // ---
@@ -8,6 +8,7 @@ use smallvec::SmallVec;
 use tracing::{trace, trace_span, Span};
 
 use crate::action::Action;
+use crate::command::ACTIVATE;
 use crate::widget::{Label, WidgetMut, WidgetPod, WidgetRef};
 use crate::{
     theme, ArcStr, BoxConstraints, Env, Event, EventCtx, Insets, LayoutCtx, LifeCycle,
@@ -89,6 +90,13 @@ impl Widget for Button {
                 }
                 ctx.set_active(false);
             }
+            Event::Command(command) if command.is(ACTIVATE) => {
+                if !ctx.is_disabled() {
+                    ctx.submit_action(Action::ButtonPressed);
+                    trace!("Button {:?} activated", ctx.widget_id());
+                }
+                ctx.set_handled();
+            }
             _ => (),
         }
     }
@@ -213,6 +221,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn activate_button() {
+        let [button_id] = widget_ids();
+        let widget = Button::new("Hello").with_id(button_id);
+
+        let mut harness = TestHarness::create(widget);
+
+        harness.activate(button_id);
+        assert_eq!(
+            harness.pop_action(),
+            Some((Action::ButtonPressed, button_id))
+        );
+    }
+
     #[test]
     fn edit_button() {
         let image_1 = {
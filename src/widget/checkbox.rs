@@ -4,10 +4,12 @@
 
 //! A checkbox widget.
 
+use druid_shell::KbKey;
 use smallvec::SmallVec;
 use tracing::{trace, trace_span, Span};
 
 use crate::action::Action;
+use crate::command::{ACTIVATE, CHECKBOX_TOGGLED};
 use crate::kurbo::{BezPath, Size};
 use crate::piet::{LineCap, LineJoin, LinearGradient, RenderContext, StrokeStyle, UnitPoint};
 use crate::widget::{Label, WidgetMut, WidgetRef};
@@ -16,7 +18,15 @@ use crate::{
     PaintCtx, StatusChange, Widget, WidgetPod,
 };
 
+const FOCUS_RING_WIDTH: f64 = 2.0;
+const FOCUS_RING_INSETS: f64 = 4.0;
+
 /// A checkbox that can be toggled.
+///
+/// The checkbox is toggled by clicking it, by pressing Space or Enter while it
+/// has focus, or with the [`ACTIVATE`] command. When toggled by the user, it
+/// submits an [`Action::CheckboxChecked`] and a [`CHECKBOX_TOGGLED`]
+/// notification. A disabled checkbox is dimmed and ignores input.
 pub struct Checkbox {
     checked: bool,
     label: WidgetPod<Label>,
@@ -42,6 +52,15 @@ impl Checkbox {
     }
 }
 
+impl Checkbox {
+    fn toggle(&mut self, ctx: &mut EventCtx) {
+        self.checked = !self.checked;
+        ctx.submit_action(Action::CheckboxChecked(self.checked));
+        ctx.submit_notification(CHECKBOX_TOGGLED.with(self.checked));
+        ctx.request_paint();
+    }
+}
+
 impl<'a, 'b> CheckboxMut<'a, 'b> {
     pub fn set_checked(&mut self, checked: bool) {
         self.1.checked = checked;
@@ -71,14 +90,32 @@ impl Widget for Checkbox {
             Event::MouseUp(_) => {
                 if ctx.is_active() && !ctx.is_disabled() {
                     if ctx.is_hot() {
-                        self.checked = !self.checked;
-                        ctx.submit_action(Action::CheckboxChecked(self.checked));
+                        self.toggle(ctx);
                         trace!("Checkbox {:?} released", ctx.widget_id());
                     }
                     ctx.request_paint();
                 }
                 ctx.set_active(false);
             }
+            Event::KeyDown(key_event) if ctx.is_focused() => {
+                let is_activation_key = match &key_event.key {
+                    KbKey::Enter => true,
+                    KbKey::Character(c) => c == " ",
+                    _ => false,
+                };
+                if is_activation_key && !key_event.repeat && !ctx.is_disabled() {
+                    self.toggle(ctx);
+                    ctx.set_handled();
+                    trace!("Checkbox {:?} toggled with keyboard", ctx.widget_id());
+                }
+            }
+            Event::Command(command) if command.is(ACTIVATE) => {
+                if !ctx.is_disabled() {
+                    self.toggle(ctx);
+                    trace!("Checkbox {:?} activated", ctx.widget_id());
+                }
+                ctx.set_handled();
+            }
             _ => (),
         }
     }
@@ -88,6 +125,10 @@ impl Widget for Checkbox {
     }
 
     fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, env: &Env) {
+        if let LifeCycle::BuildFocusChain = event {
+            ctx.register_for_focus();
+            ctx.suppress_focus_ring();
+        }
         self.label.lifecycle(ctx, event, env);
     }
 
@@ -105,6 +146,8 @@ impl Widget for Checkbox {
         let our_size = bc.constrain(desired_size);
         let baseline = self.label.baseline_offset() + (our_size.height - label_size.height);
         ctx.set_baseline_offset(baseline);
+        // Leave room for the focus ring.
+        ctx.set_paint_insets(FOCUS_RING_INSETS);
         trace!("Computed layout: size={}, baseline={}", our_size, baseline);
         our_size
     }
@@ -119,14 +162,19 @@ impl Widget for Checkbox {
             .to_rounded_rect(2.);
 
         //Paint the background
-        let background_gradient = LinearGradient::new(
-            UnitPoint::TOP,
-            UnitPoint::BOTTOM,
+        let (light, dark) = if ctx.is_disabled() {
+            (
+                env.get(theme::DISABLED_FOREGROUND_LIGHT),
+                env.get(theme::DISABLED_FOREGROUND_DARK),
+            )
+        } else {
             (
                 env.get(theme::BACKGROUND_LIGHT),
                 env.get(theme::BACKGROUND_DARK),
-            ),
-        );
+            )
+        };
+        let background_gradient =
+            LinearGradient::new(UnitPoint::TOP, UnitPoint::BOTTOM, (light, dark));
 
         ctx.fill(rect, &background_gradient);
 
@@ -138,6 +186,17 @@ impl Widget for Checkbox {
 
         ctx.stroke(rect, &border_color, border_width);
 
+        if ctx.is_focus_visible() {
+            // High-contrast themes get a ring twice as thick, still within the insets.
+            let ring_width = if env.high_contrast() {
+                2.0 * FOCUS_RING_WIDTH
+            } else {
+                FOCUS_RING_WIDTH
+            };
+            let focus_ring = rect.rect().inset(FOCUS_RING_WIDTH).to_rounded_rect(4.0);
+            ctx.stroke(focus_ring, &env.get(theme::PRIMARY_LIGHT), ring_width);
+        }
+
         if self.checked {
             // Paint the checkmark
             let mut path = BezPath::new();
@@ -183,10 +242,49 @@ impl Widget for Checkbox {
 mod tests {
     use insta::assert_debug_snapshot;
 
+    use druid_shell::KeyEvent;
+
     use super::*;
     use crate::assert_render_snapshot;
-    use crate::testing::{widget_ids, TestHarness, TestWidgetExt};
+    use crate::testing::{widget_ids, ModularWidget, TestHarness, TestWidgetExt, REQUEST_FOCUS};
     use crate::theme::PRIMARY_LIGHT;
+    use crate::{Point, WidgetId};
+
+    /// Wrap a checkbox in a widget that gives it focus on `REQUEST_FOCUS`,
+    /// and that disables it if `disabled` is true.
+    fn focus_wrapper(checkbox: impl Widget, checkbox_id: WidgetId, disabled: bool) -> impl Widget {
+        ModularWidget::new(WidgetPod::new(checkbox))
+            .event_fn(move |child, ctx, event, env| {
+                if let Event::Command(cmd) = event {
+                    if cmd.is(REQUEST_FOCUS) {
+                        ctx.set_focus(checkbox_id);
+                        ctx.set_handled();
+                        return;
+                    }
+                }
+                child.on_event(ctx, event, env)
+            })
+            .lifecycle_fn(move |child, ctx, event, env| {
+                if let LifeCycle::WidgetAdded = event {
+                    ctx.set_disabled(disabled);
+                }
+                child.lifecycle(ctx, event, env)
+            })
+            .layout_fn(|child, ctx, bc, env| {
+                let size = child.layout(ctx, bc, env);
+                ctx.place_child(child, Point::ORIGIN, env);
+                size
+            })
+            .paint_fn(|child, ctx, env| child.paint(ctx, env))
+            .children_fn(|child| smallvec::smallvec![child.as_dyn()])
+    }
+
+    fn key_down(key: KbKey) -> Event {
+        Event::KeyDown(KeyEvent {
+            key,
+            ..Default::default()
+        })
+    }
 
     #[test]
     fn simple_checkbox() {
@@ -216,6 +314,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn activate_checkbox() {
+        let [checkbox_id] = widget_ids();
+        let widget = Checkbox::new(false, "Hello").with_id(checkbox_id);
+
+        let mut harness = TestHarness::create(widget);
+
+        harness.activate(checkbox_id);
+        assert_eq!(
+            harness.pop_action(),
+            Some((Action::CheckboxChecked(true), checkbox_id))
+        );
+
+        harness.activate(checkbox_id);
+        assert_eq!(
+            harness.pop_action(),
+            Some((Action::CheckboxChecked(false), checkbox_id))
+        );
+    }
+
+    #[test]
+    fn keyboard_checkbox() {
+        let [checkbox_id, wrapper_id] = widget_ids();
+        let checkbox = Checkbox::new(false, "Hello").with_id(checkbox_id);
+        let widget = focus_wrapper(checkbox, checkbox_id, false).with_id(wrapper_id);
+
+        let mut harness = TestHarness::create(widget);
+
+        // Keys are ignored until the checkbox has focus.
+        harness.process_event(key_down(KbKey::Character(" ".into())));
+        assert_eq!(harness.pop_action(), None);
+
+        harness.submit_command(REQUEST_FOCUS.to(wrapper_id));
+        harness.process_event(key_down(KbKey::Character(" ".into())));
+        assert_eq!(
+            harness.pop_action(),
+            Some((Action::CheckboxChecked(true), checkbox_id))
+        );
+
+        harness.process_event(key_down(KbKey::Enter));
+        assert_eq!(
+            harness.pop_action(),
+            Some((Action::CheckboxChecked(false), checkbox_id))
+        );
+
+        harness.process_event(key_down(KbKey::Character("a".into())));
+        assert_eq!(harness.pop_action(), None);
+    }
+
+    #[test]
+    fn disabled_checkbox() {
+        let [checkbox_id, wrapper_id] = widget_ids();
+        let checkbox = Checkbox::new(false, "Hello").with_id(checkbox_id);
+        let widget = focus_wrapper(checkbox, checkbox_id, true).with_id(wrapper_id);
+
+        let mut harness = TestHarness::create(widget);
+
+        harness.mouse_click_on(checkbox_id);
+        assert_eq!(harness.pop_action(), None);
+
+        harness.activate(checkbox_id);
+        assert_eq!(harness.pop_action(), None);
+
+        harness.submit_command(REQUEST_FOCUS.to(wrapper_id));
+        harness.process_event(key_down(KbKey::Enter));
+        assert_eq!(harness.pop_action(), None);
+    }
+
     #[test]
     fn edit_checkbox() {
         let image_1 = {
@@ -0,0 +1,158 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! Children kept in the tree while they animate out.
+
+use std::time::Duration;
+
+use crate::widget::{Axis, WidgetRef};
+use crate::{
+    BoxConstraints, Env, Event, EventCtx, InternalEvent, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, RenderContext, Widget, WidgetPod,
+};
+
+/// Children of a container that are being removed with an exit animation.
+///
+/// Instead of removing a child right away, a container can hand it to
+/// [`start_exit`](Self::start_exit). The child then stays in the widget tree,
+/// at the position it had, while it collapses along the container's axis.
+/// Once the animation completes, it's removed and receives
+/// [`LifeCycle::WidgetRemoved`].
+///
+/// Exiting children don't take part in the container's layout, and don't
+/// receive pointer events. The container forwards its passes to the matching
+/// methods of this type, and lists [`children`](Self::children) after its own.
+/// Since they overlap the children that took their place, the container must
+/// also leave them out of [`Widget::get_child_at_pos`].
+pub struct ExitingChildren {
+    axis: Axis,
+    children: Vec<ExitingChild>,
+}
+
+struct ExitingChild {
+    widget: WidgetPod<Box<dyn Widget>>,
+    duration: Duration,
+    elapsed: Duration,
+}
+
+impl ExitingChildren {
+    /// Create an empty set, for children collapsing along `axis`.
+    pub fn new(axis: Axis) -> Self {
+        ExitingChildren {
+            axis,
+            children: Vec::new(),
+        }
+    }
+
+    /// Start the exit animation of a child taken out of the container.
+    ///
+    /// The container must call [`children_changed`](EventCtx::children_changed)
+    /// and [`request_anim_frame`](EventCtx::request_anim_frame), so that the
+    /// animation starts.
+    pub fn start_exit(&mut self, child: WidgetPod<Box<dyn Widget>>, duration: Duration) {
+        self.children.push(ExitingChild {
+            widget: child,
+            duration,
+            elapsed: Duration::ZERO,
+        });
+    }
+
+    /// Whether no child is exiting.
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// The exiting children, for [`Widget::children`].
+    pub fn children(&self) -> impl Iterator<Item = WidgetRef<'_, dyn Widget>> {
+        self.children.iter().map(|child| child.widget.as_dyn())
+    }
+
+    /// Forward an event to the exiting children, and advance their animations
+    /// on [`Event::AnimFrame`].
+    ///
+    /// Pointer events are replaced with a mouse leave, so the children lose
+    /// their hot state.
+    pub fn on_event(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) {
+        let mouse_leave = Event::Internal(InternalEvent::MouseLeave);
+        let event = match event {
+            Event::MouseDown(_) | Event::MouseUp(_) | Event::MouseMove(_) | Event::Wheel(_) => {
+                &mouse_leave
+            }
+            event => event,
+        };
+        for child in &mut self.children {
+            child.widget.on_event(ctx, event, env);
+        }
+
+        if let Event::AnimFrame(interval) = event {
+            if self.children.is_empty() {
+                return;
+            }
+            let interval = Duration::from_nanos(*interval);
+            let (done, running) = std::mem::take(&mut self.children)
+                .into_iter()
+                .map(|mut child| {
+                    child.elapsed += interval;
+                    child
+                })
+                .partition::<Vec<_>, _>(|child| child.elapsed >= child.duration);
+            self.children = running;
+            for child in done {
+                ctx.remove_child(child.widget);
+            }
+            if !self.children.is_empty() {
+                ctx.request_anim_frame();
+            }
+            ctx.request_paint();
+        }
+    }
+
+    /// Forward a lifecycle event to the exiting children.
+    pub fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, env: &Env) {
+        for child in &mut self.children {
+            child.widget.lifecycle(ctx, event, env);
+        }
+    }
+
+    /// Lay out the exiting children where they were, at the size they had.
+    pub fn layout(&mut self, ctx: &mut LayoutCtx, env: &Env) {
+        for child in &mut self.children {
+            let rect = child.widget.layout_rect();
+            child
+                .widget
+                .layout(ctx, &BoxConstraints::tight(rect.size()), env);
+            ctx.place_child(&mut child.widget, rect.origin(), env);
+        }
+    }
+
+    /// Paint the exiting children, clipped to a rect that shrinks along the
+    /// axis as the animation progresses.
+    pub fn paint(&mut self, ctx: &mut PaintCtx, env: &Env) {
+        for child in &mut self.children {
+            let progress = if child.duration.is_zero() {
+                1.0
+            } else {
+                (child.elapsed.as_secs_f64() / child.duration.as_secs_f64()).min(1.0)
+            };
+            let mut clip = child.widget.layout_rect();
+            match self.axis {
+                Axis::Horizontal => clip.x1 = clip.x0 + clip.width() * (1.0 - progress),
+                Axis::Vertical => clip.y1 = clip.y0 + clip.height() * (1.0 - progress),
+            }
+            ctx.with_save(|ctx| {
+                ctx.clip(clip);
+                child.widget.paint(ctx, env);
+            });
+        }
+    }
+}
+
+impl std::fmt::Debug for ExitingChildren {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExitingChildren")
+            .field("axis", &self.axis)
+            .field("children", &self.children.len())
+            .finish()
+    }
+}
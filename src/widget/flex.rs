@@ -4,12 +4,14 @@
 
 //! A widget that arranges its children in a one-dimensional array.
 
+use std::time::Duration;
+
 use smallvec::SmallVec;
 use tracing::{trace, trace_span, Span};
 
 use crate::kurbo::common::FloatExt;
 use crate::kurbo::Vec2;
-use crate::widget::{WidgetMut, WidgetRef};
+use crate::widget::{ExitingChildren, WidgetMut, WidgetRef};
 use crate::{
     BoxConstraints, Data, Env, Event, EventCtx, KeyOrValue, LayoutCtx, LifeCycle, LifeCycleCtx,
     PaintCtx, Point, Rect, RenderContext, Size, StatusChange, Widget, WidgetId, WidgetPod,
@@ -24,6 +26,7 @@ pub struct Flex {
     main_alignment: MainAxisAlignment,
     fill_major_axis: bool,
     children: Vec<Child>,
+    exiting: ExitingChildren,
 }
 
 crate::declare_widget!(FlexMut, Flex);
@@ -39,6 +42,7 @@ crate::declare_widget!(FlexMut, Flex);
 pub struct FlexParams {
     flex: f64,
     alignment: Option<CrossAxisAlignment>,
+    fill: bool,
 }
 
 /// An axis in visual space.
@@ -103,6 +107,7 @@ impl Flex {
             cross_alignment: CrossAxisAlignment::Center,
             main_alignment: MainAxisAlignment::Start,
             fill_major_axis: false,
+            exiting: ExitingChildren::new(axis),
         }
     }
 
@@ -177,6 +182,7 @@ impl Flex {
                 widget: WidgetPod::new(Box::new(child)),
                 alignment: params.alignment,
                 flex: params.flex,
+                fill: params.fill,
             }
         } else {
             // TODO
@@ -300,6 +306,7 @@ impl<'a, 'b> FlexMut<'a, 'b> {
                 widget: WidgetPod::new(Box::new(child)),
                 alignment: params.alignment,
                 flex: params.flex,
+                fill: params.fill,
             }
         } else {
             // TODO
@@ -392,6 +399,7 @@ impl<'a, 'b> FlexMut<'a, 'b> {
                 widget: WidgetPod::new(Box::new(child)),
                 alignment: params.alignment,
                 flex: params.flex,
+                fill: params.fill,
             }
         } else {
             // TODO
@@ -458,7 +466,27 @@ impl<'a, 'b> FlexMut<'a, 'b> {
     }
 
     pub fn remove_child(&mut self, idx: usize) {
-        self.1.children.remove(idx);
+        if let Child::Fixed { widget, .. } | Child::Flex { widget, .. } =
+            self.1.children.remove(idx)
+        {
+            self.0.remove_child(widget);
+        }
+        self.0.widget_state.needs_layout = true;
+    }
+
+    /// Remove the child at `idx`, collapsing it over `duration` first.
+    ///
+    /// The child stops taking space right away, but stays in the widget tree
+    /// and is painted where it was until its exit animation completes. See
+    /// [`ExitingChildren`].
+    pub fn remove_child_animated(&mut self, idx: usize, duration: Duration) {
+        if let Child::Fixed { widget, .. } | Child::Flex { widget, .. } =
+            self.1.children.remove(idx)
+        {
+            self.1.exiting.start_exit(widget, duration);
+            self.0.children_changed();
+            self.0.request_anim_frame();
+        }
         self.0.widget_state.needs_layout = true;
     }
 
@@ -474,7 +502,11 @@ impl<'a, 'b> FlexMut<'a, 'b> {
     }
 
     pub fn clear(&mut self) {
-        self.1.children.clear();
+        for child in std::mem::take(&mut self.1.children) {
+            if let Child::Fixed { widget, .. } | Child::Flex { widget, .. } = child {
+                self.0.remove_child(widget);
+            }
+        }
         self.0.widget_state.needs_layout = true;
     }
 }
@@ -484,6 +516,7 @@ impl Widget for Flex {
         for child in self.children.iter_mut().filter_map(|x| x.widget_mut()) {
             child.on_event(ctx, event, env);
         }
+        self.exiting.on_event(ctx, event, env);
     }
 
     fn on_status_change(&mut self, _ctx: &mut LifeCycleCtx, _event: &StatusChange, _env: &Env) {}
@@ -492,6 +525,7 @@ impl Widget for Flex {
         for child in self.children.iter_mut().filter_map(|x| x.widget_mut()) {
             child.lifecycle(ctx, event, env);
         }
+        self.exiting.lifecycle(ctx, event, env);
     }
 
     fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, env: &Env) -> Size {
@@ -508,6 +542,8 @@ impl Widget for Flex {
         // Measure non-flex children.
         let mut major_non_flex = 0.0;
         let mut flex_sum = 0.0;
+        // Whether all flex children are fill children.
+        let mut only_fill = true;
         for child in &mut self.children {
             match child {
                 Child::Fixed { widget, alignment } => {
@@ -541,7 +577,14 @@ impl Widget for Flex {
                     *calculated_siz = calculated_siz.max(0.0);
                     major_non_flex += *calculated_siz;
                 }
-                Child::Flex { flex, .. } | Child::FlexedSpacer(flex, _) => flex_sum += *flex,
+                Child::Flex { flex, fill, .. } => {
+                    flex_sum += *flex;
+                    only_fill &= *fill;
+                }
+                Child::FlexedSpacer(flex, _) => {
+                    flex_sum += *flex;
+                    only_fill = false;
+                }
             }
         }
 
@@ -554,12 +597,24 @@ impl Widget for Flex {
         // Measure flex children.
         for child in &mut self.children {
             match child {
-                Child::Flex { widget, flex, .. } => {
+                Child::Flex {
+                    widget, flex, fill, ..
+                } => {
                     let desired_major = (*flex) * px_per_flex + remainder;
                     let actual_major = desired_major.round();
                     remainder = desired_major - actual_major;
 
-                    let child_bc = self.direction.constraints(&loosened_bc, 0.0, actual_major);
+                    let child_bc = if *fill {
+                        let fill_major = if actual_major.is_finite() {
+                            actual_major
+                        } else {
+                            0.0
+                        };
+                        self.direction
+                            .constraints(&loosened_bc, fill_major, fill_major)
+                    } else {
+                        self.direction.constraints(&loosened_bc, 0.0, actual_major)
+                    };
                     let child_size = widget.layout(ctx, &child_bc, env);
                     let baseline_offset = widget.baseline_offset();
 
@@ -646,11 +701,14 @@ impl Widget for Flex {
             }
         }
 
-        if flex_sum > 0.0 && total_major.is_infinite() {
+        // Fill children don't need bounded constraints: they take no space instead.
+        let fill_unbounded = only_fill && total_major.is_infinite();
+
+        if flex_sum > 0.0 && total_major.is_infinite() && !fill_unbounded {
             tracing::warn!("A child of Flex is flex, but Flex is unbounded.")
         }
 
-        if flex_sum > 0.0 {
+        if flex_sum > 0.0 && !fill_unbounded {
             major = total_major;
         }
 
@@ -684,6 +742,8 @@ impl Widget for Flex {
                 .unwrap_or(0.0),
         };
 
+        self.exiting.layout(ctx, env);
+
         ctx.set_baseline_offset(baseline_offset);
         trace!(
             "Computed layout: size={}, baseline_offset={}",
@@ -697,6 +757,7 @@ impl Widget for Flex {
         for child in self.children.iter_mut().filter_map(|x| x.widget_mut()) {
             child.paint(ctx, env);
         }
+        self.exiting.paint(ctx, env);
 
         // paint the baseline if we're debugging layout
         if env.get(Env::DEBUG_PAINT) && ctx.widget_state.baseline_offset != 0.0 {
@@ -713,9 +774,21 @@ impl Widget for Flex {
             .iter()
             .filter_map(|child| child.widget())
             .map(|widget_pod| widget_pod.as_dyn())
+            .chain(self.exiting.children())
             .collect()
     }
 
+    fn get_child_at_pos(&self, pos: Point) -> Option<WidgetRef<'_, dyn Widget>> {
+        // Exiting children keep their old rect, which the following children
+        // moved into. They must not hide them from the pointer.
+        self.children
+            .iter()
+            .filter_map(|child| child.widget())
+            .map(|widget_pod| widget_pod.as_dyn())
+            .filter(|child| !child.state().is_stashed && child.state().layout_rect().contains(pos))
+            .max_by_key(|child| child.state().z_index)
+    }
+
     fn make_trace_span(&self) -> Span {
         trace_span!("Flex")
     }
@@ -833,6 +906,25 @@ impl FlexParams {
         FlexParams {
             flex,
             alignment: alignment.into(),
+            fill: false,
+        }
+    }
+
+    /// Create `FlexParams` for a child that fills its share of the space.
+    ///
+    /// Unlike regular flex children, which may be smaller than their share,
+    /// fill children are given tight constraints on the main axis, so they
+    /// occupy exactly their share of the space left after non-flex children
+    /// are measured. Fill children share that space by weight with other
+    /// flex children.
+    ///
+    /// If the container is unbounded on its main axis, there is no space to
+    /// share, and fill children are laid out with a size of zero on that axis
+    /// instead of an infinite size.
+    pub fn fill(flex: f64) -> Self {
+        FlexParams {
+            fill: true,
+            ..FlexParams::new(flex, None)
         }
     }
 }
@@ -980,6 +1072,7 @@ enum Child {
         widget: WidgetPod<Box<dyn Widget>>,
         alignment: Option<CrossAxisAlignment>,
         flex: f64,
+        fill: bool,
     },
     FixedSpacer(KeyOrValue<f64>, f64),
     FlexedSpacer(f64, f64),
@@ -1368,6 +1461,54 @@ mod tests {
         assert!(image_1 == image_2);
     }
 
+    #[test]
+    fn fill_children_share_space_by_weight() {
+        use crate::testing::{widget_ids, ModularWidget, TestWidgetExt as _};
+
+        // A widget that always takes all the space it's given.
+        fn filler() -> ModularWidget<()> {
+            ModularWidget::new(()).layout_fn(|_, _, bc, _| bc.max())
+        }
+
+        let [id_1, id_2, id_3] = widget_ids();
+        let widget = Flex::row()
+            .with_child_id(SizedBox::empty().width(100.0), id_1)
+            .with_flex_child(filler().with_id(id_2), FlexParams::fill(1.0))
+            .with_flex_child(filler().with_id(id_3), FlexParams::fill(3.0));
+
+        let harness = TestHarness::create_with_size(widget, Size::new(500.0, 100.0));
+
+        let fixed_width = harness.get_widget(id_1).state().layout_rect().width();
+        let fill_1 = harness.get_widget(id_2).state().layout_rect();
+        let fill_2 = harness.get_widget(id_3).state().layout_rect();
+        assert_eq!(fixed_width, 100.0);
+        assert_eq!(fill_1.width(), 100.0);
+        assert_eq!(fill_2.width(), 300.0);
+        assert_eq!(fill_2.x1, 500.0);
+    }
+
+    #[test]
+    fn fill_children_in_unbounded_flex() {
+        use crate::testing::{widget_ids, TestWidgetExt as _};
+        use crate::widget::Portal;
+
+        let [id_1, id_2] = widget_ids();
+        let widget = Portal::new(
+            Flex::column()
+                .with_child_id(SizedBox::empty().height(50.0), id_1)
+                .with_flex_child(
+                    SizedBox::empty().expand_height().with_id(id_2),
+                    FlexParams::fill(1.0),
+                ),
+        );
+
+        let harness = TestHarness::create(widget);
+
+        let fill_rect = harness.get_widget(id_2).state().layout_rect();
+        assert_eq!(fill_rect.height(), 0.0);
+        assert_eq!(fill_rect.y0, 50.0);
+    }
+
     #[test]
     fn get_flex_child() {
         let widget = Flex::column()
@@ -1391,4 +1532,105 @@ mod tests {
 
         // TODO - test out-of-bounds access?
     }
+
+    #[test]
+    fn animated_removal_waits_for_exit() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        use crate::testing::{widget_ids, ModularWidget};
+        use crate::widget::SizedBox;
+
+        let [first_id, exiting_id, last_id] = widget_ids();
+        let removed = Rc::new(Cell::new(false));
+        let exiting = ModularWidget::new(removed.clone())
+            .lifecycle_fn(|removed, _, event, _| {
+                if let LifeCycle::WidgetRemoved = event {
+                    removed.set(true);
+                }
+            })
+            .layout_fn(|_, _, _, _| Size::new(50.0, 20.0));
+        let widget = Flex::column()
+            .with_child_id(SizedBox::empty().width(50.0).height(20.0), first_id)
+            .with_child_id(exiting, exiting_id)
+            .with_child_id(SizedBox::empty().width(50.0).height(20.0), last_id);
+
+        let mut harness = TestHarness::create(widget);
+        harness.edit_root_widget(|mut flex, _| {
+            let mut flex = flex.downcast::<Flex>().unwrap();
+            flex.remove_child_animated(1, Duration::from_millis(100));
+        });
+        harness.render();
+
+        // The exiting child no longer takes space, but is still in the tree.
+        let first_rect = harness.get_widget(first_id).state().layout_rect();
+        let last_rect = harness.get_widget(last_id).state().layout_rect();
+        assert_eq!(last_rect.y0, first_rect.y1);
+        assert!(harness.try_get_widget(exiting_id).is_some());
+
+        // The last child moved into the exiting child's rect, and receives
+        // the pointer there.
+        let exiting_rect = harness.get_widget(exiting_id).state().window_layout_rect();
+        assert!(exiting_rect.contains(
+            harness
+                .get_widget(last_id)
+                .state()
+                .window_layout_rect()
+                .center()
+        ));
+        harness.mouse_move_to(last_id);
+        assert!(harness.get_widget(last_id).state().is_hot);
+        assert!(!harness.get_widget(exiting_id).state().is_hot);
+
+        harness.run_frames(3);
+        assert!(harness.try_get_widget(exiting_id).is_some());
+        assert!(!removed.get());
+
+        // Frames are 16ms apart, so the exit is over after 7 frames.
+        harness.run_frames(4);
+        assert!(harness.try_get_widget(exiting_id).is_none());
+        assert!(removed.get());
+    }
+
+    #[test]
+    fn removing_focused_child() {
+        use druid_shell::MouseButton;
+
+        use crate::testing::{widget_ids, ModularWidget};
+
+        let [focused_id, other_id] = widget_ids();
+        let focusable = || {
+            ModularWidget::new(())
+                .event_fn(|_, ctx, event, _| {
+                    if let Event::MouseDown(_) = event {
+                        ctx.request_focus();
+                        ctx.set_active(true);
+                    }
+                })
+                .lifecycle_fn(|_, ctx, event, _| {
+                    if let LifeCycle::BuildFocusChain = event {
+                        ctx.register_for_focus();
+                    }
+                })
+                .layout_fn(|_, _, _, _| Size::new(50.0, 20.0))
+        };
+        let widget = Flex::column()
+            .with_child_id(focusable(), focused_id)
+            .with_child_id(focusable(), other_id);
+
+        let mut harness = TestHarness::create(widget);
+        harness.mouse_move_to(focused_id);
+        harness.mouse_button_press(MouseButton::Left);
+        assert_eq!(harness.focused_widget().map(|w| w.id()), Some(focused_id));
+        assert!(harness.root_widget().state().has_active);
+
+        harness.edit_root_widget(|mut flex, _| {
+            let mut flex = flex.downcast::<Flex>().unwrap();
+            flex.remove_child(0);
+        });
+
+        assert!(harness.focused_widget().is_none());
+        assert!(!harness.root_widget().state().has_focus);
+        assert!(!harness.root_widget().state().has_active);
+    }
 }
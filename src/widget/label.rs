@@ -305,7 +305,7 @@ mod tests {
 
     use super::*;
     use crate::assert_render_snapshot;
-    use crate::testing::TestHarness;
+    use crate::testing::{widget_ids, TestHarness};
     use crate::theme::{PRIMARY_DARK, PRIMARY_LIGHT};
     use crate::widget::{Flex, SizedBox};
 
@@ -319,6 +319,27 @@ mod tests {
         assert_render_snapshot!(harness, "hello");
     }
 
+    #[test]
+    fn text_size_scale_relayouts_label() {
+        let [label_id] = widget_ids();
+        let widget = Flex::column().with_child_id(Label::new("Hello"), label_id);
+
+        let mut harness = TestHarness::create(widget);
+        let old_size = harness.get_widget(label_id).state().layout_rect().size();
+
+        harness.update_env(|env| env.set(crate::theme::TEXT_SIZE_SCALE, 2.0));
+        let new_size = harness.get_widget(label_id).state().layout_rect().size();
+
+        assert!(
+            new_size.height > old_size.height * 1.5,
+            "{old_size:?} -> {new_size:?}"
+        );
+        assert!(
+            new_size.width > old_size.width * 1.5,
+            "{old_size:?} -> {new_size:?}"
+        );
+    }
+
     #[test]
     fn styled_label() {
         let label = Label::new("The quick brown fox jumps over the lazy dog")
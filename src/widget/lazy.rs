@@ -0,0 +1,220 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! A widget that builds its child the first time it's shown.
+
+use smallvec::{smallvec, SmallVec};
+use tracing::{trace_span, Span};
+
+use crate::widget::{StoreInWidgetMut, WidgetMut, WidgetRef};
+use crate::{
+    BoxConstraints, Env, Event, EventCtx, InternalLifeCycle, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, Size, StatusChange, Widget, WidgetPod,
+};
+
+/// A widget that defers building its child until it's first shown.
+///
+/// Until then, it takes the size of its placeholder, and paints nothing. The
+/// child is built once the `Lazy` is visible in the window, ie laid out, not
+/// stashed, and not scrolled or clipped away; see
+/// [`WidgetState::is_effectively_visible`](crate::WidgetState::is_effectively_visible).
+/// A `Lazy` in a tab or section that's never shown never builds its child.
+///
+/// The child can also be built right away with [`LazyMut::build`], eg when
+/// a tab is activated.
+pub struct Lazy<W: Widget> {
+    builder: Option<Box<dyn FnOnce() -> W>>,
+    child: Option<WidgetPod<W>>,
+    placeholder_size: Size,
+}
+
+crate::declare_widget!(LazyMut, Lazy<W: (Widget)>);
+
+impl<W: Widget> Lazy<W> {
+    /// Create a widget that calls `builder` to build its child when it's
+    /// first shown.
+    pub fn new(builder: impl FnOnce() -> W + 'static) -> Self {
+        Lazy {
+            builder: Some(Box::new(builder)),
+            child: None,
+            placeholder_size: Size::ZERO,
+        }
+    }
+
+    /// Builder-style method to set the size this widget takes before the
+    /// child is built.
+    ///
+    /// The size is constrained by the layout constraints. The default is
+    /// [`Size::ZERO`], which keeps the widget from ever being visible unless
+    /// the constraints force a minimum size.
+    pub fn placeholder_size(mut self, size: Size) -> Self {
+        self.placeholder_size = size;
+        self
+    }
+
+    /// Whether the child was built.
+    pub fn is_built(&self) -> bool {
+        self.child.is_some()
+    }
+
+    /// The child, if it was built.
+    pub fn child(&self) -> Option<WidgetRef<'_, W>> {
+        self.child.as_ref().map(|child| child.as_ref())
+    }
+
+    /// Build the child, returning `true` if it wasn't built yet.
+    fn build_child(&mut self) -> bool {
+        match self.builder.take() {
+            Some(builder) => {
+                self.child = Some(WidgetPod::new(builder()));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<'a, 'b, W: Widget> LazyMut<'a, 'b, W> {
+    /// Build the child now, if it isn't built yet.
+    pub fn build(&mut self) {
+        if self.1.build_child() {
+            self.0.children_changed();
+        }
+    }
+
+    /// The child, if it was built.
+    pub fn child_mut(&mut self) -> Option<WidgetMut<'_, 'b, W>>
+    where
+        W: StoreInWidgetMut,
+    {
+        let child = self.1.child.as_mut()?;
+        Some(self.0.get_mut(child))
+    }
+}
+
+impl<W: Widget> Widget for Lazy<W> {
+    fn on_event(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) {
+        if let Event::AnimFrame(_) = event {
+            if self.build_child() {
+                ctx.children_changed();
+                return;
+            }
+        }
+        if let Some(child) = &mut self.child {
+            child.on_event(ctx, event, env);
+        }
+    }
+
+    fn on_status_change(&mut self, _ctx: &mut LifeCycleCtx, _event: &StatusChange, _env: &Env) {}
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, env: &Env) {
+        if let LifeCycle::Internal(InternalLifeCycle::ParentWindowOrigin) = event {
+            // Our window position and clip were just updated. The child is
+            // built in the next frame, outside of this pass.
+            if self.child.is_none() && ctx.widget_state.is_effectively_visible() {
+                ctx.request_anim_frame();
+            }
+        }
+        if let Some(child) = &mut self.child {
+            child.lifecycle(ctx, event, env);
+        }
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, env: &Env) -> Size {
+        match &mut self.child {
+            Some(child) => {
+                let size = child.layout(ctx, bc, env);
+                ctx.place_child(child, Point::ORIGIN, env);
+                size
+            }
+            None => bc.constrain(self.placeholder_size),
+        }
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, env: &Env) {
+        if let Some(child) = &mut self.child {
+            child.paint(ctx, env);
+        }
+    }
+
+    fn children(&self) -> SmallVec<[WidgetRef<'_, dyn Widget>; 16]> {
+        match &self.child {
+            Some(child) => smallvec![child.as_dyn()],
+            None => SmallVec::new(),
+        }
+    }
+
+    fn make_trace_span(&self) -> Span {
+        trace_span!("Lazy")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::testing::{widget_ids, TestHarness};
+    use crate::widget::{Flex, Portal, SizedBox};
+    use crate::Vec2;
+
+    #[test]
+    fn child_built_once_scrolled_into_view() {
+        let [lazy_id] = widget_ids();
+        let built = Rc::new(Cell::new(false));
+
+        let builder = {
+            let built = built.clone();
+            move || {
+                built.set(true);
+                SizedBox::empty().width(100.0).height(50.0)
+            }
+        };
+        let lazy = Lazy::new(builder).placeholder_size(Size::new(100.0, 50.0));
+        let list = Flex::column()
+            .with_child(SizedBox::empty().width(100.0).height(200.0))
+            .with_child_id(lazy, lazy_id);
+        let widget = Portal::new(list);
+        let mut harness = TestHarness::create_with_size(widget, Size::new(100.0, 100.0));
+
+        // Below the viewport, the child isn't built.
+        harness.run_frames(2);
+        assert!(!built.get());
+        assert_eq!(harness.get_widget(lazy_id).children().len(), 0);
+
+        harness.mouse_move((50.0, 50.0));
+        harness.mouse_wheel(Vec2::new(0.0, 150.0));
+        harness.run_frames(1);
+        assert!(built.get());
+        assert_eq!(harness.get_widget(lazy_id).children().len(), 1);
+    }
+
+    #[test]
+    fn build_on_demand() {
+        let [lazy_id] = widget_ids();
+        let built = Rc::new(Cell::new(false));
+        let builder = {
+            let built = built.clone();
+            move || {
+                built.set(true);
+                SizedBox::empty()
+            }
+        };
+        // A zero-size placeholder is never visible.
+        let widget = Flex::column().with_child_id(Lazy::new(builder), lazy_id);
+        let mut harness = TestHarness::create(widget);
+        harness.run_frames(1);
+        assert!(!built.get());
+
+        harness.edit_root_widget(|mut flex, _| {
+            let mut flex = flex.downcast::<Flex>().unwrap();
+            let mut lazy = flex.child_mut(0).unwrap();
+            lazy.downcast::<Lazy<SizedBox>>().unwrap().build();
+        });
+        assert!(built.get());
+        let lazy = harness.get_widget(lazy_id);
+        assert!(lazy.downcast::<Lazy<SizedBox>>().unwrap().is_built());
+    }
+}
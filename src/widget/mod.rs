@@ -17,10 +17,13 @@ mod tests;
 mod align;
 mod button;
 mod checkbox;
+mod exiting;
 mod flex;
 mod image;
 mod label;
+mod lazy;
 mod portal;
+mod radio_group;
 mod scroll_bar;
 mod sized_box;
 mod spinner;
@@ -31,15 +34,19 @@ mod web_image;
 pub use align::Align;
 pub use button::Button;
 pub use checkbox::Checkbox;
+pub use exiting::ExitingChildren;
 pub use flex::{Axis, CrossAxisAlignment, Flex, FlexParams, MainAxisAlignment};
 pub use label::{Label, LineBreaking};
-pub use portal::Portal;
+pub use lazy::Lazy;
+pub use portal::{OverscrollBehavior, Portal};
+pub use radio_group::RadioGroup;
 pub use scroll_bar::ScrollBar;
 pub use sized_box::SizedBox;
 pub use spinner::Spinner;
 pub use split::Split;
 pub use textbox::TextBox;
 pub use web_image::WebImage;
+pub(crate) use widget::layout_preferred_size;
 pub use widget::StoreInWidgetMut;
 #[doc(hidden)]
 pub use widget::{Widget, WidgetId};
@@ -49,7 +56,8 @@ pub use widget::{Widget, WidgetId};
 pub use widget_mut::WidgetMut;
 pub use widget_pod::WidgetPod;
 pub use widget_ref::WidgetRef;
-pub use widget_state::WidgetState;
+pub(crate) use widget_state::DiffedState;
+pub use widget_state::{StateDelta, WidgetState};
 
 pub use self::image::Image;
 
@@ -5,7 +5,9 @@
 #![allow(missing_docs)]
 
 use std::ops::Range;
+use std::time::Duration;
 
+use druid_shell::TimerToken;
 use smallvec::{smallvec, SmallVec};
 use tracing::{trace_span, Span};
 
@@ -14,7 +16,7 @@ use crate::widget::scroll_bar::SCROLLBAR_MOVED;
 use crate::widget::{Axis, ScrollBar, StoreInWidgetMut, WidgetMut, WidgetRef};
 use crate::{
     BoxConstraints, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx,
-    RenderContext, StatusChange, Widget, WidgetPod,
+    PanAlignment, RenderContext, StatusChange, Widget, WidgetPod,
 };
 
 // TODO - refactor - see issue #15
@@ -28,6 +30,9 @@ pub struct Portal<W: Widget> {
     // on re-layouts
     // TODO - rename
     viewport_pos: Point,
+    /// The size the viewport scrolls over: the child's laid-out size, or its
+    /// size hint if that's larger.
+    scroll_extent: Size,
     // TODO - test how it looks like
     constrain_horizontal: bool,
     constrain_vertical: bool,
@@ -36,15 +41,43 @@ pub struct Portal<W: Widget> {
     scrollbar_horizontal_visible: bool,
     scrollbar_vertical: WidgetPod<ScrollBar>,
     scrollbar_vertical_visible: bool,
+    /// If set, hot changes are suppressed while scrolling, until no wheel
+    /// event was received for this long.
+    hover_settle_delay: Option<Duration>,
+    settle_timer: Option<TimerToken>,
+    scroll_horizontal: bool,
+    scroll_vertical: bool,
+    overscroll_behavior: OverscrollBehavior,
+    /// How far the content is pulled past its edges, on top of `viewport_pos`.
+    overscroll: Vec2,
 }
 
 crate::declare_widget!(PortalMut, Portal<W: (Widget)>);
 
+/// What a [`Portal`] does when it's scrolled past the edges of its content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverscrollBehavior {
+    /// The viewport stops at the edges.
+    #[default]
+    Clamp,
+    /// The content follows the scroll past the edges, with some resistance,
+    /// then springs back once scrolling stops.
+    Bounce,
+}
+
+/// How much of the scroll past the edges the content follows, when bouncing.
+const OVERSCROLL_RESISTANCE: f64 = 0.5;
+/// The largest overscroll, as a fraction of the viewport size.
+const MAX_OVERSCROLL_FRACTION: f64 = 0.25;
+/// The time it takes an overscroll to shrink by a factor of e.
+const BOUNCE_TIME_CONSTANT: f64 = 0.1;
+
 impl<W: Widget> Portal<W> {
     pub fn new(child: W) -> Self {
         Portal {
             child: WidgetPod::new(child),
             viewport_pos: Point::ORIGIN,
+            scroll_extent: Size::ZERO,
             constrain_horizontal: false,
             constrain_vertical: false,
             must_fill: false,
@@ -53,6 +86,12 @@ impl<W: Widget> Portal<W> {
             scrollbar_horizontal_visible: false,
             scrollbar_vertical: WidgetPod::new(ScrollBar::new(Axis::Vertical, 1.0, 1.0)),
             scrollbar_vertical_visible: false,
+            hover_settle_delay: None,
+            settle_timer: None,
+            scroll_horizontal: true,
+            scroll_vertical: true,
+            overscroll_behavior: OverscrollBehavior::Clamp,
+            overscroll: Vec2::ZERO,
         }
     }
 
@@ -101,6 +140,88 @@ impl<W: Widget> Portal<W> {
         self.must_fill = must_fill;
         self
     }
+
+    /// Builder-style method to stop hover updates while the content scrolls.
+    ///
+    /// Widgets passing under a stationary pointer then don't flicker between
+    /// hot and not hot. Hover resumes once no wheel event was received for
+    /// `settle_delay`, or when the pointer moves. See
+    /// [`EventCtx::suppress_hot_changes`].
+    pub fn suppress_hover_while_scrolling(mut self, settle_delay: Duration) -> Self {
+        self.hover_settle_delay = Some(settle_delay);
+        self
+    }
+
+    /// Builder-style method to set whether the user can scroll horizontally.
+    ///
+    /// The default is `true`. When `false`, the horizontal part of wheel
+    /// events is ignored and the horizontal scrollbar is hidden. The viewport
+    /// can still be moved with [`PortalMut::set_viewport_pos`].
+    ///
+    /// The ignored part isn't chained to an enclosing portal: wheel events
+    /// reach enclosing portals before this one, and each of them scrolls
+    /// along the axes it allows, whether or not this one does.
+    pub fn horizontal_scroll(mut self, enabled: bool) -> Self {
+        self.scroll_horizontal = enabled;
+        self
+    }
+
+    /// Builder-style method to set whether the user can scroll vertically.
+    ///
+    /// The default is `true`. See [`horizontal_scroll`](Self::horizontal_scroll).
+    pub fn vertical_scroll(mut self, enabled: bool) -> Self {
+        self.scroll_vertical = enabled;
+        self
+    }
+
+    /// Builder-style method to set what happens when scrolling past the edges
+    /// of the content.
+    ///
+    /// The default is [`OverscrollBehavior::Clamp`].
+    pub fn overscroll(mut self, behavior: OverscrollBehavior) -> Self {
+        self.overscroll_behavior = behavior;
+        self
+    }
+}
+
+/// Apply a scroll `delta` along one axis, returning the new viewport position
+/// and overscroll.
+///
+/// An overscroll is taken back before the viewport moves the other way.
+fn scroll_axis(
+    pos: f64,
+    overscroll: f64,
+    delta: f64,
+    max_pos: f64,
+    max_overscroll: f64,
+    bounce: bool,
+) -> (f64, f64) {
+    let mut overscroll = overscroll;
+    let mut delta = delta;
+    if overscroll * delta < 0.0 {
+        let taken = delta.abs().min(overscroll.abs()).copysign(delta);
+        overscroll += taken;
+        delta -= taken;
+    }
+    let target = pos + delta;
+    let new_pos = target.clamp(0.0, max_pos.max(0.0));
+    if bounce {
+        overscroll = (overscroll + (target - new_pos) * OVERSCROLL_RESISTANCE)
+            .clamp(-max_overscroll, max_overscroll);
+    }
+    (new_pos, overscroll)
+}
+
+/// The scrollbar progress of each axis, between 0 and 1.
+///
+/// An axis along which the content fits in the portal has a progress of 0.
+fn scroll_progress(viewport_pos: Point, portal_size: Size, content_size: Size) -> Vec2 {
+    let scroll_range = content_size - portal_size;
+    let progress = |pos: f64, range: f64| if range > 0.0 { pos / range } else { 0.0 };
+    Vec2::new(
+        progress(viewport_pos.x, scroll_range.width),
+        progress(viewport_pos.y, scroll_range.height),
+    )
 }
 
 fn compute_pan_range(mut viewport: Range<f64>, target: Range<f64>) -> Range<f64> {
@@ -129,6 +250,40 @@ fn compute_pan_range(mut viewport: Range<f64>, target: Range<f64>) -> Range<f64>
     viewport
 }
 
+fn compute_aligned_pan_range(
+    viewport: Range<f64>,
+    target: Range<f64>,
+    alignment: PanAlignment,
+) -> Range<f64> {
+    let viewport_width = viewport.end - viewport.start;
+    let start = match alignment {
+        PanAlignment::Nearest => return compute_pan_range(viewport, target),
+        PanAlignment::Start => target.start,
+        PanAlignment::Center => (target.start + target.end - viewport_width) / 2.0,
+        PanAlignment::End => target.end - viewport_width,
+    };
+    start..(start + viewport_width)
+}
+
+/// Compute the viewport position that makes `target` visible.
+///
+/// The result may be out of bounds; it's clamped by `set_viewport_pos_raw`.
+fn compute_pan_pos(viewport: Rect, target: Rect, alignment: PanAlignment) -> Point {
+    let new_pos_x = compute_aligned_pan_range(
+        viewport.min_x()..viewport.max_x(),
+        target.min_x()..target.max_x(),
+        alignment,
+    )
+    .start;
+    let new_pos_y = compute_aligned_pan_range(
+        viewport.min_y()..viewport.max_y(),
+        target.min_y()..target.max_y(),
+        alignment,
+    )
+    .start;
+    Point::new(new_pos_x, new_pos_y)
+}
+
 impl<W: Widget> Portal<W> {
     // TODO - rename
     fn set_viewport_pos_raw(&mut self, portal_size: Size, content_size: Size, pos: Point) -> bool {
@@ -146,6 +301,46 @@ impl<W: Widget> Portal<W> {
             false
         }
     }
+
+    /// Scroll by a user-provided `delta`, ignoring disabled axes.
+    fn scroll_by(&mut self, portal_size: Size, content_size: Size, delta: Vec2) {
+        let bounce = self.overscroll_behavior == OverscrollBehavior::Bounce;
+        let max_pos = content_size - portal_size;
+        let max_overscroll = portal_size * MAX_OVERSCROLL_FRACTION;
+        let mut pos = self.viewport_pos;
+        if self.scroll_horizontal {
+            (pos.x, self.overscroll.x) = scroll_axis(
+                pos.x,
+                self.overscroll.x,
+                delta.x,
+                max_pos.width,
+                max_overscroll.width,
+                bounce,
+            );
+        }
+        if self.scroll_vertical {
+            (pos.y, self.overscroll.y) = scroll_axis(
+                pos.y,
+                self.overscroll.y,
+                delta.y,
+                max_pos.height,
+                max_overscroll.height,
+                bounce,
+            );
+        }
+        self.set_viewport_pos_raw(portal_size, content_size, pos);
+    }
+
+    /// Shrink the overscroll after `interval` nanoseconds, returning whether
+    /// some is left.
+    fn settle_overscroll(&mut self, interval: u64) -> bool {
+        let decay = (-(interval as f64 * 1e-9) / BOUNCE_TIME_CONSTANT).exp();
+        self.overscroll *= decay;
+        if self.overscroll.hypot() < 0.5 {
+            self.overscroll = Vec2::ZERO;
+        }
+        self.overscroll != Vec2::ZERO
+    }
 }
 
 impl<'a, 'b, W: Widget> PortalMut<'a, 'b, W> {
@@ -198,62 +393,95 @@ impl<'a, 'b, W: Widget> PortalMut<'a, 'b, W> {
 
     pub fn set_viewport_pos(&mut self, position: Point) -> bool {
         let portal_size = self.0.widget_state.layout_rect().size();
-        let content_size = self.1.child.layout_rect().size();
+        let content_size = self.1.scroll_extent;
 
         let pos_changed = self
             .1
             .set_viewport_pos_raw(portal_size, content_size, position);
         if pos_changed {
-            let progress_x = self.1.viewport_pos.x / (content_size - portal_size).width;
+            let progress = scroll_progress(self.1.viewport_pos, portal_size, content_size);
             self.horizontal_scrollbar_mut()
-                .set_cursor_progress(progress_x);
-            let progress_y = self.1.viewport_pos.y / (content_size - portal_size).height;
+                .set_cursor_progress(progress.x);
             self.vertical_scrollbar_mut()
-                .set_cursor_progress(progress_y);
+                .set_cursor_progress(progress.y);
             self.0.request_layout();
         }
         pos_changed
     }
 
+    /// Set whether the user can scroll horizontally.
+    ///
+    /// See [`Portal::horizontal_scroll`].
+    pub fn set_horizontal_scroll(&mut self, enabled: bool) {
+        self.1.scroll_horizontal = enabled;
+        self.0.request_layout();
+    }
+
+    /// Set whether the user can scroll vertically.
+    ///
+    /// See [`Portal::vertical_scroll`].
+    pub fn set_vertical_scroll(&mut self, enabled: bool) {
+        self.1.scroll_vertical = enabled;
+        self.0.request_layout();
+    }
+
+    /// Set what happens when scrolling past the edges of the content.
+    ///
+    /// See [`Portal::overscroll`].
+    pub fn set_overscroll(&mut self, behavior: OverscrollBehavior) {
+        self.1.overscroll_behavior = behavior;
+        if behavior == OverscrollBehavior::Clamp {
+            self.1.overscroll = Vec2::ZERO;
+        }
+        self.0.request_layout();
+    }
+
     pub fn pan_viewport_by(&mut self, translation: Vec2) -> bool {
         self.set_viewport_pos(self.1.viewport_pos + translation)
     }
 
     // Note - Rect is in child coordinates
     pub fn pan_viewport_to(&mut self, target: Rect) -> bool {
-        let viewport = Rect::from_origin_size(self.1.viewport_pos, self.0.widget_state.size);
-
-        let new_pos_x = compute_pan_range(
-            viewport.min_x()..viewport.max_x(),
-            target.min_x()..target.max_x(),
-        )
-        .start;
-        let new_pos_y = compute_pan_range(
-            viewport.min_y()..viewport.max_y(),
-            target.min_y()..target.max_y(),
-        )
-        .start;
+        self.pan_viewport_to_aligned(target, PanAlignment::Nearest)
+    }
 
-        self.set_viewport_pos(Point::new(new_pos_x, new_pos_y))
+    // Note - Rect is in child coordinates
+    pub fn pan_viewport_to_aligned(&mut self, target: Rect, alignment: PanAlignment) -> bool {
+        let viewport = Rect::from_origin_size(self.1.viewport_pos, self.0.widget_state.size);
+        self.set_viewport_pos(compute_pan_pos(viewport, target, alignment))
     }
 }
 
 impl<W: Widget> Widget for Portal<W> {
     fn on_event(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) {
         let portal_size = ctx.size();
-        let content_size = self.child.layout_rect().size();
+        let content_size = self.scroll_extent;
 
         // TODO - handle Home/End keys, etc
         match event {
             Event::Wheel(wheel_event) => {
-                self.set_viewport_pos_raw(
-                    portal_size,
-                    content_size,
-                    self.viewport_pos + wheel_event.wheel_delta,
-                );
-                // TODO - horizontal scrolling?
+                if let Some(delay) = self.hover_settle_delay {
+                    ctx.suppress_hot_changes();
+                    self.settle_timer = Some(ctx.request_timer(delay));
+                }
+                self.scroll_by(portal_size, content_size, wheel_event.wheel_delta);
+                if self.overscroll != Vec2::ZERO {
+                    ctx.request_anim_frame();
+                }
+                let progress = scroll_progress(self.viewport_pos, portal_size, content_size);
+                ctx.get_mut(&mut self.scrollbar_horizontal)
+                    .set_cursor_progress(progress.x);
                 ctx.get_mut(&mut self.scrollbar_vertical)
-                    .set_cursor_progress(self.viewport_pos.y / (content_size - portal_size).height);
+                    .set_cursor_progress(progress.y);
+            }
+            Event::AnimFrame(interval) if self.overscroll != Vec2::ZERO => {
+                if self.settle_overscroll(*interval) {
+                    ctx.request_anim_frame();
+                }
+            }
+            Event::Timer(token) if self.settle_timer == Some(*token) => {
+                self.settle_timer = None;
+                ctx.resume_hot_changes();
             }
             Event::Notification(notif) => {
                 if let Some((axis, progress)) = notif.try_get(SCROLLBAR_MOVED) {
@@ -282,8 +510,24 @@ impl<W: Widget> Widget for Portal<W> {
             LifeCycle::WidgetAdded => {
                 ctx.register_as_portal();
             }
-            //TODO
-            //LifeCycle::RequestPanToChild(target_rect) => {}
+            LifeCycle::RequestPanToChild(target_rect, alignment) => {
+                let portal_size = ctx.size();
+                let content_rect = self.child.layout_rect();
+                // The target is in our coordinates, we need it in the child's.
+                let target_rect = *target_rect - content_rect.origin().to_vec2();
+                let viewport = Rect::from_origin_size(self.viewport_pos, portal_size);
+
+                let new_pos = compute_pan_pos(viewport, target_rect, *alignment);
+                if self.set_viewport_pos_raw(portal_size, self.scroll_extent, new_pos) {
+                    let progress =
+                        scroll_progress(self.viewport_pos, portal_size, self.scroll_extent);
+                    ctx.get_mut(&mut self.scrollbar_horizontal)
+                        .set_cursor_progress(progress.x);
+                    ctx.get_mut(&mut self.scrollbar_vertical)
+                        .set_cursor_progress(progress.y);
+                    ctx.request_layout();
+                }
+            }
             _ => {}
         }
 
@@ -304,7 +548,16 @@ impl<W: Widget> Widget for Portal<W> {
 
         let child_bc = BoxConstraints::new(min_child_size, max_child_size);
 
-        let content_size = self.child.layout(ctx, &child_bc, env);
+        let laid_out_size = self.child.layout(ctx, &child_bc, env);
+        // The child may only lay out what's visible, and estimate the rest.
+        let content_size = match self.child.widget().size_hint(&child_bc, env) {
+            Some(hint) => Size::new(
+                hint.width.max(laid_out_size.width),
+                hint.height.max(laid_out_size.height),
+            ),
+            None => laid_out_size,
+        };
+        self.scroll_extent = content_size;
         let portal_size = bc.constrain(content_size);
 
         // TODO - document better
@@ -312,12 +565,15 @@ impl<W: Widget> Widget for Portal<W> {
         self.set_viewport_pos_raw(portal_size, content_size, self.viewport_pos);
         // TODO - recompute portal progress
 
-        ctx.place_child(&mut self.child, Point::new(0.0, -self.viewport_pos.y), env);
+        let content_origin = Point::ORIGIN - (self.viewport_pos.to_vec2() + self.overscroll);
+        ctx.place_child(&mut self.child, content_origin, env);
 
-        self.scrollbar_horizontal_visible =
-            !self.constrain_horizontal && portal_size.width < content_size.width;
-        self.scrollbar_vertical_visible =
-            !self.constrain_vertical && portal_size.height < content_size.height;
+        self.scrollbar_horizontal_visible = self.scroll_horizontal
+            && !self.constrain_horizontal
+            && portal_size.width < content_size.width;
+        self.scrollbar_vertical_visible = self.scroll_vertical
+            && !self.constrain_vertical
+            && portal_size.height < content_size.height;
 
         if self.scrollbar_horizontal_visible {
             self.scrollbar_horizontal.widget_mut().portal_size = portal_size.width;
@@ -381,8 +637,9 @@ mod tests {
 
     use super::*;
     use crate::assert_render_snapshot;
-    use crate::testing::{widget_ids, TestHarness};
-    use crate::widget::{Button, Flex, SizedBox};
+    use crate::testing::{widget_ids, ModularWidget, TestHarness, TestWidgetExt as _};
+    use crate::widget::{layout_preferred_size, Button, Flex, SizedBox};
+    use crate::{Selector, Target, WidgetId};
 
     fn button(text: &str) -> impl Widget {
         SizedBox::new(Button::new(text)).width(70.0).height(40.0)
@@ -453,6 +710,119 @@ mod tests {
         assert_render_snapshot!(harness, "button_list_scroll_to_item_13");
     }
 
+    /// A list of 20px rows that only lays out the first few, and estimates
+    /// its full height.
+    struct EstimatedRows {
+        laid_out: usize,
+        total: usize,
+    }
+
+    impl Widget for EstimatedRows {
+        fn on_event(&mut self, _ctx: &mut EventCtx, _event: &Event, _env: &Env) {}
+
+        fn on_status_change(&mut self, _: &mut LifeCycleCtx, _: &StatusChange, _: &Env) {}
+
+        fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _env: &Env) {}
+
+        fn paint(&mut self, _ctx: &mut PaintCtx, _env: &Env) {}
+
+        fn children(&self) -> SmallVec<[WidgetRef<'_, dyn Widget>; 16]> {
+            SmallVec::new()
+        }
+
+        fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, env: &Env) -> Size {
+            layout_preferred_size(self, bc, env)
+        }
+
+        fn preferred_size(&self, _bc: &BoxConstraints, _env: &Env) -> Option<Size> {
+            Some(Size::new(100.0, self.laid_out as f64 * 20.0))
+        }
+
+        fn size_hint(&self, _bc: &BoxConstraints, _env: &Env) -> Option<Size> {
+            Some(Size::new(100.0, self.total as f64 * 20.0))
+        }
+    }
+
+    #[test]
+    fn size_hint_sizes_scrollbar() {
+        let rows = EstimatedRows {
+            laid_out: 10,
+            total: 1000,
+        };
+        let mut harness = TestHarness::create_with_size(Portal::new(rows), Size::new(200., 200.));
+
+        let portal = harness.root_widget();
+        let portal = portal.downcast::<Portal<EstimatedRows>>().unwrap();
+        assert!(portal.scrollbar_vertical_visible);
+        assert_eq!(portal.scrollbar_vertical.widget().content_size, 20000.0);
+
+        // The viewport scrolls over the whole estimate.
+        harness.edit_root_widget(|mut portal, _| {
+            let mut portal = portal.downcast::<Portal<EstimatedRows>>().unwrap();
+            portal.set_viewport_pos(Point::new(0.0, 50000.0))
+        });
+        let portal = harness.root_widget();
+        let portal = portal.downcast::<Portal<EstimatedRows>>().unwrap();
+        assert_eq!(portal.get_viewport_pos().y, 19800.0);
+    }
+
+    const PAN_TO: Selector<(WidgetId, PanAlignment)> = Selector::new("masonry-test.pan-to");
+
+    fn pan_target() -> ModularWidget<()> {
+        ModularWidget::new(())
+            .event_fn(|_, ctx, event, _| {
+                if let Event::Command(cmd) = event {
+                    if let Some((id, alignment)) = cmd.try_get(PAN_TO) {
+                        if *id == ctx.widget_id() {
+                            ctx.request_pan_to_this_aligned(*alignment);
+                        }
+                    }
+                }
+            })
+            .layout_fn(|_, _, _, _| Size::new(100.0, 50.0))
+    }
+
+    /// Pan to the 11th item of a 200px high portal over 20 items of 50px,
+    /// and return the new vertical viewport position.
+    fn pan_to_item_with_alignment(alignment: PanAlignment) -> f64 {
+        let [target_id] = widget_ids();
+        let mut column = Flex::column();
+        for idx in 0..20 {
+            column = if idx == 10 {
+                column.with_child_id(pan_target(), target_id)
+            } else {
+                column.with_child(pan_target())
+            };
+        }
+
+        let mut harness = TestHarness::create_with_size(Portal::new(column), Size::new(200., 200.));
+        harness.submit_command(PAN_TO.with((target_id, alignment)).to(Target::Global));
+
+        let portal = harness.root_widget();
+        let portal = portal.downcast::<Portal<Flex>>().unwrap();
+        portal.get_viewport_pos().y
+    }
+
+    #[test]
+    fn request_pan_to_nearest() {
+        assert_eq!(pan_to_item_with_alignment(PanAlignment::Nearest), 350.0);
+    }
+
+    #[test]
+    fn request_pan_to_start() {
+        assert_eq!(pan_to_item_with_alignment(PanAlignment::Start), 500.0);
+    }
+
+    #[test]
+    fn request_pan_to_center() {
+        assert_eq!(pan_to_item_with_alignment(PanAlignment::Center), 425.0);
+    }
+
+    #[test]
+    fn request_pan_to_end() {
+        assert_eq!(pan_to_item_with_alignment(PanAlignment::End), 350.0);
+    }
+
     // Helper function for panning tests
     fn make_range(repr: &str) -> Range<f64> {
         let repr = &repr[repr.find('_').unwrap()..];
@@ -563,4 +933,140 @@ mod tests {
 
         assert_eq!(compute_pan_range(initial_range, target_range), result_range);
     }
+
+    #[test]
+    fn test_pan_aligned_start() {
+        let initial_range = make_range("____xxxx________");
+        let target_range = make_range(" __________xx____");
+        let result_range = make_range(" __________xxxx__");
+
+        assert_eq!(
+            compute_aligned_pan_range(initial_range, target_range, PanAlignment::Start),
+            result_range
+        );
+    }
+
+    #[test]
+    fn test_pan_aligned_center() {
+        let initial_range = make_range("____xxxx________");
+        let target_range = make_range(" __________xx____");
+        let result_range = make_range(" _________xxxx___");
+
+        assert_eq!(
+            compute_aligned_pan_range(initial_range, target_range, PanAlignment::Center),
+            result_range
+        );
+    }
+
+    #[test]
+    fn test_pan_aligned_end() {
+        let initial_range = make_range("__________xxxx__");
+        let target_range = make_range(" ____xx__________");
+        let result_range = make_range(" __xxxx__________");
+
+        assert_eq!(
+            compute_aligned_pan_range(initial_range, target_range, PanAlignment::End),
+            result_range
+        );
+    }
+
+    #[test]
+    fn test_pan_aligned_nearest() {
+        let initial_range = make_range("____xxxx________");
+        let target_range = make_range(" __________xx____");
+        let result_range = make_range(" ________xxxx____");
+
+        assert_eq!(
+            compute_aligned_pan_range(initial_range, target_range, PanAlignment::Nearest),
+            result_range
+        );
+    }
+
+    fn content_origin(harness: &TestHarness, content_id: WidgetId) -> Point {
+        harness
+            .get_widget(content_id)
+            .state()
+            .window_layout_rect()
+            .origin()
+    }
+
+    #[test]
+    fn clamped_overscroll_stops_at_edges() {
+        let [content_id] = widget_ids();
+        let content = SizedBox::empty()
+            .width(400.0)
+            .height(400.0)
+            .with_id(content_id);
+        let widget = Portal::new(content);
+        let mut harness = TestHarness::create_with_size(widget, Size::new(100.0, 100.0));
+        harness.mouse_move((50.0, 50.0));
+
+        harness.mouse_wheel(Vec2::new(0.0, -20.0));
+        assert_eq!(content_origin(&harness, content_id), Point::ORIGIN);
+
+        harness.mouse_wheel(Vec2::new(0.0, 1000.0));
+        assert_eq!(
+            content_origin(&harness, content_id),
+            Point::new(0.0, -300.0)
+        );
+    }
+
+    #[test]
+    fn bouncing_overscroll_springs_back() {
+        let [content_id] = widget_ids();
+        let content = SizedBox::empty()
+            .width(400.0)
+            .height(400.0)
+            .with_id(content_id);
+        let widget = Portal::new(content).overscroll(OverscrollBehavior::Bounce);
+        let mut harness = TestHarness::create_with_size(widget, Size::new(100.0, 100.0));
+        harness.mouse_move((50.0, 50.0));
+
+        // The content follows half of the scroll past the top edge.
+        harness.mouse_wheel(Vec2::new(0.0, -20.0));
+        assert_eq!(content_origin(&harness, content_id), Point::new(0.0, 10.0));
+        harness.run_frames(40);
+        assert_eq!(content_origin(&harness, content_id), Point::ORIGIN);
+
+        // Past the bottom edge, the overscroll is capped at a quarter of the viewport.
+        harness.mouse_wheel(Vec2::new(0.0, 1000.0));
+        assert_eq!(
+            content_origin(&harness, content_id),
+            Point::new(0.0, -325.0)
+        );
+        harness.run_frames(40);
+        assert_eq!(
+            content_origin(&harness, content_id),
+            Point::new(0.0, -300.0)
+        );
+    }
+
+    #[test]
+    fn locked_axis_ignores_wheel() {
+        let [content_id] = widget_ids();
+        let content = SizedBox::empty()
+            .width(400.0)
+            .height(400.0)
+            .with_id(content_id);
+        let widget = Portal::new(content).vertical_scroll(false);
+        let mut harness = TestHarness::create_with_size(widget, Size::new(100.0, 100.0));
+        harness.mouse_move((50.0, 50.0));
+
+        harness.mouse_wheel(Vec2::new(30.0, 30.0));
+        assert_eq!(content_origin(&harness, content_id), Point::new(-30.0, 0.0));
+    }
+
+    #[test]
+    fn vertical_list_keeps_horizontal_progress() {
+        let content = SizedBox::empty().width(100.0).height(400.0);
+        let widget = Portal::new(content);
+        let mut harness = TestHarness::create_with_size(widget, Size::new(100.0, 100.0));
+        harness.mouse_move((50.0, 50.0));
+
+        harness.mouse_wheel(Vec2::new(0.0, 150.0));
+        let portal = harness.root_widget();
+        let portal = portal.downcast::<Portal<SizedBox>>().unwrap();
+        assert_eq!(portal.scrollbar_horizontal.widget().cursor_progress(), 0.0);
+        assert_eq!(portal.scrollbar_vertical.widget().cursor_progress(), 0.5);
+    }
 }
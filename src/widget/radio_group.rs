@@ -0,0 +1,246 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! A container that keeps exactly one of its children selected.
+
+use smallvec::SmallVec;
+use tracing::{trace, trace_span, Span};
+
+use crate::command::{SELECT_RADIO_MEMBER, SET_RADIO_SELECTED};
+use crate::widget::{WidgetMut, WidgetRef};
+use crate::{
+    BoxConstraints, Command, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx,
+    Point, Size, StatusChange, Widget, WidgetPod,
+};
+
+/// A column of mutually exclusive members, such as radio buttons or tabs.
+///
+/// Each member is associated with a value of type `T`. Members take part in
+/// the selection by following a simple protocol:
+///
+/// - When the user picks a member, that member submits a [`SELECT_RADIO_MEMBER`]
+/// notification from its own `on_event`.
+/// - The group then sends a [`SET_RADIO_SELECTED`] command to every member,
+/// with a payload of `true` for the newly selected member and `false` for the others.
+///
+/// The group also sends [`SET_RADIO_SELECTED`] to every member when it is
+/// added to the widget tree, so members don't need to know the initial
+/// selection.
+///
+/// [`SELECT_RADIO_MEMBER`]: crate::command::SELECT_RADIO_MEMBER
+/// [`SET_RADIO_SELECTED`]: crate::command::SET_RADIO_SELECTED
+pub struct RadioGroup<T> {
+    members: Vec<(T, WidgetPod<Box<dyn Widget>>)>,
+    selected: Option<usize>,
+}
+
+crate::declare_widget!(RadioGroupMut, RadioGroup<T: (PartialEq + 'static)>);
+
+impl<T: PartialEq + 'static> RadioGroup<T> {
+    /// Create an empty group with nothing selected.
+    pub fn new() -> Self {
+        RadioGroup {
+            members: Vec::new(),
+            selected: None,
+        }
+    }
+
+    /// Builder-style method to add a member associated with `value`.
+    pub fn with_member(mut self, value: T, member: impl Widget) -> Self {
+        self.members.push((value, WidgetPod::new(Box::new(member))));
+        self
+    }
+
+    /// Builder-style method to set the initially selected value.
+    ///
+    /// If no member is associated with `value`, nothing is selected.
+    pub fn with_selected(mut self, value: &T) -> Self {
+        self.selected = self.index_of(value);
+        self
+    }
+
+    /// Return the value of the selected member, if any.
+    pub fn selected(&self) -> Option<&T> {
+        self.selected.map(|idx| &self.members[idx].0)
+    }
+
+    fn index_of(&self, value: &T) -> Option<usize> {
+        self.members.iter().position(|(v, _)| v == value)
+    }
+
+    /// The commands telling each member whether it is selected.
+    fn selection_commands(&self) -> Vec<Command> {
+        self.members
+            .iter()
+            .enumerate()
+            .map(|(idx, (_, member))| {
+                SET_RADIO_SELECTED
+                    .with(Some(idx) == self.selected)
+                    .to(member.id())
+            })
+            .collect()
+    }
+}
+
+impl<T: PartialEq + 'static> Default for RadioGroup<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, 'b, T: PartialEq + 'static> RadioGroupMut<'a, 'b, T> {
+    /// Add a member associated with `value`.
+    pub fn add_member(&mut self, value: T, member: impl Widget) {
+        self.1
+            .members
+            .push((value, WidgetPod::new(Box::new(member))));
+        self.0.children_changed();
+    }
+
+    /// Select the member associated with `value`, and notify all members.
+    ///
+    /// If no member is associated with `value`, nothing is selected.
+    pub fn set_selected(&mut self, value: &T) {
+        self.1.selected = self.1.index_of(value);
+        for command in self.1.selection_commands() {
+            self.0.submit_command(command);
+        }
+    }
+
+    /// Get a [`WidgetMut`] to the member at the given index.
+    pub fn member_mut(&mut self, idx: usize) -> WidgetMut<'_, 'b, Box<dyn Widget>> {
+        self.0.get_mut(&mut self.1.members[idx].1)
+    }
+}
+
+impl<T: PartialEq + 'static> Widget for RadioGroup<T> {
+    fn on_event(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) {
+        if let Event::Notification(notification) = event {
+            if notification.is(SELECT_RADIO_MEMBER) {
+                let source = notification.source();
+                let idx = self
+                    .members
+                    .iter()
+                    .position(|(_, member)| member.id() == source);
+                if let Some(idx) = idx {
+                    trace!("RadioGroup {:?} selected member {}", ctx.widget_id(), idx);
+                    if self.selected != Some(idx) {
+                        self.selected = Some(idx);
+                        for command in self.selection_commands() {
+                            ctx.submit_command(command);
+                        }
+                    }
+                    ctx.set_handled();
+                }
+            }
+            return;
+        }
+
+        for (_, member) in &mut self.members {
+            member.on_event(ctx, event, env);
+        }
+    }
+
+    fn on_status_change(&mut self, _ctx: &mut LifeCycleCtx, _event: &StatusChange, _env: &Env) {}
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, env: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            for command in self.selection_commands() {
+                ctx.submit_command(command);
+            }
+        }
+
+        for (_, member) in &mut self.members {
+            member.lifecycle(ctx, event, env);
+        }
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, env: &Env) -> Size {
+        let child_bc = bc.loosen();
+        let mut width: f64 = 0.0;
+        let mut height = 0.0;
+        for (_, member) in &mut self.members {
+            let size = member.layout(ctx, &child_bc, env);
+            ctx.place_child(member, Point::new(0.0, height), env);
+            width = width.max(size.width);
+            height += size.height;
+        }
+
+        let size = bc.constrain(Size::new(width, height));
+        trace!("Computed layout: size={}", size);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, env: &Env) {
+        for (_, member) in &mut self.members {
+            member.paint(ctx, env);
+        }
+    }
+
+    fn children(&self) -> SmallVec<[WidgetRef<'_, dyn Widget>; 16]> {
+        self.members
+            .iter()
+            .map(|(_, member)| member.as_dyn())
+            .collect()
+    }
+
+    fn make_trace_span(&self) -> Span {
+        trace_span!("RadioGroup")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::testing::{ModularWidget, TestHarness};
+
+    fn member(selected: Rc<Cell<bool>>) -> ModularWidget<Rc<Cell<bool>>> {
+        ModularWidget::new(selected)
+            .event_fn(|selected, ctx, event, _| match event {
+                Event::MouseDown(_) => ctx.submit_notification(SELECT_RADIO_MEMBER),
+                Event::Command(command) => {
+                    if let Some(is_selected) = command.try_get(SET_RADIO_SELECTED) {
+                        selected.set(*is_selected);
+                    }
+                }
+                _ => (),
+            })
+            .layout_fn(|_, _, _, _| Size::new(50.0, 20.0))
+    }
+
+    #[test]
+    fn click_selects_exactly_one_member() {
+        let states: Vec<_> = (0..3).map(|_| Rc::new(Cell::new(false))).collect();
+
+        let group = RadioGroup::new()
+            .with_member(0, member(states[0].clone()))
+            .with_member(1, member(states[1].clone()))
+            .with_member(2, member(states[2].clone()))
+            .with_selected(&0);
+
+        let mut harness = TestHarness::create(group);
+        assert!(states[0].get());
+        assert!(!states[1].get());
+        assert!(!states[2].get());
+
+        let member_ids: Vec<_> = harness
+            .root_widget()
+            .children()
+            .iter()
+            .map(|member| member.id())
+            .collect();
+        harness.mouse_click_on(member_ids[2]);
+
+        let selected_count = states.iter().filter(|state| state.get()).count();
+        assert_eq!(selected_count, 1);
+        assert!(states[2].get());
+
+        let group = harness.root_widget();
+        let group = group.downcast::<RadioGroup<i32>>().unwrap();
+        assert_eq!(group.selected(), Some(&2));
+    }
+}
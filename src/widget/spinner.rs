@@ -69,15 +69,15 @@ impl Default for Spinner {
 }
 
 impl Widget for Spinner {
-    fn on_event(&mut self, ctx: &mut EventCtx, event: &Event, _env: &Env) {
-        if let Event::AnimFrame(interval) = event {
-            self.t += (*interval as f64) * 1e-9;
-            if self.t >= 1.0 {
-                self.t = 0.0;
-            }
-            ctx.request_anim_frame();
-            ctx.request_paint();
+    fn on_event(&mut self, _ctx: &mut EventCtx, _event: &Event, _env: &Env) {}
+
+    fn on_anim_frame(&mut self, ctx: &mut EventCtx, interval: u64, _env: &Env) -> bool {
+        self.t += (interval as f64) * 1e-9;
+        if self.t >= 1.0 {
+            self.t = 0.0;
         }
+        ctx.request_paint();
+        true
     }
 
     fn on_status_change(&mut self, _ctx: &mut LifeCycleCtx, _event: &StatusChange, _env: &Env) {}
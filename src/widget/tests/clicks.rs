@@ -0,0 +1,60 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use druid_shell::MouseButton;
+
+use crate::testing::{ModularWidget, TestHarness};
+use crate::*;
+
+/// A widget that records the click count of the mouse presses it receives.
+fn click_counter(counts: Rc<RefCell<Vec<u8>>>) -> impl Widget {
+    ModularWidget::new(counts)
+        .event_fn(|counts, _, event, _| {
+            if let Event::MouseDown(mouse) = event {
+                counts.borrow_mut().push(mouse.count);
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(100.0, 100.0))
+}
+
+fn click(harness: &mut TestHarness, button: MouseButton) {
+    harness.mouse_button_press(button);
+    harness.mouse_button_release(button);
+}
+
+#[test]
+fn multi_click_count() {
+    let counts = Rc::new(RefCell::new(Vec::new()));
+    let mut harness = TestHarness::create(click_counter(counts.clone()));
+    let take_counts = || std::mem::take(&mut *counts.borrow_mut());
+
+    harness.mouse_move((50.0, 50.0));
+    click(&mut harness, MouseButton::Left);
+    click(&mut harness, MouseButton::Left);
+    click(&mut harness, MouseButton::Left);
+    assert_eq!(take_counts(), [1, 2, 3]);
+
+    // Too slow.
+    harness.move_timers_forward(Duration::from_millis(600));
+    click(&mut harness, MouseButton::Left);
+    assert_eq!(take_counts(), [1]);
+
+    // Another button starts a new count.
+    click(&mut harness, MouseButton::Right);
+    click(&mut harness, MouseButton::Left);
+    assert_eq!(take_counts(), [1, 1]);
+
+    // Small moves are tolerated, but not bigger ones, even if the pointer
+    // comes back.
+    harness.mouse_move((52.0, 50.0));
+    click(&mut harness, MouseButton::Left);
+    harness.mouse_move((60.0, 50.0));
+    harness.mouse_move((52.0, 50.0));
+    click(&mut harness, MouseButton::Left);
+    assert_eq!(take_counts(), [2, 1]);
+}
@@ -0,0 +1,251 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use smallvec::SmallVec;
+
+use crate::testing::{ModularWidget, TestHarness, TestWidgetExt as _};
+use crate::widget::{layout_preferred_size, Flex, WidgetRef};
+use crate::*;
+
+const PING: Selector<u32> = Selector::new("masonry-test.ping");
+const SEND_BATCH: Selector = Selector::new("masonry-test.send-batch");
+
+/// A widget that adds the payload of every PING it receives to `total`.
+fn receiver(total: Rc<Cell<u32>>) -> impl Widget {
+    ModularWidget::new(total).event_fn(|total, _ctx, event, _| {
+        if let Event::Command(cmd) = event {
+            if let Some(value) = cmd.try_get(PING) {
+                total.set(total.get() + value);
+            }
+        }
+    })
+}
+
+fn receivers<const N: usize>() -> (Flex, [WidgetId; N], Vec<Rc<Cell<u32>>>) {
+    let ids: [WidgetId; N] = crate::testing::widget_ids();
+    let totals: Vec<_> = (0..N).map(|_| Rc::new(Cell::new(0))).collect();
+    let mut flex = Flex::column();
+    for (id, total) in ids.iter().zip(&totals) {
+        flex = flex.with_child(receiver(total.clone()).with_id(*id));
+    }
+    (flex, ids, totals)
+}
+
+#[test]
+fn batched_commands_reach_their_targets() {
+    let (flex, ids, totals) = receivers::<4>();
+    let mut harness = TestHarness::create(flex);
+
+    harness.submit_commands(vec![
+        PING.with(1).to(ids[0]),
+        PING.with(2).to(ids[2]),
+        PING.with(3).to(ids[2]),
+        PING.with(4).to(ids[3]),
+    ]);
+
+    let totals: Vec<_> = totals.iter().map(|total| total.get()).collect();
+    assert_eq!(totals, [1, 0, 5, 4]);
+}
+
+#[test]
+fn submit_commands_from_widget() {
+    let (flex, ids, totals) = receivers::<3>();
+    let sender = ModularWidget::new(ids).event_fn(|ids, ctx, event, _| {
+        if let Event::Command(cmd) = event {
+            if cmd.is(SEND_BATCH) {
+                ctx.submit_commands(ids.iter().map(|id| PING.with(10).to(*id)).collect());
+            }
+        }
+    });
+    let mut harness = TestHarness::create(Flex::row().with_child(sender).with_child(flex));
+
+    harness.submit_command(SEND_BATCH);
+
+    assert!(totals.iter().all(|total| total.get() == 10));
+}
+
+#[test]
+fn batched_window_and_global_commands() {
+    let (flex, ids, totals) = receivers::<3>();
+    let target = ids[0];
+    let sender = ModularWidget::new(()).event_fn(move |_, ctx, event, _| {
+        if let Event::Command(cmd) = event {
+            if cmd.is(SEND_BATCH) {
+                ctx.submit_commands(vec![
+                    PING.with(1).to(target),
+                    PING.with(10).to(Target::Window(ctx.window_id())),
+                    PING.with(100).to(Target::Global),
+                ]);
+            }
+        }
+    });
+    let mut harness = TestHarness::create(Flex::row().with_child(sender).with_child(flex));
+
+    harness.submit_command(SEND_BATCH);
+
+    // Window and global commands in a batch reach every widget, as if they
+    // were submitted on their own.
+    let totals: Vec<_> = totals.iter().map(|total| total.get()).collect();
+    assert_eq!(totals, [111, 110, 110]);
+}
+
+/// A widget that handles PING in `on_command`, and records whether
+/// `on_event` received a command.
+struct CommandCounter {
+    total: Rc<Cell<u32>>,
+    saw_command_event: Rc<Cell<bool>>,
+}
+
+impl Widget for CommandCounter {
+    fn on_event(&mut self, _ctx: &mut EventCtx, event: &Event, _env: &Env) {
+        if let Event::Command(_) = event {
+            self.saw_command_event.set(true);
+        }
+    }
+
+    fn on_command(&mut self, _ctx: &mut EventCtx, cmd: &Command, _env: &Env) -> Handled {
+        match cmd.try_get(PING) {
+            Some(value) => {
+                self.total.set(self.total.get() + value);
+                Handled::Yes
+            }
+            None => Handled::No,
+        }
+    }
+
+    fn on_status_change(&mut self, _ctx: &mut LifeCycleCtx, _event: &StatusChange, _env: &Env) {}
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _env: &Env) {}
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, env: &Env) -> Size {
+        layout_preferred_size(self, bc, env)
+    }
+
+    fn preferred_size(&self, _bc: &BoxConstraints, _env: &Env) -> Option<Size> {
+        Some(Size::new(10.0, 10.0))
+    }
+
+    fn paint(&mut self, _ctx: &mut PaintCtx, _env: &Env) {}
+
+    fn children(&self) -> SmallVec<[WidgetRef<'_, dyn Widget>; 16]> {
+        SmallVec::new()
+    }
+}
+
+#[test]
+fn on_command_runs_before_on_event() {
+    let [id] = crate::testing::widget_ids();
+    let total = Rc::new(Cell::new(0));
+    let saw_command_event = Rc::new(Cell::new(false));
+    let counter = CommandCounter {
+        total: total.clone(),
+        saw_command_event: saw_command_event.clone(),
+    };
+    let mut harness = TestHarness::create(Flex::row().with_child_id(counter, id));
+
+    harness.submit_commands(vec![PING.with(2).to(id), PING.with(3).to(id)]);
+    assert_eq!(total.get(), 5);
+    assert!(!saw_command_event.get());
+
+    // Commands not handled in `on_command` still reach `on_event`.
+    harness.submit_command(SEND_BATCH.to(id));
+    assert!(saw_command_event.get());
+}
+
+/// Compares routing 1000 commands one by one against routing them as a batch.
+#[test]
+#[ignore]
+fn bench_command_batching() {
+    const COUNT: usize = 1000;
+
+    let (flex, ids, _) = receivers::<COUNT>();
+    let mut harness = TestHarness::create(flex);
+
+    super::bench(&format!("{COUNT} commands one by one"), 1, || {
+        for id in ids {
+            harness.submit_command(PING.with(1).to(id));
+        }
+    });
+    super::bench(&format!("{COUNT} commands batched"), 1, || {
+        harness.submit_commands(ids.iter().map(|id| PING.with(1).to(*id)).collect());
+    });
+}
+
+#[test]
+fn pointer_target_reaches_widget_under_pointer() {
+    let ids: [WidgetId; 2] = crate::testing::widget_ids();
+    let totals = [Rc::new(Cell::new(0)), Rc::new(Cell::new(0))];
+    let sized_receiver = |total: Rc<Cell<u32>>| {
+        ModularWidget::new(total)
+            .event_fn(|total, _ctx, event, _| {
+                if let Event::Command(cmd) = event {
+                    if let Some(value) = cmd.try_get(PING) {
+                        total.set(total.get() + value);
+                    }
+                }
+            })
+            .layout_fn(|_, _, _, _| Size::new(100.0, 50.0))
+    };
+    let flex = Flex::column()
+        .with_child_id(sized_receiver(totals[0].clone()), ids[0])
+        .with_child_id(sized_receiver(totals[1].clone()), ids[1]);
+    let mut harness = TestHarness::create(flex);
+
+    let second_center = harness
+        .get_widget(ids[1])
+        .state()
+        .window_layout_rect()
+        .center();
+    harness.mouse_move(second_center);
+    harness.submit_command(PING.with(1).to(Target::Pointer));
+    assert_eq!([totals[0].get(), totals[1].get()], [0, 1]);
+
+    // Without a widget under the pointer, the command is dropped.
+    harness.mouse_leave();
+    harness.submit_command(PING.with(1).to(Target::Pointer));
+    assert_eq!([totals[0].get(), totals[1].get()], [0, 1]);
+}
+
+#[test]
+fn high_priority_commands_run_before_paint() {
+    let [receiver_id] = crate::testing::widget_ids();
+    let total = Rc::new(Cell::new(0));
+    let painted_total = Rc::new(Cell::new(0));
+
+    // Sends its commands during its first layout.
+    let sender = ModularWidget::new(false).layout_fn(move |sent, ctx, _, _| {
+        if !std::mem::replace(sent, true) {
+            ctx.submit_command(PING.with(1).to(receiver_id));
+            ctx.submit_command(
+                PING.with(10)
+                    .to(receiver_id)
+                    .with_priority(CommandPriority::High),
+            );
+        }
+        Size::ZERO
+    });
+    let receiver = ModularWidget::new((total.clone(), painted_total.clone()))
+        .event_fn(|(total, _), ctx, event, _| {
+            if let Event::Command(cmd) = event {
+                if let Some(value) = cmd.try_get(PING) {
+                    total.set(total.get() + value);
+                    ctx.request_paint();
+                }
+            }
+        })
+        .paint_fn(|(total, painted_total), _, _| painted_total.set(total.get()));
+    let flex = Flex::column()
+        .with_child(sender)
+        .with_child_id(receiver, receiver_id);
+    let mut harness = TestHarness::create(flex);
+
+    // The high-priority command is seen by the first frame, the other one
+    // runs after it.
+    harness.run_frames(1);
+    assert_eq!(painted_total.get(), 10);
+    assert_eq!(total.get(), 11);
+}
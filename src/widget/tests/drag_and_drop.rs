@@ -0,0 +1,265 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use druid_shell::{KbKey, KeyEvent, MouseButton};
+
+use crate::testing::{
+    widget_ids, ModularWidget, Record, Recording, ReplaceChild, TestHarness, TestWidgetExt as _,
+    REPLACE_CHILD,
+};
+use crate::widget::{Flex, SizedBox};
+use crate::*;
+
+type DragLog = Rc<RefCell<Vec<(DragPhase, Option<u32>)>>>;
+
+/// A widget that starts dragging `42` when pressed.
+fn drag_source() -> impl Widget {
+    ModularWidget::new(())
+        .event_fn(|_, ctx, event, _| {
+            if let Event::MouseDown(_) = event {
+                ctx.begin_drag(42_u32, SizedBox::empty().width(20.0).height(20.0));
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(50.0, 50.0))
+}
+
+/// A drag source that records the drag events it receives.
+fn logged_drag_source(log: DragLog) -> impl Widget {
+    ModularWidget::new(log)
+        .event_fn(|log, ctx, event, _| match event {
+            Event::MouseDown(_) => {
+                ctx.begin_drag(42_u32, SizedBox::empty().width(20.0).height(20.0));
+            }
+            Event::Drag(drag) => {
+                log.borrow_mut()
+                    .push((drag.phase, drag.payload::<u32>().copied()));
+            }
+            _ => (),
+        })
+        .layout_fn(|_, _, _, _| Size::new(50.0, 50.0))
+}
+
+/// A drag source that captures the pointer, with a preview that records
+/// its lifecycle events.
+fn capturing_drag_source(preview: Recording) -> impl Widget {
+    ModularWidget::new(preview)
+        .event_fn(|preview, ctx, event, _| match event {
+            Event::MouseDown(_) => {
+                ctx.set_active(true);
+                let preview_widget = SizedBox::empty().width(20.0).height(20.0);
+                ctx.begin_drag(42_u32, preview_widget.record(preview));
+            }
+            Event::MouseUp(_) => ctx.set_active(false),
+            _ => (),
+        })
+        .layout_fn(|_, _, _, _| Size::new(50.0, 50.0))
+}
+
+fn preview_removed(preview: &Recording) -> bool {
+    preview
+        .drain()
+        .iter()
+        .any(|record| matches!(record, Record::L(LifeCycle::WidgetRemoved)))
+}
+
+/// A widget that records the drag events it receives.
+fn drop_target(log: DragLog, accept: bool) -> impl Widget {
+    ModularWidget::new(log)
+        .event_fn(move |log, ctx, event, _| {
+            if let Event::Drag(drag) = event {
+                log.borrow_mut()
+                    .push((drag.phase, drag.payload::<u32>().copied()));
+                if accept && drag.phase == DragPhase::Enter {
+                    ctx.set_handled();
+                }
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(50.0, 50.0))
+}
+
+#[test]
+fn drop_on_accepting_target() {
+    let [source_id, target_id] = widget_ids();
+    let log = DragLog::default();
+
+    let widget = Flex::row()
+        .with_child(drag_source().with_id(source_id))
+        .with_child(drop_target(log.clone(), true).with_id(target_id));
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(source_id);
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_move_to(target_id);
+    harness.mouse_button_release(MouseButton::Left);
+
+    assert_eq!(
+        log.borrow()[..],
+        [(DragPhase::Enter, Some(42)), (DragPhase::Drop, Some(42))]
+    );
+}
+
+#[test]
+fn leave_target_before_release() {
+    let [source_id, target_id] = widget_ids();
+    let log = DragLog::default();
+
+    let widget = Flex::row()
+        .with_child(drag_source().with_id(source_id))
+        .with_child(drop_target(log.clone(), true).with_id(target_id));
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(source_id);
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_move_to(target_id);
+    harness.mouse_move_to(source_id);
+    harness.mouse_button_release(MouseButton::Left);
+
+    assert_eq!(
+        log.borrow()[..],
+        [(DragPhase::Enter, Some(42)), (DragPhase::Leave, Some(42))]
+    );
+}
+
+#[test]
+fn declined_enter_goes_to_parent() {
+    let [source_id, target_id] = widget_ids();
+    let inner_log = DragLog::default();
+    let outer_log = DragLog::default();
+
+    let inner = drop_target(inner_log.clone(), false).with_id(target_id);
+    let outer = ModularWidget::new((WidgetPod::new(inner), outer_log.clone()))
+        .event_fn(|(child, log), ctx, event, env| {
+            child.on_event(ctx, event, env);
+            if let Event::Drag(drag) = event {
+                log.borrow_mut()
+                    .push((drag.phase, drag.payload::<u32>().copied()));
+                if drag.phase == DragPhase::Enter {
+                    ctx.set_handled();
+                }
+            }
+        })
+        .lifecycle_fn(|(child, _), ctx, event, env| child.lifecycle(ctx, event, env))
+        .layout_fn(|(child, _), ctx, bc, env| {
+            let size = child.layout(ctx, bc, env);
+            ctx.place_child(child, Point::ORIGIN, env);
+            size
+        })
+        .paint_fn(|(child, _), ctx, env| child.paint(ctx, env))
+        .children_fn(|(child, _)| smallvec::smallvec![child.as_dyn()]);
+
+    let widget = Flex::row()
+        .with_child(drag_source().with_id(source_id))
+        .with_child(outer);
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(source_id);
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_move_to(target_id);
+    harness.mouse_button_release(MouseButton::Left);
+
+    assert_eq!(inner_log.borrow()[..], [(DragPhase::Enter, Some(42))]);
+    assert_eq!(
+        outer_log.borrow()[..],
+        [(DragPhase::Enter, Some(42)), (DragPhase::Drop, Some(42))]
+    );
+}
+
+#[test]
+fn escape_cancels_drag() {
+    let [source_id, target_id] = widget_ids();
+    let source_log = DragLog::default();
+    let target_log = DragLog::default();
+
+    let widget = Flex::row()
+        .with_child(logged_drag_source(source_log.clone()).with_id(source_id))
+        .with_child(drop_target(target_log.clone(), true).with_id(target_id));
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(source_id);
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_move_to(target_id);
+    harness.process_event(Event::KeyDown(KeyEvent {
+        key: KbKey::Escape,
+        ..Default::default()
+    }));
+    harness.mouse_button_release(MouseButton::Left);
+
+    assert_eq!(source_log.borrow()[..], [(DragPhase::Cancel, Some(42))]);
+    // The payload isn't dropped on release.
+    assert_eq!(
+        target_log.borrow()[..],
+        [(DragPhase::Enter, Some(42)), (DragPhase::Cancel, Some(42))]
+    );
+}
+
+#[test]
+fn removing_source_cancels_drag() {
+    let [source_id, target_id] = widget_ids();
+    let log = DragLog::default();
+
+    let source = ReplaceChild::new(drag_source().with_id(source_id), || {
+        SizedBox::empty().width(50.0).height(50.0)
+    });
+    let widget = Flex::row()
+        .with_child(source)
+        .with_child(drop_target(log.clone(), true).with_id(target_id));
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(source_id);
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_move_to(target_id);
+    harness.submit_command(REPLACE_CHILD);
+    harness.mouse_button_release(MouseButton::Left);
+
+    assert_eq!(
+        log.borrow()[..],
+        [(DragPhase::Enter, Some(42)), (DragPhase::Cancel, Some(42))]
+    );
+}
+
+#[test]
+fn drop_removes_preview() {
+    let [source_id, target_id] = widget_ids();
+    let preview = Recording::default();
+
+    let widget = Flex::row()
+        .with_child(capturing_drag_source(preview.clone()).with_id(source_id))
+        .with_child(drop_target(DragLog::default(), true).with_id(target_id));
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(source_id);
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_move_to(target_id);
+    assert!(!preview_removed(&preview));
+
+    harness.mouse_button_release(MouseButton::Left);
+    assert!(preview_removed(&preview));
+}
+
+#[test]
+fn cancel_removes_preview_and_releases_pointer() {
+    let [source_id, target_id] = widget_ids();
+    let preview = Recording::default();
+
+    let widget = Flex::row()
+        .with_child(capturing_drag_source(preview.clone()).with_id(source_id))
+        .with_child(drop_target(DragLog::default(), true).with_id(target_id));
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(source_id);
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_move_to(target_id);
+    assert!(harness.get_widget(source_id).state().is_active);
+
+    harness.process_event(Event::KeyDown(KeyEvent {
+        key: KbKey::Escape,
+        ..Default::default()
+    }));
+    assert!(preview_removed(&preview));
+    assert!(!harness.get_widget(source_id).state().is_active);
+    assert!(!harness.root_widget().state().has_active);
+}
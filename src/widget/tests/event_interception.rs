@@ -0,0 +1,110 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use druid_shell::MouseButton;
+
+use crate::testing::{Record, Recording, TestHarness, TestWidgetExt as _};
+use crate::widget::SizedBox;
+use crate::*;
+
+#[test]
+fn interceptor_consumes_input() {
+    let record = Recording::default();
+    let mut harness = TestHarness::create(SizedBox::empty().expand().record(&record));
+    harness.set_event_interceptor(|_, _| Interception::Consume);
+    record.clear();
+
+    harness.mouse_move((10.0, 10.0));
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_button_release(MouseButton::Left);
+    assert!(!record.drain().iter().any(|record| matches!(
+        record,
+        Record::E(Event::MouseMove(_) | Event::MouseDown(_) | Event::MouseUp(_))
+    )));
+
+    // Window events keep the window in sync with the platform, and aren't
+    // intercepted.
+    harness.process_event(Event::WindowSize(Size::new(200.0, 100.0)));
+    assert!(record
+        .drain()
+        .iter()
+        .any(|record| matches!(record, Record::E(Event::WindowSize(_)))));
+}
+
+#[test]
+fn interceptor_replaces_input() {
+    let record = Recording::default();
+    let mut harness = TestHarness::create(SizedBox::empty().expand().record(&record));
+    harness.set_event_interceptor(|_, event| match event {
+        // Invert the scrolling direction.
+        Event::Wheel(mut mouse_event) => {
+            mouse_event.wheel_delta = -mouse_event.wheel_delta;
+            Interception::Dispatch(Event::Wheel(mouse_event))
+        }
+        event => Interception::Dispatch(event),
+    });
+    record.clear();
+
+    harness.mouse_move((10.0, 10.0));
+    harness.mouse_wheel(Vec2::new(0.0, 30.0));
+    let wheel_deltas: Vec<_> = record
+        .drain()
+        .into_iter()
+        .filter_map(|record| match record {
+            Record::E(Event::Wheel(mouse_event)) => Some(mouse_event.wheel_delta),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(wheel_deltas, vec![Vec2::new(0.0, -30.0)]);
+}
+
+#[test]
+fn spies_observe_events() {
+    let record = Recording::default();
+    let mut harness = TestHarness::create(SizedBox::empty().expand().record(&record));
+    let window_id = harness.window().id;
+
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    harness.add_event_spy({
+        let seen = seen.clone();
+        move |id, event, _| seen.borrow_mut().push((id, event.short_name()))
+    });
+    record.clear();
+
+    harness.mouse_move((10.0, 10.0));
+    harness.mouse_button_press(MouseButton::Left);
+    harness.process_event(Event::WindowSize(Size::new(200.0, 100.0)));
+
+    assert_eq!(
+        seen.borrow().as_slice(),
+        [
+            (window_id, "MouseMove"),
+            (window_id, "MouseDown"),
+            (window_id, "WindowSize"),
+        ]
+    );
+    // The widgets still get the events.
+    let widget_events: Vec<_> = record
+        .drain()
+        .into_iter()
+        .filter_map(|record| match record {
+            Record::E(event) => Some(event.short_name()),
+            _ => None,
+        })
+        .collect();
+    for name in ["MouseMove", "MouseDown", "WindowSize"] {
+        assert!(widget_events.contains(&name), "{name} wasn't dispatched");
+    }
+
+    // Spies see events as they were received, even the ones the interceptor
+    // consumes.
+    harness.set_event_interceptor(|_, _| Interception::Consume);
+    seen.borrow_mut().clear();
+    harness.mouse_button_release(MouseButton::Left);
+    assert_eq!(seen.borrow().as_slice(), [(window_id, "MouseUp")]);
+    assert!(record.is_empty());
+}
@@ -4,8 +4,19 @@
 
 //! Tests related to propagation of invalid rects.
 
-use crate::testing::{widget_ids, TestHarness};
-use crate::widget::{Button, Flex};
+use std::cell::Cell;
+use std::rc::Rc;
+
+use druid_shell::WindowState;
+
+use crate::promise::PromiseToken;
+use crate::testing::{
+    widget_ids, ModularWidget, Record, Recording, TestHarness, TestWidgetExt as _,
+};
+use crate::widget::{Button, Flex, SizedBox};
+use crate::*;
+
+const INVALIDATE: Selector<Rect> = Selector::new("masonry-test.invalidate");
 
 #[test]
 fn invalidate_union() {
@@ -37,4 +48,475 @@ fn invalidate_union() {
     );
 }
 
+#[test]
+fn clear_matches_invalid_region() {
+    let [id] = widget_ids();
+
+    let widget = ModularWidget::new(()).event_fn(|_, ctx, event, _| {
+        if let Event::Command(cmd) = event {
+            if let Some(rect) = cmd.try_get(INVALIDATE) {
+                ctx.request_paint_rect(*rect);
+            }
+        }
+    });
+    let mut harness = TestHarness::create_with_size(widget.with_id(id), Size::new(40.0, 40.0));
+    harness.window_mut().background_color = Some(Color::rgb8(255, 0, 0));
+
+    let pixel = |image: &[u8], x: usize, y: usize| image[(y * 40 + x) * 4..][..4].to_vec();
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const UNTOUCHED: [u8; 4] = [0, 0, 0, 0];
+
+    let image = harness.render();
+    assert_eq!(pixel(&image, 5, 5), RED);
+    assert_eq!(pixel(&image, 25, 25), RED);
+
+    // Only the invalid region is cleared.
+    harness.submit_command(INVALIDATE.with(Rect::new(0.0, 0.0, 10.0, 10.0)).to(id));
+    let image = harness.render();
+    assert_eq!(pixel(&image, 5, 5), RED);
+    assert_eq!(pixel(&image, 25, 25), UNTOUCHED);
+
+    harness.window_mut().clear = false;
+    harness.submit_command(INVALIDATE.with(Rect::new(0.0, 0.0, 10.0, 10.0)).to(id));
+    let image = harness.render();
+    assert_eq!(pixel(&image, 5, 5), UNTOUCHED);
+}
+
+#[test]
+fn replay_last_frame() {
+    let [id] = widget_ids();
+    let paints = Rc::new(Cell::new(0));
+
+    let widget = ModularWidget::new(paints.clone())
+        .event_fn(|_, ctx, event, _| {
+            if let Event::Command(cmd) = event {
+                if let Some(rect) = cmd.try_get(INVALIDATE) {
+                    ctx.request_paint_rect(*rect);
+                }
+            }
+        })
+        .paint_fn(|paints, ctx, _| {
+            paints.set(paints.get() + 1);
+            let rect = ctx.size().to_rect();
+            ctx.fill(rect, &Color::rgb8(0, 0, 255));
+        });
+    let mut harness = TestHarness::create_with_size(widget.with_id(id), Size::new(40.0, 40.0));
+    harness.window_mut().replay_last_frame = true;
+
+    let first = harness.render();
+    assert_eq!(paints.get(), 1);
+
+    // Nothing changed, so the last frame is drawn again without painting.
+    let replayed = harness.render();
+    assert_eq!(paints.get(), 1);
+    assert!(first == replayed);
+
+    // An invalidation makes the image stale.
+    harness.submit_command(INVALIDATE.with(Rect::new(0.0, 0.0, 10.0, 10.0)).to(id));
+    let _ = harness.render();
+    assert_eq!(paints.get(), 2);
+}
+
+#[test]
+fn covered_rects_are_coalesced() {
+    let ids: [WidgetId; 3] = widget_ids();
+    let mut flex = Flex::column();
+    for id in ids {
+        let child = ModularWidget::new(())
+            .event_fn(|_, ctx, event, _| {
+                if let Event::Command(cmd) = event {
+                    if let Some(rect) = cmd.try_get(INVALIDATE) {
+                        ctx.request_paint_rect(*rect);
+                    }
+                }
+            })
+            .layout_fn(|_, _, _, _| Size::new(20.0, 20.0));
+        flex = flex.with_child(child.with_id(id));
+    }
+    let mut harness = TestHarness::create(flex);
+    let _ = harness.render();
+
+    let whole = Rect::new(0.0, 0.0, 20.0, 20.0);
+    harness.submit_commands(vec![
+        INVALIDATE.with(whole).to(ids[0]),
+        INVALIDATE.with(Rect::new(0.0, 0.0, 5.0, 5.0)).to(ids[1]),
+        INVALIDATE.with(whole).to(ids[1]),
+        INVALIDATE.with(whole).to(ids[2]),
+        INVALIDATE.with(whole).to(ids[2]),
+    ]);
+
+    let invalid = harness.window().invalid().clone();
+    for id in ids {
+        let rect = harness.get_widget(id).state().layout_rect();
+        assert!(
+            invalid.rects().iter().any(|r| r.union(rect) == *r),
+            "{rect:?} isn't in {invalid:?}"
+        );
+    }
+
+    // Duplicates and covered rects aren't sent to the platform.
+    let coalesced = crate::app_root::coalesced_rects(&invalid);
+    assert_eq!(coalesced.len(), 3);
+}
+
+#[test]
+fn ext_event_batch_invalidates_union() {
+    let ids: [WidgetId; 3] = widget_ids();
+    let paints: Vec<_> = ids.iter().map(|_| Rc::new(Cell::new(0))).collect();
+    let mut flex = Flex::column();
+    for (id, paints) in ids.iter().zip(&paints) {
+        let child = ModularWidget::new(paints.clone())
+            .event_fn(|_, ctx, event, _| {
+                if let Event::Command(cmd) = event {
+                    if let Some(rect) = cmd.try_get(INVALIDATE) {
+                        ctx.request_paint_rect(*rect);
+                    }
+                }
+            })
+            .layout_fn(|_, _, _, _| Size::new(20.0, 20.0))
+            .paint_fn(|paints, _, _| paints.set(paints.get() + 1));
+        flex = flex.with_child(child.with_id(*id));
+    }
+    let mut harness = TestHarness::create(flex);
+    let _ = harness.render();
+    let paint_counts = |paints: &[Rc<Cell<u32>>]| -> Vec<u32> {
+        paints.iter().map(|paints| paints.replace(0)).collect()
+    };
+    paint_counts(&paints);
+
+    // Commands submitted one by one only repaint their targets.
+    let whole = Rect::new(0.0, 0.0, 20.0, 20.0);
+    harness.submit_command(INVALIDATE.with(whole).to(ids[0]));
+    harness.submit_command(INVALIDATE.with(whole).to(ids[2]));
+    let _ = harness.render();
+    assert_eq!(paint_counts(&paints), [1, 0, 1]);
+
+    // The invalidations of a batch of external events are merged into their
+    // union, which covers the widget in between.
+    let sink = harness.ext_event_sink();
+    sink.submit_command(INVALIDATE, whole, ids[0]).unwrap();
+    sink.submit_command(INVALIDATE, whole, ids[2]).unwrap();
+    harness.run_frames(1);
+    assert_eq!(paint_counts(&paints), [1, 1, 1]);
+}
+
+const ADD_ROW: Selector = Selector::new("masonry-test.add-row");
+
+/// A row that counts the times it's painted.
+fn counted_row(paints: &Rc<Cell<u32>>) -> WidgetPod<Box<dyn Widget>> {
+    let row = ModularWidget::new(paints.clone())
+        .layout_fn(|_, _, _, _| Size::new(40.0, 10.0))
+        .paint_fn(|paints, ctx, _| {
+            paints.set(paints.get() + 1);
+            let rect = ctx.size().to_rect();
+            ctx.fill(rect, &Color::WHITE);
+        });
+    WidgetPod::new(row).boxed()
+}
+
+/// A static list of `row_count` rows, cached in a layer, that adds a row on command.
+fn layer_list(row_paints: &Rc<Cell<u32>>, row_count: usize) -> impl Widget {
+    let rows: Vec<_> = (0..row_count).map(|_| counted_row(row_paints)).collect();
+    ModularWidget::new((rows, row_paints.clone()))
+        .event_fn(|(rows, paints), ctx, event, env| {
+            if let Event::Command(cmd) = event {
+                if cmd.is(ADD_ROW) {
+                    rows.push(counted_row(paints));
+                    ctx.children_changed();
+                }
+            }
+            for row in rows {
+                row.on_event(ctx, event, env);
+            }
+        })
+        .lifecycle_fn(|(rows, _), ctx, event, env| {
+            if let LifeCycle::WidgetAdded = event {
+                ctx.set_is_layer(true);
+            }
+            for row in rows {
+                row.lifecycle(ctx, event, env);
+            }
+        })
+        .layout_fn(|(rows, _), ctx, bc, env| {
+            let mut y = 0.0;
+            for row in rows {
+                let size = row.layout(ctx, &bc.loosen(), env);
+                ctx.place_child(row, Point::new(0.0, y), env);
+                y += size.height;
+            }
+            Size::new(40.0, y.max(40.0))
+        })
+        .paint_fn(|(rows, _), ctx, env| {
+            let rect = ctx.size().to_rect();
+            ctx.fill(rect, &Color::BLACK);
+            for row in rows {
+                row.paint(ctx, env);
+            }
+        })
+        .children_fn(|(rows, _)| rows.iter().map(|row| row.as_dyn()).collect())
+}
+
+/// A list with a blinking caret drawn over its first row.
+fn list_with_caret(list: impl Widget, list_id: WidgetId, caret_id: WidgetId) -> impl Widget {
+    let caret = ModularWidget::new(())
+        .event_fn(|_, ctx, event, _| {
+            if let Event::Command(cmd) = event {
+                if let Some(rect) = cmd.try_get(INVALIDATE) {
+                    ctx.request_paint_rect(*rect);
+                }
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(2.0, 10.0));
+
+    let children = (
+        WidgetPod::new_with_id(list, list_id),
+        WidgetPod::new_with_id(caret, caret_id),
+    );
+    ModularWidget::new(children)
+        .event_fn(|(list, caret), ctx, event, env| {
+            list.on_event(ctx, event, env);
+            caret.on_event(ctx, event, env);
+        })
+        .lifecycle_fn(|(list, caret), ctx, event, env| {
+            list.lifecycle(ctx, event, env);
+            caret.lifecycle(ctx, event, env);
+        })
+        .layout_fn(|(list, caret), ctx, bc, env| {
+            list.layout(ctx, bc, env);
+            ctx.place_child(list, Point::ORIGIN, env);
+            caret.layout(ctx, bc, env);
+            ctx.place_child(caret, Point::new(5.0, 0.0), env);
+            bc.max()
+        })
+        .paint_fn(|(list, caret), ctx, env| {
+            list.paint(ctx, env);
+            caret.paint(ctx, env);
+        })
+        .children_fn(|(list, caret)| smallvec::smallvec![list.as_dyn(), caret.as_dyn()])
+}
+
+#[test]
+fn clean_layer_skips_painting_subtree() {
+    let [list_id, caret_id] = widget_ids();
+    let row_paints = Rc::new(Cell::new(0));
+    let widget = list_with_caret(layer_list(&row_paints, 3), list_id, caret_id);
+    let mut harness = TestHarness::create_with_size(widget, Size::new(40.0, 40.0));
+
+    let _ = harness.render();
+    assert_eq!(row_paints.get(), 3);
+
+    // The caret's blinks repaint the list where they overlap it, from the
+    // cached layer.
+    for _ in 0..5 {
+        let caret_rect = Rect::new(0.0, 0.0, 2.0, 10.0);
+        harness.submit_command(INVALIDATE.with(caret_rect).to(caret_id));
+        let _ = harness.render();
+    }
+    assert_eq!(row_paints.get(), 3);
+
+    // A new row makes the subtree dirty.
+    harness.submit_command(ADD_ROW.to(list_id));
+    let _ = harness.render();
+    assert_eq!(row_paints.get(), 3 + 4);
+    assert_eq!(harness.get_widget(list_id).children().len(), 4);
+}
+
+/// Times the frames of a caret blinking over a static list of 500 rows.
+#[test]
+#[ignore]
+fn bench_caret_over_static_list() {
+    const ROWS: usize = 500;
+
+    let [list_id, caret_id] = widget_ids();
+    let row_paints = Rc::new(Cell::new(0));
+    let widget = list_with_caret(layer_list(&row_paints, ROWS), list_id, caret_id);
+    let mut harness = TestHarness::create_with_size(widget, Size::new(40.0, 10.0 * ROWS as f64));
+    let _ = harness.render();
+
+    let caret_rect = Rect::new(0.0, 0.0, 2.0, 10.0);
+    super::bench(&format!("caret blinks over {ROWS} rows"), 100, || {
+        harness.submit_command(INVALIDATE.with(caret_rect).to(caret_id));
+        let _ = harness.render();
+    });
+}
+
+#[test]
+fn moved_layer_is_drawn_from_cache() {
+    const MOVE_TO: Selector<f64> = Selector::new("masonry-test.move-to");
+
+    let paints = Rc::new(Cell::new(0));
+    let layer = ModularWidget::new(paints.clone())
+        .lifecycle_fn(|_, ctx, event, _| {
+            if let LifeCycle::WidgetAdded = event {
+                ctx.set_is_layer(true);
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(10.0, 10.0))
+        .paint_fn(|paints, ctx, _| {
+            paints.set(paints.get() + 1);
+            let rect = ctx.size().to_rect();
+            ctx.fill(rect, &Color::RED);
+        });
+    let widget = ModularWidget::new((WidgetPod::new(layer), 0.0))
+        .event_fn(|(layer, x), ctx, event, env| {
+            if let Event::Command(cmd) = event {
+                if let Some(new_x) = cmd.try_get(MOVE_TO) {
+                    *x = *new_x;
+                    ctx.request_layout();
+                    ctx.request_paint();
+                }
+            }
+            layer.on_event(ctx, event, env);
+        })
+        .lifecycle_fn(|(layer, _), ctx, event, env| layer.lifecycle(ctx, event, env))
+        .layout_fn(|(layer, x), ctx, bc, env| {
+            layer.layout(ctx, &bc.loosen(), env);
+            ctx.place_child(layer, Point::new(*x, 0.0), env);
+            bc.max()
+        })
+        .paint_fn(|(layer, _), ctx, env| {
+            let rect = ctx.size().to_rect();
+            ctx.fill(rect, &Color::BLACK);
+            layer.paint(ctx, env);
+        })
+        .children_fn(|(layer, _)| smallvec::smallvec![layer.as_dyn()]);
+    let mut harness = TestHarness::create_with_size(widget, Size::new(40.0, 10.0));
+    let _ = harness.render();
+    assert_eq!(paints.get(), 1);
+
+    // Moving the layer doesn't paint its content again.
+    harness.submit_command(MOVE_TO.with(20.0));
+    let image = harness.render();
+    assert_eq!(paints.get(), 1);
+    let red_at = |x: usize| image[(5 * 40 + x) * 4];
+    assert_eq!(red_at(25), 0xff);
+    assert_eq!(red_at(5), 0x00);
+}
+
+#[test]
+fn deferred_layout_repaints_layer() {
+    let [list_id] = widget_ids();
+    let row_paints = Rc::new(Cell::new(0));
+    let mut harness = TestHarness::create_with_size(
+        layer_list(&row_paints, 3).with_id(list_id),
+        Size::new(40.0, 40.0),
+    );
+    harness.set_layout_budget(Some(std::time::Duration::ZERO));
+
+    // The new row was never visible, so its layout is deferred, and the
+    // layer is painted without it.
+    harness.submit_command(ADD_ROW.to(list_id));
+    assert!(harness.root_widget().state().has_deferred_layout);
+    let _ = harness.render();
+    row_paints.set(0);
+
+    // The list's size doesn't change, but its cached layer is stale.
+    harness.run_frames(1);
+    assert!(!harness.root_widget().state().has_deferred_layout);
+    assert_eq!(row_paints.get(), 4);
+}
+
+#[test]
+fn capture_window_keeps_invalidation() {
+    const CAPTURE: Selector = Selector::new("masonry-test.capture");
+
+    let [id] = widget_ids();
+    let image_size = Rc::new(Cell::new(None));
+    let widget = ModularWidget::new((image_size.clone(), None::<PromiseToken<ImageBuf>>))
+        .event_fn(|(image_size, token), ctx, event, _| match event {
+            Event::Command(cmd) if cmd.is(CAPTURE) => {
+                *token = Some(ctx.capture_window());
+            }
+            Event::PromiseResult(result) => {
+                if let Some(image) = (*token).and_then(|token| result.try_get(token)) {
+                    image_size.set(Some((image.width(), image.height())));
+                }
+            }
+            _ => {}
+        })
+        .layout_fn(|_, _, _, _| Size::new(20.0, 20.0));
+    let mut harness = TestHarness::create_with_size(widget.with_id(id), Size::new(40.0, 40.0));
+    let _ = harness.render();
+
+    harness.submit_command(CAPTURE.to(id));
+
+    // The capture painted the window off-screen, which mustn't let the next
+    // frame skip anything.
+    assert_eq!(
+        harness.window().invalid().rects(),
+        &[Size::new(40.0, 40.0).to_rect()]
+    );
+
+    harness.run_frames(1);
+    assert_eq!(image_size.get(), Some((40, 40)));
+}
+
+#[test]
+fn capture_window_leaves_layer_cache_alone() {
+    const REPAINT_AND_CAPTURE: Selector = Selector::new("masonry-test.repaint-and-capture");
+
+    let [id] = widget_ids();
+    let paints = Rc::new(Cell::new(0));
+    let layer = ModularWidget::new(paints.clone())
+        .event_fn(|_, ctx, event, _| {
+            if let Event::Command(cmd) = event {
+                if cmd.is(REPAINT_AND_CAPTURE) {
+                    ctx.request_paint();
+                    ctx.capture_window();
+                }
+            }
+        })
+        .lifecycle_fn(|_, ctx, event, _| {
+            if let LifeCycle::WidgetAdded = event {
+                ctx.set_is_layer(true);
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(20.0, 20.0))
+        .paint_fn(|paints, ctx, _| {
+            paints.set(paints.get() + 1);
+            let rect = ctx.size().to_rect();
+            ctx.fill(rect, &Color::RED);
+        });
+    let mut harness = TestHarness::create_with_size(layer.with_id(id), Size::new(40.0, 40.0));
+    let before = harness.render();
+    paints.set(0);
+
+    harness.submit_command(REPAINT_AND_CAPTURE.to(id));
+    assert_eq!(paints.get(), 1);
+
+    // The layer is still dirty: it isn't drawn from an image of the capture.
+    let after = harness.render();
+    assert_eq!(paints.get(), 2);
+    assert_eq!(before, after);
+}
+
 // TODO: Add a test with scrolling/viewport
+
+#[test]
+fn restoring_minimized_window_invalidates_it() {
+    let record = Recording::default();
+    let mut harness = TestHarness::create(SizedBox::empty().record(&record));
+    let _ = harness.render();
+    record.clear();
+
+    harness.process_event(Event::WindowStateChanged {
+        old: WindowState::Restored,
+        new: WindowState::Minimized,
+    });
+    assert!(record.drain().iter().any(|record| matches!(
+        record,
+        Record::E(Event::WindowStateChanged {
+            old: WindowState::Restored,
+            new: WindowState::Minimized,
+        })
+    )));
+    assert!(harness.window().invalid().is_empty());
+
+    // Painting was skipped while minimized, so the whole window is repainted.
+    harness.process_event(Event::WindowStateChanged {
+        old: WindowState::Minimized,
+        new: WindowState::Restored,
+    });
+    let window_rect = harness.window().size.to_rect();
+    assert_eq!(harness.window().invalid().rects(), &[window_rect]);
+}
@@ -0,0 +1,181 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use druid_shell::{Code, Cursor, KbKey, KeyEvent, Modifiers};
+
+use crate::testing::{widget_ids, ModularWidget, TestHarness, TestWidgetExt as _, REQUEST_FOCUS};
+use crate::widget::Flex;
+use crate::*;
+
+type KeyLog = Rc<RefCell<Vec<(Option<KbKey>, Option<Code>)>>>;
+
+/// A focusable widget that records the keys of the events it receives.
+fn key_reader(log: KeyLog) -> impl Widget {
+    ModularWidget::new(log)
+        .event_fn(|log, ctx, event, _| match event {
+            Event::Command(cmd) if cmd.is(REQUEST_FOCUS) => {
+                log.borrow_mut()
+                    .push((ctx.logical_key().cloned(), ctx.physical_key()));
+            }
+            Event::KeyDown(_) => {
+                log.borrow_mut()
+                    .push((ctx.logical_key().cloned(), ctx.physical_key()));
+            }
+            _ => (),
+        })
+        .focusable()
+}
+
+#[test]
+fn logical_and_physical_keys() {
+    let [id] = widget_ids();
+    let log = KeyLog::default();
+    let mut harness =
+        TestHarness::create(Flex::row().with_child(key_reader(log.clone()).with_id(id)));
+    harness.submit_command(REQUEST_FOCUS.to(id));
+
+    // The key above S, on an AZERTY keyboard.
+    let event = KeyEvent {
+        key: KbKey::Character("z".into()),
+        code: Code::KeyW,
+        ..Default::default()
+    };
+    harness.process_event(Event::KeyDown(event));
+
+    assert_eq!(
+        log.borrow()[..],
+        [
+            // Other events have no key.
+            (None, None),
+            (Some(KbKey::Character("z".into())), Some(Code::KeyW)),
+        ]
+    );
+}
+
+const SET_KEY_REPEAT: Selector<KeyRepeat> = Selector::new("masonry-test.set-key-repeat");
+
+/// A focusable counter, incremented by each `KeyDown` by its key repeat factor.
+fn key_counter(count: Rc<Cell<f64>>) -> impl Widget {
+    ModularWidget::new(count)
+        .event_fn(|count, ctx, event, _| match event {
+            Event::Command(cmd) if cmd.is(SET_KEY_REPEAT) => {
+                ctx.set_key_repeat(*cmd.get_unchecked(SET_KEY_REPEAT));
+            }
+            Event::KeyDown(_) => count.set(count.get() + ctx.key_repeat_factor()),
+            _ => (),
+        })
+        .focusable()
+}
+
+/// Hold the up arrow for two repeats, release it, and press it once more.
+fn press_and_hold(harness: &mut TestHarness) {
+    let event = KeyEvent {
+        key: KbKey::ArrowUp,
+        code: Code::ArrowUp,
+        ..Default::default()
+    };
+    // The platform doesn't report the repeats: they're inferred.
+    for _ in 0..3 {
+        harness.process_event(Event::KeyDown(event.clone()));
+    }
+    harness.process_event(Event::KeyUp(event.clone()));
+    harness.process_event(Event::KeyDown(event.clone()));
+    harness.process_event(Event::KeyUp(event));
+}
+
+#[test]
+fn key_repeat_suppression() {
+    let [id] = widget_ids();
+    let count = Rc::new(Cell::new(0.0));
+    let mut harness =
+        TestHarness::create(Flex::row().with_child(key_counter(count.clone()).with_id(id)));
+    harness.submit_command(REQUEST_FOCUS.to(id));
+
+    press_and_hold(&mut harness);
+    assert_eq!(count.get(), 4.0);
+
+    harness.submit_command(SET_KEY_REPEAT.with(KeyRepeat::Ignore).to(id));
+    press_and_hold(&mut harness);
+    assert_eq!(count.get(), 6.0);
+
+    harness.submit_command(
+        SET_KEY_REPEAT
+            .with(KeyRepeat::Accelerate {
+                step: 1.0,
+                max: 2.5,
+            })
+            .to(id),
+    );
+    press_and_hold(&mut harness);
+    // 1, then 2 and 2.5 for the repeats, then 1 again.
+    assert_eq!(count.get(), 12.5);
+}
+
+#[test]
+fn held_keys_survive_modifier_changes() {
+    let [id] = widget_ids();
+    let count = Rc::new(Cell::new(0.0));
+    let mut harness =
+        TestHarness::create(Flex::row().with_child(key_counter(count.clone()).with_id(id)));
+    harness.submit_command(REQUEST_FOCUS.to(id));
+    harness.submit_command(SET_KEY_REPEAT.with(KeyRepeat::Ignore).to(id));
+
+    // Shift is released before the key, so the key goes up as "1", not "!".
+    harness.process_event(Event::KeyDown(KeyEvent {
+        key: KbKey::Character("!".into()),
+        code: Code::Digit1,
+        mods: Modifiers::SHIFT,
+        ..Default::default()
+    }));
+    let one = KeyEvent {
+        key: KbKey::Character("1".into()),
+        code: Code::Digit1,
+        ..Default::default()
+    };
+    harness.process_event(Event::KeyUp(one.clone()));
+
+    // The next press isn't a repeat.
+    harness.process_event(Event::KeyDown(one));
+    assert_eq!(count.get(), 2.0);
+}
+
+#[test]
+fn modifier_keys_update_cursor() {
+    let [id] = widget_ids();
+    // Shows a crosshair while Alt is held.
+    let widget = ModularWidget::new(())
+        .event_fn(|_, ctx, event, _| {
+            if let Event::MouseMove(mouse_event) = event {
+                if mouse_event.mods.alt() {
+                    ctx.set_cursor(&Cursor::Crosshair);
+                } else {
+                    ctx.clear_cursor();
+                }
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(50.0, 50.0));
+    let mut harness = TestHarness::create(Flex::row().with_child(widget.with_id(id)));
+
+    harness.mouse_move_to(id);
+    assert_eq!(harness.window().cursor(), &Cursor::Arrow);
+
+    // The pointer doesn't move, but the cursor follows the modifiers.
+    let alt_down = KeyEvent {
+        key: KbKey::Alt,
+        mods: Modifiers::ALT,
+        ..Default::default()
+    };
+    harness.process_event(Event::KeyDown(alt_down));
+    assert_eq!(harness.window().cursor(), &Cursor::Crosshair);
+
+    let alt_up = KeyEvent {
+        key: KbKey::Alt,
+        ..Default::default()
+    };
+    harness.process_event(Event::KeyUp(alt_up));
+    assert_eq!(harness.window().cursor(), &Cursor::Arrow);
+}
@@ -6,10 +6,20 @@
 
 #![allow(unused_imports)]
 
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
 use druid_shell::kurbo::{Insets, Size};
+use smallvec::SmallVec;
 
-use crate::testing::{widget_ids, ModularWidget, TestHarness, TestWidgetExt};
-use crate::widget::{Flex, SizedBox};
+use crate::command::{LayoutRectChange, LAYOUT_RECT_CHANGED};
+use crate::testing::{widget_ids, ModularWidget, Record, Recording, TestHarness, TestWidgetExt};
+use crate::text::TextLayout;
+use crate::widget::{layout_preferred_size, Flex, Portal, SizedBox, WidgetRef};
+use crate::{
+    theme, ArcStr, BoxConstraints, Color, Env, Event, EventCtx, Key, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, Rect, Selector, StatusChange, Vec2, Widget, WidgetId, WidgetPod,
+};
 
 #[test]
 fn layout_simple() {
@@ -75,3 +85,855 @@ fn layout_insets() {
 // TODO - insets + flex
 // TODO - viewport
 // TODO - insets + viewport
+
+#[test]
+fn render_widget_to_export_size() {
+    use crate::piet::{Device, RenderContext as _};
+    use crate::testing::HARNESS_DEFAULT_SIZE;
+
+    let export_size = Size::new(50., 30.);
+
+    let mut harness = TestHarness::create(SizedBox::empty().expand());
+
+    let mut device = Device::new().unwrap();
+    let mut render_target = device.bitmap_target(50, 30, 1.0).unwrap();
+    {
+        let mut piet = render_target.render_context();
+        harness.render_widget_to(&mut piet, export_size);
+        piet.finish().unwrap();
+    }
+    assert_eq!(
+        harness.root_widget().state().layout_rect().size(),
+        export_size
+    );
+
+    // The next render lays the widget out at the window size again.
+    harness.render();
+    assert_eq!(
+        harness.root_widget().state().layout_rect().size(),
+        HARNESS_DEFAULT_SIZE
+    );
+}
+
+#[test]
+fn export_image_at_scale() {
+    let paint_scales: Rc<RefCell<Vec<f64>>> = Default::default();
+    let widget = ModularWidget::new(paint_scales.clone())
+        .layout_fn(|_, _, bc, _| bc.max())
+        .paint_fn(|paint_scales, ctx, _| paint_scales.borrow_mut().push(ctx.scale()));
+    let mut harness = TestHarness::create(widget);
+    let export_size = Size::new(50., 30.);
+
+    let image = harness.export_image(export_size, 1.0);
+    assert_eq!((image.width(), image.height()), (50, 30));
+
+    let image = harness.export_image(export_size, 2.0);
+    assert_eq!((image.width(), image.height()), (100, 60));
+    assert_eq!(*paint_scales.borrow(), vec![1.0, 2.0]);
+
+    // Once the export is done, the window's own scale is used again.
+    harness.render();
+    assert_eq!(paint_scales.borrow().last(), Some(&1.0));
+    assert_eq!(
+        harness.root_widget().state().layout_rect().size(),
+        crate::testing::HARNESS_DEFAULT_SIZE
+    );
+}
+
+#[test]
+fn render_thumbnail_fits_max_size() {
+    use std::cell::RefCell;
+    use std::sync::Arc;
+
+    use crate::{ImageBuf, Point, WidgetPod};
+
+    let thumbnails: Rc<RefCell<Vec<ImageBuf>>> = Default::default();
+    let child = WidgetPod::new(SizedBox::empty().width(200.).height(100.));
+    let widget = ModularWidget::new((child, thumbnails.clone()))
+        .lifecycle_fn(|(child, _), ctx, event, env| child.lifecycle(ctx, event, env))
+        .layout_fn(|(child, _), ctx, bc, env| {
+            child.layout(ctx, &bc.loosen(), env);
+            ctx.place_child(child, Point::ORIGIN, env);
+            bc.max()
+        })
+        .paint_fn(|(child, thumbnails), ctx, env| {
+            let thumbnail = child.render_thumbnail(ctx, Size::new(50., 50.), env);
+            thumbnails.borrow_mut().push(thumbnail);
+        })
+        .children_fn(|(child, _)| smallvec::smallvec![child.as_dyn()]);
+
+    let mut harness = TestHarness::create(widget);
+    harness.render();
+    harness.render();
+
+    let thumbnails = thumbnails.borrow();
+    let first = &thumbnails[0];
+    assert_eq!((first.width(), first.height()), (50, 25));
+
+    // The child didn't change, so the image is reused.
+    for thumbnail in &thumbnails[1..] {
+        assert!(Arc::ptr_eq(
+            &thumbnail.raw_pixels_shared(),
+            &first.raw_pixels_shared()
+        ));
+    }
+}
+
+#[test]
+fn user_data_in_layout() {
+    use crate::{Point, WidgetPod};
+
+    let [id_1, id_2] = widget_ids();
+
+    // Each child's width is stored on its pod.
+    let children = vec![
+        WidgetPod::new_with_id(SizedBox::empty(), id_1).with_user_data(30.0_f64),
+        WidgetPod::new_with_id(SizedBox::empty(), id_2).with_user_data(70.0_f64),
+    ];
+    let widget = ModularWidget::new(children)
+        .lifecycle_fn(|children, ctx, event, env| {
+            for child in children {
+                child.lifecycle(ctx, event, env);
+            }
+        })
+        .layout_fn(|children, ctx, bc, env| {
+            let mut x = 0.0;
+            for child in children {
+                let width = *child.user_data::<f64>().unwrap();
+                let child_bc = BoxConstraints::tight(Size::new(width, 10.0));
+                child.layout(ctx, &child_bc, env);
+                ctx.place_child(child, Point::new(x, 0.0), env);
+                x += width;
+            }
+            bc.constrain(Size::new(x, 10.0))
+        })
+        .children_fn(|children| children.iter().map(|child| child.as_dyn()).collect());
+
+    let harness = TestHarness::create(widget);
+
+    assert_eq!(harness.get_widget(id_1).state().layout_rect().width(), 30.0);
+    assert_eq!(harness.get_widget(id_2).state().layout_rect().x0, 30.0);
+    assert_eq!(harness.get_widget(id_2).state().layout_rect().width(), 70.0);
+}
+
+const RELAYOUT: crate::Selector = crate::Selector::new("masonry-test.relayout");
+
+/// A column of widgets with the given ids, that each request a layout three
+/// times when they receive `RELAYOUT`, and a counter of layout calls for each.
+fn relayout_column(ids: &[WidgetId]) -> (Flex, Vec<Rc<Cell<usize>>>) {
+    let layout_counts: Vec<_> = ids.iter().map(|_| Rc::new(Cell::new(0))).collect();
+    let mut column = Flex::column();
+    for (id, layout_count) in ids.iter().zip(&layout_counts) {
+        let layout_count = layout_count.clone();
+        let child = ModularWidget::new(())
+            .event_fn(|_, ctx, event, _| {
+                if let Event::Command(cmd) = event {
+                    if cmd.is(RELAYOUT) {
+                        ctx.request_layout();
+                        ctx.request_layout();
+                        ctx.request_layout();
+                    }
+                }
+            })
+            .layout_fn(move |_, _, _, _| {
+                layout_count.set(layout_count.get() + 1);
+                Size::new(1., 1.)
+            });
+        column = column.with_child(child.with_id(*id));
+    }
+    (column, layout_counts)
+}
+
+/// `count` new widget ids.
+fn new_ids(count: usize) -> Vec<WidgetId> {
+    (0..count).map(|_| WidgetId::next()).collect()
+}
+
+#[test]
+fn repeated_layout_requests_coalesce() {
+    let ids: [_; 10] = widget_ids();
+    let (column, layout_counts) = relayout_column(&ids);
+    let mut harness = TestHarness::create(column);
+    for layout_count in &layout_counts {
+        layout_count.set(0);
+    }
+
+    // The first half of the widgets request a layout three times, in each of
+    // two events of the same frame.
+    let commands = ids[..5]
+        .iter()
+        .chain(&ids[..5])
+        .map(|id| RELAYOUT.to(*id))
+        .collect();
+    harness.submit_commands(commands);
+
+    // They're laid out once, and the other widgets aren't laid out again.
+    let counts: Vec<_> = layout_counts.iter().map(|count| count.get()).collect();
+    assert_eq!(counts, [1, 1, 1, 1, 1, 0, 0, 0, 0, 0]);
+
+    // The requests don't carry over to the next frame.
+    harness.submit_commands(vec![RELAYOUT.to(ids[9])]);
+    let counts: Vec<_> = layout_counts.iter().map(|count| count.get()).collect();
+    assert_eq!(counts, [1, 1, 1, 1, 1, 0, 0, 0, 0, 1]);
+}
+
+/// Times a frame where 500 widgets each request a layout several times.
+#[test]
+#[ignore]
+fn bench_layout_requests() {
+    const COUNT: usize = 500;
+
+    let (column, _) = relayout_column(&new_ids(COUNT));
+    let mut harness = TestHarness::create(column);
+
+    super::bench(&format!("{COUNT} widgets requesting layout"), 100, || {
+        harness.submit_command(RELAYOUT);
+    });
+}
+
+#[test]
+fn layout_with_debug_logger() {
+    for enabled in [false, true] {
+        let (column, layout_counts) = relayout_column(&new_ids(20));
+        let mut harness = TestHarness::create(column);
+        harness.set_debug_logger_enabled(enabled);
+        for layout_count in &layout_counts {
+            layout_count.set(0);
+        }
+
+        harness.submit_command(RELAYOUT);
+        assert!(layout_counts.iter().all(|count| count.get() == 1));
+    }
+}
+
+/// Compares the same frames with the debug logger on and off.
+///
+/// Also run it with `--no-default-features --features gtk` to compile the
+/// logger out.
+#[test]
+#[ignore]
+fn bench_debug_logger() {
+    const COUNT: usize = 500;
+
+    for enabled in [false, true] {
+        let (column, _) = relayout_column(&new_ids(COUNT));
+        let mut harness = TestHarness::create(column);
+        harness.set_debug_logger_enabled(enabled);
+
+        let logger = if enabled { "on" } else { "off" };
+        super::bench(
+            &format!("{COUNT} widgets, debug logger {logger}"),
+            20,
+            || {
+                harness.submit_command(RELAYOUT);
+            },
+        );
+    }
+}
+
+/// A leaf widget that only declares its size.
+struct FixedSize(Size);
+
+impl Widget for FixedSize {
+    fn on_event(&mut self, _ctx: &mut EventCtx, _event: &Event, _env: &Env) {}
+
+    fn on_status_change(&mut self, _ctx: &mut LifeCycleCtx, _event: &StatusChange, _env: &Env) {}
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _env: &Env) {}
+
+    fn paint(&mut self, _ctx: &mut PaintCtx, _env: &Env) {}
+
+    fn children(&self) -> SmallVec<[WidgetRef<'_, dyn Widget>; 16]> {
+        SmallVec::new()
+    }
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, env: &Env) -> Size {
+        layout_preferred_size(self, bc, env)
+    }
+
+    fn preferred_size(&self, _bc: &BoxConstraints, _env: &Env) -> Option<Size> {
+        Some(self.0)
+    }
+}
+
+#[test]
+fn layout_preferred_size() {
+    let [loose_id, tight_id] = widget_ids();
+
+    let widget = Flex::column()
+        .with_child_id(FixedSize(Size::new(30.0, 20.0)), loose_id)
+        .with_child(SizedBox::new_with_id(FixedSize(Size::new(30.0, 20.0)), tight_id).width(100.0));
+
+    let harness = TestHarness::create(widget);
+
+    assert_eq!(
+        harness.get_widget(loose_id).state().layout_rect().size(),
+        Size::new(30.0, 20.0)
+    );
+    // The preferred size is clamped to the constraints.
+    assert_eq!(
+        harness.get_widget(tight_id).state().layout_rect().size(),
+        Size::new(100.0, 20.0)
+    );
+}
+
+#[test]
+fn layout_relative_to_window() {
+    let [sidebar_id] = widget_ids();
+
+    let sidebar = ModularWidget::new(()).layout_fn(|_, ctx, bc, _| {
+        let width = ctx.window_size().width * 0.3;
+        bc.constrain(Size::new(width, 50.0))
+    });
+    let widget = Flex::row().with_child_id(sidebar, sidebar_id);
+    let mut harness = TestHarness::create_with_size(widget, Size::new(400.0, 300.0));
+
+    let sidebar_width =
+        |harness: &TestHarness| harness.get_widget(sidebar_id).state().layout_rect().width();
+    assert_eq!(sidebar_width(&harness), 120.0);
+
+    // Resizing the window lays the sidebar out again.
+    harness.process_event(Event::WindowSize(Size::new(1000.0, 300.0)));
+    assert_eq!(sidebar_width(&harness), 300.0);
+}
+
+#[test]
+fn prewarm_text_layout() {
+    let needed_rebuild = Rc::new(Cell::new(None));
+    let text = TextLayout::from_text(ArcStr::from("Hello"));
+    let widget = ModularWidget::new((text, needed_rebuild.clone())).layout_fn(
+        |(text, needed_rebuild), ctx, _, env| {
+            ctx.prewarm_text_layout(text, env);
+            needed_rebuild.set(Some(text.needs_rebuild()));
+            text.size()
+        },
+    );
+
+    // Layout runs without any paint.
+    let _harness = TestHarness::create(widget);
+
+    assert_eq!(needed_rebuild.get(), Some(false));
+}
+
+const ADD_ITEMS: Selector<usize> = Selector::new("masonry-test.add-items");
+
+/// A scrolling list that stays scrolled to the bottom when items are added.
+struct ChatLog {
+    portal: WidgetPod<Portal<Flex>>,
+}
+
+impl Widget for ChatLog {
+    fn on_event(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) {
+        if let Event::Command(cmd) = event {
+            if let Some(count) = cmd.try_get(ADD_ITEMS) {
+                {
+                    let mut portal = ctx.get_mut(&mut self.portal);
+                    for _ in 0..*count {
+                        portal
+                            .child_mut()
+                            .add_child(SizedBox::empty().width(100.0).height(50.0));
+                    }
+                }
+                // The new content height is only known after layout.
+                ctx.run_after_layout(|this: &mut ChatLog, ctx, _| {
+                    ctx.get_mut(&mut this.portal)
+                        .set_viewport_pos(Point::new(0.0, f64::INFINITY));
+                });
+                return;
+            }
+        }
+        self.portal.on_event(ctx, event, env);
+    }
+
+    fn on_status_change(&mut self, _ctx: &mut LifeCycleCtx, _event: &StatusChange, _env: &Env) {}
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, env: &Env) {
+        self.portal.lifecycle(ctx, event, env);
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, env: &Env) -> Size {
+        let size = self.portal.layout(ctx, bc, env);
+        ctx.place_child(&mut self.portal, Point::ORIGIN, env);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, env: &Env) {
+        self.portal.paint(ctx, env);
+    }
+
+    fn children(&self) -> SmallVec<[WidgetRef<'_, dyn Widget>; 16]> {
+        smallvec::smallvec![self.portal.as_dyn()]
+    }
+}
+
+/// A leaf widget with a fixed height and baseline offset.
+fn baseline_box(height: f64, baseline_offset: f64) -> impl Widget {
+    ModularWidget::new(()).layout_fn(move |_, ctx, _, _| {
+        ctx.set_baseline_offset(baseline_offset);
+        Size::new(20.0, height)
+    })
+}
+
+#[test]
+fn place_children_on_baseline() {
+    const BASELINE: f64 = 50.0;
+    let [id_1, id_2] = widget_ids();
+    let children = (
+        WidgetPod::new_with_id(baseline_box(20.0, 4.0), id_1),
+        WidgetPod::new_with_id(baseline_box(30.0, 10.0), id_2),
+    );
+    let row = ModularWidget::new(children)
+        .lifecycle_fn(|(first, second), ctx, event, env| {
+            first.lifecycle(ctx, event, env);
+            second.lifecycle(ctx, event, env);
+        })
+        .layout_fn(|(first, second), ctx, bc, env| {
+            first.layout(ctx, &bc.loosen(), env);
+            ctx.place_child_on_baseline(first, 0.0, BASELINE, env);
+            second.layout(ctx, &bc.loosen(), env);
+            ctx.place_child_on_baseline(second, 20.0, BASELINE, env);
+            Size::new(40.0, 80.0)
+        })
+        .children_fn(|(first, second)| smallvec::smallvec![first.as_dyn(), second.as_dyn()]);
+
+    let harness = TestHarness::create(row);
+
+    let first_rect = harness.get_widget(id_1).state().layout_rect();
+    let second_rect = harness.get_widget(id_2).state().layout_rect();
+    assert_eq!(first_rect.origin(), Point::new(0.0, 34.0));
+    assert_eq!(second_rect.origin(), Point::new(20.0, 30.0));
+    // Both baselines land on the same line.
+    assert_eq!(first_rect.y1 - 4.0, BASELINE);
+    assert_eq!(second_rect.y1 - 10.0, BASELINE);
+}
+
+#[test]
+fn measure_text_matches_text_layout() {
+    let sizes = Rc::new(Cell::new((Size::ZERO, Size::ZERO)));
+    let text = TextLayout::from_text(ArcStr::from("Hello, world"));
+    let widget =
+        ModularWidget::new((text, sizes.clone())).layout_fn(|(text, sizes), ctx, _, env| {
+            ctx.prewarm_text_layout(text, env);
+            let font = env.get(theme::UI_FONT);
+            let measured = ctx.measure_text("Hello, world", &font, font.size, f64::INFINITY);
+            sizes.set((measured, text.size()));
+            text.size()
+        });
+
+    let _harness = TestHarness::create(widget);
+
+    let (measured, laid_out) = sizes.get();
+    assert!(measured.width > 0.0);
+    assert_eq!(measured, laid_out);
+}
+
+#[test]
+fn run_after_layout_scrolls_to_bottom() {
+    let [log_id, portal_id] = widget_ids();
+    let widget = ChatLog {
+        portal: WidgetPod::new_with_id(Portal::new(Flex::column()), portal_id),
+    };
+
+    let mut harness =
+        TestHarness::create_with_size(widget.with_id(log_id), Size::new(200.0, 400.0));
+    let viewport_pos = |harness: &TestHarness| {
+        harness
+            .get_widget(portal_id)
+            .downcast::<Portal<Flex>>()
+            .unwrap()
+            .get_viewport_pos()
+    };
+
+    harness.submit_command(ADD_ITEMS.with(20).to(log_id));
+    // 20 items of 50px in a 400px viewport.
+    assert_eq!(viewport_pos(&harness), Point::new(0.0, 600.0));
+
+    harness.submit_command(ADD_ITEMS.with(2).to(log_id));
+    assert_eq!(viewport_pos(&harness), Point::new(0.0, 700.0));
+}
+
+#[test]
+fn run_after_layout_submits_command() {
+    const RESIZE: Selector = Selector::new("masonry-test.resize");
+    const RESIZED: Selector = Selector::new("masonry-test.resized");
+
+    let [id] = widget_ids();
+    let resized = Rc::new(Cell::new(false));
+    let widget = ModularWidget::new(resized.clone()).event_fn(|resized, ctx, event, _| {
+        if let Event::Command(cmd) = event {
+            if cmd.is(RESIZE) {
+                ctx.request_layout();
+                ctx.run_after_layout(|_: &mut ModularWidget<Rc<Cell<bool>>>, ctx, _| {
+                    ctx.submit_command(RESIZED.to(ctx.widget_id()));
+                });
+            } else if cmd.is(RESIZED) {
+                resized.set(true);
+            }
+        }
+    });
+    let mut harness = TestHarness::create(widget.with_id(id));
+
+    harness.submit_command(RESIZE.to(id));
+    assert!(resized.get());
+}
+
+/// A widget whose layout depends on the text size, and its paint on the text color.
+struct TextSizedBox {
+    layout_count: Rc<Cell<u32>>,
+    declare_keys: bool,
+}
+
+const LAYOUT_KEYS: &[Key<()>] = &[theme::TEXT_SIZE_NORMAL.erased()];
+const PAINT_KEYS: &[Key<()>] = &[theme::TEXT_COLOR.erased()];
+
+impl Widget for TextSizedBox {
+    fn on_event(&mut self, _ctx: &mut EventCtx, _event: &Event, _env: &Env) {}
+
+    fn on_status_change(&mut self, _ctx: &mut LifeCycleCtx, _event: &StatusChange, _env: &Env) {}
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _env: &Env) {}
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, env: &Env) -> Size {
+        self.layout_count.set(self.layout_count.get() + 1);
+        let side = env.get(theme::TEXT_SIZE_NORMAL) * 2.0;
+        bc.constrain(Size::new(side, side))
+    }
+
+    fn paint(&mut self, _ctx: &mut PaintCtx, _env: &Env) {}
+
+    fn children(&self) -> SmallVec<[WidgetRef<'_, dyn Widget>; 16]> {
+        SmallVec::new()
+    }
+
+    fn env_keys_affecting_layout(&self) -> Option<&[Key<()>]> {
+        self.declare_keys.then_some(LAYOUT_KEYS)
+    }
+
+    fn env_keys_affecting_paint(&self) -> Option<&[Key<()>]> {
+        self.declare_keys.then_some(PAINT_KEYS)
+    }
+}
+
+#[test]
+fn env_change_relayouts_only_on_declared_keys() {
+    let layout_count = Rc::new(Cell::new(0));
+    let widget = TextSizedBox {
+        layout_count: layout_count.clone(),
+        declare_keys: true,
+    };
+    let mut harness = TestHarness::create(widget);
+    let initial_count = layout_count.get();
+
+    harness.update_env(|env| env.set(theme::TEXT_COLOR, Color::rgb8(255, 0, 0)));
+    assert_eq!(layout_count.get(), initial_count);
+
+    harness.update_env(|env| {
+        let text_size = env.get(theme::TEXT_SIZE_NORMAL);
+        env.set(theme::TEXT_SIZE_NORMAL, text_size + 1.0);
+    });
+    assert_eq!(layout_count.get(), initial_count + 1);
+}
+
+#[test]
+fn env_change_relayouts_undeclared_widgets() {
+    let layout_count = Rc::new(Cell::new(0));
+    let widget = TextSizedBox {
+        layout_count: layout_count.clone(),
+        declare_keys: false,
+    };
+    let mut harness = TestHarness::create(widget);
+    let initial_count = layout_count.get();
+
+    harness.update_env(|env| env.set(theme::TEXT_COLOR, Color::rgb8(255, 0, 0)));
+    assert_eq!(layout_count.get(), initial_count + 1);
+}
+
+#[test]
+fn with_env_overrides_parent_env() {
+    let [id] = widget_ids();
+    let child = WidgetPod::new_with_id(
+        TextSizedBox {
+            layout_count: Rc::default(),
+            declare_keys: true,
+        },
+        id,
+    )
+    .with_env(|env| env.set(theme::TEXT_SIZE_NORMAL, 10.0));
+    let widget = ModularWidget::new(child)
+        .event_fn(|child, ctx, event, env| child.on_event(ctx, event, env))
+        .lifecycle_fn(|child, ctx, event, env| child.lifecycle(ctx, event, env))
+        .layout_fn(|child, ctx, bc, env| {
+            child.layout(ctx, &bc.loosen(), env);
+            ctx.place_child(child, Point::ORIGIN, env);
+            bc.max()
+        })
+        .paint_fn(|child, ctx, env| child.paint(ctx, env))
+        .children_fn(|child| smallvec::smallvec![child.as_dyn()]);
+    let mut harness = TestHarness::create(widget);
+    assert_eq!(harness.get_widget(id).state().size, Size::new(20.0, 20.0));
+
+    // The override is applied again to the new env, and hides the change of
+    // the key from the child.
+    harness.update_env(|env| env.set(theme::TEXT_SIZE_NORMAL, 30.0));
+    assert_eq!(harness.get_widget(id).state().size, Size::new(20.0, 20.0));
+}
+
+#[test]
+fn scrolled_off_widgets_are_not_visible() {
+    let [first, last] = widget_ids();
+    let mut list = Flex::column().with_child_id(SizedBox::empty().width(100.0).height(50.0), first);
+    for _ in 0..8 {
+        list = list.with_child(SizedBox::empty().width(100.0).height(50.0));
+    }
+    let list = list.with_child_id(SizedBox::empty().width(100.0).height(50.0), last);
+    let mut harness = TestHarness::create_with_size(Portal::new(list), Size::new(100.0, 200.0));
+
+    assert!(harness.get_widget(first).state().is_effectively_visible());
+    assert!(!harness.get_widget(last).state().is_effectively_visible());
+
+    harness.mouse_move((50.0, 50.0));
+    harness.mouse_wheel(Vec2::new(0.0, 400.0));
+    assert!(!harness.get_widget(first).state().is_effectively_visible());
+    assert!(harness.get_widget(last).state().is_effectively_visible());
+}
+
+#[test]
+fn layout_of_off_screen_widgets_is_deferred() {
+    let [first, last, added] = widget_ids();
+    let mut list = Flex::column().with_child_id(SizedBox::empty().width(100.0).height(50.0), first);
+    for _ in 0..8 {
+        list = list.with_child(SizedBox::empty().width(100.0).height(50.0));
+    }
+    let list = list.with_child_id(SizedBox::empty().width(100.0).height(50.0), last);
+    let mut harness = TestHarness::create_with_size(Portal::new(list), Size::new(100.0, 200.0));
+    harness.set_layout_budget(Some(std::time::Duration::ZERO));
+
+    harness.edit_root_widget(|mut portal, _| {
+        let mut portal = portal.downcast::<Portal<Flex>>().unwrap();
+        let mut list = portal.child_mut();
+        list.add_child_id(SizedBox::empty().width(100.0).height(50.0), added);
+    });
+
+    // The visible rows are laid out, and the off-screen rows that didn't
+    // change keep their layout.
+    assert_eq!(
+        harness.get_widget(first).state().size,
+        Size::new(100.0, 50.0)
+    );
+    assert_eq!(
+        harness.get_widget(last).state().size,
+        Size::new(100.0, 50.0)
+    );
+    assert!(!harness.get_widget(last).state().layout_deferred);
+    // The new row is laid out in the next frame.
+    assert!(harness.get_widget(added).state().layout_deferred);
+    assert_eq!(harness.get_widget(added).state().size, Size::ZERO);
+
+    harness.run_frames(1);
+    assert!(!harness.get_widget(added).state().layout_deferred);
+    assert_eq!(
+        harness.get_widget(added).state().size,
+        Size::new(100.0, 50.0)
+    );
+    assert!(!harness.root_widget().state().has_deferred_layout);
+}
+
+#[test]
+fn clipped_widgets_are_not_visible() {
+    let [inside, outside, empty] = widget_ids();
+    let children = (
+        WidgetPod::new_with_id(SizedBox::empty().width(40.0).height(40.0), inside),
+        WidgetPod::new_with_id(SizedBox::empty().width(40.0).height(40.0), outside),
+        WidgetPod::new_with_id(SizedBox::empty().width(0.0).height(40.0), empty),
+    );
+    let clipper = ModularWidget::new(children)
+        .lifecycle_fn(|(a, b, c), ctx, event, env| {
+            a.lifecycle(ctx, event, env);
+            b.lifecycle(ctx, event, env);
+            c.lifecycle(ctx, event, env);
+        })
+        .layout_fn(|(a, b, c), ctx, bc, env| {
+            ctx.set_paint_clip(Some(Rect::new(0.0, 0.0, 50.0, 100.0)));
+            for (child, x) in [(a, 0.0), (b, 60.0), (c, 0.0)] {
+                child.layout(ctx, &bc.loosen(), env);
+                ctx.place_child(child, Point::new(x, 0.0), env);
+            }
+            Size::new(100.0, 100.0)
+        })
+        .children_fn(|(a, b, c)| smallvec::smallvec![a.as_dyn(), b.as_dyn(), c.as_dyn()]);
+
+    let harness = TestHarness::create(clipper);
+
+    assert!(harness.get_widget(inside).state().is_effectively_visible());
+    assert!(!harness.get_widget(outside).state().is_effectively_visible());
+    assert!(!harness.get_widget(empty).state().is_effectively_visible());
+}
+
+#[test]
+fn layout_rect_observers() {
+    let [observer, parent, target] = widget_ids();
+    let changes = Rc::new(std::cell::RefCell::new(Vec::new()));
+
+    let observer_widget = ModularWidget::new(changes.clone())
+        .lifecycle_fn(move |_, ctx, event, _| {
+            if let LifeCycle::WidgetAdded = event {
+                ctx.observe_layout_rect(target);
+            }
+        })
+        .event_fn(|changes, _, event, _| {
+            if let Event::Command(cmd) = event {
+                if let Some(change) = cmd.try_get(LAYOUT_RECT_CHANGED) {
+                    changes.borrow_mut().push(*change);
+                }
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(10.0, 10.0));
+    let target_widget = SizedBox::new_with_id(SizedBox::empty().expand(), target);
+    let widget = Flex::column()
+        .with_child_id(observer_widget, observer)
+        .with_child_id(
+            SizedBox::new(target_widget).width(50.0).height(50.0),
+            parent,
+        );
+
+    let mut harness = TestHarness::create(widget);
+
+    // The first layout sends the current rect.
+    let rect = harness.get_widget(target).state().window_layout_rect();
+    assert_eq!(rect.size(), Size::new(50.0, 50.0));
+    assert_eq!(
+        changes.borrow_mut().drain(..).collect::<Vec<_>>(),
+        vec![LayoutRectChange {
+            widget: target,
+            rect
+        }]
+    );
+
+    // A layout that doesn't move the target sends nothing.
+    harness.edit_root_widget(|mut flex, _| {
+        let mut flex = flex.downcast::<Flex>().unwrap();
+        let mut parent = flex.child_mut(1).unwrap();
+        parent.downcast::<SizedBox>().unwrap().set_width(50.0);
+    });
+    assert!(changes.borrow().is_empty());
+
+    // Resizing the parent resizes the target.
+    harness.edit_root_widget(|mut flex, _| {
+        let mut flex = flex.downcast::<Flex>().unwrap();
+        let mut parent = flex.child_mut(1).unwrap();
+        parent.downcast::<SizedBox>().unwrap().set_width(80.0);
+    });
+    let rect = harness.get_widget(target).state().window_layout_rect();
+    assert_eq!(rect.size(), Size::new(80.0, 50.0));
+    assert_eq!(
+        changes.borrow_mut().drain(..).collect::<Vec<_>>(),
+        vec![LayoutRectChange {
+            widget: target,
+            rect
+        }]
+    );
+}
+
+#[test]
+fn place_child_snapped() {
+    let [parent_id, child_id] = widget_ids();
+    let child = WidgetPod::new_with_id(SizedBox::empty().width(10.0).height(10.0), child_id);
+    let widget = ModularWidget::new(child)
+        .lifecycle_fn(|child, ctx, event, env| {
+            if let LifeCycle::EnvChanged = event {
+                ctx.request_layout();
+            }
+            child.lifecycle(ctx, event, env);
+        })
+        .layout_fn(|child, ctx, bc, env| {
+            child.layout(ctx, &bc.loosen(), env);
+            ctx.place_child_snapped(child, Point::new(13.0, 21.0), env);
+            ctx.snap_size_to_grid(Size::new(33.0, 40.0), 8.0)
+        })
+        .children_fn(|child| smallvec::smallvec![child.as_dyn()]);
+
+    let mut harness = TestHarness::create(Flex::row().with_child_id(widget, parent_id));
+    assert_eq!(
+        harness.get_widget(parent_id).state().layout_rect().size(),
+        Size::new(40.0, 40.0)
+    );
+    assert_eq!(
+        harness.get_widget(child_id).state().layout_rect().origin(),
+        Point::new(16.0, 24.0)
+    );
+
+    harness.update_env(|env| env.set(Env::GRID_INCREMENT, 5.0));
+    assert_eq!(
+        harness.get_widget(child_id).state().layout_rect().origin(),
+        Point::new(15.0, 20.0)
+    );
+
+    harness.update_env(|env| env.set(Env::GRID_INCREMENT, 0.0));
+    assert_eq!(
+        harness.get_widget(child_id).state().layout_rect().origin(),
+        Point::new(13.0, 21.0)
+    );
+}
+
+#[test]
+fn content_sized_window_fits_screen() {
+    let mut harness = TestHarness::create(SizedBox::empty().width(300.0).height(200.0));
+    harness.window_mut().size_policy = crate::WindowSizePolicy::Content;
+    harness.window_mut().screen_size = Some(Size::new(250.0, 600.0));
+    harness.window_mut().min_size = Some(Size::new(50.0, 100.0));
+
+    // The window follows its content, up to the width of the screen.
+    harness.edit_root_widget(|mut root, _| {
+        root.downcast::<SizedBox>().unwrap().set_width(400.0);
+    });
+    assert_eq!(harness.window().size, Size::new(250.0, 200.0));
+
+    harness.edit_root_widget(|mut root, _| {
+        root.downcast::<SizedBox>().unwrap().set_width(120.0);
+    });
+    assert_eq!(harness.window().size, Size::new(120.0, 200.0));
+
+    // The minimum size applies to content smaller than it.
+    harness.edit_root_widget(|mut root, _| {
+        let mut sized_box = root.downcast::<SizedBox>().unwrap();
+        sized_box.set_width(20.0);
+        sized_box.set_height(20.0);
+    });
+    assert_eq!(harness.window().size, Size::new(50.0, 100.0));
+}
+
+#[test]
+fn layout_on_connect() {
+    fn layout_sizes(record: &Recording) -> Vec<Size> {
+        record
+            .drain()
+            .into_iter()
+            .filter_map(|record| match record {
+                Record::Layout(size) => Some(size),
+                _ => None,
+            })
+            .collect()
+    }
+
+    let initial_size = Size::new(300.0, 200.0);
+    let record = Recording::default();
+    let harness = TestHarness::create_with_layout_on_connect(
+        SizedBox::empty().expand().record(&record),
+        initial_size,
+        initial_size,
+    );
+    // The window is laid out and painted once, at its initial size. The
+    // platform then reports the same size, which changes nothing.
+    assert_eq!(layout_sizes(&record), [initial_size]);
+    assert!(harness.window().connect_frame.is_some());
+
+    let window_size = Size::new(400.0, 250.0);
+    let record = Recording::default();
+    let _harness = TestHarness::create_with_layout_on_connect(
+        SizedBox::empty().expand().record(&record),
+        initial_size,
+        window_size,
+    );
+    // A different size lays out the window again.
+    assert_eq!(layout_sizes(&record), [initial_size, window_size]);
+}
@@ -2,10 +2,14 @@
 // "as-is" basis without warranties of any kind. See the LICENSE file for
 // details.
 
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
 use insta::assert_debug_snapshot;
 
 use crate::testing::{
-    widget_ids, Record, Recording, ReplaceChild, TestHarness, TestWidgetExt as _, REPLACE_CHILD,
+    widget_ids, ModularWidget, Record, Recording, ReplaceChild, TestHarness, TestWidgetExt as _,
+    REPLACE_CHILD, REQUEST_FOCUS,
 };
 use crate::widget::{Flex, Label, SizedBox};
 use crate::*;
@@ -116,3 +120,270 @@ fn register_after_adding_child() {
     assert!(root_state.children.may_contain(&id_2));
     assert!(root_state.children.may_contain(&id_3));
 }
+
+/// Test that the children filter never misses a descendant.
+#[cfg(feature = "diagnostics")]
+#[test]
+fn children_filter_has_no_false_negatives() {
+    use crate::widget::WidgetRef;
+
+    fn descendants(widget: WidgetRef<'_, dyn Widget>, ids: &mut Vec<WidgetId>) {
+        for child in widget.children() {
+            ids.push(child.id());
+            descendants(child, ids);
+        }
+    }
+
+    fn check(widget: WidgetRef<'_, dyn Widget>) {
+        let mut ids = Vec::new();
+        descendants(widget, &mut ids);
+        let state = widget.state();
+        for id in &ids {
+            assert!(state.children_filter_may_contain(*id));
+        }
+        assert!(state.children_filter_len() >= ids.len());
+        let rate = state.children_filter_false_positive_rate();
+        assert!((0.0..=1.0).contains(&rate));
+        for child in widget.children() {
+            check(child);
+        }
+    }
+
+    let mut root = Flex::column();
+    for _ in 0..10 {
+        let mut row = Flex::row();
+        for _ in 0..10 {
+            row = row.with_child(SizedBox::empty().width(1.0).height(1.0));
+        }
+        root = root.with_child(row);
+    }
+    let harness = TestHarness::create(root);
+
+    check(harness.root_widget());
+}
+
+#[test]
+fn removed_widget_submits_command() {
+    const SAVE_STATE: Selector<u32> = Selector::new("masonry-test.save-state");
+
+    let [receiver_id] = widget_ids();
+    let saved_state = Rc::new(Cell::new(0));
+
+    let removed = ModularWidget::new(42).lifecycle_fn(move |state, ctx, event, _| {
+        if let LifeCycle::WidgetRemoved = event {
+            ctx.submit_command(SAVE_STATE.with(*state).to(receiver_id));
+        }
+    });
+    let receiver = ModularWidget::new(saved_state.clone()).event_fn(|saved_state, _, event, _| {
+        if let Event::Command(cmd) = event {
+            if let Some(state) = cmd.try_get(SAVE_STATE) {
+                saved_state.set(*state);
+            }
+        }
+    });
+
+    let widget = Flex::row()
+        .with_child(removed)
+        .with_child_id(receiver, receiver_id);
+
+    let mut harness = TestHarness::create(widget);
+
+    harness.edit_root_widget(|mut root, _| {
+        let mut flex = root.downcast::<Flex>().unwrap();
+        flex.remove_child(0);
+    });
+
+    assert_eq!(saved_state.get(), 42);
+}
+
+#[test]
+fn replace_window_root() {
+    let [old_id, focused_id, new_id] = widget_ids();
+    let old_record = Recording::default();
+    let new_record = Recording::default();
+
+    let old_root = Flex::row()
+        .with_child_id(SizedBox::empty().focusable(), focused_id)
+        .record(&old_record)
+        .with_id(old_id);
+
+    let mut harness = TestHarness::create(old_root);
+    harness.submit_command(REQUEST_FOCUS.to(focused_id));
+    assert_eq!(harness.focused_widget().map(|w| w.id()), Some(focused_id));
+    old_record.clear();
+
+    harness.set_root_widget(SizedBox::empty().record(&new_record).with_id(new_id));
+
+    assert!(old_record
+        .drain()
+        .iter()
+        .any(|record| matches!(record, Record::L(LifeCycle::WidgetRemoved))));
+    assert!(harness.try_get_widget(old_id).is_none());
+    assert_eq!(harness.root_widget().id(), new_id);
+    assert!(harness.focused_widget().is_none());
+
+    let records = new_record.drain();
+    assert!(records
+        .iter()
+        .any(|record| matches!(record, Record::L(LifeCycle::WidgetAdded))));
+    assert!(records
+        .iter()
+        .any(|record| matches!(record, Record::E(Event::WindowConnected))));
+    assert!(records
+        .iter()
+        .any(|record| matches!(record, Record::Layout(_))));
+}
+
+#[test]
+fn set_window_title() {
+    const MARK_MODIFIED: Selector<bool> = Selector::new("masonry-test.mark-modified");
+
+    // Like an editor showing unsaved changes in the title bar.
+    let editor = ModularWidget::new(()).event_fn(|_, ctx, event, _| {
+        if let Event::Command(cmd) = event {
+            if let Some(modified) = cmd.try_get(MARK_MODIFIED) {
+                let title = if *modified {
+                    "• Untitled"
+                } else {
+                    "Untitled"
+                };
+                ctx.submit_command(command::SET_WINDOW_TITLE.with(title.into()));
+            }
+        }
+    });
+    let mut harness = TestHarness::create(editor);
+
+    harness.submit_command(MARK_MODIFIED.with(true));
+    assert_eq!(&**harness.window().title(), "• Untitled");
+
+    harness.submit_command(MARK_MODIFIED.with(false));
+    assert_eq!(&**harness.window().title(), "Untitled");
+}
+
+#[test]
+fn set_window_title_while_connecting() {
+    // The command is submitted while the window connects, before it has a
+    // size or has been laid out.
+    let widget = ModularWidget::new(()).lifecycle_fn(|_, ctx, event, _| {
+        if let LifeCycle::WidgetAdded = event {
+            ctx.submit_command(command::SET_WINDOW_TITLE.with("Untitled".into()));
+        }
+    });
+    let harness = TestHarness::create_with_layout_on_connect(
+        widget,
+        Size::new(200.0, 100.0),
+        Size::new(400.0, 300.0),
+    );
+
+    assert_eq!(&**harness.window().title(), "Untitled");
+}
+
+#[test]
+fn reinit_subtree_replays_widget_added() {
+    const REINIT: Selector = Selector::new("masonry-test.reinit");
+
+    let [container_id, child_id] = widget_ids();
+    let record = Recording::default();
+
+    let container = ModularWidget::new(WidgetPod::new_with_id(
+        Label::new("hello").record(&record),
+        child_id,
+    ))
+    .event_fn(|child, ctx, event, env| {
+        if let Event::Command(cmd) = event {
+            if cmd.is(REINIT) {
+                ctx.reinit_subtree();
+                return;
+            }
+        }
+        child.on_event(ctx, event, env);
+    })
+    .lifecycle_fn(|child, ctx, event, env| {
+        child.lifecycle(ctx, event, env);
+    })
+    .layout_fn(|child, ctx, bc, env| {
+        let size = child.layout(ctx, bc, env);
+        ctx.place_child(child, Point::ZERO, env);
+        size
+    })
+    .children_fn(|child| smallvec::smallvec![child.as_dyn()]);
+
+    let mut harness = TestHarness::create(Flex::row().with_child_id(container, container_id));
+    record.clear();
+
+    harness.submit_command(REINIT.to(container_id));
+
+    let records = record.drain();
+    assert!(records
+        .iter()
+        .any(|record| matches!(record, Record::L(LifeCycle::WidgetAdded))));
+
+    // The child keeps its identity.
+    assert_eq!(harness.get_widget(child_id).id(), child_id);
+    assert!(harness
+        .get_widget(container_id)
+        .state()
+        .children
+        .may_contain(&child_id));
+}
+
+#[test]
+fn env_changed_reaches_every_widget() {
+    let record = Recording::default();
+    let locale = Rc::new(RefCell::new(ArcStr::from("")));
+    let locale_seen = locale.clone();
+
+    let reader = ModularWidget::new(()).lifecycle_fn(move |_, _, event, env| {
+        if let LifeCycle::EnvChanged = event {
+            *locale_seen.borrow_mut() = env.get(Env::LOCALE);
+        }
+    });
+    let widget = Flex::row()
+        .with_child(reader)
+        .with_child(SizedBox::empty().record(&record));
+
+    let mut harness = TestHarness::create(widget);
+    record.clear();
+
+    harness.set_locale("fr-FR");
+
+    assert_eq!(&*locale.borrow().clone(), "fr-FR");
+    let records = record.drain();
+    assert!(records
+        .iter()
+        .any(|record| matches!(record, Record::L(LifeCycle::EnvChanged))));
+    assert!(records
+        .iter()
+        .any(|record| matches!(record, Record::Layout(_))));
+}
+
+#[test]
+fn accessibility_settings_reach_widgets() {
+    let seen = Rc::new(Cell::new(AccessibilitySettings::default()));
+    let seen_inner = seen.clone();
+
+    let reader = ModularWidget::new(()).lifecycle_fn(move |_, _, event, env| {
+        if let LifeCycle::EnvChanged = event {
+            seen_inner.set(env.accessibility_settings());
+        }
+    });
+    let mut harness = TestHarness::create(reader);
+
+    // Everything is off by default.
+    assert_eq!(
+        Env::with_theme().accessibility_settings(),
+        AccessibilitySettings::default()
+    );
+
+    let settings = AccessibilitySettings {
+        reduced_transparency: true,
+        high_contrast: true,
+        increased_contrast: false,
+        focus_follows_accessibility_focus: false,
+    };
+    harness.set_accessibility_settings(settings);
+    assert_eq!(seen.get(), settings);
+
+    // Envs without the keys read as the defaults.
+    assert!(!Env::empty().high_contrast());
+}
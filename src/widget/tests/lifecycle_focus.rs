@@ -5,16 +5,16 @@
 use std::cell::Cell;
 use std::rc::Rc;
 
+use druid_shell::{KeyEvent, Region};
 use smallvec::smallvec;
 
 use crate::testing::{
     widget_ids, ModularWidget, ReplaceChild, TestHarness, TestWidgetExt as _, REPLACE_CHILD,
+    REQUEST_FOCUS,
 };
-use crate::widget::Flex;
+use crate::widget::{Flex, Portal};
 use crate::*;
 
-const REQUEST_FOCUS: Selector<()> = Selector::new("masonry-test.request-focus");
-
 struct FocusTaker;
 
 impl FocusTaker {
@@ -45,6 +45,41 @@ impl FocusTaker {
     }
 }
 
+/// A focusable widget that tracks whether it has accessibility focus.
+fn screen_reader_target(accessibility_focused: Rc<Cell<bool>>) -> impl Widget {
+    ModularWidget::new(accessibility_focused)
+        .status_change_fn(|accessibility_focused, ctx, event, _env| {
+            if let StatusChange::AccessibilityFocusChanged(focus) = event {
+                assert_eq!(*focus, ctx.is_accessibility_focused());
+                accessibility_focused.set(*focus);
+            }
+        })
+        .focusable()
+}
+
+/// A container that tracks whether focus is within its subtree.
+fn focus_within_tracker(child: impl Widget, focus_within: Rc<Cell<bool>>) -> impl Widget {
+    ModularWidget::new((WidgetPod::new(child), focus_within))
+        .event_fn(|(child, _), ctx, event, env| {
+            child.on_event(ctx, event, env);
+        })
+        .status_change_fn(|(_, focus_within), ctx, event, _env| {
+            if let StatusChange::FocusWithinChanged(focus) = event {
+                assert_eq!(*focus, ctx.has_focus_within());
+                focus_within.set(*focus);
+            }
+        })
+        .lifecycle_fn(|(child, _), ctx, event, env| {
+            child.lifecycle(ctx, event, env);
+        })
+        .layout_fn(|(child, _), ctx, bc, env| {
+            let layout = child.layout(ctx, bc, env);
+            ctx.place_child(child, Point::ZERO, env);
+            layout
+        })
+        .children_fn(|(child, _)| smallvec![child.as_dyn()])
+}
+
 /// Check that a focus chain is correctly built initially..
 #[test]
 fn build_focus_chain() {
@@ -233,3 +268,263 @@ fn resign_focus_on_disable() {
     assert_eq!(harness.window().focus_chain(), &[focus_2]);
     assert_eq!(harness.window().focus, None);
 }
+
+#[test]
+fn skip_widgets_that_cannot_receive_focus() {
+    const SET_FOCUSABLE: Selector<bool> = Selector::new("masonry-test.set-focusable");
+
+    fn make_gated_widget(focusable: bool) -> impl Widget {
+        ModularWidget::new(focusable)
+            .event_fn(|focusable, ctx, event, _env| {
+                if let Event::Command(cmd) = event {
+                    if cmd.is(REQUEST_FOCUS) {
+                        ctx.request_focus();
+                    }
+                    if let Some(new_focusable) = cmd.try_get(SET_FOCUSABLE) {
+                        *focusable = *new_focusable;
+                        ctx.focusability_changed();
+                    }
+                }
+            })
+            .lifecycle_fn(|_focusable, ctx, event, _env| {
+                if let LifeCycle::BuildFocusChain = event {
+                    ctx.register_for_focus();
+                }
+            })
+            .can_receive_focus_now_fn(|focusable| *focusable)
+    }
+
+    let [id_1, id_2, id_3] = widget_ids();
+
+    let widget = Flex::row()
+        .with_child_id(make_gated_widget(true), id_1)
+        .with_child_id(make_gated_widget(false), id_2)
+        .with_child_id(make_gated_widget(true), id_3);
+
+    let mut harness = TestHarness::create(widget);
+    assert_eq!(harness.window().focus_chain(), &[id_1, id_3]);
+
+    // Make 2 focusable -> Full focus chain
+    harness.submit_command(SET_FOCUSABLE.with(true).to(id_2));
+    assert_eq!(harness.window().focus_chain(), &[id_1, id_2, id_3]);
+
+    // Focus 2, then make it unfocusable -> 2 resigns focus
+    harness.submit_command(REQUEST_FOCUS.to(id_2));
+    assert_eq!(harness.window().focus, Some(id_2));
+
+    harness.submit_command(SET_FOCUSABLE.with(false).to(id_2));
+    assert_eq!(harness.window().focus_chain(), &[id_1, id_3]);
+    assert_eq!(harness.window().focus, None);
+}
+
+#[test]
+fn describe_focus_chain() {
+    let [id_1, id_2, id_3] = widget_ids();
+
+    let widget = Flex::column()
+        .with_child_id(FocusTaker::new(), id_1)
+        .with_child_id(FocusTaker::new(), id_2)
+        .with_child_id(FocusTaker::new(), id_3);
+
+    let harness = TestHarness::create(widget);
+
+    let entries = harness.window().focus_chain_entries();
+    let ids: Vec<_> = entries.iter().map(|entry| entry.id).collect();
+    assert_eq!(ids, [id_1, id_2, id_3]);
+
+    for entry in &entries {
+        assert_eq!(entry.type_name, "ModularWidget");
+        assert_eq!(
+            entry.window_layout_rect,
+            harness.get_widget(entry.id).state().window_layout_rect()
+        );
+    }
+    assert!(entries[0].window_layout_rect.y1 <= entries[1].window_layout_rect.y0);
+}
+
+#[test]
+fn accessibility_focus() {
+    let [id_1, id_2, portal_id] = widget_ids();
+    let target_focused = Rc::new(Cell::new(false));
+
+    let column = Flex::column()
+        .with_child_id(FocusTaker::new(), id_1)
+        .with_spacer(1000.0)
+        .with_child_id(screen_reader_target(target_focused.clone()), id_2);
+    let mut harness = TestHarness::create(Portal::new(column).with_id(portal_id));
+    let viewport_pos = |harness: &TestHarness| {
+        harness
+            .get_widget(portal_id)
+            .downcast::<Portal<Flex>>()
+            .unwrap()
+            .get_viewport_pos()
+    };
+
+    harness.submit_command(REQUEST_FOCUS.to(id_1));
+    assert_eq!(harness.window().focus, Some(id_1));
+
+    // By default, the screen reader cursor moves on its own, and scrolls
+    // its target into view.
+    harness.set_accessibility_focus(Some(id_2));
+    assert!(target_focused.get());
+    assert!(viewport_pos(&harness).y > 0.0);
+    assert_eq!(harness.window().focus, Some(id_1));
+
+    harness.set_accessibility_focus(None);
+    assert!(!target_focused.get());
+    assert_eq!(harness.window().focus, Some(id_1));
+
+    // Keyboard focus can follow it.
+    harness.set_accessibility_settings(AccessibilitySettings {
+        focus_follows_accessibility_focus: true,
+        ..Default::default()
+    });
+    harness.set_accessibility_focus(Some(id_2));
+    assert!(target_focused.get());
+    assert_eq!(harness.window().focus, Some(id_2));
+}
+
+#[test]
+fn accessibility_focus_cleared_on_removal() {
+    let [target_id] = widget_ids();
+    let target_focused = Rc::new(Cell::new(false));
+
+    let widget = Flex::column()
+        .with_child(FocusTaker::new())
+        .with_child_id(screen_reader_target(target_focused.clone()), target_id);
+    let mut harness = TestHarness::create(widget);
+
+    harness.set_accessibility_focus(Some(target_id));
+    assert_eq!(harness.window().accessibility_focus, Some(target_id));
+
+    harness.edit_root_widget(|mut root, _| {
+        let mut flex = root.downcast::<Flex>().unwrap();
+        flex.remove_child(1);
+    });
+    assert_eq!(harness.window().accessibility_focus, None);
+}
+
+#[test]
+fn focus_within() {
+    let [field_id, other_id] = widget_ids();
+    let form_focused = Rc::new(Cell::new(false));
+
+    let form = Flex::column().with_child(Flex::row().with_child_id(FocusTaker::new(), field_id));
+    let root = Flex::row()
+        .with_child(focus_within_tracker(form, form_focused.clone()))
+        .with_child_id(FocusTaker::new(), other_id);
+    let mut harness = TestHarness::create(root);
+    assert!(!form_focused.get());
+
+    // Focusing a nested field puts focus within the form.
+    harness.submit_command(REQUEST_FOCUS.to(field_id));
+    assert!(form_focused.get());
+
+    harness.submit_command(REQUEST_FOCUS.to(other_id));
+    assert!(!form_focused.get());
+}
+
+/// A focusable square, which may paint its own focus ring.
+fn focus_square(suppress_ring: bool) -> impl Widget {
+    ModularWidget::new(())
+        .lifecycle_fn(move |_, ctx, event, _env| {
+            if let LifeCycle::BuildFocusChain = event {
+                if suppress_ring {
+                    ctx.suppress_focus_ring();
+                }
+            }
+        })
+        .layout_fn(|_, _, bc, _| bc.constrain(Size::new(40.0, 40.0)))
+        .focusable()
+}
+
+#[test]
+fn auto_focus_ring() {
+    let render_focused = |suppress_ring: bool, auto_ring: bool| {
+        let [id] = widget_ids();
+        let widget = Flex::row().with_child(focus_square(suppress_ring).with_id(id));
+        let mut harness = TestHarness::create_with_size(widget, Size::new(60.0, 60.0));
+        harness.update_env(|env| env.set(Env::AUTO_FOCUS_RING, auto_ring));
+        harness.submit_command(REQUEST_FOCUS.to(id));
+        // Focus is only shown after keyboard use.
+        let unfocused = harness.render();
+        harness.process_event(Event::KeyDown(KeyEvent::default()));
+        let focused = harness.render();
+        (unfocused, focused)
+    };
+
+    let (unfocused, focused) = render_focused(false, true);
+    assert!(unfocused != focused);
+
+    let (unfocused, focused) = render_focused(false, false);
+    assert!(unfocused == focused);
+
+    let (unfocused, focused) = render_focused(true, true);
+    assert!(unfocused == focused);
+}
+
+#[test]
+fn focus_ring_is_erased_when_focus_moves() {
+    let [first, second] = widget_ids();
+    let widget = Flex::row()
+        .with_child(focus_square(false).with_id(first))
+        .with_child(focus_square(false).with_id(second));
+    let mut harness = TestHarness::create_with_size(widget, Size::new(100.0, 60.0));
+    harness.update_env(|env| env.set(Env::AUTO_FOCUS_RING, true));
+    let unfocused = harness.render();
+    harness.submit_command(REQUEST_FOCUS.to(first));
+    harness.process_event(Event::KeyDown(KeyEvent::default()));
+    *harness.window_mut().invalid_mut() = Region::from(Rect::new(0.0, 0.0, 100.0, 60.0));
+    let focused = harness.render();
+
+    // The ring is painted within the widget's layout rect...
+    let ring_rect = harness.get_widget(first).state().window_layout_rect();
+    for (idx, (before, after)) in unfocused.chunks(4).zip(focused.chunks(4)).enumerate() {
+        let pixel = Point::new((idx % 100) as f64 + 0.5, (idx / 100) as f64 + 0.5);
+        if !ring_rect.contains(pixel) {
+            assert_eq!(before, after, "ring painted at {pixel:?}");
+        }
+    }
+
+    // ...which is repainted when the focus moves away.
+    harness.submit_command(REQUEST_FOCUS.to(second));
+    assert!(harness
+        .window()
+        .invalid()
+        .rects()
+        .iter()
+        .any(|rect| rect.union(ring_rect) == *rect));
+}
+
+#[test]
+fn focus_ring_suppression_is_rebuilt() {
+    const SET_SUPPRESS: Selector<bool> = Selector::new("masonry-test.set-suppress");
+
+    let [id] = widget_ids();
+    let widget = ModularWidget::new(true)
+        .event_fn(|suppress_ring, ctx, event, _| {
+            if let Event::Command(cmd) = event {
+                if let Some(suppress) = cmd.try_get(SET_SUPPRESS) {
+                    *suppress_ring = *suppress;
+                    ctx.focusability_changed();
+                }
+            }
+        })
+        .lifecycle_fn(|suppress_ring, ctx, event, _| {
+            if let LifeCycle::BuildFocusChain = event {
+                ctx.register_for_focus();
+                if *suppress_ring {
+                    ctx.suppress_focus_ring();
+                }
+            }
+        })
+        .with_id(id);
+    let mut harness = TestHarness::create(Flex::row().with_child(widget));
+    assert!(harness.get_widget(id).state().draws_own_focus_ring);
+
+    harness.submit_command(SET_SUPPRESS.with(false).to(id));
+    assert!(!harness.get_widget(id).state().draws_own_focus_ring);
+
+    harness.submit_command(SET_SUPPRESS.with(true).to(id));
+    assert!(harness.get_widget(id).state().draws_own_focus_ring);
+}
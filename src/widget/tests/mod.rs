@@ -3,15 +3,39 @@
 // details.
 
 mod aspect_ratio;
+mod clicks;
+mod command_batching;
+mod drag_and_drop;
+mod event_interception;
 mod event_notification;
 mod invalidation;
+mod keyboard;
 mod layout;
 mod lifecycle_basic;
 mod lifecycle_disable;
 mod lifecycle_focus;
+mod pointer_velocity;
 mod safety_rails;
+mod state_propagation;
 mod status_change;
 mod timers;
+mod undo;
+mod z_order;
+
+use std::time::Instant;
+
+/// Time `runs` calls of `run`, and print the total and per-run durations.
+///
+/// Benchmarks are `#[ignore]`d tests calling this. Run one with
+/// `cargo test --release <name> -- --ignored --nocapture`.
+fn bench(label: &str, runs: u32, mut run: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..runs {
+        run();
+    }
+    let elapsed = start.elapsed();
+    println!("{label}: {elapsed:?}, {:?} per run", elapsed / runs);
+}
 
 // TODO
 // - InternalLifeCycle::RouteDisabledChanged
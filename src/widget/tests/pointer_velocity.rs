@@ -0,0 +1,61 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use druid_shell::MouseButton;
+
+use crate::testing::{ModularWidget, TestHarness};
+use crate::*;
+
+/// A widget that becomes active on click and records the pointer velocity on each move.
+fn velocity_tracker(velocity: Rc<Cell<Vec2>>) -> impl Widget {
+    ModularWidget::new(velocity).event_fn(|velocity, ctx, event, _| match event {
+        Event::MouseDown(_) => ctx.set_active(true),
+        Event::MouseMove(_) if ctx.is_active() => velocity.set(ctx.pointer_velocity()),
+        Event::MouseUp(_) => ctx.set_active(false),
+        _ => (),
+    })
+}
+
+#[test]
+fn pointer_velocity_from_moves() {
+    let velocity = Rc::new(Cell::new(Vec2::ZERO));
+    let mut harness = TestHarness::create(velocity_tracker(velocity.clone()));
+
+    harness.mouse_move((10.0, 10.0));
+    harness.mouse_button_press(MouseButton::Left);
+
+    for i in 1..=4 {
+        harness.move_timers_forward(Duration::from_millis(10));
+        harness.mouse_move((10.0 + 5.0 * i as f64, 10.0 - 2.0 * i as f64));
+    }
+
+    // 5px right and 2px up every 10ms.
+    let expected = Vec2::new(500.0, -200.0);
+    assert!((velocity.get() - expected).hypot() < 1e-6);
+}
+
+#[test]
+fn pointer_velocity_resets_on_mouse_down() {
+    let velocity = Rc::new(Cell::new(Vec2::ZERO));
+    let mut harness = TestHarness::create(velocity_tracker(velocity.clone()));
+
+    harness.mouse_move((10.0, 10.0));
+    harness.mouse_button_press(MouseButton::Left);
+    harness.move_timers_forward(Duration::from_millis(10));
+    harness.mouse_move((50.0, 10.0));
+    harness.mouse_button_release(MouseButton::Left);
+
+    // A new press starts a new gesture: only moves after it count.
+    harness.move_timers_forward(Duration::from_millis(10));
+    harness.mouse_button_press(MouseButton::Left);
+    harness.move_timers_forward(Duration::from_millis(20));
+    harness.mouse_move((50.0, 30.0));
+
+    let expected = Vec2::new(0.0, 1000.0);
+    assert!((velocity.get() - expected).hypot() < 1e-6);
+}
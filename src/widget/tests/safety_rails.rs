@@ -2,10 +2,13 @@
 // "as-is" basis without warranties of any kind. See the LICENSE file for
 // details.
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use smallvec::smallvec;
 
 use crate::testing::{ModularWidget, TestHarness};
-use crate::widget::Flex;
+use crate::widget::{Flex, SizedBox};
 use crate::*;
 
 fn make_parent_widget<W: Widget>(child: W) -> ModularWidget<WidgetPod<W>> {
@@ -39,6 +42,26 @@ fn check_forget_to_recurse_event() {
     harness.mouse_move(Point::ZERO);
 }
 
+#[test]
+fn debug_panic_handler_receives_errors() {
+    let messages = Rc::new(RefCell::new(Vec::new()));
+    let messages_clone = messages.clone();
+    set_debug_panic_handler(move |message| messages_clone.borrow_mut().push(message.to_string()));
+
+    let widget = make_parent_widget(Flex::row()).event_fn(|_child, _ctx, _event, _| {
+        // We forget to call child.on_event();
+    });
+
+    let mut harness = TestHarness::create(widget);
+    harness.mouse_move(Point::ZERO);
+    clear_debug_panic_handler();
+
+    assert!(messages
+        .borrow()
+        .iter()
+        .any(|message| message.contains("not visited in method event")));
+}
+
 #[should_panic(expected = "not visited in method lifecycle")]
 #[test]
 fn check_forget_to_recurse_lifecycle() {
@@ -340,3 +363,37 @@ fn check_paint_rect_includes_children() {
     harness.mouse_move(Point::ZERO);
     harness.render();
 }
+
+#[test]
+fn allow_acknowledged_overflow() {
+    let widget = make_parent_widget(SizedBox::empty().width(20.0).height(20.0)).layout_fn(
+        |child, ctx, bc, env| {
+            child.layout(ctx, &bc.loosen(), env);
+            ctx.place_child(child, Point::new(30.0, 30.0), env);
+            ctx.expand_paint_rect_to_include(child.paint_rect());
+            Size::new(40.0, 40.0)
+        },
+    );
+
+    let harness = TestHarness::create_with_size(widget, Size::new(40.0, 40.0));
+    assert_eq!(
+        harness.root_widget().state().paint_rect(),
+        Rect::new(0.0, 0.0, 50.0, 50.0)
+    );
+}
+
+#[should_panic(expected = "doesn't contain paint_rect")]
+#[test]
+fn check_unacknowledged_overflow() {
+    let widget = make_parent_widget(SizedBox::empty().width(20.0).height(20.0)).layout_fn(
+        |child, ctx, bc, env| {
+            child.layout(ctx, &bc.loosen(), env);
+            ctx.place_child(child, Point::new(30.0, 30.0), env);
+            // We acknowledge a badge in the corner, but not the overflowing child.
+            ctx.expand_paint_rect_to_include(Rect::new(-5.0, -5.0, 5.0, 5.0));
+            Size::new(40.0, 40.0)
+        },
+    );
+
+    let _harness = TestHarness::create_with_size(widget, Size::new(40.0, 40.0));
+}
@@ -0,0 +1,79 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+//! Tests related to the merging of widget state into ancestors.
+
+use druid_shell::MouseButton;
+
+use crate::testing::{widget_ids, TestHarness, TestWidgetExt as _};
+use crate::widget::{Button, Flex, SizedBox};
+use crate::*;
+
+/// Nest `leaf` in `depth` columns, each with an empty sibling.
+fn deep_tree(depth: usize, leaf: impl Widget) -> Box<dyn Widget> {
+    let mut widget: Box<dyn Widget> = Box::new(leaf);
+    for _ in 0..depth {
+        widget = Box::new(
+            Flex::column()
+                .with_child(SizedBox::empty().width(1.0).height(1.0))
+                .with_child(widget),
+        );
+    }
+    widget
+}
+
+#[test]
+fn leaf_changes_reach_root_of_deep_tree() {
+    let [button] = widget_ids();
+
+    let mut harness = TestHarness::create(deep_tree(50, Button::new("leaf").with_id(button)));
+    let _ = harness.render();
+    assert!(!harness.root_widget().state().has_hot);
+
+    harness.mouse_move_to(button);
+    assert!(harness.root_widget().state().has_hot);
+    let button_rect = harness.get_widget(button).state().window_layout_rect();
+    assert!(harness.window().invalid().rects().contains(&button_rect));
+
+    harness.mouse_button_press(MouseButton::Left);
+    assert!(harness.get_widget(button).state().is_active);
+    assert!(harness.root_widget().state().has_active);
+
+    harness.mouse_button_release(MouseButton::Left);
+    assert!(!harness.root_widget().state().has_active);
+    assert!(matches!(
+        harness.pop_action(),
+        Some((Action::ButtonPressed, id)) if id == button
+    ));
+
+    // Nothing changes on the way back, so nothing is left over.
+    let _ = harness.render();
+    harness.mouse_move_to(button);
+    assert!(harness.window().invalid().is_empty());
+    assert!(!harness.root_widget().state().needs_layout);
+}
+
+/// Measures the dispatch of mouse moves to the leaf of a deep tree.
+#[test]
+#[ignore]
+fn bench_deep_tree_dispatch() {
+    const DEPTH: usize = 200;
+    const COUNT: u32 = 1000;
+
+    let [button] = widget_ids();
+    let mut harness = TestHarness::create(deep_tree(DEPTH, Button::new("leaf").with_id(button)));
+    let center = harness
+        .get_widget(button)
+        .state()
+        .window_layout_rect()
+        .center();
+
+    // Alternate between two points inside the button, so only the leaf
+    // sees the pointer move.
+    let mut offset = 0.0;
+    super::bench(&format!("mouse moves at depth {DEPTH}"), COUNT, || {
+        offset = 1.0 - offset;
+        harness.mouse_move(center + Vec2::new(offset, 0.0));
+    });
+}
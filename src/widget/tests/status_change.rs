@@ -2,12 +2,12 @@
 // "as-is" basis without warranties of any kind. See the LICENSE file for
 // details.
 
-use druid_shell::MouseButton;
+use druid_shell::{KbKey, KeyEvent, Modifiers, MouseButton};
 
 use crate::testing::{
-    widget_ids, ModularWidget, Record, Recording, TestHarness, TestWidgetExt as _,
+    widget_ids, ModularWidget, Record, Recording, TestHarness, TestWidgetExt as _, REQUEST_FOCUS,
 };
-use crate::widget::{Button, Flex, Label, SizedBox};
+use crate::widget::{Button, Flex, Label, SizedBox, TextBox};
 use crate::*;
 
 fn is_hot(harness: &TestHarness, id: WidgetId) -> bool {
@@ -139,6 +139,108 @@ fn update_hot_on_mouse_leave() {
     assert_eq!(next_hot_changed(&label_rec), Some(false));
 }
 
+#[test]
+fn keep_tracking_captured_pointer_outside_window() {
+    let [button, label] = widget_ids();
+
+    let button_rec = Recording::default();
+
+    let widget = Flex::row()
+        .with_child_id(Button::new("hello").record(&button_rec), button)
+        .with_child_id(Label::new("world"), label);
+
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(label);
+    harness.mouse_move_to(button);
+    harness.mouse_button_press(MouseButton::Left);
+    assert!(harness.get_widget(button).state().is_active);
+
+    // The pointer leaves the window while the button has captured it.
+    button_rec.clear();
+    harness.mouse_leave();
+
+    assert!(is_hot(&harness, button));
+    assert_eq!(next_hot_changed(&button_rec), None);
+
+    // Moves and the release from outside the window still reach the button.
+    button_rec.clear();
+    harness.mouse_move((-50.0, -50.0));
+    harness.mouse_button_release(MouseButton::Left);
+
+    let events = button_rec.drain();
+    assert!(events
+        .iter()
+        .any(|record| matches!(record, Record::E(Event::MouseMove(_)))));
+    assert!(events
+        .iter()
+        .any(|record| matches!(record, Record::E(Event::MouseUp(_)))));
+    assert!(!harness.get_widget(button).state().is_active);
+    assert!(!is_hot(&harness, button));
+
+    // Without a capture, leaving the window clears hot state right away.
+    harness.mouse_move_to(label);
+    harness.mouse_leave();
+    assert!(!is_hot(&harness, label));
+}
+
+#[test]
+fn hover_delay() {
+    const HOVER_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
+    let [delayed, other] = widget_ids();
+    let delayed_rec = Recording::default();
+
+    let delayed_widget = ModularWidget::new(())
+        .lifecycle_fn(|_, ctx, event, _| {
+            if let LifeCycle::WidgetAdded = event {
+                ctx.set_hover_delay(Some(HOVER_DELAY));
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(50.0, 50.0));
+    let widget = Flex::row()
+        .with_child_id(delayed_widget.record(&delayed_rec), delayed)
+        .with_child_id(SizedBox::empty().width(50.0).height(50.0), other);
+
+    let mut harness = TestHarness::create(widget);
+    delayed_rec.clear();
+
+    // The widget only becomes hot once the delay elapsed.
+    harness.mouse_move_to(delayed);
+    assert!(!is_hot(&harness, delayed));
+    harness.move_timers_forward(HOVER_DELAY);
+    assert!(is_hot(&harness, delayed));
+    assert_eq!(next_hot_changed(&delayed_rec), Some(true));
+
+    // Passing out and back quickly cancels the change. The timer of the
+    // cancelled change isn't delivered to the widget.
+    harness.mouse_move_to(other);
+    harness.mouse_move_to(delayed);
+    delayed_rec.clear();
+    harness.move_timers_forward(HOVER_DELAY);
+    assert!(is_hot(&harness, delayed));
+    assert!(!delayed_rec
+        .drain()
+        .iter()
+        .any(|record| matches!(record, Record::E(Event::Timer(_)))));
+
+    // Leaving for good is deferred too.
+    harness.mouse_move_to(other);
+    assert!(is_hot(&harness, delayed));
+    harness.move_timers_forward(HOVER_DELAY);
+    assert!(!is_hot(&harness, delayed));
+    assert_eq!(next_hot_changed(&delayed_rec), Some(false));
+
+    // Presses aren't delayed.
+    harness.mouse_move_to(delayed);
+    harness.mouse_button_press(MouseButton::Left);
+    assert!(is_hot(&harness, delayed));
+    assert!(delayed_rec
+        .drain()
+        .iter()
+        .any(|record| matches!(record, Record::E(Event::MouseDown(_)))));
+}
+
 #[test]
 fn update_hot_from_layout() {
     pub const COLLAPSE: Selector = Selector::new("masonry-test.collapse");
@@ -193,6 +295,65 @@ fn update_hot_from_layout() {
     assert_eq!(next_hot_changed(&box_rec), Some(true));
 }
 
+#[test]
+fn update_nested_hot_from_layout() {
+    pub const COLLAPSE: Selector = Selector::new("masonry-test.collapse");
+
+    let [collapsable_id, box_id, inner_box_id] = widget_ids();
+
+    let inner_box_rec = Recording::default();
+
+    let collapsable_box = ModularWidget::new(false)
+        .event_fn(move |collapsed, ctx, event, _env| {
+            if let Event::Command(command) = event {
+                if command.is(COLLAPSE) {
+                    *collapsed = true;
+                    ctx.request_layout();
+                }
+            }
+        })
+        .layout_fn(move |collapsed, _ctx, _bc, _env| {
+            if *collapsed {
+                Size::ZERO
+            } else {
+                Size::new(50.0, 50.0)
+            }
+        });
+
+    // The inner box is placed by its parent, which doesn't move relative to it,
+    // so only a synthetic MouseMove can update its hot state.
+    let nested_box = SizedBox::new(
+        SizedBox::empty()
+            .width(50.0)
+            .height(50.0)
+            .record(&inner_box_rec)
+            .with_id(inner_box_id),
+    );
+
+    let widget = Flex::row()
+        .with_child(
+            Flex::column()
+                .with_child_id(collapsable_box, collapsable_id)
+                .with_child_id(nested_box, box_id)
+                .with_flex_spacer(1.0),
+        )
+        .with_flex_spacer(1.0);
+
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(collapsable_id);
+    assert!(is_hot(&harness, collapsable_id));
+    assert!(!is_hot(&harness, inner_box_id));
+
+    inner_box_rec.clear();
+    harness.submit_command(COLLAPSE);
+    assert!(!is_hot(&harness, collapsable_id));
+    assert!(is_hot(&harness, box_id));
+    assert!(is_hot(&harness, inner_box_id));
+
+    assert_eq!(next_hot_changed(&inner_box_rec), Some(true));
+}
+
 #[test]
 fn get_mouse_events_while_active() {
     fn next_mouse_event(recording: &Recording) -> Option<Event> {
@@ -306,3 +467,635 @@ fn get_mouse_events_while_active() {
     harness.mouse_move_to(empty_2);
     assert!(next_mouse_event(&button_rec).is_none());
 }
+
+#[test]
+fn window_focus_reaches_every_widget() {
+    let [inner] = widget_ids();
+    let root_rec = Recording::default();
+    let inner_rec = Recording::default();
+
+    // Records what the widget sees through its context when notified.
+    let seen = std::rc::Rc::new(std::cell::Cell::new(None));
+    let observer = ModularWidget::new(seen.clone()).status_change_fn(|seen, ctx, event, _| {
+        if let StatusChange::WindowFocusChanged(_) = event {
+            seen.set(Some(ctx.is_window_focused()));
+        }
+    });
+
+    let widget = Flex::column()
+        .with_child_id(SizedBox::empty().record(&inner_rec), inner)
+        .with_child(observer)
+        .record(&root_rec);
+
+    let mut harness = TestHarness::create(widget);
+    root_rec.clear();
+    inner_rec.clear();
+
+    harness.set_window_focused(false);
+    for recording in [&root_rec, &inner_rec] {
+        assert!(recording
+            .drain()
+            .iter()
+            .any(|record| matches!(record, Record::SC(StatusChange::WindowFocusChanged(false)))));
+    }
+    assert_eq!(seen.get(), Some(false));
+
+    // Setting the same state again doesn't notify anyone.
+    harness.set_window_focused(false);
+    assert!(root_rec
+        .drain()
+        .iter()
+        .all(|record| !matches!(record, Record::SC(_))));
+
+    harness.set_window_focused(true);
+    assert_eq!(seen.get(), Some(true));
+}
+
+#[test]
+fn new_window_starts_focused() {
+    let seen = std::rc::Rc::new(std::cell::Cell::new(None));
+    let widget = ModularWidget::new(seen.clone()).lifecycle_fn(|seen, ctx, event, _| {
+        if let LifeCycle::WidgetAdded = event {
+            seen.set(Some(ctx.is_window_focused()));
+        }
+    });
+
+    let harness = TestHarness::create(widget);
+    assert_eq!(seen.get(), Some(true));
+    assert!(harness.window().window_focused);
+}
+
+#[test]
+fn paint_clip_limits_hit_testing_and_painting() {
+    let [child_id] = widget_ids();
+    let child_rec = Recording::default();
+
+    let child = ModularWidget::new(())
+        .layout_fn(|_, _, _, _| Size::new(100.0, 100.0))
+        .paint_fn(|_, ctx, _| {
+            let rect = ctx.size().to_rect();
+            ctx.fill(rect, &Color::WHITE);
+        })
+        .record(&child_rec)
+        .with_id(child_id);
+
+    // The child is as large as its parent, but clipped to its left half.
+    let parent = ModularWidget::single_child(child).layout_fn(|child, ctx, bc, env| {
+        ctx.set_paint_clip(Some(Rect::new(0.0, 0.0, 50.0, 100.0)));
+        child.layout(ctx, &bc.loosen(), env);
+        ctx.place_child(child, Point::ORIGIN, env);
+        Size::new(100.0, 100.0)
+    });
+
+    let mut harness = TestHarness::create_with_size(parent, Size::new(100.0, 100.0));
+
+    harness.mouse_move((75.0, 50.0));
+    assert!(!is_hot(&harness, child_id));
+    harness.mouse_move((25.0, 50.0));
+    assert!(is_hot(&harness, child_id));
+
+    // Clicks outside the clip don't reach the child.
+    harness.mouse_move((75.0, 50.0));
+    child_rec.clear();
+    harness.mouse_button_press(MouseButton::Left);
+    assert!(!child_rec
+        .drain()
+        .iter()
+        .any(|record| matches!(record, Record::E(Event::MouseDown(_)))));
+
+    let image = harness.render();
+    let red_at = |x: usize, y: usize| image[(y * 100 + x) * 4];
+    assert_eq!(red_at(25, 50), 0xff);
+    assert_eq!(red_at(75, 50), 0x29);
+}
+
+#[test]
+fn widget_state_diff() {
+    let [button] = widget_ids();
+
+    let widget = Flex::column()
+        .with_spacer(10.0)
+        .with_child_id(Button::new("hot"), button);
+    let mut harness = TestHarness::create(widget);
+
+    let before = harness.get_widget(button).state().clone();
+    assert!(harness.get_widget(button).state().diff(&before).is_empty());
+
+    harness.mouse_move_to(button);
+    let delta = harness.get_widget(button).state().diff(&before);
+    assert_eq!(delta.flags, [("is_hot", true)]);
+    assert_eq!(delta.layout_rect, None);
+    assert_eq!(delta.changed(), ["is_hot"]);
+}
+
+#[test]
+fn widget_state_snapshots() {
+    let [button] = widget_ids();
+
+    let widget = Flex::column()
+        .with_spacer(10.0)
+        .with_child_id(Button::new("hot"), button);
+    let mut harness = TestHarness::create(widget);
+
+    let before = harness.state_snapshot(button);
+    assert_eq!(before, harness.state_snapshot(button));
+    assert!(before.flags().is_empty());
+
+    harness.mouse_move_to(button);
+    harness.mouse_button_press(MouseButton::Left);
+    let after = harness.state_snapshot(button);
+    assert_eq!(after.flags(), ["hot", "active"]);
+    assert_eq!(after.layout_rect(), before.layout_rect());
+    assert_eq!(
+        after,
+        testing::WidgetStateSnapshot {
+            is_hot: true,
+            is_active: true,
+            ..before
+        }
+    );
+}
+
+#[test]
+fn hot_changes_suppressed_while_scrolling() {
+    const SETTLE_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+    let [first, second] = widget_ids();
+
+    let mut list = Flex::column()
+        .with_child_id(SizedBox::empty().width(100.0).height(50.0), first)
+        .with_child_id(SizedBox::empty().width(100.0).height(50.0), second);
+    for _ in 0..8 {
+        list = list.with_child(SizedBox::empty().width(100.0).height(50.0));
+    }
+    let widget = widget::Portal::new(list).suppress_hover_while_scrolling(SETTLE_DELAY);
+    let mut harness = TestHarness::create_with_size(widget, Size::new(100.0, 200.0));
+
+    harness.mouse_move((50.0, 25.0));
+    assert!(is_hot(&harness, first));
+
+    // The second item scrolls under the pointer, but hot state doesn't follow yet.
+    harness.mouse_wheel(Vec2::new(0.0, 50.0));
+    assert!(is_hot(&harness, first));
+    assert!(!is_hot(&harness, second));
+
+    harness.move_timers_forward(SETTLE_DELAY);
+    assert!(!is_hot(&harness, first));
+    assert!(is_hot(&harness, second));
+}
+
+fn context_menu_requests(recording: &Recording) -> Vec<ContextMenuRequest> {
+    recording
+        .drain()
+        .into_iter()
+        .filter_map(|record| match record {
+            Record::E(Event::ContextMenuRequested(request)) => Some(request),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn secondary_click_requests_context_menu() {
+    let [inner, outer] = widget_ids();
+    let inner_rec = Recording::default();
+    let outer_rec = Recording::default();
+
+    let inner_widget = ModularWidget::new(())
+        .event_fn(|_, ctx, event, _| {
+            if let Event::ContextMenuRequested(_) = event {
+                ctx.set_handled();
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(50.0, 50.0));
+    let widget = Flex::row()
+        .with_child_id(inner_widget.record(&inner_rec), inner)
+        .with_child_id(
+            SizedBox::empty()
+                .width(50.0)
+                .height(50.0)
+                .record(&outer_rec),
+            outer,
+        );
+    let mut harness = TestHarness::create(widget);
+
+    // A primary click doesn't request a menu.
+    harness.mouse_move((10.0, 20.0));
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_button_release(MouseButton::Left);
+    assert_eq!(context_menu_requests(&inner_rec), vec![]);
+
+    // The request carries the pointer position in window coordinates.
+    harness.mouse_button_press(MouseButton::Right);
+    assert_eq!(
+        context_menu_requests(&inner_rec),
+        vec![ContextMenuRequest {
+            window_pos: Point::new(10.0, 20.0),
+            from_keyboard: false,
+        }]
+    );
+    harness.mouse_button_release(MouseButton::Right);
+
+    // Only the widgets under the pointer receive it.
+    assert_eq!(context_menu_requests(&outer_rec), vec![]);
+}
+
+#[test]
+fn secondary_click_on_text_box_requests_context_menu() {
+    let text_box_rec = Recording::default();
+    let widget = Flex::row().with_child(TextBox::new("Hello").record(&text_box_rec));
+    let mut harness = TestHarness::create(widget);
+
+    // Focus the text box first: editing doesn't consume secondary clicks.
+    harness.mouse_move((10.0, 10.0));
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_button_release(MouseButton::Left);
+    text_box_rec.clear();
+
+    harness.mouse_button_press(MouseButton::Right);
+    harness.mouse_button_release(MouseButton::Right);
+    assert_eq!(
+        context_menu_requests(&text_box_rec),
+        vec![ContextMenuRequest {
+            window_pos: Point::new(10.0, 10.0),
+            from_keyboard: false,
+        }]
+    );
+}
+
+#[test]
+fn menu_key_requests_context_menu_at_focused_widget() {
+    let [hovered, focused] = widget_ids();
+    let hovered_rec = Recording::default();
+    let focused_rec = Recording::default();
+
+    let focusable = || {
+        ModularWidget::new(())
+            .event_fn(|_, ctx, event, _| {
+                if let Event::ContextMenuRequested(_) = event {
+                    ctx.set_handled();
+                }
+            })
+            .layout_fn(|_, _, _, _| Size::new(50.0, 30.0))
+            .focusable()
+    };
+    let widget = Flex::row()
+        .with_child_id(focusable().record(&hovered_rec), hovered)
+        .with_child_id(focusable().record(&focused_rec), focused);
+    let mut harness = TestHarness::create(widget);
+
+    harness.submit_command(REQUEST_FOCUS.to(focused));
+    // The pointer being over another widget doesn't matter.
+    harness.mouse_move((10.0, 10.0));
+    hovered_rec.clear();
+    focused_rec.clear();
+    // The menu is placed at the bottom-left corner of the focused widget.
+    let keyboard_request = ContextMenuRequest {
+        window_pos: Point::new(50.0, 30.0),
+        from_keyboard: true,
+    };
+
+    harness.process_event(Event::KeyDown(KeyEvent {
+        key: KbKey::ContextMenu,
+        ..Default::default()
+    }));
+    assert_eq!(context_menu_requests(&focused_rec), vec![keyboard_request]);
+    assert_eq!(context_menu_requests(&hovered_rec), vec![]);
+
+    harness.process_event(Event::KeyDown(KeyEvent {
+        key: KbKey::F10,
+        mods: Modifiers::SHIFT,
+        ..Default::default()
+    }));
+    assert_eq!(context_menu_requests(&focused_rec), vec![keyboard_request]);
+
+    // F10 alone doesn't request a menu.
+    harness.process_event(Event::KeyDown(KeyEvent {
+        key: KbKey::F10,
+        ..Default::default()
+    }));
+    assert_eq!(context_menu_requests(&focused_rec), vec![]);
+}
+
+#[test]
+fn all_pointer_moves_reach_widget_when_not_hot() {
+    fn next_mouse_move(recording: &Recording) -> Option<MouseEvent> {
+        loop {
+            match recording.next() {
+                Record::E(Event::MouseMove(mouse_event)) => return Some(mouse_event),
+                Record::None => return None,
+                _ => {}
+            }
+        }
+    }
+
+    let [other, layer] = widget_ids();
+    let layer_rec = Recording::default();
+    let plain_rec = Recording::default();
+
+    let layer_widget = ModularWidget::new(())
+        .layout_fn(|_, _, _, _| Size::new(20.0, 20.0))
+        .all_pointer_moves()
+        .record(&layer_rec);
+    let plain_widget = ModularWidget::new(())
+        .layout_fn(|_, _, _, _| Size::new(20.0, 20.0))
+        .record(&plain_rec);
+    let widget = Flex::row()
+        .with_child_id(SizedBox::empty().width(50.0).height(50.0), other)
+        .with_child_id(layer_widget, layer)
+        .with_child(plain_widget);
+
+    let mut harness = TestHarness::create(widget);
+    layer_rec.clear();
+    plain_rec.clear();
+
+    harness.mouse_move_to(other);
+    let mouse_event = next_mouse_move(&layer_rec).unwrap();
+    assert!(!is_hot(&harness, layer));
+    // The position is relative to the layer, which is to the right of the pointer.
+    assert!(mouse_event.pos.x < 0.0);
+    assert!(next_mouse_move(&plain_rec).is_none());
+}
+
+#[test]
+fn pointer_left_subtree() {
+    fn left_subtree_count(recording: &Recording) -> usize {
+        recording
+            .drain()
+            .into_iter()
+            .filter(|record| matches!(record, Record::SC(StatusChange::PointerLeftSubtree)))
+            .count()
+    }
+
+    let [first_item, second_item, outside] = widget_ids();
+    let menu_rec = Recording::default();
+    let item_rec = Recording::default();
+
+    let menu = Flex::column()
+        .with_child_id(
+            SizedBox::empty().width(40.0).height(20.0).record(&item_rec),
+            first_item,
+        )
+        .with_child_id(SizedBox::empty().width(40.0).height(20.0), second_item)
+        .record(&menu_rec);
+    let widget = Flex::row()
+        .with_child(menu)
+        .with_child_id(SizedBox::empty().width(40.0).height(40.0), outside);
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(first_item);
+    assert_eq!(left_subtree_count(&menu_rec), 0);
+
+    // Moving between items keeps the pointer in the menu.
+    harness.mouse_move_to(second_item);
+    assert_eq!(left_subtree_count(&item_rec), 1);
+    assert_eq!(left_subtree_count(&menu_rec), 0);
+
+    harness.mouse_move_to(outside);
+    assert_eq!(left_subtree_count(&menu_rec), 1);
+    assert_eq!(left_subtree_count(&item_rec), 0);
+
+    harness.mouse_move_to(outside);
+    assert_eq!(left_subtree_count(&menu_rec), 0);
+}
+
+#[test]
+fn pointer_left_subtree_with_child_outside_bounds() {
+    let container_rec = Recording::default();
+
+    // The child is placed to the right of the container's own rect.
+    let container = ModularWidget::single_child(SizedBox::empty().width(40.0).height(40.0))
+        .layout_fn(|child, ctx, bc, env| {
+            child.layout(ctx, bc, env);
+            ctx.place_child(child, Point::new(40.0, 0.0), env);
+            Size::new(40.0, 40.0)
+        })
+        .record(&container_rec);
+    let mut harness = TestHarness::create(container);
+
+    harness.mouse_move((10.0, 10.0));
+    harness.mouse_move((60.0, 10.0));
+    assert!(!container_rec
+        .drain()
+        .iter()
+        .any(|record| matches!(record, Record::SC(StatusChange::PointerLeftSubtree))));
+
+    harness.mouse_move((150.0, 10.0));
+    assert!(container_rec
+        .drain()
+        .iter()
+        .any(|record| matches!(record, Record::SC(StatusChange::PointerLeftSubtree))));
+}
+
+#[test]
+fn handled_press_keeps_child_outside_bounds_hot() {
+    let container_rec = Recording::default();
+
+    // The container handles presses before its child, which is placed to the
+    // right of the container's own rect.
+    let container = ModularWidget::single_child(SizedBox::empty().width(40.0).height(40.0))
+        .event_fn(|child, ctx, event, env| {
+            match event {
+                Event::MouseDown(_) => {
+                    ctx.set_active(true);
+                    ctx.set_handled();
+                }
+                Event::MouseUp(_) => {
+                    ctx.set_active(false);
+                    ctx.set_handled();
+                }
+                _ => (),
+            }
+            child.on_event(ctx, event, env);
+        })
+        .layout_fn(|child, ctx, bc, env| {
+            child.layout(ctx, bc, env);
+            ctx.place_child(child, Point::new(40.0, 0.0), env);
+            Size::new(40.0, 40.0)
+        })
+        .record(&container_rec);
+    let mut harness = TestHarness::create(container);
+
+    // The press starts in the container, and is released over the child.
+    harness.mouse_move((10.0, 10.0));
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_move((60.0, 10.0));
+    container_rec.clear();
+    harness.mouse_button_release(MouseButton::Left);
+    assert!(harness.root_widget().state().has_hot);
+    assert!(!container_rec
+        .drain()
+        .iter()
+        .any(|record| matches!(record, Record::SC(StatusChange::PointerLeftSubtree))));
+}
+
+#[test]
+fn scroll_container_claims_drag_from_button() {
+    const DRAG_THRESHOLD: f64 = 10.0;
+
+    let [container, button] = widget_ids();
+
+    // A minimal scroll-like container: it lets clicks through to its child,
+    // but claims the pointer once a press turns into a drag.
+    let mut drag_start = None::<Point>;
+    let scroll = ModularWidget::single_child(Button::new("hello").with_id(button))
+        .event_capture_fn(move |_, ctx, event, _| match event {
+            Event::MouseDown(mouse) => {
+                drag_start = Some(mouse.pos);
+                Handled::No
+            }
+            Event::MouseMove(_) if ctx.is_active() => Handled::Yes,
+            Event::MouseMove(mouse) => match drag_start {
+                Some(start) if (mouse.pos - start).hypot() > DRAG_THRESHOLD => {
+                    ctx.set_active(true);
+                    Handled::Yes
+                }
+                _ => Handled::No,
+            },
+            Event::MouseUp(_) => {
+                drag_start = None;
+                if ctx.is_active() {
+                    ctx.set_active(false);
+                    Handled::Yes
+                } else {
+                    Handled::No
+                }
+            }
+            _ => Handled::No,
+        })
+        .with_id(container);
+
+    let mut harness = TestHarness::create(scroll);
+
+    // A plain click still reaches the button.
+    harness.mouse_move_to(button);
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_button_release(MouseButton::Left);
+    assert!(matches!(
+        harness.pop_action(),
+        Some((Action::ButtonPressed, id)) if id == button
+    ));
+
+    // Dragging past the threshold hands the pointer over to the container.
+    harness.mouse_move_to(button);
+    harness.mouse_button_press(MouseButton::Left);
+    assert!(harness.get_widget(button).state().is_active);
+
+    let start = harness
+        .get_widget(button)
+        .state()
+        .window_layout_rect()
+        .center();
+    harness.mouse_move(start + Vec2::new(0.0, 30.0));
+
+    assert!(!harness.get_widget(button).state().is_active);
+    assert!(harness.get_widget(container).state().is_active);
+
+    harness.mouse_button_release(MouseButton::Left);
+
+    assert!(!harness.get_widget(container).state().is_active);
+    assert!(!harness.get_widget(container).state().has_active);
+    assert!(harness.pop_action().is_none());
+}
+
+#[test]
+fn captured_key_keeps_pressed_button_active() {
+    let [container, button] = widget_ids();
+
+    let widget = ModularWidget::single_child(Button::new("hello").with_id(button))
+        .event_capture_fn(|_, _, event, _| match event {
+            Event::KeyDown(_) => Handled::Yes,
+            _ => Handled::No,
+        })
+        .with_id(container);
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(button);
+    harness.mouse_button_press(MouseButton::Left);
+    harness.process_event(Event::KeyDown(KeyEvent::default()));
+    // The button didn't see the key, so it's still pressed.
+    assert!(harness.get_widget(button).state().is_active);
+    assert!(harness.get_widget(container).state().has_active);
+    assert!(harness.get_widget(container).state().has_hot);
+
+    harness.mouse_button_release(MouseButton::Left);
+    assert!(matches!(
+        harness.pop_action(),
+        Some((Action::ButtonPressed, id)) if id == button
+    ));
+}
+
+#[test]
+fn pointer_left_subtree_after_hover_delay() {
+    const HOVER_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
+    let [delayed, outside] = widget_ids();
+    let menu_rec = Recording::default();
+
+    let delayed_widget = ModularWidget::new(())
+        .lifecycle_fn(|_, ctx, event, _| {
+            if let LifeCycle::WidgetAdded = event {
+                ctx.set_hover_delay(Some(HOVER_DELAY));
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(40.0, 40.0));
+    let menu = Flex::column()
+        .with_child_id(delayed_widget, delayed)
+        .record(&menu_rec);
+    let widget = Flex::row()
+        .with_child(menu)
+        .with_child_id(SizedBox::empty().width(40.0).height(40.0), outside);
+    let mut harness = TestHarness::create(widget);
+
+    harness.mouse_move_to(delayed);
+    harness.move_timers_forward(HOVER_DELAY);
+    assert!(is_hot(&harness, delayed));
+
+    // The delayed widget stays hot until its delay elapses, and so the
+    // pointer hasn't left the menu yet.
+    harness.mouse_move_to(outside);
+    menu_rec.clear();
+    harness.move_timers_forward(HOVER_DELAY);
+    assert!(!is_hot(&harness, delayed));
+    assert!(menu_rec
+        .drain()
+        .iter()
+        .any(|record| matches!(record, Record::SC(StatusChange::PointerLeftSubtree))));
+}
+
+#[test]
+fn handled_timer_keeps_later_sibling_hot() {
+    const DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
+    let [hovered] = widget_ids();
+    let row_rec = Recording::default();
+
+    let timer_widget = ModularWidget::new(())
+        .lifecycle_fn(|_, ctx, event, _| {
+            if let LifeCycle::WidgetAdded = event {
+                let _ = ctx.request_timer(DELAY);
+            }
+        })
+        .event_fn(|_, ctx, event, _| {
+            if let Event::Timer(_) = event {
+                ctx.set_handled();
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(40.0, 40.0));
+    let row = Flex::row()
+        .with_child(timer_widget)
+        .with_child_id(SizedBox::empty().width(40.0).height(40.0), hovered)
+        .record(&row_rec);
+    let mut harness = TestHarness::create(row);
+
+    harness.mouse_move_to(hovered);
+    row_rec.clear();
+
+    // The timer stops being routed before it reaches the hot widget.
+    harness.move_timers_forward(DELAY);
+    assert!(is_hot(&harness, hovered));
+    assert!(harness.root_widget().state().has_hot);
+    assert!(!row_rec
+        .drain()
+        .iter()
+        .any(|record| matches!(record, Record::SC(StatusChange::PointerLeftSubtree))));
+}
@@ -2,12 +2,14 @@
 // "as-is" basis without warranties of any kind. See the LICENSE file for
 // details.
 
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use instant::Duration;
+use smallvec::SmallVec;
 
-use crate::testing::{ModularWidget, TestHarness};
+use crate::testing::{ModularWidget, TestHarness, HARNESS_FRAME_INTERVAL};
+use crate::widget::WidgetRef;
 use crate::*;
 
 #[test]
@@ -39,3 +41,266 @@ fn basic_timer() {
     harness.move_timers_forward(Duration::from_secs(2));
     assert_eq!(timer_handled.get(), true);
 }
+
+const TRIGGER: Selector = Selector::new("masonry-test.trigger");
+
+#[test]
+fn debounce_fires_last_of_burst() {
+    let fire_count: Rc<Cell<u32>> = Rc::new(0.into());
+
+    let widget = ModularWidget::new((
+        Debouncer::new(Duration::from_millis(100)),
+        fire_count.clone(),
+    ))
+    .event_fn(|(debouncer, fire_count), ctx, event, _| match event {
+        Event::Command(command) if command.is(TRIGGER) => debouncer.trigger(ctx),
+        event if debouncer.fired(event) => fire_count.set(fire_count.get() + 1),
+        _ => {}
+    });
+
+    let mut harness = TestHarness::create(widget);
+
+    for _ in 0..3 {
+        harness.submit_command(TRIGGER);
+        harness.move_timers_forward(Duration::from_millis(60));
+    }
+    assert_eq!(fire_count.get(), 0);
+
+    harness.move_timers_forward(Duration::from_millis(60));
+    assert_eq!(fire_count.get(), 1);
+
+    harness.move_timers_forward(Duration::from_secs(1));
+    assert_eq!(fire_count.get(), 1);
+}
+
+#[test]
+fn throttle_runs_first_and_trailing() {
+    let run_count: Rc<Cell<u32>> = Rc::new(0.into());
+
+    let widget = ModularWidget::new((
+        Throttler::new(Duration::from_millis(100)),
+        run_count.clone(),
+    ))
+    .event_fn(|(throttler, run_count), ctx, event, _| match event {
+        Event::Command(command) if command.is(TRIGGER) => {
+            if throttler.trigger(ctx) {
+                run_count.set(run_count.get() + 1);
+            }
+        }
+        event if throttler.fired(ctx, event) => run_count.set(run_count.get() + 1),
+        _ => {}
+    });
+
+    let mut harness = TestHarness::create(widget);
+
+    for _ in 0..3 {
+        harness.submit_command(TRIGGER);
+    }
+    assert_eq!(run_count.get(), 1);
+
+    harness.move_timers_forward(Duration::from_millis(100));
+    assert_eq!(run_count.get(), 2);
+
+    harness.move_timers_forward(Duration::from_secs(1));
+    assert_eq!(run_count.get(), 2);
+}
+
+/// A widget that animates for a fixed number of frames.
+struct FrameCounter {
+    remaining: u32,
+    elapsed: Rc<Cell<u64>>,
+}
+
+impl Widget for FrameCounter {
+    fn on_event(&mut self, _ctx: &mut EventCtx, _event: &Event, _env: &Env) {}
+
+    fn on_anim_frame(&mut self, _ctx: &mut EventCtx, interval: u64, _env: &Env) -> bool {
+        self.elapsed.set(self.elapsed.get() + interval);
+        self.remaining -= 1;
+        self.remaining > 0
+    }
+
+    fn on_status_change(&mut self, _ctx: &mut LifeCycleCtx, _event: &StatusChange, _env: &Env) {}
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _env: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            ctx.request_anim_frame();
+        }
+    }
+
+    fn paint(&mut self, _ctx: &mut PaintCtx, _env: &Env) {}
+
+    fn children(&self) -> SmallVec<[WidgetRef<'_, dyn Widget>; 16]> {
+        SmallVec::new()
+    }
+}
+
+#[test]
+fn on_anim_frame_requests_next_frame() {
+    let elapsed = Rc::new(Cell::new(0));
+    let widget = FrameCounter {
+        remaining: 2,
+        elapsed: elapsed.clone(),
+    };
+
+    let mut harness = TestHarness::create(widget);
+    assert!(harness.root_widget().state().request_anim);
+
+    harness.process_event(Event::AnimFrame(10));
+    assert!(harness.root_widget().state().request_anim);
+
+    harness.process_event(Event::AnimFrame(20));
+    assert!(!harness.root_widget().state().request_anim);
+    assert_eq!(elapsed.get(), 30);
+
+    // No frame was requested, so this one isn't delivered.
+    harness.process_event(Event::AnimFrame(40));
+    assert_eq!(elapsed.get(), 30);
+}
+
+#[test]
+fn run_frames_completes_animation() {
+    let elapsed = Rc::new(Cell::new(0));
+    let widget = FrameCounter {
+        remaining: 3,
+        elapsed: elapsed.clone(),
+    };
+    let frame = HARNESS_FRAME_INTERVAL.as_nanos() as u64;
+
+    let mut harness = TestHarness::create(widget);
+    harness.run_frames(2);
+    assert!(harness.root_widget().state().request_anim);
+    assert_eq!(elapsed.get(), 2 * frame);
+
+    harness.run_frames(1);
+    assert!(!harness.root_widget().state().request_anim);
+    assert_eq!(elapsed.get(), 3 * frame);
+
+    // The animation is over, so later frames don't deliver AnimFrame.
+    harness.run_frames(5);
+    assert_eq!(elapsed.get(), 3 * frame);
+}
+
+#[test]
+fn run_frames_delivers_timers_and_ext_events() {
+    const PING: Selector = Selector::new("masonry-test.ping");
+
+    let timer_fired = Rc::new(Cell::new(false));
+    let pinged = Rc::new(Cell::new(false));
+
+    let widget = ModularWidget::new((timer_fired.clone(), pinged.clone()))
+        .event_fn(|(timer_fired, pinged), _, event, _| match event {
+            Event::Timer(_) => timer_fired.set(true),
+            Event::Command(cmd) if cmd.is(PING) => pinged.set(true),
+            _ => (),
+        })
+        .lifecycle_fn(|_, ctx, event, _| {
+            if let LifeCycle::WidgetAdded = event {
+                let _ = ctx.request_timer(HARNESS_FRAME_INTERVAL * 3);
+            }
+        });
+
+    let mut harness = TestHarness::create(widget);
+    harness
+        .ext_event_sink()
+        .submit_command(PING, (), Target::Auto)
+        .unwrap();
+    assert!(!pinged.get());
+
+    harness.run_frames(2);
+    assert!(pinged.get());
+    assert!(!timer_fired.get());
+
+    harness.run_frames(1);
+    assert!(timer_fired.get());
+}
+
+#[test]
+fn idle_budget_splits_ext_events() {
+    const PING: Selector<u32> = Selector::new("masonry-test.ping");
+    const FOLLOW_UP: Selector = Selector::new("masonry-test.follow-up");
+
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let widget = ModularWidget::new(log.clone()).event_fn(|log, ctx, event, _| match event {
+        Event::Command(cmd) if cmd.is(PING) => {
+            let n = *cmd.get_unchecked(PING);
+            log.borrow_mut().push(format!("ping {n}"));
+            if n == 1 {
+                ctx.submit_command(FOLLOW_UP);
+            }
+        }
+        Event::Command(cmd) if cmd.is(FOLLOW_UP) => log.borrow_mut().push("follow-up".into()),
+        _ => (),
+    });
+
+    let mut harness = TestHarness::create(widget);
+    // The mocked time doesn't move during the callback, so it goes over a
+    // zero budget after the first event.
+    harness.set_idle_budget(Some(Duration::ZERO));
+    let sink = harness.ext_event_sink();
+    for n in 1..=3 {
+        sink.submit_command(PING, n, Target::Auto).unwrap();
+    }
+
+    harness.idle(IdleWork::ExtEvents);
+    assert_eq!(log.borrow()[..], ["ping 1"]);
+
+    // The command submitted by the first event waits for the other events,
+    // as if the callback hadn't yielded.
+    harness.idle(IdleWork::ExtEvents);
+    assert_eq!(log.borrow()[..], ["ping 1", "ping 2"]);
+
+    harness.idle(IdleWork::ExtEvents);
+    assert_eq!(
+        log.borrow()[..],
+        ["ping 1", "ping 2", "ping 3", "follow-up"]
+    );
+
+    // With a budget the mocked time can't exceed, everything runs at once.
+    log.borrow_mut().clear();
+    harness.set_idle_budget(Some(Duration::from_millis(8)));
+    for n in 1..=3 {
+        sink.submit_command(PING, n, Target::Auto).unwrap();
+    }
+    harness.idle(IdleWork::ExtEvents);
+    assert_eq!(
+        log.borrow()[..],
+        ["ping 1", "ping 2", "ping 3", "follow-up"]
+    );
+}
+
+#[test]
+fn pending_timers() {
+    const SCHEDULE: Selector = Selector::new("masonry-test.schedule");
+
+    let widget = ModularWidget::new(())
+        .event_fn(|_, ctx, event, _| {
+            if let Event::Command(cmd) = event {
+                if cmd.is(SCHEDULE) {
+                    let _ = ctx.request_timer(Duration::from_secs(2));
+                }
+            }
+        })
+        .lifecycle_fn(|_, ctx, event, _| {
+            if let LifeCycle::WidgetAdded = event {
+                let _ = ctx.request_timer(Duration::from_millis(500));
+            }
+        });
+
+    let mut harness = TestHarness::create(widget);
+    let widget_id = harness.root_widget().id();
+    assert_eq!(harness.pending_timers().len(), 1);
+
+    harness.submit_command(SCHEDULE);
+    let timers = harness.pending_timers();
+    assert_eq!(timers.len(), 2);
+    assert!(timers.iter().all(|timer| timer.widget_id == widget_id));
+    assert_eq!(
+        timers[1].deadline - timers[0].deadline,
+        Duration::from_millis(1500)
+    );
+
+    // Fired timers are gone.
+    harness.move_timers_forward(Duration::from_secs(1));
+    assert_eq!(harness.pending_timers(), timers[1..]);
+}
@@ -0,0 +1,74 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::command::{APPLY_EDIT, REDO, UNDO, UNDOABLE_EDIT};
+use crate::testing::{widget_ids, ModularWidget, TestHarness, TestWidgetExt as _};
+use crate::widget::Flex;
+use crate::*;
+
+const TYPE_CHAR: Selector<char> = Selector::new("masonry-test.type-char");
+
+/// A text field that records its edits as undoable.
+fn field(text: Rc<RefCell<String>>) -> impl Widget {
+    ModularWidget::new(text).event_fn(|text, ctx, event, _| {
+        if let Event::Command(cmd) = event {
+            if let Some(c) = cmd.try_get(TYPE_CHAR) {
+                let old = text.borrow().clone();
+                text.borrow_mut().push(*c);
+                let edit = UndoableEdit::new(old, text.borrow().clone()).coalescing("typing");
+                ctx.submit_notification(UNDOABLE_EDIT.with(edit));
+            } else if let Some(delta) = cmd.try_get(APPLY_EDIT) {
+                *text.borrow_mut() = delta.get::<String>().unwrap().clone();
+            }
+        }
+    })
+}
+
+/// A container sharing an undo stack between its descendants.
+fn undo_scope(child: impl Widget) -> impl Widget {
+    let mut stack = UndoStack::new();
+    ModularWidget::single_child(child).event_fn(move |child, ctx, event, env| {
+        if stack.handle_event(ctx, event) {
+            return;
+        }
+        child.on_event(ctx, event, env);
+    })
+}
+
+#[test]
+fn undo_across_two_fields() {
+    let [first_id, second_id] = widget_ids();
+    let first = Rc::new(RefCell::new(String::new()));
+    let second = Rc::new(RefCell::new(String::new()));
+    let widget = undo_scope(
+        Flex::column()
+            .with_child(field(first.clone()).with_id(first_id))
+            .with_child(field(second.clone()).with_id(second_id)),
+    );
+    let mut harness = TestHarness::create(widget);
+
+    // Rapid typing is coalesced into a single step.
+    harness.submit_command(TYPE_CHAR.with('a').to(first_id));
+    harness.submit_command(TYPE_CHAR.with('b').to(first_id));
+    harness.submit_command(TYPE_CHAR.with('x').to(second_id));
+    // A pause starts a new step.
+    harness.move_timers_forward(DEFAULT_COALESCE_INTERVAL + Duration::from_millis(1));
+    harness.submit_command(TYPE_CHAR.with('y').to(second_id));
+
+    harness.submit_command(UNDO);
+    assert_eq!(*second.borrow(), "x");
+    harness.submit_command(UNDO);
+    assert_eq!(*second.borrow(), "");
+    assert_eq!(*first.borrow(), "ab");
+    harness.submit_command(UNDO);
+    assert_eq!(*first.borrow(), "");
+
+    harness.submit_command(REDO);
+    assert_eq!(*first.borrow(), "ab");
+    assert_eq!(*second.borrow(), "");
+}
@@ -0,0 +1,279 @@
+// This software is licensed under Apache License 2.0 and distributed on an
+// "as-is" basis without warranties of any kind. See the LICENSE file for
+// details.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use druid_shell::MouseButton;
+
+use crate::testing::{widget_ids, ModularWidget, TestHarness, TestWidgetExt as _};
+use crate::widget::{Flex, SizedBox};
+use crate::*;
+
+type PaintLog = Rc<RefCell<Vec<&'static str>>>;
+
+/// A widget that submits an op logging `name` for each `(z_index, name)`.
+fn z_painter(log: &PaintLog, ops: Vec<(u32, &'static str)>) -> impl Widget {
+    ModularWidget::new(log.clone())
+        .paint_fn(move |log, ctx, _| {
+            for (z_index, name) in ops.clone() {
+                let log = log.clone();
+                ctx.paint_with_z_index(z_index, move |_| log.borrow_mut().push(name));
+            }
+        })
+        .layout_fn(|_, _, _, _| Size::new(20.0, 20.0))
+}
+
+/// A button that paints at `z_index` and logs `name` when it's pressed.
+fn z_button(log: &PaintLog, name: &'static str, z_index: u32) -> impl Widget {
+    ModularWidget::new(log.clone())
+        .event_fn(move |log, ctx, event, _| {
+            if let Event::MouseDown(_) = event {
+                log.borrow_mut().push(name);
+                ctx.set_handled();
+            }
+        })
+        .layout_fn(move |_, ctx, _, _| {
+            ctx.set_z_index(z_index);
+            Size::new(40.0, 40.0)
+        })
+        .paint_fn(move |_, ctx, _| {
+            let rect = ctx.size().to_rect();
+            ctx.paint_with_z_index(ctx.z_index(), move |ctx| ctx.fill(rect, &Color::WHITE));
+        })
+}
+
+/// A container that places both its children at the origin.
+fn overlapping(first: WidgetPod<impl Widget>, second: WidgetPod<impl Widget>) -> impl Widget {
+    ModularWidget::new((first, second))
+        .event_fn(|(first, second), ctx, event, env| {
+            first.on_event(ctx, event, env);
+            second.on_event(ctx, event, env);
+        })
+        .lifecycle_fn(|(first, second), ctx, event, env| {
+            first.lifecycle(ctx, event, env);
+            second.lifecycle(ctx, event, env);
+        })
+        .layout_fn(|(first, second), ctx, bc, env| {
+            for child in [first, second] {
+                child.layout(ctx, &bc.loosen(), env);
+                ctx.place_child(child, Point::ORIGIN, env);
+            }
+            Size::new(40.0, 40.0)
+        })
+        .paint_fn(|(first, second), ctx, env| {
+            first.paint(ctx, env);
+            second.paint(ctx, env);
+        })
+        .children_fn(|(first, second)| smallvec::smallvec![first.as_dyn(), second.as_dyn()])
+}
+
+#[test]
+fn z_index_decides_hit_testing() {
+    let log = PaintLog::default();
+    let [top, bottom] = widget_ids();
+
+    // The top button comes first in tree order, so only its z-index puts it on top.
+    let widget = overlapping(
+        WidgetPod::new_with_id(z_button(&log, "top", 2), top),
+        WidgetPod::new_with_id(z_button(&log, "bottom", 1), bottom),
+    );
+    let mut harness = TestHarness::create_with_size(widget, Size::new(40.0, 40.0));
+
+    harness.mouse_move((20.0, 20.0));
+    assert!(harness.get_widget(top).state().is_hot);
+    assert!(!harness.get_widget(bottom).state().is_hot);
+
+    harness.mouse_button_press(MouseButton::Left);
+    assert_eq!(log.borrow()[..], ["top"]);
+}
+
+#[test]
+fn hit_test_overlay_follows_z_index() {
+    let log = PaintLog::default();
+    let [top, bottom] = widget_ids();
+
+    let widget = overlapping(
+        WidgetPod::new_with_id(z_button(&log, "top", 2), top),
+        WidgetPod::new_with_id(z_button(&log, "bottom", 1), bottom),
+    );
+    let mut harness = TestHarness::create_with_size(widget, Size::new(40.0, 40.0));
+    harness.update_env(|env| env.set(Env::DEBUG_HIT_TEST, true));
+
+    harness.mouse_move((20.0, 20.0));
+    let target = harness.window().hit_test_target;
+    assert_eq!(target, Some((top, Rect::new(0.0, 0.0, 40.0, 40.0))));
+    // The overlay is drawn over the widgets.
+    let _ = harness.render();
+
+    harness.mouse_leave();
+    assert_eq!(harness.window().hit_test_target, None);
+}
+
+#[test]
+fn hit_test_overlay_follows_layout() {
+    const GROW: Selector = Selector::new("masonry-test.grow");
+
+    let [id] = widget_ids();
+    let widget = ModularWidget::new(20.0)
+        .event_fn(|width, ctx, event, _| {
+            if let Event::Command(cmd) = event {
+                if cmd.is(GROW) {
+                    *width = 40.0;
+                    ctx.request_layout();
+                }
+            }
+        })
+        .layout_fn(|width, _, _, _| Size::new(*width, 20.0))
+        .with_id(id);
+    let mut harness = TestHarness::create_with_size(widget, Size::new(40.0, 40.0));
+    harness.update_env(|env| env.set(Env::DEBUG_HIT_TEST, true));
+
+    harness.mouse_move((30.0, 10.0));
+    assert_ne!(harness.window().hit_test_target.map(|(id, _)| id), Some(id));
+
+    // The widget grows under the stationary pointer.
+    harness.submit_command(GROW.to(id));
+    assert_eq!(
+        harness.window().hit_test_target,
+        Some((id, Rect::new(0.0, 0.0, 40.0, 20.0)))
+    );
+    let _ = harness.render();
+
+    // Turning the overlay off erases it.
+    harness.update_env(|env| env.set(Env::DEBUG_HIT_TEST, false));
+    assert_eq!(harness.window().hit_test_target, None);
+}
+
+#[test]
+fn equal_z_index_hits_last_child() {
+    let log = PaintLog::default();
+
+    let widget = overlapping(
+        WidgetPod::new(z_button(&log, "first", 0)),
+        WidgetPod::new(z_button(&log, "last", 0)),
+    );
+    let mut harness = TestHarness::create_with_size(widget, Size::new(40.0, 40.0));
+
+    harness.mouse_move((20.0, 20.0));
+    harness.mouse_button_press(MouseButton::Left);
+    assert_eq!(log.borrow()[..], ["last"]);
+}
+
+#[test]
+fn z_ops_interleave_across_subtrees() {
+    let log = PaintLog::default();
+
+    let widget = Flex::row()
+        .with_child(z_painter(&log, vec![(5, "a5"), (1, "a1")]))
+        .with_child(
+            Flex::column()
+                .with_child(z_painter(&log, vec![(3, "b3")]))
+                .with_child(z_painter(&log, vec![(5, "c5"), (0, "c0")])),
+        );
+    let mut harness = TestHarness::create(widget);
+    log.borrow_mut().clear();
+    harness.render();
+
+    assert_eq!(log.borrow()[..], ["c0", "a1", "b3", "a5", "c5"]);
+}
+
+#[test]
+fn equal_z_ops_paint_ancestors_first() {
+    let log = PaintLog::default();
+
+    // The outer widget is painted after its child, but its op is shallower.
+    let outer_log = log.clone();
+    let outer = ModularWidget::single_child(z_painter(&log, vec![(2, "inner")])).paint_fn(
+        move |child, ctx, env| {
+            child.paint(ctx, env);
+            let log = outer_log.clone();
+            ctx.paint_with_z_index(2, move |_| log.borrow_mut().push("outer"));
+        },
+    );
+
+    let mut harness = TestHarness::create(SizedBox::new(outer));
+    log.borrow_mut().clear();
+    harness.render();
+
+    assert_eq!(log.borrow()[..], ["outer", "inner"]);
+}
+
+#[test]
+fn nested_z_ops_paint_last() {
+    let log = PaintLog::default();
+
+    let nesting_log = log.clone();
+    let nesting = ModularWidget::new(())
+        .paint_fn(move |_, ctx, _| {
+            let log = nesting_log.clone();
+            ctx.paint_with_z_index(1, move |ctx| {
+                log.borrow_mut().push("outer");
+                let log = log.clone();
+                ctx.paint_with_z_index(0, move |_| log.borrow_mut().push("nested"));
+            });
+        })
+        .layout_fn(|_, _, _, _| Size::new(20.0, 20.0));
+
+    let widget = Flex::row()
+        .with_child(nesting)
+        .with_child(z_painter(&log, vec![(9, "top")]));
+    let mut harness = TestHarness::create(widget);
+    log.borrow_mut().clear();
+    harness.render();
+
+    assert_eq!(log.borrow()[..], ["outer", "top", "nested"]);
+}
+
+#[test]
+fn input_sink_blocks_content_behind() {
+    let log = PaintLog::default();
+    let [sink_id] = widget_ids();
+
+    let sink = ModularWidget::new(())
+        .layout_fn(|_, ctx, _, _| {
+            ctx.set_z_index(1);
+            Size::new(40.0, 40.0)
+        })
+        .input_sink();
+    let overlay = overlapping(
+        WidgetPod::new(z_button(&log, "button", 0)),
+        WidgetPod::new_with_id(sink, sink_id),
+    );
+
+    // A parent that handles the clicks its children leave unhandled.
+    let parent_log = log.clone();
+    let parent = ModularWidget::single_child(overlay).event_fn(move |child, ctx, event, env| {
+        child.on_event(ctx, event, env);
+        if let Event::MouseDown(_) = event {
+            if !ctx.is_handled() {
+                parent_log.borrow_mut().push("parent");
+            }
+        }
+    });
+    let mut harness = TestHarness::create_with_size(parent, Size::new(40.0, 40.0));
+
+    harness.mouse_move((20.0, 20.0));
+    assert!(harness.get_widget(sink_id).state().is_hot);
+    harness.mouse_button_press(MouseButton::Left);
+    harness.mouse_button_release(MouseButton::Left);
+    assert!(log.borrow().is_empty());
+}
+
+#[should_panic(expected = "z-index 2 set in layout")]
+#[test]
+fn check_z_index_paint_mismatch() {
+    let widget = ModularWidget::new(())
+        .layout_fn(|_, ctx, _, _| {
+            ctx.set_z_index(2);
+            Size::new(40.0, 40.0)
+        })
+        .paint_fn(|_, ctx, _| {
+            let rect = ctx.size().to_rect();
+            ctx.paint_with_z_index(1, move |ctx| ctx.fill(rect, &Color::WHITE));
+        });
+    let mut harness = TestHarness::create(widget);
+
+    harness.render();
+}
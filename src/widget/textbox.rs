@@ -11,6 +11,7 @@ use smallvec::{smallvec, SmallVec};
 use tracing::{trace_span, Span};
 
 use crate::action::Action;
+use crate::command::{SET_CARET, SET_SELECTION};
 use crate::kurbo::Insets;
 use crate::piet::{RenderContext as _, TextLayout as _};
 use crate::shell::{HotKey, KeyEvent, SysMods, TimerToken};
@@ -18,7 +19,7 @@ use crate::text::{ImeInvalidation, Selection, TextAlignment, TextComponent, Text
 use crate::widget::{Portal, WidgetMut, WidgetRef};
 use crate::{
     theme, ArcStr, BoxConstraints, Command, Env, Event, EventCtx, LayoutCtx, LifeCycle,
-    LifeCycleCtx, PaintCtx, Point, Rect, Size, StatusChange, Vec2, Widget, WidgetPod,
+    LifeCycleCtx, PaintCtx, Point, Rect, Size, StatusChange, Target, Vec2, Widget, WidgetPod,
 };
 
 const CURSOR_BLINK_DURATION: Duration = Duration::from_millis(500);
@@ -293,6 +294,16 @@ impl TextBox {
             .to_string()
     }
 
+    /// Return the current selection, in byte offsets in the text.
+    pub fn selection(&self) -> Selection {
+        self.inner.as_ref().child().borrow().selection()
+    }
+
+    /// Return the byte offset of the caret, ie the active end of the selection.
+    pub fn caret(&self) -> usize {
+        self.selection().active
+    }
+
     fn text_len(&self) -> usize {
         self.inner.as_ref().child().borrow().layout.text_len()
     }
@@ -349,6 +360,40 @@ impl TextBox {
         }
     }
 
+    /// Apply a selection from a [`SET_CARET`] or [`SET_SELECTION`] command,
+    /// and scroll its active end into view.
+    fn set_selection_from_command(&mut self, ctx: &mut EventCtx, selection: Selection) {
+        let text = self.text();
+        let selection = Selection::new(
+            floor_char_boundary(&text, selection.anchor),
+            floor_char_boundary(&text, selection.active),
+        );
+        let inval = self
+            .inner
+            .as_ref()
+            .child()
+            .borrow_mut()
+            .set_selection(selection);
+        if let Some(inval) = inval {
+            ctx.invalidate_text_input(inval);
+        }
+        if self
+            .inner
+            .as_ref()
+            .child()
+            .borrow()
+            .layout
+            .layout()
+            .is_some()
+        {
+            let selection_end = self.rect_for_selection_end();
+            let mut child = ctx.get_mut(&mut self.inner);
+            child.pan_viewport_to(selection_end);
+        }
+        self.reset_cursor_blink(ctx.request_timer(CURSOR_BLINK_DURATION));
+        ctx.request_paint();
+    }
+
     /// These commands may be supplied by menus; but if they aren't, we
     /// inject them again, here.
     fn fallback_do_builtin_command(
@@ -438,7 +483,10 @@ impl Widget for TextBox {
                     ctx.set_handled();
                 }
             }
-            Event::MouseDown(mouse) if self.inner.as_ref().child().can_write() => {
+            // Other buttons are left unhandled, eg to request a context menu.
+            Event::MouseDown(mouse)
+                if mouse.button.is_left() && self.inner.as_ref().child().can_write() =>
+            {
                 if !ctx.is_disabled() {
                     if !mouse.focus {
                         ctx.request_focus();
@@ -451,7 +499,7 @@ impl Widget for TextBox {
             }
             Event::Timer(id) => {
                 if !ctx.is_disabled() {
-                    if *id == self.cursor_timer && ctx.has_focus() {
+                    if *id == self.cursor_timer && ctx.has_focus() && ctx.is_window_focused() {
                         self.cursor_on = !self.cursor_on;
                         ctx.request_paint();
                         self.cursor_timer = ctx.request_timer(CURSOR_BLINK_DURATION);
@@ -502,6 +550,18 @@ impl Widget for TextBox {
                 }
                 ctx.set_handled();
             }
+            Event::Command(cmd) if cmd.is(SET_CARET) || cmd.is(SET_SELECTION) => {
+                let is_target = ctx.is_focused() || cmd.target() == Target::Widget(ctx.widget_id());
+                let child = self.inner.as_ref();
+                if is_target && child.child().can_write() && !child.child().is_composing() {
+                    let selection = match cmd.try_get(SET_CARET) {
+                        Some(caret) => Selection::caret(*caret),
+                        None => *cmd.get(SET_SELECTION),
+                    };
+                    self.set_selection_from_command(ctx, selection);
+                    ctx.set_handled();
+                }
+            }
             Event::Paste(ref item) if self.inner.as_ref().child().can_write() => {
                 if let Some(string) = item.get_string() {
                     let _text = if self.multiline {
@@ -567,6 +627,15 @@ impl Widget for TextBox {
                 self.was_focused_from_click = false;
                 ctx.request_paint();
             }
+            StatusChange::WindowFocusChanged(focused) => {
+                // The caret stops blinking while the window is inactive.
+                if *focused && ctx.has_focus() {
+                    self.reset_cursor_blink(ctx.request_timer(CURSOR_BLINK_DURATION));
+                } else {
+                    self.cursor_timer = TimerToken::INVALID;
+                }
+                ctx.request_paint();
+            }
             _ => (),
         }
     }
@@ -579,6 +648,8 @@ impl Widget for TextBox {
             LifeCycle::BuildFocusChain => {
                 //TODO: make this a configurable option? maybe?
                 ctx.register_for_focus();
+                // The border is highlighted instead.
+                ctx.suppress_focus_ring();
             }
             _ => (),
         }
@@ -669,7 +740,7 @@ impl Widget for TextBox {
         }
 
         // Paint the cursor if focused and there's no selection
-        if is_focused && self.should_draw_cursor() {
+        if is_focused && ctx.is_window_focused() && self.should_draw_cursor() {
             // if there's no data, we always draw the cursor based on
             // our alignment.
             let cursor_pos = self.inner.as_ref().child().borrow().selection().active;
@@ -711,6 +782,15 @@ impl Widget for TextBox {
     }
 }
 
+/// The largest character boundary of `text` that's at most `offset`.
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
 fn x_offset_for_extra_width(alignment: TextAlignment, extra_width: f64) -> f64 {
     match alignment {
         TextAlignment::Start | TextAlignment::Justified => 0.0,
@@ -740,6 +820,9 @@ mod tests {
 
         assert_eq!(harness.pop_action(), None);
 
+        // A triple click selects the whole line, so typing replaces it.
+        harness.mouse_click_on(textbox_id);
+        harness.mouse_click_on(textbox_id);
         harness.mouse_click_on(textbox_id);
         assert_eq!(harness.focused_widget().unwrap().id(), textbox_id);
         assert_eq!(harness.pop_action(), None);
@@ -779,6 +862,31 @@ mod tests {
         assert_render_snapshot!(harness, "placeholder");
     }
 
+    #[test]
+    fn set_caret_and_selection() {
+        let [textbox_id] = widget_ids();
+        let text = "The quick brown fox jumps over the lazy dog";
+        let textbox = TextBox::new(text).with_id(textbox_id);
+        let mut harness = TestHarness::create_with_size(textbox, Size::new(50.0, 50.0));
+        harness.mouse_click_on(textbox_id);
+
+        // The command is routed to the focused textbox.
+        harness.submit_command(SET_CARET.with(text.len()));
+        let textbox = harness.get_widget(textbox_id);
+        let textbox = textbox.downcast::<TextBox>().unwrap();
+        assert_eq!(textbox.selection(), Selection::caret(text.len()));
+        // The caret was scrolled into view.
+        assert!(textbox.inner.as_ref().get_viewport_pos().x > 0.0);
+
+        // Selecting backwards scrolls back to the start.
+        harness.submit_command(SET_SELECTION.with(Selection::new(9, 0)));
+        let textbox = harness.get_widget(textbox_id);
+        let textbox = textbox.downcast::<TextBox>().unwrap();
+        assert_eq!(textbox.selection(), Selection::new(9, 0));
+        assert_eq!(textbox.caret(), 0);
+        assert_eq!(textbox.inner.as_ref().get_viewport_pos().x, 0.0);
+    }
+
     // TODO - styled textbox
 
     #[test]
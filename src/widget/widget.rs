@@ -12,8 +12,8 @@ use tracing::{trace_span, Span};
 use crate::event::StatusChange;
 use crate::widget::WidgetRef;
 use crate::{
-    AsAny, BoxConstraints, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx,
-    Point, Size, WidgetCtx,
+    AsAny, BoxConstraints, Command, Env, Event, EventCtx, Handled, Key, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, Size, WidgetCtx,
 };
 
 /// A unique identifier for a single [`Widget`].
@@ -71,6 +71,53 @@ pub trait Widget: AsAny {
     /// a [`Command`](crate::Command).
     fn on_event(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env);
 
+    /// Handle a command sent to this widget.
+    ///
+    /// This is called before [`on_event`](Self::on_event) for every
+    /// [`Event::Command`], so widgets can handle commands without matching on
+    /// the event first. If this returns [`Handled::Yes`], the event is marked
+    /// as handled and `on_event` isn't called with it; otherwise `on_event`
+    /// receives the command as usual.
+    ///
+    /// The default implementation returns [`Handled::No`].
+    fn on_command(&mut self, _ctx: &mut EventCtx, _cmd: &Command, _env: &Env) -> Handled {
+        Handled::No
+    }
+
+    /// Get a chance to claim an event before any descendant sees it.
+    ///
+    /// This is the capture phase of event dispatch: it's called before
+    /// [`on_event`](Self::on_event) for every event this widget receives,
+    /// so an ancestor is asked before its descendants are, from the root down.
+    /// If this returns [`Handled::Yes`], the event is marked as handled and
+    /// neither `on_event` nor the descendants receive it; otherwise dispatch
+    /// proceeds as usual.
+    ///
+    /// This resolves gesture conflicts, eg a scroll container that lets a
+    /// press reach the button under it, but claims the pointer once it moves
+    /// far enough to be a drag. When a pointer event is claimed, descendants
+    /// that were [active](crate::EventCtx::is_active) stop being active, so
+    /// the button doesn't fire when the pointer is released.
+    ///
+    /// The default implementation returns [`Handled::No`].
+    fn on_event_capture(&mut self, _ctx: &mut EventCtx, _event: &Event, _env: &Env) -> Handled {
+        Handled::No
+    }
+
+    /// Advance animations by one frame.
+    ///
+    /// This is called before [`on_event`](Self::on_event) for every
+    /// [`Event::AnimFrame`], with the same `interval` in nanoseconds. Return
+    /// `true` to get another animation frame, instead of calling
+    /// [`EventCtx::request_anim_frame`]. `on_event` still receives the event
+    /// afterwards, so widgets implementing this method shouldn't also handle
+    /// `AnimFrame` there.
+    ///
+    /// The default implementation does nothing and returns `false`.
+    fn on_anim_frame(&mut self, _ctx: &mut EventCtx, _interval: u64, _env: &Env) -> bool {
+        false
+    }
+
     #[allow(missing_docs)]
     fn on_status_change(&mut self, ctx: &mut LifeCycleCtx, event: &StatusChange, env: &Env);
 
@@ -98,6 +145,9 @@ pub trait Widget: AsAny {
     /// once, though there is nothing enforcing this.
     ///
     /// The layout strategy is strongly inspired by Flutter.
+    ///
+    /// Leaf widgets with trivial sizing can implement [`preferred_size`](Self::preferred_size),
+    /// and return the same size here, clamped to `bc`.
     fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, env: &Env) -> Size;
 
     /// Paint the widget appearance.
@@ -143,6 +193,129 @@ pub trait Widget: AsAny {
         None
     }
 
+    /// Return whether this widget and its descendants can currently receive focus.
+    ///
+    /// This is checked every time the focus chain is rebuilt. If it returns `false`,
+    /// the widget and its descendants are left out of the focus chain, the same way
+    /// disabled widgets are, and are skipped by Tab traversal. If one of them has
+    /// focus, it resigns it.
+    ///
+    /// The focus chain isn't rebuilt automatically when the return value of this
+    /// method changes; widgets should call [`focusability_changed`] when that happens.
+    ///
+    /// [`focusability_changed`]: crate::EventCtx::focusability_changed
+    fn can_receive_focus_now(&self) -> bool {
+        true
+    }
+
+    /// Whether this widget consumes the input it receives.
+    ///
+    /// Input sinks are meant for overlays that block the content beneath them,
+    /// such as a busy indicator or the backdrop of a modal. After a sink
+    /// receives a pointer, key, paste, zoom or context menu event, the event
+    /// is marked as handled, whether the sink handled it or not. It isn't
+    /// passed to the siblings that come after the sink, and the ancestors of
+    /// the sink see it as handled.
+    ///
+    /// Pointer events reach the sink when the pointer is over it, and it's the
+    /// topmost widget there (see [`get_child_at_pos`](Self::get_child_at_pos)).
+    /// Mouse moves aren't consumed, so that the hot state of the widgets
+    /// around the sink stays up to date. Key events only reach the sink if it
+    /// or one of its descendants has focus; an overlay that should block the
+    /// keyboard must take focus when it's shown.
+    ///
+    /// The default implementation returns `false`.
+    fn is_input_sink(&self) -> bool {
+        false
+    }
+
+    /// Whether this widget receives every mouse move over its window.
+    ///
+    /// Mouse moves are normally only passed to widgets that are hot or active,
+    /// or that just stopped being hot. A widget returning `true` receives them
+    /// wherever the pointer is, eg a drop target covering the whole window, or
+    /// a layer tracking hover gestures. The positions are still in the
+    /// widget's coordinate space, so they can be outside of its bounds. The
+    /// widget decides which of its children it passes them to; children only
+    /// become hot when the pointer is over them.
+    ///
+    /// Unlike pointer capture, this doesn't keep other widgets from receiving
+    /// the moves, and it lasts for the whole life of the widget.
+    ///
+    /// Every mouse move walks down to such widgets, through all of their
+    /// ancestors, so this should be rare. The method is only called when the
+    /// widget is added to the tree. Its ancestors keep receiving all mouse
+    /// moves after it's removed, until they're removed themselves.
+    ///
+    /// The default implementation returns `false`.
+    fn wants_all_pointer_moves(&self) -> bool {
+        false
+    }
+
+    /// Return the size of this widget, if it doesn't need a layout pass.
+    ///
+    /// When this returns `Some`, [`WidgetPod`](crate::WidgetPod) doesn't call
+    /// [`layout`](Self::layout), and uses the returned size, clamped to `bc`.
+    /// This saves simple leaf widgets from implementing `layout` just to
+    /// return a fixed size. Since `layout` isn't called, such widgets can't
+    /// set a baseline offset or paint insets.
+    ///
+    /// Widgets implementing this still implement `layout`, usually by
+    /// returning the same size, clamped to `bc`. Widgets with children must
+    /// return `None`, and lay out and place their children in `layout`.
+    fn preferred_size(&self, _bc: &BoxConstraints, _env: &Env) -> Option<Size> {
+        None
+    }
+
+    /// Return an estimate of the full size of this widget, for widgets that
+    /// only lay out part of their content.
+    ///
+    /// A list that only lays out its visible rows can't report its full size
+    /// from [`layout`](Self::layout) without laying out every row. If all its
+    /// rows have the same height, it can return `row_height * row_count` here
+    /// instead, which is cheap to compute.
+    ///
+    /// The estimate may be approximate. [`Portal`](crate::widget::Portal)
+    /// asks its child for it after each layout pass, and uses the larger of
+    /// the estimate and the laid-out size as the scrollable size: the viewport
+    /// can be scrolled over the whole estimate, and the scrollbar thumb is
+    /// sized from it. Since this is recomputed on every layout, a wrong
+    /// estimate is corrected as soon as the widget returns a better one, and
+    /// the viewport position is clamped to the new size.
+    ///
+    /// The default implementation returns `None`, and the laid-out size is used.
+    fn size_hint(&self, _bc: &BoxConstraints, _env: &Env) -> Option<Size> {
+        None
+    }
+
+    /// Return the [`Env`] keys whose values affect this widget's layout.
+    ///
+    /// On [`LifeCycle::EnvChanged`], the widget is only laid out again if one of
+    /// these keys changed value. Keys of different types are listed with
+    /// [`Key::erased`]:
+    ///
+    /// ```ignore
+    /// const LAYOUT_KEYS: &[Key<()>] = &[theme::TEXT_SIZE_NORMAL.erased()];
+    /// ```
+    ///
+    /// The default implementation returns `None`: any change lays the widget
+    /// out again.
+    fn env_keys_affecting_layout(&self) -> Option<&[Key<()>]> {
+        None
+    }
+
+    /// Return the [`Env`] keys whose values affect this widget's painting.
+    ///
+    /// On [`LifeCycle::EnvChanged`], if the widget isn't laid out again (see
+    /// [`env_keys_affecting_layout`](Self::env_keys_affecting_layout)), it's only
+    /// painted again if one of these keys changed value.
+    ///
+    /// The default implementation returns `None`: any change paints the widget
+    /// again.
+    fn env_keys_affecting_paint(&self) -> Option<&[Key<()>]> {
+        None
+    }
+
     // --- Auto-generated implementations ---
 
     /// Return which child, if any, has the given `pos` in its layout rect.
@@ -150,13 +323,20 @@ pub trait Widget: AsAny {
     /// The child return is a direct child, not eg a grand-child. The position is in
     /// relative cordinates. (Eg `(0,0)` is the top-left corner of `self`).
     ///
+    /// When several children contain `pos`, the topmost one is returned: the one
+    /// with the highest [`z_index`](crate::LayoutCtx::set_z_index), and at equal
+    /// z-index the last one, since containers paint their children in order. Only
+    /// the returned child becomes hot and receives pointer events.
+    ///
     /// Has a default implementation, that can be overriden to search children more
     /// efficiently.
     fn get_child_at_pos(&self, pos: Point) -> Option<WidgetRef<'_, dyn Widget>> {
         // layout_rect() is in parent coordinate space
         self.children()
             .into_iter()
-            .find(|child| child.state().layout_rect().contains(pos))
+            .filter(|child| !child.state().is_stashed && child.state().layout_rect().contains(pos))
+            // max_by_key returns the last of equal elements.
+            .max_by_key(|child| child.state().z_index)
     }
 
     /// Get the (verbose) type name of the widget for debugging purposes.
@@ -198,6 +378,23 @@ pub trait Widget: AsAny {
     }
 }
 
+/// Return the [`preferred_size`](Widget::preferred_size) of a leaf widget,
+/// clamped to `bc`.
+///
+/// This is the `layout` implementation of widgets that declare their size
+/// with `preferred_size`, so that they're sized the same whether or not a
+/// [`WidgetPod`](crate::WidgetPod) skips their `layout` method.
+pub(crate) fn layout_preferred_size<W: Widget + ?Sized>(
+    widget: &W,
+    bc: &BoxConstraints,
+    env: &Env,
+) -> Size {
+    match widget.preferred_size(bc, env) {
+        Some(size) => bc.constrain(size),
+        None => bc.min(),
+    }
+}
+
 /// Trait that widgets must implement to be in [`WidgetMut`](crate::widget::WidgetMut).
 ///
 /// This trait should usually be implemented with [`declare_widget`](crate::declare_widget).
@@ -352,6 +549,18 @@ impl Widget for Box<dyn Widget> {
         self.deref_mut().on_event(ctx, event, env)
     }
 
+    fn on_command(&mut self, ctx: &mut EventCtx, cmd: &Command, env: &Env) -> Handled {
+        self.deref_mut().on_command(ctx, cmd, env)
+    }
+
+    fn on_event_capture(&mut self, ctx: &mut EventCtx, event: &Event, env: &Env) -> Handled {
+        self.deref_mut().on_event_capture(ctx, event, env)
+    }
+
+    fn on_anim_frame(&mut self, ctx: &mut EventCtx, interval: u64, env: &Env) -> bool {
+        self.deref_mut().on_anim_frame(ctx, interval, env)
+    }
+
     fn on_status_change(&mut self, ctx: &mut LifeCycleCtx, event: &StatusChange, env: &Env) {
         self.deref_mut().on_status_change(ctx, event, env)
     }
@@ -384,6 +593,34 @@ impl Widget for Box<dyn Widget> {
         self.deref().get_debug_text()
     }
 
+    fn can_receive_focus_now(&self) -> bool {
+        self.deref().can_receive_focus_now()
+    }
+
+    fn is_input_sink(&self) -> bool {
+        self.deref().is_input_sink()
+    }
+
+    fn wants_all_pointer_moves(&self) -> bool {
+        self.deref().wants_all_pointer_moves()
+    }
+
+    fn preferred_size(&self, bc: &BoxConstraints, env: &Env) -> Option<Size> {
+        self.deref().preferred_size(bc, env)
+    }
+
+    fn size_hint(&self, bc: &BoxConstraints, env: &Env) -> Option<Size> {
+        self.deref().size_hint(bc, env)
+    }
+
+    fn env_keys_affecting_layout(&self) -> Option<&[Key<()>]> {
+        self.deref().env_keys_affecting_layout()
+    }
+
+    fn env_keys_affecting_paint(&self) -> Option<&[Key<()>]> {
+        self.deref().env_keys_affecting_paint()
+    }
+
     fn as_any(&self) -> &dyn Any {
         self.deref().as_dyn_any()
     }
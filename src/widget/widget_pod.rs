@@ -2,18 +2,23 @@
 // "as-is" basis without warranties of any kind. See the LICENSE file for
 // details.
 
+use std::any::Any;
 use std::collections::VecDeque;
 
+use druid_shell::Region;
 use tracing::{info_span, trace, warn};
 
-use crate::contexts::GlobalPassCtx;
+use crate::contexts::{sort_z_ops, GlobalPassCtx};
 use crate::kurbo::{Affine, Insets, Point, Rect, Shape, Size};
+use crate::piet::{
+    Device, Error as PietError, ImageBuf, ImageFormat, InterpolationMode, PietImage, PietText,
+};
 use crate::text::TextLayout;
 use crate::widget::{FocusChange, WidgetRef, WidgetState};
 use crate::{
-    ArcStr, BoxConstraints, Color, Env, Event, EventCtx, InternalEvent, InternalLifeCycle,
-    LayoutCtx, LifeCycle, LifeCycleCtx, Notification, PaintCtx, RenderContext, StatusChange,
-    Target, Widget, WidgetId,
+    ArcStr, BoxConstraints, Color, Data, Env, Event, EventCtx, Handled, InternalEvent,
+    InternalLifeCycle, Key, KeyRepeat, LayoutCtx, LifeCycle, LifeCycleCtx, Notification, PaintCtx,
+    PanAlignment, RenderContext, StatusChange, Target, Widget, WidgetId,
 };
 
 // TODO - rewrite links in doc
@@ -30,10 +35,39 @@ pub struct WidgetPod<W> {
     pub(crate) state: WidgetState,
     pub(crate) inner: W,
     pub(crate) env: Option<Env>,
+    pub(crate) env_override: Option<EnvOverride>,
+    // The cached content of the widget, if it's a layer.
+    pub(crate) layer_cache: Option<LayerCache>,
+    // The last image returned by `render_thumbnail`.
+    pub(crate) thumbnail_cache: Option<ThumbnailCache>,
+    // Arbitrary data attached by the parent, see `with_user_data`.
+    pub(crate) user_data: Option<Box<dyn Any>>,
     // stashed layout so we don't recompute this when debugging
     pub(crate) debug_widget_text: TextLayout<ArcStr>,
 }
 
+/// An env mutation set with [`WidgetPod::with_env`].
+///
+/// The scoped env is cached along with the parent env it was derived from,
+/// and is only rebuilt when the parent env changes.
+pub(crate) struct EnvOverride {
+    mutation: Box<dyn Fn(&mut Env)>,
+    cached: Option<(Env, Env)>,
+}
+
+/// The painted content of a widget promoted to a layer.
+pub(crate) struct LayerCache {
+    image: PietImage,
+    // The widget's paint rect when the image was captured, in local coordinates.
+    rect: Rect,
+}
+
+/// A downscaled rendering of a widget, see [`WidgetPod::render_thumbnail`].
+pub(crate) struct ThumbnailCache {
+    image: ImageBuf,
+    max_size: Size,
+}
+
 // ---
 
 impl<W: Widget> WidgetPod<W> {
@@ -55,10 +89,53 @@ impl<W: Widget> WidgetPod<W> {
             state,
             inner,
             env: None,
+            env_override: None,
+            layer_cache: None,
+            thumbnail_cache: None,
+            user_data: None,
             debug_widget_text: TextLayout::new(),
         }
     }
 
+    /// Override parts of the [`Env`] passed to this widget and its descendants.
+    ///
+    /// `f` is applied to the env received from the parent before it is passed
+    /// down. This is a lighter-weight alternative to a wrapper widget when the
+    /// override is constant; the resulting env is only recomputed when the
+    /// parent env changes.
+    pub fn with_env(mut self, f: impl Fn(&mut Env) + 'static) -> Self {
+        self.env_override = Some(EnvOverride {
+            mutation: Box::new(f),
+            cached: None,
+        });
+        self
+    }
+
+    /// Attach arbitrary data to this pod.
+    ///
+    /// This lets a container keep per-child parameters, such as a flex factor or
+    /// a grid cell, next to the child itself, instead of in a separate list that
+    /// must be kept in sync with its children. Masonry never reads this data.
+    pub fn with_user_data(mut self, data: impl Any) -> Self {
+        self.user_data = Some(Box::new(data));
+        self
+    }
+
+    /// Replace the data attached with [`with_user_data`](Self::with_user_data).
+    pub fn set_user_data(&mut self, data: impl Any) {
+        self.user_data = Some(Box::new(data));
+    }
+
+    /// Return the attached data, if there is some of type `T`.
+    pub fn user_data<T: Any>(&self) -> Option<&T> {
+        self.user_data.as_ref()?.downcast_ref()
+    }
+
+    /// Return the attached data mutably, if there is some of type `T`.
+    pub fn user_data_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.user_data.as_mut()?.downcast_mut()
+    }
+
     /// Read-only access to state. We don't mark the field as `pub` because
     /// we want to control mutation.
     pub(crate) fn state(&self) -> &WidgetState {
@@ -131,6 +208,21 @@ impl<W: Widget> WidgetPod<W> {
         self.state.layout_rect()
     }
 
+    /// Return the widget's origin in window coordinates.
+    ///
+    /// This is the top-left corner of the [`layout_rect`], relative to the top-left
+    /// corner of the window's content area. It's useful for positioning overlays
+    /// and popups relative to the widget.
+    ///
+    /// This value is only valid once the widget has been laid out and its parent's
+    /// window origin has been propagated to it, which happens right after each
+    /// layout pass. Before that, it may be stale or zero.
+    ///
+    /// [`layout_rect`]: #method.layout_rect
+    pub fn window_origin(&self) -> Point {
+        self.state.window_origin()
+    }
+
     /// Get the widget's paint rectangle.
     ///
     /// This is the [`Rect`] that widget has indicated it needs to paint in.
@@ -183,6 +275,13 @@ impl<W: Widget> WidgetPod<W> {
         self.state.baseline_offset
     }
 
+    /// Whether the widget can currently be seen in the window.
+    ///
+    /// See [`WidgetState::is_effectively_visible`].
+    pub fn is_effectively_visible(&self) -> bool {
+        self.state.is_effectively_visible()
+    }
+
     // FIXME - Remove
     /// Return a mutable reference to the inner widget.
     pub(crate) fn widget_mut(&mut self) -> &mut W {
@@ -224,6 +323,9 @@ impl<W: Widget> WidgetPod<W> {
     /// Determines if the provided `mouse_pos` is inside `rect`
     /// and if so updates the hot state and sends `LifeCycle::HotChanged`.
     ///
+    /// If the widget has a hover delay, the change is deferred instead: it's
+    /// committed when the delay's timer fires, unless the pointer goes back first.
+    ///
     /// Return `true` if the hot state changed.
     ///
     /// The provided `child_state` should be merged up if this returns `true`.
@@ -235,11 +337,69 @@ impl<W: Widget> WidgetPod<W> {
         mouse_pos: Option<Point>,
         env: &Env,
     ) -> bool {
-        let had_hot = inner_state.is_hot;
-        inner_state.is_hot = match mouse_pos {
+        if global_state.hot_suppressed {
+            return false;
+        }
+        let is_hot = match mouse_pos {
             Some(pos) => rect.winding(pos) != 0,
             None => false,
         };
+        let delay = match inner_state.hover_delay {
+            Some(delay) => delay,
+            None => return WidgetPod::set_hot_state(inner, inner_state, global_state, is_hot, env),
+        };
+        match inner_state.pending_hot {
+            // The pointer went back before the delay elapsed.
+            Some(_) if is_hot == inner_state.is_hot => {
+                trace!(
+                    "Widget '{}' #{}: cancel pending hot state",
+                    inner.short_type_name(),
+                    inner_state.id.to_raw(),
+                );
+                inner_state.pending_hot = None;
+            }
+            None if is_hot != inner_state.is_hot => {
+                let token = global_state.request_timer(delay, inner_state.id);
+                inner_state.pending_hot = Some((is_hot, token));
+                inner_state.hover_timers.push(token);
+            }
+            _ => (),
+        }
+        false
+    }
+
+    /// Same as [`update_hot_state`](Self::update_hot_state), but ignores the
+    /// hover delay and drops any pending change.
+    ///
+    /// Used for button presses, which must reach the widget under the pointer.
+    pub(crate) fn update_hot_state_immediately(
+        inner: &mut W,
+        inner_state: &mut WidgetState,
+        global_state: &mut GlobalPassCtx,
+        rect: Rect,
+        mouse_pos: Option<Point>,
+        env: &Env,
+    ) -> bool {
+        inner_state.pending_hot = None;
+        let is_hot = match mouse_pos {
+            Some(pos) => rect.winding(pos) != 0,
+            None => false,
+        };
+        WidgetPod::set_hot_state(inner, inner_state, global_state, is_hot, env)
+    }
+
+    /// Set the hot state and send `LifeCycle::HotChanged` if it changed.
+    ///
+    /// Return `true` if the hot state changed.
+    fn set_hot_state(
+        inner: &mut W,
+        inner_state: &mut WidgetState,
+        global_state: &mut GlobalPassCtx,
+        is_hot: bool,
+        env: &Env,
+    ) -> bool {
+        let had_hot = inner_state.is_hot;
+        inner_state.is_hot = is_hot;
         // FIXME - don't send event, update flags instead
         if had_hot != inner_state.is_hot {
             trace!(
@@ -334,7 +494,32 @@ impl<W: Widget + 'static> WidgetPod<W> {
     /// Convert a `WidgetPod` containing a widget of a specific concrete type
     /// into a dynamically boxed widget.
     pub fn boxed(self) -> WidgetPod<Box<dyn Widget>> {
-        WidgetPod::new_with_id(Box::new(self.inner), self.state.id)
+        let mut pod = WidgetPod::new_with_id(Box::new(self.inner), self.state.id);
+        pod.env_override = self.env_override;
+        pod.user_data = self.user_data;
+        pod
+    }
+
+    /// Return the env this widget's passes should see.
+    fn scoped_env(&mut self, env: &Env) -> Env {
+        match &mut self.env_override {
+            Some(env_override) => env_override.resolve(env),
+            None => env.clone(),
+        }
+    }
+}
+
+impl EnvOverride {
+    fn resolve(&mut self, parent_env: &Env) -> Env {
+        if let Some((cached_parent, scoped)) = &self.cached {
+            if cached_parent.same(parent_env) {
+                return scoped.clone();
+            }
+        }
+        let mut scoped = parent_env.clone();
+        (self.mutation)(&mut scoped);
+        self.cached = Some((parent_env.clone(), scoped.clone()));
+        scoped
     }
 }
 
@@ -357,6 +542,7 @@ impl<W: Widget> WidgetPod<W> {
     ///
     /// [`event`]: trait.Widget.html#tymethod.event
     pub fn on_event(&mut self, parent_ctx: &mut EventCtx, event: &Event, env: &Env) {
+        let env = &self.scoped_env(env);
         let _span = self.inner.make_trace_span().entered();
         // TODO #11
         parent_ctx
@@ -369,29 +555,49 @@ impl<W: Widget> WidgetPod<W> {
         self.check_initialized("on_event");
 
         if parent_ctx.is_handled {
+            // Our parent reset its `has_hot` before handling a pointer event,
+            // and we're still hot or not as before.
+            parent_ctx.widget_state.has_hot |= self.state.has_hot;
             parent_ctx.global_state.debug_logger.pop_span();
             // If the event was already handled, we quit early.
             return;
         }
 
         let had_active = self.state.has_active;
+        let had_pointer = self.state.has_hot || had_active;
         let rect = self.layout_rect();
+        // The pointer isn't over us where our parent clips us out.
+        let mouse_pos = match event {
+            Event::MouseDown(mouse_event)
+            | Event::MouseUp(mouse_event)
+            | Event::MouseMove(mouse_event)
+            | Event::Wheel(mouse_event) => parent_ctx
+                .widget_state
+                .child_pointer_pos(self.id(), Some(mouse_event.pos)),
+            _ => None,
+        };
 
         // If we need to replace either the event or its data.
         let mut modified_event = None;
+        // Commands from a batch that target this widget.
+        let mut own_commands = Vec::new();
 
         // TODO: factor as much logic as possible into monomorphic functions.
         let call_inner = match event {
             Event::Internal(internal) => match internal {
                 InternalEvent::MouseLeave => {
-                    let hot_changed = WidgetPod::update_hot_state(
-                        &mut self.inner,
-                        &mut self.state,
-                        parent_ctx.global_state,
-                        rect,
-                        None,
-                        env,
-                    );
+                    // While the pointer is captured, the platform keeps sending
+                    // moves from outside the window to the capturing widget, so
+                    // it and its ancestors stay hot until the capture is released.
+                    let hot_changed = !had_active
+                        && WidgetPod::update_hot_state(
+                            &mut self.inner,
+                            &mut self.state,
+                            parent_ctx.global_state,
+                            rect,
+                            None,
+                            env,
+                        );
                     had_active || hot_changed
                 }
                 InternalEvent::TargetedCommand(cmd) => {
@@ -412,10 +618,58 @@ impl<W: Widget> WidgetPod<W> {
                         _ => false,
                     }
                 }
+                InternalEvent::TargetedCommands(cmds) => {
+                    let id = self.id();
+                    let mut descendant_cmds = Vec::new();
+                    for cmd in cmds {
+                        match cmd.target() {
+                            Target::Widget(target) if target == id => {
+                                own_commands.push(cmd.clone());
+                            }
+                            // The bloom filter we're checking can return false positives.
+                            Target::Widget(target) if self.state.children.may_contain(&target) => {
+                                descendant_cmds.push(cmd.clone());
+                            }
+                            _ => (),
+                        }
+                    }
+                    if descendant_cmds.is_empty() {
+                        false
+                    } else {
+                        modified_event = Some(Event::Internal(InternalEvent::TargetedCommands(
+                            descendant_cmds,
+                        )));
+                        true
+                    }
+                }
                 InternalEvent::RouteTimer(token, widget_id) => {
                     if *widget_id == self.id() {
-                        modified_event = Some(Event::Timer(*token));
-                        true
+                        let hover_timer = self.state.hover_timers.iter().position(|t| t == token);
+                        match hover_timer {
+                            Some(idx) => {
+                                self.state.hover_timers.swap_remove(idx);
+                                // A timer of a cancelled change is dropped.
+                                if let Some((is_hot, hover_token)) = self.state.pending_hot {
+                                    if hover_token == *token {
+                                        self.state.pending_hot = None;
+                                        WidgetPod::set_hot_state(
+                                            &mut self.inner,
+                                            &mut self.state,
+                                            parent_ctx.global_state,
+                                            is_hot,
+                                            env,
+                                        );
+                                        self.recompute_has_hot();
+                                        parent_ctx.global_state.hover_committed = true;
+                                    }
+                                }
+                                false
+                            }
+                            None => {
+                                modified_event = Some(Event::Timer(*token));
+                                true
+                            }
+                        }
                     } else {
                         self.state.children.may_contain(widget_id)
                     }
@@ -428,6 +682,17 @@ impl<W: Widget> WidgetPod<W> {
                         self.state.children.may_contain(widget_id)
                     }
                 }
+                InternalEvent::RouteDrag(widget_id, drag_event) => {
+                    if *widget_id == self.id() {
+                        let mut drag_event = drag_event.clone();
+                        drag_event.pos =
+                            drag_event.window_pos - self.state.window_origin().to_vec2();
+                        modified_event = Some(Event::Drag(drag_event));
+                        true
+                    } else {
+                        self.state.children.may_contain(widget_id)
+                    }
+                }
                 InternalEvent::RouteImeStateChange(widget_id) => {
                     if *widget_id == self.id() {
                         modified_event = Some(Event::ImeStateChange);
@@ -436,6 +701,22 @@ impl<W: Widget> WidgetPod<W> {
                         self.state.children.may_contain(widget_id)
                     }
                 }
+                InternalEvent::RouteAfterLayout(widget_id) => {
+                    // The target runs the callback instead of handling the event.
+                    *widget_id == self.id() || self.state.children.may_contain(widget_id)
+                }
+                InternalEvent::RoutePanToWidget(widget_id) => {
+                    // The target requests the pan instead of handling the event.
+                    *widget_id == self.id() || self.state.children.may_contain(widget_id)
+                }
+                InternalEvent::PointerClaimed => {
+                    if self.state.is_active {
+                        self.state.is_active = false;
+                        // The widget may show that it's pressed.
+                        self.state.invalid.add_rect(self.state.local_paint_rect);
+                    }
+                    had_active
+                }
             },
             Event::WindowConnected | Event::WindowCloseRequested => true,
             Event::WindowDisconnected => true,
@@ -444,15 +725,19 @@ impl<W: Widget> WidgetPod<W> {
                 parent_ctx.is_root
             }
             Event::MouseDown(mouse_event) => {
-                WidgetPod::update_hot_state(
+                WidgetPod::update_hot_state_immediately(
                     &mut self.inner,
                     &mut self.state,
                     parent_ctx.global_state,
                     rect,
-                    Some(mouse_event.pos),
+                    mouse_pos,
                     env,
                 );
                 if (had_active || self.state.is_hot) && !self.state.is_stashed {
+                    self.state.pointer_history.clear();
+                    let now = parent_ctx.global_state.now();
+                    self.state.record_pointer_pos(now, mouse_event.window_pos);
+
                     let mut mouse_event = mouse_event.clone();
                     mouse_event.pos -= rect.origin().to_vec2();
                     modified_event = Some(Event::MouseDown(mouse_event));
@@ -462,12 +747,12 @@ impl<W: Widget> WidgetPod<W> {
                 }
             }
             Event::MouseUp(mouse_event) => {
-                WidgetPod::update_hot_state(
+                WidgetPod::update_hot_state_immediately(
                     &mut self.inner,
                     &mut self.state,
                     parent_ctx.global_state,
                     rect,
-                    Some(mouse_event.pos),
+                    mouse_pos,
                     env,
                 );
                 if (had_active || self.state.is_hot) && !self.state.is_stashed {
@@ -485,13 +770,23 @@ impl<W: Widget> WidgetPod<W> {
                     &mut self.state,
                     parent_ctx.global_state,
                     rect,
-                    Some(mouse_event.pos),
+                    mouse_pos,
                     env,
                 );
                 // MouseMove is recursed even if the widget is not active and not hot,
                 // but was hot previously. This is to allow the widget to respond to the movement,
                 // e.g. drag functionality where the widget wants to follow the mouse.
-                if (had_active || self.state.is_hot || hot_changed) && !self.state.is_stashed {
+                if self.state.is_active {
+                    let now = parent_ctx.global_state.now();
+                    self.state.record_pointer_pos(now, mouse_event.window_pos);
+                }
+                // While a descendant is hot, it may be laid out outside of our
+                // bounds, so we keep recursing to let it see the pointer.
+                let recurse = had_pointer
+                    || self.state.is_hot
+                    || hot_changed
+                    || self.state.wants_all_pointer_moves;
+                if recurse && !self.state.is_stashed {
                     let mut mouse_event = mouse_event.clone();
                     mouse_event.pos -= rect.origin().to_vec2();
                     modified_event = Some(Event::MouseMove(mouse_event));
@@ -506,7 +801,7 @@ impl<W: Widget> WidgetPod<W> {
                     &mut self.state,
                     parent_ctx.global_state,
                     rect,
-                    Some(mouse_event.pos),
+                    mouse_pos,
                     env,
                 );
                 if (had_active || self.state.is_hot) && !self.state.is_stashed {
@@ -526,48 +821,83 @@ impl<W: Widget> WidgetPod<W> {
                 self.state.request_anim = false;
                 r
             }
-            Event::KeyDown(_) => self.state.has_focus,
+            Event::KeyDown(key_event) => {
+                let ignore_repeat = key_event.repeat
+                    && self.state.key_repeat == KeyRepeat::Ignore
+                    && parent_ctx.global_state.focus_widget == Some(self.id());
+                self.state.has_focus && !ignore_repeat
+            }
             Event::KeyUp(_) => self.state.has_focus,
             Event::Paste(_) => self.state.has_focus,
             Event::Zoom(_) => had_active || self.state.is_hot,
+            Event::ContextMenuRequested(request) => {
+                if request.from_keyboard {
+                    self.state.has_focus
+                } else {
+                    self.state.is_hot && !self.state.is_stashed
+                }
+            }
             Event::Timer(_) => false, // This event was targeted only to our parent
             Event::ImeStateChange => true, // once delivered to the focus widget, recurse to the component?
             Event::Command(_) => true,
             Event::Notification(_) => false,
             Event::PromiseResult(_) => false,
+            Event::Drag(_) => false, // This event was targeted only to our parent
         };
 
-        if call_inner {
-            self.call_widget_method_with_checks("event", |widget_pod| {
-                // widget_pod is a reborrow of `self`
-                let mut notifications = VecDeque::new();
-                let mut inner_ctx = EventCtx {
-                    global_state: parent_ctx.global_state,
-                    widget_state: &mut widget_pod.state,
-                    notifications: &mut notifications,
-                    is_handled: false,
-                    is_root: false,
-                    request_pan_to_child: None,
-                };
-                let inner_event = modified_event.as_ref().unwrap_or(event);
-                inner_ctx.widget_state.has_active = false;
-
-                widget_pod.inner.on_event(&mut inner_ctx, inner_event, env);
+        for cmd in own_commands {
+            self.call_inner_on_event(parent_ctx, &Event::Command(cmd), env);
+        }
 
-                inner_ctx.widget_state.has_active |= inner_ctx.widget_state.is_active;
-                parent_ctx.is_handled |= inner_ctx.is_handled;
+        if call_inner {
+            let inner_event = modified_event.as_ref().unwrap_or(event);
+            if let Event::MouseDown(mouse_event)
+            | Event::MouseUp(mouse_event)
+            | Event::MouseMove(mouse_event)
+            | Event::Wheel(mouse_event) = inner_event
+            {
+                self.update_hit_child(mouse_event.pos);
+            }
+            self.call_inner_on_event(parent_ctx, inner_event, env);
+
+            // Input sinks consume the input they receive. See `Widget::is_input_sink`.
+            if self.inner.is_input_sink() {
+                match event {
+                    Event::MouseDown(_)
+                    | Event::MouseUp(_)
+                    | Event::Wheel(_)
+                    | Event::Zoom(_)
+                    | Event::KeyDown(_)
+                    | Event::KeyUp(_)
+                    | Event::Paste(_)
+                    | Event::ContextMenuRequested(_) => parent_ctx.is_handled = true,
+                    _ => (),
+                }
+            }
 
-                // TODO - there's some dubious logic here
-                if let Some(target_rect) = inner_ctx.request_pan_to_child {
-                    widget_pod.pan_to_child(parent_ctx, env, target_rect);
-                    let new_rect = target_rect
-                        .with_origin(target_rect.origin() + widget_pod.state.origin.to_vec2());
-                    parent_ctx.request_pan_to_child = Some(new_rect);
+            // A hover delay elapsed in our subtree: the hot descendant may
+            // have changed, wherever the timer stopped being routed.
+            if let Event::Internal(InternalEvent::RouteTimer(..)) = event {
+                if parent_ctx.global_state.hover_committed {
+                    self.recompute_has_hot();
                 }
+            }
+        }
 
-                // we try to handle the notifications that occured below us in the tree
-                widget_pod.process_notifications(parent_ctx, &mut notifications, env);
-            });
+        if had_pointer && updates_has_hot(event) && !self.state.has_hot && !self.state.has_active {
+            let mut inner_ctx = LifeCycleCtx {
+                global_state: parent_ctx.global_state,
+                widget_state: &mut self.state,
+            };
+            let _span = info_span!("on_status_change").entered();
+            self.inner
+                .on_status_change(&mut inner_ctx, &StatusChange::PointerLeftSubtree, env);
+        }
+
+        if let Event::Internal(InternalEvent::TargetedCommands(_)) = event {
+            // Commands in a batch are independent: one of them being handled
+            // mustn't stop the others from reaching our siblings.
+            parent_ctx.is_handled = false;
         }
 
         // Always merge even if not needed, because merging is idempotent and gives us simpler code.
@@ -577,21 +907,136 @@ impl<W: Widget> WidgetPod<W> {
         parent_ctx
             .global_state
             .debug_logger
-            .update_widget_state(self.as_dyn());
-        parent_ctx
-            .global_state
-            .debug_logger
-            .push_log(false, "updated state");
+            .log_widget_state(self.as_dyn());
 
         parent_ctx.global_state.debug_logger.pop_span();
     }
 
-    fn pan_to_child(&mut self, parent_ctx: &mut EventCtx, env: &Env, rect: Rect) {
+    /// Set `has_hot` from our own hot state and the `has_hot` of our children.
+    fn recompute_has_hot(&mut self) {
+        self.state.has_hot = self.state.is_hot
+            || self
+                .inner
+                .children()
+                .iter()
+                .any(|child| child.state().has_hot);
+    }
+
+    /// Record which child is topmost at `pos`, in local coordinates, so that
+    /// the siblings it covers don't become hot.
+    fn update_hit_child(&mut self, pos: Point) {
+        let hit_child = self
+            .state
+            .clip_pointer_pos(Some(pos))
+            .and_then(|pos| self.inner.get_child_at_pos(pos))
+            .map(|child| child.state().id);
+        self.state.hit_child = hit_child;
+    }
+
+    fn call_inner_on_event(&mut self, parent_ctx: &mut EventCtx, inner_event: &Event, env: &Env) {
+        self.call_widget_method_with_checks("event", |widget_pod| {
+            // widget_pod is a reborrow of `self`
+            let mut notifications = VecDeque::new();
+            let mut inner_ctx = EventCtx {
+                global_state: parent_ctx.global_state,
+                widget_state: &mut widget_pod.state,
+                notifications: &mut notifications,
+                is_handled: false,
+                is_root: false,
+                request_pan_to_child: None,
+            };
+            let had_active = inner_ctx.widget_state.has_active;
+            let had_hot = inner_ctx.widget_state.has_hot;
+            inner_ctx.widget_state.has_active = false;
+            if is_pointer_event(inner_event) {
+                inner_ctx.widget_state.has_hot = false;
+            }
+
+            // The capture phase, see `Widget::on_event_capture`.
+            let captured = match inner_event {
+                Event::Internal(_) => Handled::No,
+                _ => widget_pod
+                    .inner
+                    .on_event_capture(&mut inner_ctx, inner_event, env),
+            };
+            if captured.is_handled() {
+                // Our children don't see the event, so their flags haven't changed.
+                inner_ctx.widget_state.has_hot |= had_hot;
+                if had_active && is_pointer_event(inner_event) {
+                    // Unless a pointer is claimed from them: they stop being
+                    // active, and `has_active` is recomputed as they're told.
+                    let is_handled = std::mem::take(&mut inner_ctx.is_handled);
+                    let claimed = Event::Internal(InternalEvent::PointerClaimed);
+                    widget_pod.inner.on_event(&mut inner_ctx, &claimed, env);
+                    inner_ctx.is_handled = is_handled;
+                } else {
+                    inner_ctx.widget_state.has_active |= had_active;
+                }
+            }
+
+            let handled = match inner_event {
+                _ if captured.is_handled() => Handled::Yes,
+                Event::Command(cmd) => widget_pod.inner.on_command(&mut inner_ctx, cmd, env),
+                Event::Internal(InternalEvent::RouteAfterLayout(widget_id))
+                    if *widget_id == inner_ctx.widget_id() =>
+                {
+                    if let Some(callback) = inner_ctx.global_state.after_layout_callback.take() {
+                        callback(widget_pod.inner.as_mut_any(), &mut inner_ctx, env);
+                    }
+                    Handled::Yes
+                }
+                Event::Internal(InternalEvent::RoutePanToWidget(widget_id))
+                    if *widget_id == inner_ctx.widget_id() =>
+                {
+                    inner_ctx.request_pan_to_this();
+                    Handled::Yes
+                }
+                Event::AnimFrame(interval) => {
+                    if widget_pod
+                        .inner
+                        .on_anim_frame(&mut inner_ctx, *interval, env)
+                    {
+                        inner_ctx.request_anim_frame();
+                    }
+                    Handled::No
+                }
+                _ => Handled::No,
+            };
+            if handled.is_handled() {
+                inner_ctx.set_handled();
+            } else {
+                widget_pod.inner.on_event(&mut inner_ctx, inner_event, env);
+            }
+
+            inner_ctx.widget_state.has_active |= inner_ctx.widget_state.is_active;
+            inner_ctx.widget_state.has_hot |= inner_ctx.widget_state.is_hot;
+            parent_ctx.is_handled |= inner_ctx.is_handled;
+
+            // The target rect is in this widget's coordinate space; we let the
+            // widget pan to it, then pass it up in the parent's coordinate space.
+            if let Some((target_rect, alignment)) = inner_ctx.request_pan_to_child {
+                widget_pod.pan_to_child(parent_ctx, env, target_rect, alignment);
+                let new_rect = target_rect + widget_pod.state.origin.to_vec2();
+                parent_ctx.request_pan_to_child = Some((new_rect, alignment));
+            }
+
+            // we try to handle the notifications that occured below us in the tree
+            widget_pod.process_notifications(parent_ctx, &mut notifications, env);
+        });
+    }
+
+    fn pan_to_child(
+        &mut self,
+        parent_ctx: &mut EventCtx,
+        env: &Env,
+        rect: Rect,
+        alignment: PanAlignment,
+    ) {
         let mut inner_ctx = LifeCycleCtx {
             global_state: parent_ctx.global_state,
             widget_state: &mut self.state,
         };
-        let event = LifeCycle::RequestPanToChild(rect);
+        let event = LifeCycle::RequestPanToChild(rect, alignment);
 
         self.inner.lifecycle(&mut inner_ctx, &event, env);
     }
@@ -648,6 +1093,7 @@ impl<W: Widget> WidgetPod<W> {
     ///
     /// [`LifeCycle`]: enum.LifeCycle.html
     pub fn lifecycle(&mut self, parent_ctx: &mut LifeCycleCtx, event: &LifeCycle, env: &Env) {
+        let env = &self.scoped_env(env);
         let _span = self.inner.make_trace_span().entered();
 
         // TODO #11
@@ -672,16 +1118,12 @@ impl<W: Widget> WidgetPod<W> {
                     // which case we need to change lifecycle event to
                     // WidgetAdded or in case we were already created
                     // we just pass this event down
-                    if self.state.is_new {
+                    if self.state.is_new || self.state.needs_reinit {
                         self.lifecycle(parent_ctx, &LifeCycle::WidgetAdded, env);
                         parent_ctx
                             .global_state
                             .debug_logger
-                            .update_widget_state(self.as_dyn());
-                        parent_ctx
-                            .global_state
-                            .debug_logger
-                            .push_log(false, "updated state");
+                            .log_widget_state(self.as_dyn());
                         parent_ctx.global_state.debug_logger.pop_span();
                         return;
                     } else {
@@ -732,6 +1174,13 @@ impl<W: Widget> WidgetPod<W> {
                     if let Some(change) = this_changed {
                         self.state.has_focus = change;
                         extra_event = Some(StatusChange::FocusChanged(change));
+                        // Show or hide the automatic focus ring.
+                        if !self.state.draws_own_focus_ring {
+                            self.state.invalid.set_rect(
+                                self.state.paint_rect()
+                                    - self.state.layout_rect().origin().to_vec2(),
+                            );
+                        }
                     } else {
                         self.state.has_focus = false;
                     }
@@ -746,13 +1195,33 @@ impl<W: Widget> WidgetPod<W> {
                 }
                 InternalLifeCycle::ParentWindowOrigin => {
                     self.state.parent_window_origin = parent_ctx.widget_state.window_origin();
+                    self.state.ancestor_clip = parent_ctx.widget_state.children_clip();
                     self.state.needs_window_origin = false;
                     // TODO - self.state.is_hidden
                     true
                 }
+                InternalLifeCycle::RouteWindowFocusChanged(focused) => {
+                    extra_event = Some(StatusChange::WindowFocusChanged(*focused));
+                    true
+                }
+                InternalLifeCycle::RouteAccessibilityFocusChanged { old, new } => {
+                    if *old == Some(self.state.id) {
+                        extra_event = Some(StatusChange::AccessibilityFocusChanged(false));
+                    } else if *new == Some(self.state.id) {
+                        extra_event = Some(StatusChange::AccessibilityFocusChanged(true));
+                    }
+
+                    match (old, new) {
+                        (Some(old), _) if self.state.children.may_contain(old) => true,
+                        (_, Some(new)) if self.state.children.may_contain(new) => true,
+                        _ => false,
+                    }
+                }
             },
             LifeCycle::WidgetAdded => {
-                if !self.state.is_new {
+                // Widgets in a subtree being re-initialized receive WidgetAdded again.
+                self.state.needs_reinit |= parent_ctx.widget_state.needs_reinit;
+                if !self.state.is_new && !self.state.needs_reinit {
                     // TODO - better warning.
                     warn!("Already initialized.");
                 }
@@ -767,6 +1236,17 @@ impl<W: Widget> WidgetPod<W> {
 
                 true
             }
+            LifeCycle::WidgetRemoved => {
+                trace!(
+                    "{} Received LifeCycle::WidgetRemoved",
+                    self.inner.short_type_name()
+                );
+                parent_ctx
+                    .global_state
+                    .debug_logger
+                    .forget_widget_state(self.state.id);
+                self.is_initialized()
+            }
             _ if !self.is_initialized() => {
                 debug_panic!(
                     "Error in '{}' #{}: received LifeCycle::{:?} before receiving WidgetAdded.",
@@ -796,13 +1276,32 @@ impl<W: Widget> WidgetPod<W> {
                     self.state.has_focus = is_focused;
 
                     self.state.focus_chain.clear();
+                    // Set again by `suppress_focus_ring` during this pass.
+                    self.state.draws_own_focus_ring = false;
                     true
                 } else {
                     false
                 }
             }
+            LifeCycle::EnvChanged => {
+                let old_env = self.env.replace(env.clone());
+                let changed = |keys: Option<&[Key<()>]>| match (keys, &old_env) {
+                    (Some(keys), Some(old_env)) => {
+                        keys.iter().any(|key| old_env.key_changed(env, key))
+                    }
+                    _ => true,
+                };
+                if changed(self.inner.env_keys_affecting_layout()) {
+                    self.state.needs_layout = true;
+                } else if changed(self.inner.env_keys_affecting_paint()) {
+                    self.state.invalid.set_rect(
+                        self.state.paint_rect() - self.state.layout_rect().origin().to_vec2(),
+                    );
+                }
+                true
+            }
             // This is called by children when going up the widget tree.
-            LifeCycle::RequestPanToChild(_) => false,
+            LifeCycle::RequestPanToChild(..) => false,
         };
 
         // widget_pod is a reborrow of `self`
@@ -817,7 +1316,18 @@ impl<W: Widget> WidgetPod<W> {
             });
         }
 
-        if let Some(event) = extra_event.as_ref() {
+        // Our children have merged their focus into ours, so we know whether
+        // focus entered or left our subtree.
+        let focus_within_event = match event {
+            LifeCycle::Internal(InternalLifeCycle::RouteFocusChanged { .. })
+                if had_focus != self.state.has_focus =>
+            {
+                Some(StatusChange::FocusWithinChanged(self.state.has_focus))
+            }
+            _ => None,
+        };
+
+        for event in extra_event.iter().chain(&focus_within_event) {
             let mut inner_ctx = LifeCycleCtx {
                 global_state: parent_ctx.global_state,
                 widget_state: &mut self.state,
@@ -834,6 +1344,10 @@ impl<W: Widget> WidgetPod<W> {
             // we need to (re)register children in case of one of the following events
             LifeCycle::WidgetAdded | LifeCycle::Internal(InternalLifeCycle::RouteWidgetAdded) => {
                 self.state.children_changed = false;
+                self.state.needs_reinit = false;
+                self.state.wants_all_pointer_moves |= self.inner.wants_all_pointer_moves();
+                parent_ctx.widget_state.wants_all_pointer_moves |=
+                    self.state.wants_all_pointer_moves;
                 parent_ctx.widget_state.children =
                     parent_ctx.widget_state.children.union(self.state.children);
                 parent_ctx.register_child(self.id());
@@ -866,7 +1380,20 @@ impl<W: Widget> WidgetPod<W> {
                 }
                 self.state.has_focus = had_focus;
 
-                if !self.state.is_disabled() {
+                // If the widget can't receive focus right now, neither it nor its
+                // descendants may keep it.
+                let can_receive_focus = self.inner.can_receive_focus_now();
+                if !can_receive_focus {
+                    let focus_widget = parent_ctx.global_state.focus_widget;
+                    let focus_in_subtree = focus_widget
+                        .map(|id| self.state.focus_chain.contains(&id))
+                        .unwrap_or(false);
+                    if self.state.has_focus || focus_in_subtree {
+                        self.state.request_focus = Some(FocusChange::Resign);
+                    }
+                }
+
+                if !self.state.is_disabled() && can_receive_focus {
                     parent_ctx
                         .widget_state
                         .focus_chain
@@ -881,11 +1408,7 @@ impl<W: Widget> WidgetPod<W> {
         parent_ctx
             .global_state
             .debug_logger
-            .update_widget_state(self.as_dyn());
-        parent_ctx
-            .global_state
-            .debug_logger
-            .push_log(false, "updated state");
+            .log_widget_state(self.as_dyn());
 
         parent_ctx.global_state.debug_logger.pop_span();
     }
@@ -899,6 +1422,7 @@ impl<W: Widget> WidgetPod<W> {
     ///
     /// [`layout`]: trait.Widget.html#tymethod.layout
     pub fn layout(&mut self, parent_ctx: &mut LayoutCtx, bc: &BoxConstraints, env: &Env) -> Size {
+        let env = &self.scoped_env(env);
         let _span = self.inner.make_trace_span().entered();
 
         // TODO #11
@@ -920,32 +1444,89 @@ impl<W: Widget> WidgetPod<W> {
         self.mark_as_visited();
         self.check_initialized("layout");
 
+        // Once the layout budget is spent, widgets that weren't visible in the
+        // last frame are left for later. See `AppLauncher::with_layout_budget`.
+        if !self.state.layout_deferred
+            && !self.state.has_deferred_layout
+            && parent_ctx.global_state.layout_budget_spent()
+            && !self.state.is_effectively_visible()
+        {
+            // A widget that would get the same layout keeps it.
+            if self.state.needs_layout || self.state.last_bc != Some(*bc) {
+                self.state.layout_deferred = true;
+                self.state.has_deferred_layout = true;
+                self.state.needs_layout = false;
+            }
+            self.state.is_expecting_place_child_call = true;
+            let size = bc.constrain(self.state.size);
+            parent_ctx.widget_state.merge_up(&mut self.state);
+            self.state.size = size;
+            parent_ctx.global_state.debug_logger.pop_span();
+            return size;
+        }
+        // When the deferred layout of a descendant is done, the cached layers
+        // on the way to it were painted without it.
+        let resumes_deferred = self.state.has_deferred_layout;
+        self.state.layout_deferred = false;
+        self.state.has_deferred_layout = false;
+        self.state.last_bc = Some(*bc);
+
+        if self.state.needs_layout || resumes_deferred {
+            self.state.subtree_dirty = true;
+            self.state.thumbnail_content_changed = true;
+        }
         self.state.needs_layout = false;
         self.state.needs_window_origin = false;
         self.state.is_expecting_place_child_call = true;
 
+        // Build the id label now rather than during the first paint.
+        if env.get(Env::DEBUG_WIDGET_ID) {
+            self.make_widget_id_layout_if_needed(self.state.id, parent_ctx.text(), env);
+        }
+
         bc.debug_check(self.inner.short_type_name());
 
         let inner_mouse_pos = parent_ctx
-            .mouse_pos
+            .widget_state
+            .child_pointer_pos(self.state.id, parent_ctx.mouse_pos)
             .map(|pos| pos - self.layout_rect().origin().to_vec2());
 
         // TODO - remove ?
         let _prev_size = self.state.size;
 
         self.state.local_paint_rect = Rect::ZERO;
+        self.state.acknowledged_paint_rect = None;
+        // The children may move, the next pointer event finds the topmost one.
+        self.state.hit_child = None;
+
+        let new_size = match self.inner.preferred_size(bc, env) {
+            // Leaf widgets with trivial sizing don't need a layout call.
+            Some(size) => {
+                if !self.inner.children().is_empty() {
+                    debug_panic!(
+                        "Error in '{}' #{}: widgets with children must implement layout instead of preferred_size.",
+                        self.inner.short_type_name(),
+                        self.state().id.to_raw(),
+                    );
+                }
+                // Nothing sets these without a layout call.
+                self.state.baseline_offset = 0.0;
+                self.state.paint_insets = Insets::ZERO;
+                bc.constrain(size)
+            }
+            None => self.call_widget_method_with_checks("layout", |widget_pod| {
+                // widget_pod is a reborrow of `self`
 
-        let new_size = self.call_widget_method_with_checks("layout", |widget_pod| {
-            // widget_pod is a reborrow of `self`
-
-            let mut inner_ctx = LayoutCtx {
-                widget_state: &mut widget_pod.state,
-                global_state: parent_ctx.global_state,
-                mouse_pos: inner_mouse_pos,
-            };
+                let mut inner_ctx = LayoutCtx {
+                    widget_state: &mut widget_pod.state,
+                    global_state: parent_ctx.global_state,
+                    mouse_pos: inner_mouse_pos,
+                    resize_pending: parent_ctx.resize_pending,
+                };
 
-            widget_pod.inner.layout(&mut inner_ctx, bc, env)
-        });
+                widget_pod.inner.layout(&mut inner_ctx, bc, env)
+            }),
+        };
 
         self.state.local_paint_rect = self
             .state
@@ -964,16 +1545,28 @@ impl<W: Widget> WidgetPod<W> {
                     );
                 }
 
-                // TODO - This check might be redundant with the code updating local_paint_rect
-                let child_rect = child.state().paint_rect();
-                if !rect_contains(&self.state.local_paint_rect, &child_rect)
+                // Once a widget acknowledges overflow, children must stay within
+                // its own paint rect or the acknowledged rects.
+                let allowed_rect = match self.state.acknowledged_paint_rect {
+                    Some(acknowledged) => {
+                        (new_size.to_rect() + self.state.paint_insets).union(acknowledged)
+                    }
+                    // TODO - This check might be redundant with the code updating local_paint_rect
+                    None => self.state.local_paint_rect,
+                };
+                let child_rect = match self.state.paint_clip {
+                    Some(clip) => child.state().paint_rect().intersect(clip),
+                    None => child.state().paint_rect(),
+                };
+                if child_rect.area() > 0.0
+                    && !rect_contains(&allowed_rect, &child_rect)
                     && !self.state.is_portal
                 {
                     debug_panic!(
-                        "Error in '{}' #{}: paint_rect {:?} doesn't contain paint_rect {:?} of child widget '{}' #{}",
+                        "Error in '{}' #{}: paint_rect {:?} doesn't contain paint_rect {:?} of child widget '{}' #{}. Use LayoutCtx::expand_paint_rect_to_include to acknowledge children that paint outside their parent.",
                         self.inner.short_type_name(),
                         self.state().id.to_raw(),
-                        self.state.local_paint_rect,
+                        allowed_rect,
                         child_rect,
                         child.deref().short_type_name(),
                         child.state().id.to_raw(),
@@ -999,11 +1592,7 @@ impl<W: Widget> WidgetPod<W> {
         parent_ctx
             .global_state
             .debug_logger
-            .update_widget_state(self.as_dyn());
-        parent_ctx
-            .global_state
-            .debug_logger
-            .push_log(false, "updated state");
+            .log_widget_state(self.as_dyn());
 
         parent_ctx.global_state.debug_logger.pop_span();
 
@@ -1036,11 +1625,13 @@ impl<W: Widget> WidgetPod<W> {
     /// [`Widget::paint`]: trait.Widget.html#tymethod.paint
     /// [`paint`]: #method.paint
     pub fn paint_raw(&mut self, ctx: &mut PaintCtx, env: &Env) {
+        let env = &self.scoped_env(env);
         self.mark_as_visited();
 
         // we need to do this before we borrow from self
+        // (usually a no-op, since layout already built the id label)
         if env.get(Env::DEBUG_WIDGET_ID) {
-            self.make_widget_id_layout_if_needed(self.state.id, ctx, env);
+            self.make_widget_id_layout_if_needed(self.state.id, ctx.text(), env);
         }
 
         self.call_widget_method_with_checks("paint", |widget_pod| {
@@ -1053,9 +1644,38 @@ impl<W: Widget> WidgetPod<W> {
                 z_ops: Vec::new(),
                 region: ctx.region.clone(),
                 depth: ctx.depth,
+                brush_cache: ctx.brush_cache,
             };
             widget_pod.inner.paint(&mut inner_ctx, env);
 
+            // A widget's z-index decides hit-testing, so its own z-ordered
+            // paint should land at the same index; otherwise the widget that
+            // gets the pointer isn't the one drawn on top.
+            let z_index = widget_pod.state.z_index;
+            let mut own_ops = inner_ctx
+                .z_ops
+                .iter()
+                .filter(|op| op.depth == inner_ctx.depth)
+                .peekable();
+            if z_index != 0
+                && own_ops.peek().is_some()
+                && !own_ops.any(|op| op.z_index == z_index)
+            {
+                debug_panic!(
+                    "Error in '{}' #{}: z-index {} set in layout, but paint_with_z_index was only called with other indices",
+                    widget_pod.inner.short_type_name(),
+                    widget_pod.state.id.to_raw(),
+                    z_index,
+                );
+            }
+
+            if env.get(Env::AUTO_FOCUS_RING)
+                && !widget_pod.state.draws_own_focus_ring
+                && inner_ctx.is_focus_visible()
+            {
+                widget_pod.paint_focus_ring(&mut inner_ctx, env);
+            }
+
             let debug_ids = widget_pod.state.is_hot && env.get(Env::DEBUG_WIDGET_ID);
             if debug_ids {
                 // this also draws layout bounds
@@ -1102,23 +1722,193 @@ impl<W: Widget> WidgetPod<W> {
         self.mark_as_visited();
         self.check_initialized("paint");
 
-        if !paint_if_not_visible && !parent_ctx.region().intersects(self.state.paint_rect()) {
+        // The widget is painted once it's laid out, in a later frame.
+        if self.state.layout_deferred {
+            return;
+        }
+
+        // Our parent may clip us to less than our paint rect.
+        let parent_clip = parent_ctx.widget_state.paint_clip;
+        let visible_rect = match parent_clip {
+            Some(clip) => self.state.paint_rect().intersect(clip),
+            None => self.state.paint_rect(),
+        };
+        if !paint_if_not_visible && !parent_ctx.region().intersects(visible_rect) {
             return;
         }
 
         parent_ctx.with_save(|ctx| {
+            if let Some(clip) = parent_clip {
+                ctx.clip(clip);
+            }
             let layout_origin = self.layout_rect().origin().to_vec2();
             ctx.transform(Affine::translate(layout_origin));
             let mut visible = ctx.region().clone();
-            visible.intersect_with(self.state.paint_rect());
+            visible.intersect_with(visible_rect);
             visible -= layout_origin;
+            if self.state.is_layer {
+                self.paint_layer(ctx, visible, env);
+            } else {
+                self.layer_cache = None;
+                let fully_visible = region_covers(&visible, self.state.local_paint_rect);
+                ctx.with_child_ctx(visible, |ctx| self.paint_raw(ctx, env));
+                // A partially painted subtree may still have stale parts.
+                if fully_visible {
+                    self.state.subtree_dirty = false;
+                }
+            }
+        });
+    }
+
+    /// Paint a widget promoted to a layer, reusing its cached content if it's
+    /// still valid.
+    ///
+    /// `ctx` must already be translated to the widget's origin.
+    fn paint_layer(&mut self, ctx: &mut PaintCtx, visible: Region, env: &Env) {
+        // The cache belongs to the window's surface: it's neither drawn on nor
+        // captured from another target, and stays dirty if it was.
+        if ctx.global_state.paint_offscreen {
             ctx.with_child_ctx(visible, |ctx| self.paint_raw(ctx, env));
+            return;
+        }
+
+        let layer_rect = self.state.local_paint_rect;
+        if !self.state.subtree_dirty {
+            if let Some(cache) = &self.layer_cache {
+                if cache.rect == layer_rect {
+                    ctx.draw_image(&cache.image, layer_rect, InterpolationMode::NearestNeighbor);
+                    return;
+                }
+            }
+        }
+
+        self.layer_cache = None;
+        let fully_visible = region_covers(&visible, layer_rect);
+        let mut has_z_ops = false;
+        ctx.with_child_ctx(visible, |ctx| {
+            self.paint_raw(ctx, env);
+            has_z_ops = !ctx.z_ops.is_empty();
+        });
+        if fully_visible {
+            self.state.subtree_dirty = false;
+        }
+
+        // A partially visible layer would be captured with stale pixels in its
+        // hidden parts, so we wait until it's fully painted.
+        if fully_visible && !has_z_ops {
+            match ctx.capture_image_area(layer_rect) {
+                Ok(image) => {
+                    self.layer_cache = Some(LayerCache {
+                        image,
+                        rect: layer_rect,
+                    })
+                }
+                Err(e) => warn!(
+                    "Failed to capture layer of widget #{}: '{}'",
+                    self.state.id.to_raw(),
+                    e
+                ),
+            }
+        }
+    }
+
+    /// Render the widget and its descendants into an image that fits in `max_size`.
+    ///
+    /// This is meant for previews, such as a thumbnail of a tab's content. It's
+    /// usually called from the `paint` method of the widget's parent, after the
+    /// widget was laid out: the widget is painted at its current layout size,
+    /// into an off-screen buffer, and scaled down (never up) to fit `max_size`
+    /// while keeping its aspect ratio. Text is measured with the window's text
+    /// factory, taken from `ctx`.
+    ///
+    /// The image is cached, and only rendered again after the widget or one of
+    /// its descendants requests a paint or a layout, or if `max_size` changes.
+    ///
+    /// Returns an empty image if the off-screen buffer can't be created.
+    pub fn render_thumbnail(&mut self, ctx: &mut PaintCtx, max_size: Size, env: &Env) -> ImageBuf {
+        if !self.state.thumbnail_content_changed {
+            if let Some(cache) = &self.thumbnail_cache {
+                if cache.max_size == max_size {
+                    return cache.image.clone();
+                }
+            }
+        }
+
+        let size = self.state.size();
+        let scale = (max_size.width / size.width)
+            .min(max_size.height / size.height)
+            .min(1.0);
+        // Also catches empty widgets, where the scale is NaN or infinite.
+        if !(scale > 0.0 && scale.is_finite()) {
+            return ImageBuf::empty();
+        }
+
+        let image = match self.paint_to_image(ctx, (size * scale).ceil(), scale, env) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!(
+                    "Failed to render thumbnail of widget #{}: '{}'",
+                    self.state.id.to_raw(),
+                    e
+                );
+                return ImageBuf::empty();
+            }
+        };
+        self.state.thumbnail_content_changed = false;
+        self.thumbnail_cache = Some(ThumbnailCache {
+            image: image.clone(),
+            max_size,
         });
+        image
+    }
+
+    /// Paint the widget into a new bitmap of `image_size`, scaled by `scale`.
+    fn paint_to_image(
+        &mut self,
+        ctx: &mut PaintCtx,
+        image_size: Size,
+        scale: f64,
+        env: &Env,
+    ) -> Result<ImageBuf, PietError> {
+        let mut device = Device::new()?;
+        let mut target =
+            device.bitmap_target(image_size.width as usize, image_size.height as usize, 1.0)?;
+        let paint_offscreen = std::mem::replace(&mut ctx.global_state.paint_offscreen, true);
+        {
+            let mut piet = target.render_context();
+            piet.transform(Affine::scale(scale));
+            let mut image_ctx = PaintCtx {
+                global_state: ctx.global_state,
+                widget_state: ctx.widget_state,
+                render_ctx: &mut piet,
+                z_ops: Vec::new(),
+                region: Region::from(self.state.size().to_rect()),
+                depth: ctx.depth,
+                brush_cache: ctx.brush_cache,
+            };
+            self.paint_raw(&mut image_ctx, env);
+
+            // Same as in `WindowRoot::paint`.
+            let mut z_ops = std::mem::take(&mut image_ctx.z_ops);
+            while !z_ops.is_empty() {
+                sort_z_ops(&mut z_ops);
+                for z_op in z_ops {
+                    image_ctx.with_save(|ctx| {
+                        ctx.render_ctx.transform(z_op.transform);
+                        (z_op.paint_func)(ctx);
+                    });
+                }
+                z_ops = std::mem::take(&mut image_ctx.z_ops);
+            }
+            image_ctx.global_state.paint_offscreen = paint_offscreen;
+            piet.finish()?;
+        }
+        target.to_image_buf(ImageFormat::RgbaPremul)
     }
 
     // FIXME - Add snapshot test for debug_widget_text
 
-    fn make_widget_id_layout_if_needed(&mut self, id: WidgetId, ctx: &mut PaintCtx, env: &Env) {
+    fn make_widget_id_layout_if_needed(&mut self, id: WidgetId, text: &mut PietText, env: &Env) {
         if self.debug_widget_text.needs_rebuild() {
             // switch text color based on background, this is meh and that's okay
             let border_color = env.get_debug_color(id.to_raw());
@@ -1133,7 +1923,7 @@ impl<W: Widget> WidgetPod<W> {
             self.debug_widget_text.set_text(id_string.into());
             self.debug_widget_text.set_text_size(10.0);
             self.debug_widget_text.set_text_color(text_color);
-            self.debug_widget_text.rebuild_if_needed(ctx.text(), env);
+            self.debug_widget_text.rebuild_if_needed(text, env);
         }
     }
 
@@ -1153,6 +1943,21 @@ impl<W: Widget> WidgetPod<W> {
         })
     }
 
+    fn paint_focus_ring(&self, ctx: &mut PaintCtx, env: &Env) {
+        const DASHES: &[f64] = &[4.0, 2.0];
+        // High-contrast themes get a ring twice as thick.
+        let width = if env.high_contrast() {
+            2.0 * env.get(theme::FOCUS_RING_WIDTH)
+        } else {
+            env.get(theme::FOCUS_RING_WIDTH)
+        };
+        // The ring is drawn inside the layout rect, which is invalidated when
+        // it's shown or hidden.
+        let rect = ctx.size().to_rect().inset(width / 2.0);
+        let color = env.get(theme::FOCUS_RING_COLOR);
+        ctx.stroke_dashed(rect, &color, width, DASHES, 0.0);
+    }
+
     fn debug_paint_layout_bounds(&self, ctx: &mut PaintCtx, env: &Env) {
         const BORDER_WIDTH: f64 = 1.0;
         let rect = ctx.size().to_rect().inset(BORDER_WIDTH / -2.0);
@@ -1162,6 +1967,32 @@ impl<W: Widget> WidgetPod<W> {
     }
 }
 
+/// Return `true` for the events that update the hot state.
+fn is_pointer_event(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::MouseDown(_)
+            | Event::MouseUp(_)
+            | Event::MouseMove(_)
+            | Event::Wheel(_)
+            | Event::Internal(InternalEvent::MouseLeave)
+    )
+}
+
+/// Whether `has_hot` may change as `event` goes through the tree: pointer
+/// events, and the timers that may commit a delayed hot state change.
+fn updates_has_hot(event: &Event) -> bool {
+    is_pointer_event(event) || matches!(event, Event::Internal(InternalEvent::RouteTimer(..)))
+}
+
+/// Return `true` if one of the rects of `region` contains `rect`.
+fn region_covers(region: &Region, rect: Rect) -> bool {
+    region
+        .rects()
+        .iter()
+        .any(|region_rect| region_rect.union(rect) == *region_rect)
+}
+
 // TODO - negative rects?
 /// Return `true` if all of `smaller` is within `larger`.
 fn rect_contains(larger: &Rect, smaller: &Rect) -> bool {
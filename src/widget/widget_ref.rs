@@ -2,11 +2,12 @@
 // "as-is" basis without warranties of any kind. See the LICENSE file for
 // details.
 
+use std::fmt::Write as _;
 use std::ops::Deref;
 
 use smallvec::SmallVec;
 
-use crate::kurbo::Point;
+use crate::kurbo::{Point, Rect};
 use crate::{Widget, WidgetId, WidgetState};
 
 /// A rich reference to a [`Widget`].
@@ -145,6 +146,14 @@ impl<'w> WidgetRef<'w, dyn Widget> {
         }
 
         loop {
+            // Children aren't hit outside the clip of their parent.
+            if innermost_widget
+                .state()
+                .clip_pointer_pos(Some(pos))
+                .is_none()
+            {
+                return Some(innermost_widget);
+            }
             if let Some(child) = innermost_widget.deref().get_child_at_pos(pos) {
                 pos -= innermost_widget.state().layout_rect().origin().to_vec2();
                 innermost_widget = child;
@@ -154,6 +163,62 @@ impl<'w> WidgetRef<'w, dyn Widget> {
         }
     }
 
+    /// Return a textual dump of the layout of this widget and its descendants.
+    ///
+    /// Each line describes a widget, indented by its depth: its type and debug
+    /// text, its id, its layout rect and paint rect (both relative to its parent),
+    /// its baseline offset, and its status flags. This is meant to be pasted into
+    /// bug reports about layout.
+    ///
+    /// See also [`Env::DEBUG_LAYOUT_DUMP`](crate::Env::DEBUG_LAYOUT_DUMP).
+    pub fn serialize_layout(&self) -> String {
+        let mut out = String::new();
+        self.serialize_layout_at_depth(&mut out, 0);
+        out
+    }
+
+    fn serialize_layout_at_depth(&self, out: &mut String, depth: usize) {
+        let state = self.state();
+        let children = self.children();
+
+        let widget_name = self.widget.short_type_name();
+        let _ = write!(out, "{:indent$}{widget_name}", "", indent = depth * 2);
+        if let Some(debug_text) = self.widget.get_debug_text() {
+            let _ = write!(out, "<{debug_text}>");
+        }
+        let _ = write!(
+            out,
+            " #{} layout={} paint={} baseline={:.1}",
+            state.id.to_raw(),
+            format_rect(state.layout_rect()),
+            format_rect(state.paint_rect()),
+            state.baseline_offset,
+        );
+
+        // Descendants of the focused widget aren't in the focused path.
+        let is_focused = state.has_focus && !children.iter().any(|c| c.state().has_focus);
+        let flags = [
+            (state.is_hot, "hot"),
+            (state.is_active, "active"),
+            (is_focused, "focus"),
+            (state.is_disabled(), "disabled"),
+            (state.is_stashed, "stashed"),
+        ];
+        let mut flags = flags.iter().filter(|(set, _)| *set).map(|(_, name)| *name);
+        if let Some(first) = flags.next() {
+            let _ = write!(out, " [{first}");
+            for flag in flags {
+                let _ = write!(out, ", {flag}");
+            }
+            out.push(']');
+        }
+        out.push('\n');
+
+        for child in children {
+            child.serialize_layout_at_depth(out, depth + 1);
+        }
+    }
+
     /// Recursively check that the Widget tree upholds various invariants.
     ///
     /// Can only be called after on_event and lifecycle.
@@ -205,13 +270,23 @@ impl<'w> WidgetRef<'w, dyn Widget> {
     }
 }
 
+fn format_rect(rect: Rect) -> String {
+    format!(
+        "({:.1}, {:.1}) {:.1}x{:.1}",
+        rect.x0,
+        rect.y0,
+        rect.width(),
+        rect.height()
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use assert_matches::assert_matches;
 
     use super::*;
     use crate::testing::{widget_ids, TestHarness, TestWidgetExt as _};
-    use crate::widget::{Button, Label};
+    use crate::widget::{Button, Flex, Label, SizedBox};
     use crate::{Widget, WidgetPod};
 
     #[test]
@@ -235,4 +310,24 @@ mod tests {
         assert_matches!(harness.get_widget(label_id).downcast::<Label>(), Some(_));
         assert_matches!(harness.get_widget(label_id).downcast::<Button>(), None);
     }
+
+    #[test]
+    fn serialize_layout() {
+        let [box_id] = widget_ids();
+        let widget = Flex::row()
+            .with_child_id(SizedBox::empty().width(10.0).height(20.0), box_id)
+            .with_child(Label::new("Hello"));
+
+        let mut harness = TestHarness::create(widget);
+        harness.mouse_move_to(box_id);
+
+        let dump = harness.root_widget().serialize_layout();
+        let lines: Vec<_> = dump.lines().collect();
+        assert_eq!(lines.len(), 3, "{dump}");
+        assert!(lines[0].starts_with("Flex #"));
+        assert!(lines[1].starts_with(&format!("  SizedBox #{} layout=", box_id.to_raw())));
+        assert!(lines[1].contains(" 10.0x20.0 "));
+        assert!(lines[1].ends_with("[hot]"));
+        assert!(lines[2].starts_with("  Label<Hello> #"));
+    }
 }
@@ -4,15 +4,21 @@
 
 #![cfg(not(tarpaulin_include))]
 
+use std::collections::VecDeque;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-use druid_shell::{Cursor, Region};
+use druid_shell::{Cursor, Region, TimerToken};
+use instant::Instant;
 
 use crate::bloom::Bloom;
 use crate::kurbo::{Insets, Point, Rect, Size};
 use crate::text::TextFieldRegistration;
 use crate::widget::{CursorChange, FocusChange};
-use crate::WidgetId;
+use crate::{BoxConstraints, KeyRepeat, WidgetId};
+
+/// The number of pointer positions kept to compute pointer velocity.
+const POINTER_HISTORY_LEN: usize = 8;
 
 // FIXME #5 - Make a note documenting this: the only way to get a &mut WidgetState should be in a pass.
 // A pass should reborrow the parent widget state (to avoid crossing wires) and call merge_up at
@@ -56,25 +62,63 @@ pub struct WidgetState {
     // TODO - Document
     // The computed paint rect, in local coordinates.
     pub(crate) local_paint_rect: Rect,
+    /// The rect children are clipped to, in local coordinates, if any.
+    ///
+    /// Children are only painted, and only receive pointer events, inside it.
+    pub(crate) paint_clip: Option<Rect>,
+    /// The part of the window the widget can be seen in, in window coordinates:
+    /// the window, clipped by the ancestors that clip their children.
+    ///
+    /// Updated after each layout pass, with `parent_window_origin`.
+    pub(crate) ancestor_clip: Rect,
+    /// The z-index this widget paints at, used to decide which of overlapping
+    /// siblings receives pointer events.
+    pub(crate) z_index: u32,
+    /// The widget paints its own focus ring, so the automatic one is skipped.
+    pub(crate) draws_own_focus_ring: bool,
+    /// The child that was topmost under the pointer during the last pointer
+    /// event, if any. Its siblings don't receive the pointer.
+    ///
+    /// Cleared by layout and when children are added or removed.
+    pub(crate) hit_child: Option<WidgetId>,
     /// The offset of the baseline relative to the bottom of the widget.
     ///
     /// In general, this will be zero; the bottom of the widget will be considered
     /// the baseline. Widgets that contain text or controls that expect to be
     /// laid out alongside text can set this as appropriate.
     pub(crate) baseline_offset: f64,
+    /// The union of the rects passed to `LayoutCtx::expand_paint_rect_to_include`
+    /// during the last layout, if any.
+    ///
+    /// When set, children must paint within the widget's own paint rect or this rect.
+    pub(crate) acknowledged_paint_rect: Option<Rect>,
     // TODO - Document
     pub(crate) is_portal: bool,
+    /// Whether the widget is painted into its own cached layer.
+    pub(crate) is_layer: bool,
 
     // --- PASSES ---
 
     // TODO: consider using bitflags for the booleans.
     // The region that needs to be repainted, relative to the widget's bounds.
     pub(crate) invalid: Region,
+    /// Whether this widget or a descendant was invalidated, laid out, or had
+    /// children added or removed since the widget was last painted in full.
+    ///
+    /// A layer whose subtree is clean draws its cached image instead of
+    /// painting its descendants.
+    pub(crate) subtree_dirty: bool,
+    /// Same as `subtree_dirty`, for the cache of
+    /// [`WidgetPod::render_thumbnail`](crate::WidgetPod::render_thumbnail).
+    pub(crate) thumbnail_content_changed: bool,
     /// A flag used to track and debug missing calls to place_child.
     pub(crate) is_expecting_place_child_call: bool,
 
     // True until a WidgetAdded event is received.
     pub(crate) is_new: bool,
+    // Set by `reinit_subtree`, and while a widget's subtree is receiving
+    // `WidgetAdded` again.
+    pub(crate) needs_reinit: bool,
 
     // `true` if a descendent of this widget changed its disabled state and should receive
     // LifeCycle::DisabledChanged or InternalLifeCycle::RouteDisabledChanged
@@ -119,13 +163,54 @@ pub struct WidgetState {
 
     pub(crate) is_active: bool,
 
+    /// How long the pointer must stay in or out of the widget before its hot
+    /// state changes. `None` means hot state changes immediately.
+    pub(crate) hover_delay: Option<Duration>,
+
+    /// How the widget receives repeated key presses while it has focus.
+    pub(crate) key_repeat: KeyRepeat,
+
+    /// A hot state change waiting for the hover delay, with its timer.
+    pub(crate) pending_hot: Option<(bool, TimerToken)>,
+
+    /// The timers requested for hover delays that haven't fired yet, including
+    /// those of cancelled changes. They're never delivered as `Event::Timer`.
+    pub(crate) hover_timers: Vec<TimerToken>,
+
+    /// Recent pointer positions in window coordinates, recorded since the last
+    /// `MouseDown` and while the widget is active. Used to compute pointer velocity.
+    pub(crate) pointer_history: VecDeque<(Instant, Point)>,
+
     /// Any descendant is active.
     pub(crate) has_active: bool,
 
+    /// The layout of this widget was deferred to the next frame, see
+    /// [`AppLauncher::with_layout_budget`](crate::AppLauncher::with_layout_budget).
+    pub(crate) layout_deferred: bool,
+
+    /// The layout of this widget or a descendant was deferred.
+    pub(crate) has_deferred_layout: bool,
+
+    /// The constraints of the last layout of this widget.
+    pub(crate) last_bc: Option<BoxConstraints>,
+
+    /// This widget or a descendant is hot.
+    ///
+    /// Like `has_active`, this is recomputed when pointer events pass through
+    /// the widget, and when a hover delay elapses.
+    pub(crate) has_hot: bool,
+
     /// In the focused path, starting from window and ending at the focused widget.
     /// Descendants of the focused widget are not in the focused path.
     pub(crate) has_focus: bool,
 
+    /// This widget or a descendant wants all mouse moves, see
+    /// [`Widget::wants_all_pointer_moves`](crate::Widget::wants_all_pointer_moves).
+    ///
+    /// This is set when the widget is added, and isn't cleared when the
+    /// descendant is removed.
+    pub(crate) wants_all_pointer_moves: bool,
+
     // TODO - document
     pub(crate) is_stashed: bool,
 
@@ -140,6 +225,27 @@ pub struct WidgetState {
     pub(crate) widget_name: &'static str,
 }
 
+/// What changed between two [`WidgetState`]s, see [`WidgetState::diff`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StateDelta {
+    /// The flags whose value changed, with their new value.
+    pub flags: Vec<(&'static str, bool)>,
+    /// The new layout rect, if it changed.
+    pub layout_rect: Option<Rect>,
+    /// The new paint rect, if it changed.
+    pub paint_rect: Option<Rect>,
+}
+
+/// The parts of a [`WidgetState`] compared by [`WidgetState::diff`].
+///
+/// The debug logger keeps one per widget, instead of a clone of the whole state.
+#[derive(Clone, Debug)]
+pub(crate) struct DiffedState {
+    flags: [(&'static str, bool); 17],
+    layout_rect: Rect,
+    paint_rect: Rect,
+}
+
 // This is a hack to have a simple Clone impl for WidgetState
 #[derive(Debug)]
 pub(crate) struct VisitBool(pub AtomicBool);
@@ -154,9 +260,19 @@ impl WidgetState {
             is_expecting_place_child_call: false,
             paint_insets: Insets::ZERO,
             local_paint_rect: Rect::ZERO,
+            ancestor_clip: size.unwrap_or_default().to_rect(),
+            paint_clip: None,
+            z_index: 0,
+            draws_own_focus_ring: false,
+            hit_child: None,
             invalid: Region::EMPTY,
+            subtree_dirty: true,
+            thumbnail_content_changed: true,
+            acknowledged_paint_rect: None,
             is_portal: false,
+            is_layer: false,
             is_new: true,
+            needs_reinit: false,
             children_disabled_changed: false,
             ancestor_disabled: false,
             is_explicitly_disabled: false,
@@ -165,8 +281,18 @@ impl WidgetState {
             needs_layout: false,
             needs_window_origin: false,
             is_active: false,
+            hover_delay: None,
+            key_repeat: KeyRepeat::Allow,
+            pending_hot: None,
+            hover_timers: Vec::new(),
+            pointer_history: VecDeque::new(),
             has_active: false,
+            has_hot: false,
+            layout_deferred: false,
+            has_deferred_layout: false,
+            last_bc: None,
             has_focus: false,
+            wants_all_pointer_moves: false,
             request_anim: false,
             request_focus: None,
             focus_chain: Vec::new(),
@@ -185,6 +311,44 @@ impl WidgetState {
         }
     }
 
+    /// The boolean flags of the state, with their names.
+    ///
+    /// These are the flags shown by the debug logger.
+    pub(crate) fn flags(&self) -> [(&'static str, bool); 17] {
+        [
+            (
+                "is_expecting_place_child_call",
+                self.is_expecting_place_child_call,
+            ),
+            ("is_new", self.is_new),
+            ("children_disabled_changed", self.children_disabled_changed),
+            ("ancestor_disabled", self.ancestor_disabled),
+            ("is_explicitly_disabled", self.is_explicitly_disabled),
+            ("is_hot", self.is_hot),
+            ("needs_layout", self.needs_layout),
+            ("needs_window_origin", self.needs_window_origin),
+            ("is_active", self.is_active),
+            ("has_active", self.has_active),
+            ("has_focus", self.has_focus),
+            ("request_anim", self.request_anim),
+            ("children_changed", self.children_changed),
+            (
+                "is_explicitly_disabled_new",
+                self.is_explicitly_disabled_new,
+            ),
+            ("update_focus_chain", self.update_focus_chain),
+            ("needs_reinit", self.needs_reinit),
+            ("is_stashed", self.is_stashed),
+        ]
+    }
+
+    pub(crate) fn record_pointer_pos(&mut self, time: Instant, window_pos: Point) {
+        if self.pointer_history.len() == POINTER_HISTORY_LEN {
+            self.pointer_history.pop_front();
+        }
+        self.pointer_history.push_back((time, window_pos));
+    }
+
     pub(crate) fn mark_as_visited(&self, visited: bool) {
         #[cfg(debug_assertions)]
         {
@@ -213,53 +377,103 @@ impl WidgetState {
     ///
     /// This method is idempotent and can be called multiple times.
     pub(crate) fn merge_up(&mut self, child_state: &mut WidgetState) {
-        // TODO - Ideally, we'd want to do this in global coordinates. The problem
-        // is that a parent could change this widget's coordinates through place_child
-        // later in the same pass
-        let clip = self
-            .layout_rect()
-            .with_origin(Point::ORIGIN)
-            .inset(self.paint_insets);
-        let offset = child_state.layout_rect().origin().to_vec2();
-        for &rect in child_state.invalid.rects() {
-            let rect = (rect + offset).intersect(clip);
-            if rect.area() != 0.0 {
-                self.invalid.add_rect(rect);
+        // Most passes only change a few widgets, so the rest of the tree has
+        // nothing to merge. Skip the work below for them.
+        if !child_state.has_state_to_merge() {
+            self.apply_cursor_change();
+            return;
+        }
+
+        // This is called for every widget on the path of every pass, so we skip
+        // the invalid rects entirely in the common case where there are none.
+        if !child_state.invalid.is_empty() {
+            // TODO - Ideally, we'd want to do this in global coordinates. The problem
+            // is that a parent could change this widget's coordinates through place_child
+            // later in the same pass
+            let clip = self
+                .layout_rect()
+                .with_origin(Point::ORIGIN)
+                .inset(self.paint_insets);
+            let offset = child_state.layout_rect().origin().to_vec2();
+            child_state.subtree_dirty = true;
+            child_state.thumbnail_content_changed = true;
+            for &rect in child_state.invalid.rects() {
+                let rect = (rect + offset).intersect(clip);
+                if rect.area() != 0.0 {
+                    self.invalid.add_rect(rect);
+                    self.subtree_dirty = true;
+                    self.thumbnail_content_changed = true;
+                }
             }
+            // Clearing the invalid rects here is less fragile than doing it while painting. The
+            // problem is that widgets (for example, Either) might choose not to paint certain
+            // invisible children, and we shouldn't allow these invisible children to accumulate
+            // invalid rects.
+            child_state.invalid.clear();
         }
-        // Clearing the invalid rects here is less fragile than doing it while painting. The
-        // problem is that widgets (for example, Either) might choose not to paint certain
-        // invisible children, and we shouldn't allow these invisible children to accumulate
-        // invalid rects.
-        child_state.invalid.clear();
 
         self.needs_layout |= child_state.needs_layout;
+        self.has_deferred_layout |= child_state.has_deferred_layout;
         self.needs_window_origin |= child_state.needs_window_origin;
         self.request_anim |= child_state.request_anim;
         self.children_disabled_changed |= child_state.children_disabled_changed;
         self.children_disabled_changed |=
             child_state.is_explicitly_disabled_new != child_state.is_explicitly_disabled;
         self.has_active |= child_state.has_active;
+        self.has_hot |= child_state.has_hot;
         self.has_focus |= child_state.has_focus;
         self.children_changed |= child_state.children_changed;
         self.request_focus = child_state.request_focus.take().or(self.request_focus);
-        self.text_registrations
-            .append(&mut child_state.text_registrations);
+        if !child_state.text_registrations.is_empty() {
+            self.text_registrations
+                .append(&mut child_state.text_registrations);
+        }
         self.update_focus_chain |= child_state.update_focus_chain;
 
         // We reset `child_state.cursor` no matter what, so that on the every pass through the tree,
         // things will be recalculated just from `cursor_change`.
         let child_cursor = child_state.take_cursor();
-        if let CursorChange::Override(cursor) = &self.cursor_change {
-            self.cursor = Some(cursor.clone());
-        } else if child_state.has_active || child_state.is_hot {
+        let is_overridden = matches!(self.cursor_change, CursorChange::Override(_));
+        if !is_overridden && (child_state.has_active || child_state.is_hot) {
             self.cursor = child_cursor;
         }
 
-        if self.cursor.is_none() {
-            if let CursorChange::Set(cursor) = &self.cursor_change {
+        self.apply_cursor_change();
+    }
+
+    /// Returns `true` if [`merge_up`](Self::merge_up) would change the parent
+    /// state or this state.
+    ///
+    /// This must check every field that `merge_up` reads from the child, except
+    /// the cursor set with `cursor_change`, which the parent ignores unless the
+    /// child is hot or active.
+    fn has_state_to_merge(&self) -> bool {
+        !self.invalid.is_empty()
+            || self.needs_layout
+            || self.has_deferred_layout
+            || self.needs_window_origin
+            || self.request_anim
+            || self.tree_disabled_changed()
+            || self.has_active
+            || self.is_hot
+            || self.has_hot
+            || self.has_focus
+            || self.children_changed
+            || self.update_focus_chain
+            || self.request_focus.is_some()
+            || !self.text_registrations.is_empty()
+            || self.cursor.is_some()
+    }
+
+    /// Apply the cursor set with `cursor_change`: an override replaces the
+    /// cursor of the children, otherwise it's a fallback if no child set one.
+    fn apply_cursor_change(&mut self) {
+        match &self.cursor_change {
+            CursorChange::Override(cursor) => self.cursor = Some(cursor.clone()),
+            CursorChange::Set(cursor) if self.cursor.is_none() => {
                 self.cursor = Some(cursor.clone());
             }
+            _ => {}
         }
     }
 
@@ -275,6 +489,46 @@ impl WidgetState {
         self.size
     }
 
+    /// Return `pos`, in local coordinates, if children may receive pointer
+    /// events there, or `None` if it's outside the [`paint_clip`](Self::paint_clip).
+    pub(crate) fn clip_pointer_pos(&self, pos: Option<Point>) -> Option<Point> {
+        match self.paint_clip {
+            Some(clip) => pos.filter(|pos| clip.contains(*pos)),
+            None => pos,
+        }
+    }
+
+    /// Return `pos`, in local coordinates, if the child `child_id` may receive
+    /// pointer events there.
+    ///
+    /// This is `None` outside the paint clip, and where the child is covered by
+    /// the sibling that was topmost under the pointer.
+    pub(crate) fn child_pointer_pos(
+        &self,
+        child_id: WidgetId,
+        pos: Option<Point>,
+    ) -> Option<Point> {
+        match self.hit_child {
+            Some(hit_child) if hit_child != child_id => None,
+            _ => self.clip_pointer_pos(pos),
+        }
+    }
+
+    /// The z-index this widget paints at.
+    ///
+    /// See [`LayoutCtx::set_z_index`](crate::LayoutCtx::set_z_index).
+    pub fn z_index(&self) -> u32 {
+        self.z_index
+    }
+
+    /// The rect children are clipped to, in local coordinates, if any.
+    ///
+    /// Children are only painted, and only receive pointer events, inside it.
+    /// See [`LayoutCtx::set_paint_clip`](crate::LayoutCtx::set_paint_clip).
+    pub fn paint_clip(&self) -> Option<Rect> {
+        self.paint_clip
+    }
+
     /// The paint region for this widget.
     ///
     /// For more information, see [`WidgetPod::paint_rect`](crate::WidgetPod::paint_rect).
@@ -297,9 +551,133 @@ impl WidgetState {
         Rect::from_origin_size(self.window_origin(), self.size)
     }
 
-    pub(crate) fn window_origin(&self) -> Point {
+    /// The origin of the widget in window coordinates.
+    ///
+    /// For more information, see [`WidgetPod::window_origin`](crate::WidgetPod::window_origin).
+    pub fn window_origin(&self) -> Point {
         self.parent_window_origin + self.origin.to_vec2()
     }
+
+    /// Whether the widget can currently be seen in the window.
+    ///
+    /// This is `false` if the widget is stashed, has a zero size, or its paint
+    /// rect is entirely outside of the window or clipped away by an ancestor,
+    /// eg scrolled out of a [`Portal`](crate::widget::Portal)'s viewport.
+    /// Widgets painted over by their siblings still count as visible.
+    ///
+    /// This is computed from the last layout pass.
+    pub fn is_effectively_visible(&self) -> bool {
+        if self.is_stashed || self.size.width <= 0.0 || self.size.height <= 0.0 {
+            return false;
+        }
+        let window_paint_rect = self.paint_rect() + self.parent_window_origin.to_vec2();
+        window_paint_rect.intersect(self.ancestor_clip).area() > 0.0
+    }
+
+    /// The part of the window this widget's children can be seen in, in
+    /// window coordinates.
+    pub(crate) fn children_clip(&self) -> Rect {
+        if self.is_stashed {
+            return Rect::ZERO;
+        }
+        // Portals clip their content when painting.
+        let clip = match self.paint_clip {
+            Some(clip) => Some(clip),
+            None if self.is_portal => Some(self.size.to_rect()),
+            None => None,
+        };
+        match clip {
+            Some(clip) => self
+                .ancestor_clip
+                .intersect(clip + self.window_origin().to_vec2()),
+            None => self.ancestor_clip,
+        }
+    }
+
+    /// Return what changed since `previous`, an earlier snapshot of this state.
+    ///
+    /// The delta lists the flags that flipped and the geometry that changed,
+    /// with their current values. This is what the debug logger records
+    /// after each pass.
+    pub fn diff(&self, previous: &WidgetState) -> StateDelta {
+        self.diffed_state().diff(&previous.diffed_state())
+    }
+
+    pub(crate) fn diffed_state(&self) -> DiffedState {
+        DiffedState {
+            flags: self.flags(),
+            layout_rect: self.layout_rect(),
+            paint_rect: self.paint_rect(),
+        }
+    }
+}
+
+impl DiffedState {
+    /// Return what changed since `previous`.
+    pub(crate) fn diff(&self, previous: &DiffedState) -> StateDelta {
+        let flags = self
+            .flags
+            .into_iter()
+            .zip(previous.flags)
+            .filter(|((_, new), (_, old))| new != old)
+            .map(|(flag, _)| flag)
+            .collect();
+        StateDelta {
+            flags,
+            layout_rect: (self.layout_rect != previous.layout_rect).then_some(self.layout_rect),
+            paint_rect: (self.paint_rect != previous.paint_rect).then_some(self.paint_rect),
+        }
+    }
+}
+
+impl StateDelta {
+    /// Returns `true` if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty() && self.layout_rect.is_none() && self.paint_rect.is_none()
+    }
+
+    /// The names of the flags and rects that changed.
+    pub fn changed(&self) -> Vec<&'static str> {
+        let mut changed: Vec<_> = self.flags.iter().map(|(name, _)| *name).collect();
+        if self.layout_rect.is_some() {
+            changed.push("layout_rect");
+        }
+        if self.paint_rect.is_some() {
+            changed.push("paint_rect");
+        }
+        changed
+    }
+}
+
+/// Access to the filter of descendants used to route events.
+///
+/// Each widget keeps a Bloom filter of the ids of its descendants, to skip
+/// subtrees that can't contain the target of a command or timer. The filter
+/// can return false positives, which cost extra traversals but are otherwise
+/// harmless.
+#[cfg(feature = "diagnostics")]
+impl WidgetState {
+    /// The number of ids added to the children filter.
+    ///
+    /// A descendant can be counted more than once, eg if the filter was
+    /// rebuilt without being cleared.
+    pub fn children_filter_len(&self) -> usize {
+        self.children.entry_count()
+    }
+
+    /// The estimated probability that the children filter claims to contain
+    /// a widget that isn't a descendant.
+    pub fn children_filter_false_positive_rate(&self) -> f64 {
+        self.children.false_positive_rate()
+    }
+
+    /// Returns `true` if the children filter may contain `id`.
+    ///
+    /// This is always `true` for descendants; for other widgets, it's `true`
+    /// in case of a false positive.
+    pub fn children_filter_may_contain(&self, id: WidgetId) -> bool {
+        self.children.may_contain(&id)
+    }
 }
 
 impl Clone for VisitBool {